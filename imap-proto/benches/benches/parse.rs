@@ -0,0 +1,13 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for sample in imap_proto_benches::corpus() {
+        group.throughput(Throughput::Bytes(sample.data().len() as u64));
+        group.bench_function(sample.name(), |b| b.iter(|| sample.parse().unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
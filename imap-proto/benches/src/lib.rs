@@ -0,0 +1,187 @@
+//! A corpus of server responses for benchmarking the imap-proto parser.
+//!
+//! The corpus combines captured sessions, stored as files in the `corpus`
+//! directory of this crate, with large responses generated on the fly (such as
+//! a `FETCH` of thousands of envelopes). Downstream crates can use it for their
+//! own benchmarks, and load their own captures with `load_dir()`:
+//!
+//! ```no_run
+//! for sample in imap_proto_benches::corpus() {
+//!     assert_eq!(sample.parse().unwrap(), sample.responses());
+//! }
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use imap_proto::parser::parse_response;
+
+/// A series of responses, as sent by a server.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    name: String,
+    data: Vec<u8>,
+    responses: usize,
+}
+
+impl Sample {
+    /// A sample made of the responses in `data`, which must end with a
+    /// complete response.
+    pub fn new<S: Into<String>>(name: S, data: Vec<u8>) -> Self {
+        let mut sample = Sample {
+            name: name.into(),
+            data,
+            responses: 0,
+        };
+        sample.responses = sample.parse().unwrap_or(0);
+        sample
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The number of responses in the sample.
+    pub fn responses(&self) -> usize {
+        self.responses
+    }
+
+    /// Parse all responses in the sample, returning how many there are, or
+    /// the offset of the first response that could not be parsed.
+    pub fn parse(&self) -> Result<usize, usize> {
+        let mut rest = &self.data[..];
+        let mut count = 0;
+        while !rest.is_empty() {
+            match parse_response(rest) {
+                Ok((remaining, _)) => rest = remaining,
+                Err(_) => return Err(self.data.len() - rest.len()),
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// The captured and generated samples.
+pub fn corpus() -> Vec<Sample> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    let mut samples = load_dir(dir).expect("failed to load the bundled corpus");
+    samples.push(body_structure(200));
+    samples.push(fetch_envelopes(10_000));
+    samples.push(search(100_000));
+    samples
+}
+
+/// Load the samples stored in the files of `dir`, one sample per file, named
+/// after the file. Lines in the files may end with LF rather than CRLF.
+pub fn load_dir<P: AsRef<Path>>(dir: P) -> io::Result<Vec<Sample>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    let mut samples = Vec::new();
+    for path in paths.into_iter().filter(|path| path.is_file()) {
+        let name = match path.file_stem() {
+            Some(stem) => stem.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        let data = fs::read(&path)?;
+        samples.push(Sample::new(name, crlf(&data)));
+    }
+    Ok(samples)
+}
+
+/// A `BODYSTRUCTURE` of a multipart message with `parts` attachments, each of
+/// them a nested multipart with a text and an HTML alternative.
+pub fn body_structure(parts: usize) -> Sample {
+    let mut data = b"* 1 FETCH (UID 1 BODYSTRUCTURE (".to_vec();
+    for i in 0..parts {
+        data.extend_from_slice(
+            format!(
+                "((\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"QUOTED-PRINTABLE\" \
+                 {size} {lines} NIL NIL NIL NIL)(\"TEXT\" \"HTML\" (\"CHARSET\" \"utf-8\") \
+                 NIL NIL \"BASE64\" {size} {lines} NIL NIL NIL NIL) \"ALTERNATIVE\" \
+                 (\"BOUNDARY\" \"b{i}\") NIL NIL NIL)",
+                size = 1000 + i,
+                lines = 20 + i % 7,
+                i = i,
+            )
+            .as_bytes(),
+        );
+    }
+    data.extend_from_slice(b" \"MIXED\" (\"BOUNDARY\" \"outer\") NIL NIL NIL))\r\n");
+    Sample::new(format!("body_structure_{}", parts), data)
+}
+
+/// A `FETCH` of `count` messages with their flags and envelope.
+pub fn fetch_envelopes(count: usize) -> Sample {
+    let mut data = Vec::new();
+    for i in 1..=count {
+        data.extend_from_slice(
+            format!(
+                "* {i} FETCH (UID {uid} FLAGS (\\Seen $Label{label}) RFC822.SIZE {size} \
+                 ENVELOPE (\"Tue, 12 Mar 2024 10:{min:02}:00 +0100\" \"Re: Report number {i}\" \
+                 ((\"Alice Example\" NIL \"alice\" \"example.com\")) \
+                 ((\"Alice Example\" NIL \"alice\" \"example.com\")) \
+                 ((NIL NIL \"reply\" \"example.com\")) \
+                 ((\"Bob\" NIL \"bob\" \"example.org\")(NIL NIL \"team\" \"example.org\")) \
+                 NIL NIL \"<{prev}@example.com>\" \"<{i}@example.com>\"))\r\n",
+                i = i,
+                uid = 1000 + i,
+                label = i % 5,
+                size = 2000 + i * 13 % 50_000,
+                min = i % 60,
+                prev = i - 1,
+            )
+            .as_bytes(),
+        );
+    }
+    data.extend_from_slice(b"A0001 OK Fetch completed\r\n");
+    Sample::new(format!("fetch_envelopes_{}", count), data)
+}
+
+/// A `SEARCH` response returning `count` messages.
+pub fn search(count: usize) -> Sample {
+    let mut data = b"* SEARCH".to_vec();
+    for i in 1..=count {
+        data.extend_from_slice(format!(" {}", i * 3).as_bytes());
+    }
+    data.extend_from_slice(b"\r\nA0001 OK Search completed\r\n");
+    Sample::new(format!("search_{}", count), data)
+}
+
+/// Turn bare line feeds into CRLF.
+fn crlf(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, &b) in data.iter().enumerate() {
+        if b == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+            out.push(b'\r');
+        }
+        out.push(b);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{corpus, crlf};
+
+    #[test]
+    fn corpus_parses() {
+        for sample in corpus() {
+            assert_eq!(sample.parse(), Ok(sample.responses()), "{}", sample.name());
+            assert!(sample.responses() > 0, "{}", sample.name());
+        }
+    }
+
+    #[test]
+    fn line_endings() {
+        assert_eq!(crlf(b"a\nb\r\nc"), b"a\r\nb\r\nc");
+    }
+}
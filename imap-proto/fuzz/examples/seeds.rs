@@ -0,0 +1,277 @@
+//! Generates the libFuzzer dictionary (`imap.dict`) and the seed corpora of the
+//! fuzz targets (`corpus/<target>/`) from a small grammar of server responses.
+//!
+//! Run with `cargo run --example seeds` from the `fuzz` directory, then fuzz
+//! with `cargo fuzz run <target> -- -dict=imap.dict`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Productions of the grammar: each `<name>` in an alternative is replaced by
+/// one of the alternatives of the production with that name.
+const GRAMMAR: &[(&str, &[&str])] = &[
+    (
+        "response",
+        &[
+            "* <number> FETCH (<msg-att>)\r\n",
+            "* <number> FETCH (<msg-att> <msg-att>)\r\n",
+            "* <number> EXISTS\r\n",
+            "* <number> EXPUNGE\r\n",
+            "* CAPABILITY IMAP4rev1 <capability>\r\n",
+            "* FLAGS (<flag> <flag>)\r\n",
+            "* LIST (<mbx-flag>) <delimiter> <mailbox>\r\n",
+            "* STATUS <mailbox> (<status-att> <number>)\r\n",
+            "* SEARCH <number> <number>\r\n",
+            "* ESEARCH (TAG \"A1\") UID MIN <number> COUNT <number>\r\n",
+            "* VANISHED (EARLIER) <number>:<number>\r\n",
+            "* <cond> [<resp-code>] <text>\r\n",
+            "A1 <cond> [<resp-code>] <text>\r\n",
+            "+ <text>\r\n",
+        ],
+    ),
+    (
+        "msg-att",
+        &[
+            "UID <number>",
+            "FLAGS (<flag>)",
+            "RFC822.SIZE <number>",
+            "INTERNALDATE \"17-Jul-1996 02:44:25 -0700\"",
+            "MODSEQ (<number>)",
+            "BODY[<section>] <nstring>",
+            "BODY[<section>]<<number>> <nstring>",
+            "BINARY[1] <nstring>",
+            "ENVELOPE <envelope>",
+            "BODYSTRUCTURE <body>",
+        ],
+    ),
+    ("section", &["", "HEADER", "TEXT", "1.2", "HEADER.FIELDS (FROM TO)", "1.MIME"]),
+    ("nstring", &["NIL", "\"quoted\"", "{5}\r\nhello", "{0}\r\n", "~{3}\r\n\x00\x01\x02"]),
+    (
+        "envelope",
+        &[
+            "(NIL NIL NIL NIL NIL NIL NIL NIL NIL NIL)",
+            "(\"Wed, 17 Jul 1996 02:23:25 -0700\" <nstring> (<address>) (<address>) NIL (<address><address>) NIL NIL <nstring> \"<id@host>\")",
+        ],
+    ),
+    (
+        "address",
+        &[
+            "(\"Terry Gray\" NIL \"gray\" \"cac.washington.edu\")",
+            "(NIL NIL \"imap\" \"cac.washington.edu\")",
+            "(NIL NIL \"group\" NIL)(NIL NIL NIL NIL)",
+        ],
+    ),
+    (
+        "body",
+        &[
+            "(<body-type> NIL NIL \"7BIT\" <number> <number>)",
+            "(<body-type> (\"CHARSET\" \"US-ASCII\") NIL NIL \"BASE64\" <number> <number> NIL (\"attachment\" (\"FILENAME\" \"a.pdf\")) NIL NIL)",
+            "((\"TEXT\" \"PLAIN\" NIL NIL NIL \"7BIT\" 1 1)(\"TEXT\" \"HTML\" NIL NIL NIL \"7BIT\" 1 1) \"ALTERNATIVE\")",
+            "(\"MESSAGE\" \"RFC822\" NIL NIL NIL \"7BIT\" 10 <envelope> (\"TEXT\" \"PLAIN\" NIL NIL NIL \"7BIT\" 1 1) 2)",
+        ],
+    ),
+    ("body-type", &["\"TEXT\" \"PLAIN\"", "\"APPLICATION\" \"PDF\"", "\"IMAGE\" \"PNG\""]),
+    ("flag", &["\\Seen", "\\Answered", "\\Deleted", "\\Draft", "\\Flagged", "$Junk", "\\*"]),
+    ("mbx-flag", &["\\Noselect", "\\HasChildren", "\\Sent \\HasNoChildren"]),
+    ("delimiter", &["\"/\"", "\".\"", "NIL"]),
+    ("mailbox", &["INBOX", "\"Sent Items\"", "{4}\r\nTest", "\"&AOQ-\""]),
+    ("status-att", &["MESSAGES", "UIDNEXT", "UNSEEN", "HIGHESTMODSEQ", "SIZE"]),
+    ("capability", &["IDLE", "AUTH=PLAIN", "LITERAL+", "CONDSTORE QRESYNC", "METADATA"]),
+    ("cond", &["OK", "NO", "BAD", "BYE", "PREAUTH"]),
+    (
+        "resp-code",
+        &[
+            "ALERT",
+            "UIDNEXT <number>",
+            "UIDVALIDITY <number>",
+            "PERMANENTFLAGS (<flag> \\*)",
+            "APPENDUID <number> <number>",
+            "COPYUID <number> <number>:<number> <number>",
+            "HIGHESTMODSEQ <number>",
+            "CAPABILITY IMAP4rev1 <capability>",
+            "METADATA LONGENTRIES <number>",
+        ],
+    ),
+    ("text", &["done", "Completed (0.001 secs)", ""]),
+    ("number", &["0", "1", "4294967295", "18446744073709551615"]),
+];
+
+/// The fuzz targets, with the production their input starts with.
+const TARGETS: &[(&str, &str)] = &[
+    ("parse_response", "<response>"),
+    ("utf8_parse_response", "<response>"),
+    ("body_structure", "<body>"),
+    ("envelope", "<envelope>"),
+];
+
+/// The maximum number of seeds per target.
+const MAX_SEEDS: usize = 500;
+
+fn alternatives(name: &str) -> &'static [&'static str] {
+    GRAMMAR
+        .iter()
+        .find(|(production, _)| *production == name)
+        .map(|(_, alternatives)| *alternatives)
+        .unwrap_or_else(|| panic!("unknown production <{}>", name))
+}
+
+/// Expand the productions in `input` until none is left, taking the index of
+/// the alternative to use for each from `choices`.
+fn expand(input: &str, choices: &mut impl Iterator<Item = usize>) -> String {
+    let mut out = input.to_string();
+    // Literal headers such as `{5}` and origins such as `<0>` start with a
+    // character that is not part of production names
+    while let Some((start, end)) = production(&out) {
+        let alternatives = alternatives(&out[start + 1..end - 1]);
+        let choice = choices.next().unwrap_or(0) % alternatives.len();
+        out.replace_range(start..end, alternatives[choice]);
+    }
+    out
+}
+
+fn production(input: &str) -> Option<(usize, usize)> {
+    let mut search = 0;
+    while let Some(start) = input[search..].find('<').map(|pos| search + pos) {
+        let end = input[start..].find('>').map(|pos| start + pos + 1)?;
+        let name = &input[start + 1..end - 1];
+        if !name.is_empty() && name.bytes().all(|b| b.is_ascii_lowercase() || b == b'-') {
+            return Some((start, end));
+        }
+        search = start + 1;
+    }
+    None
+}
+
+/// The keywords and punctuation of the grammar, for the dictionary.
+fn tokens() -> BTreeSet<String> {
+    let mut tokens = BTreeSet::new();
+    for (_, alternatives) in GRAMMAR {
+        for alternative in alternatives.iter() {
+            let mut rest = &alternative[..];
+            while let Some((start, end)) = production(rest) {
+                split_tokens(&rest[..start], &mut tokens);
+                rest = &rest[end..];
+            }
+            split_tokens(rest, &mut tokens);
+        }
+    }
+    tokens.extend(vec!["\r\n".to_string(), "* ".to_string(), " {".to_string()]);
+    tokens
+}
+
+fn split_tokens(text: &str, tokens: &mut BTreeSet<String>) {
+    let separators: &[char] = &[' ', '(', ')', '[', ']', '\r', '\n'];
+    for token in text.split(separators) {
+        if token.len() > 1 {
+            tokens.insert(token.to_string());
+        }
+    }
+}
+
+/// Escape a token for a libFuzzer dictionary.
+fn escape(token: &str) -> String {
+    let mut out = String::new();
+    for b in token.bytes() {
+        match b {
+            b'"' | b'\\' => out.extend(&['\\', b as char]),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\x{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn main() -> io::Result<()> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    let dict = tokens()
+        .iter()
+        .map(|token| format!("\"{}\"\n", escape(token)))
+        .collect::<String>();
+    fs::write(root.join("imap.dict"), dict)?;
+
+    for (target, start) in TARGETS {
+        let dir = root.join("corpus").join(target);
+        fs::create_dir_all(&dir)?;
+        let seeds = seeds(start);
+        for (i, seed) in seeds.iter().enumerate() {
+            fs::write(dir.join(format!("seed-{:04}", i)), seed)?;
+        }
+        println!("{}: {} seeds", target, seeds.len());
+    }
+    Ok(())
+}
+
+/// Up to `MAX_SEEDS` distinct expansions of `start`.
+fn seeds(start: &str) -> BTreeSet<String> {
+    let mut seeds = BTreeSet::new();
+    // A deterministic pseudo-random walk through the alternatives
+    for i in 0..MAX_SEEDS * 4 {
+        let mut state = i as u64 + 1;
+        let mut choices = std::iter::repeat_with(move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize
+        });
+        seeds.insert(expand(start, &mut choices));
+        if seeds.len() == MAX_SEEDS {
+            break;
+        }
+    }
+    seeds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, expand, production, seeds, tokens};
+    use imap_proto::parser::parse_response;
+
+    #[test]
+    fn expansion() {
+        assert_eq!(
+            expand("UID <number>", &mut vec![2].into_iter()),
+            "UID 4294967295"
+        );
+        // Origins and literal headers are not productions
+        assert_eq!(
+            expand("BODY[]<<number>> {5}", &mut vec![1].into_iter()),
+            "BODY[]<1> {5}"
+        );
+        // Choices wrap around, and default to the first alternative
+        let flags = expand("(<flag> <flag>)", &mut vec![8].into_iter());
+        assert_eq!(flags, "(\\Answered \\Seen)");
+    }
+
+    #[test]
+    fn dictionary() {
+        let tokens = tokens();
+        assert!(tokens.contains("FETCH") && tokens.contains("BODYSTRUCTURE"));
+        assert!(tokens.iter().all(|token| production(token).is_none()));
+        assert_eq!(escape("\"A1\"\r\n"), "\\\"A1\\\"\\x0D\\x0A");
+    }
+
+    #[test]
+    fn seeds_parse() {
+        // Some seeds are invalid by design (such as numbers out of range), but
+        // most should make it through the parsers
+        let check = |start, wrap: &dyn Fn(&str) -> String| {
+            let seeds = seeds(start);
+            let valid = seeds
+                .iter()
+                .filter(|seed| parse_response(wrap(seed).as_bytes()).is_ok())
+                .count();
+            assert!(valid * 2 > seeds.len(), "{}: {} valid", start, valid);
+        };
+        check("<response>", &|seed| seed.to_string());
+        // Wrapped like the fuzz targets do
+        check("<body>", &|seed| {
+            format!("* 1 FETCH (BODYSTRUCTURE {})\r\n", seed)
+        });
+        check("<envelope>", &|seed| {
+            format!("* 1 FETCH (ENVELOPE {})\r\n", seed)
+        });
+    }
+}
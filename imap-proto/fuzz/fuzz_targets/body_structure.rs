@@ -0,0 +1,20 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate imap_proto;
+
+use imap_proto::{AttributeValue, Response};
+
+// The body structure parser is not public, so parse it as part of a response
+fuzz_target!(|data: &[u8]| {
+    let mut rsp = b"* 1 FETCH (BODYSTRUCTURE ".to_vec();
+    rsp.extend_from_slice(data);
+    rsp.extend_from_slice(b")\r\n");
+    let parsed = imap_proto::parser::parse_response(&rsp);
+    if let Ok((_, Response::Fetch(_, attrs))) = parsed {
+        for attr in attrs {
+            if let AttributeValue::BodyStructure(body) = attr {
+                let _ = body.attachments();
+            }
+        }
+    }
+});
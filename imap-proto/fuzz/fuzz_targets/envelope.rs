@@ -0,0 +1,11 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate imap_proto;
+
+// The envelope parser is not public, so parse it as part of a response
+fuzz_target!(|data: &[u8]| {
+    let mut rsp = b"* 1 FETCH (ENVELOPE ".to_vec();
+    rsp.extend_from_slice(data);
+    rsp.extend_from_slice(b")\r\n");
+    let _ = imap_proto::parser::parse_response(&rsp);
+});
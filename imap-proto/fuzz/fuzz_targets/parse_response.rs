@@ -0,0 +1,10 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate imap_proto;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((rest, rsp)) = imap_proto::parser::parse_response(data) {
+        assert!(rest.len() < data.len());
+        let _ = format!("{:?}", rsp);
+    }
+});
@@ -3,11 +3,22 @@ use std::marker::PhantomData;
 use std::ops::{RangeFrom, RangeInclusive};
 use std::str;
 
-use crate::types::{AttrMacro, Attribute, State};
+use crate::types::{
+    AttrMacro, Attribute, FlagSet, MetadataDepth, MetadataOptions, Rights, SearchCriteria,
+    SearchReturn, SequenceSet, SortCriterion, State, StatusItem, StoreMode,
+};
 
 pub struct CommandBuilder {}
 
 impl CommandBuilder {
+    pub fn capability() -> Command {
+        let args = b"CAPABILITY".to_vec();
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
     pub fn check() -> Command {
         let args = b"CHECK".to_vec();
         Command {
@@ -24,14 +35,90 @@ impl CommandBuilder {
         }
     }
 
+    pub fn append(mailbox: &str, message: &[u8]) -> Command {
+        let mut args = b"APPEND ".to_vec();
+        push_string(&mut args, mailbox);
+        args.push(b' ');
+        push_literal(&mut args, message);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    /// Append several messages with a single command (RFC 3502 MULTIAPPEND).
+    pub fn multi_append(mailbox: &str, messages: &[&[u8]]) -> Command {
+        let mut args = b"APPEND ".to_vec();
+        push_string(&mut args, mailbox);
+        for message in messages {
+            args.push(b' ');
+            push_literal(&mut args, message);
+        }
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn create(mailbox: &str) -> Command {
+        let mut args = b"CREATE ".to_vec();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    // RFC 4314 ACL: remove the rights of `identifier` from the ACL of `mailbox`
+    pub fn delete_acl(mailbox: &str, identifier: &str) -> Command {
+        let mut args = b"DELETEACL ".to_vec();
+        push_string(&mut args, mailbox);
+        args.push(b' ');
+        push_string(&mut args, identifier);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn delete(mailbox: &str) -> Command {
+        let mut args = b"DELETE ".to_vec();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn enable(capabilities: &[&str]) -> Command {
+        let mut args = b"ENABLE".to_vec();
+        for capability in capabilities {
+            args.push(b' ');
+            args.extend(capability.as_bytes());
+        }
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
     pub fn examine(mailbox: &str) -> SelectCommand<select::NoParams> {
-        let args = format!("EXAMINE \"{}\"", quoted_string(mailbox).unwrap()).into_bytes();
+        let mut args = b"EXAMINE ".to_vec();
+        push_string(&mut args, mailbox);
         SelectCommand {
             args,
             state: PhantomData::default(),
         }
     }
 
+    pub fn expunge() -> Command {
+        let args = b"EXPUNGE".to_vec();
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
     pub fn fetch() -> FetchCommand<fetch::Empty> {
         FetchCommand {
             args: b"FETCH ".to_vec(),
@@ -39,13 +126,139 @@ impl CommandBuilder {
         }
     }
 
+    pub fn get_acl(mailbox: &str) -> Command {
+        let mut args = b"GETACL ".to_vec();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    // RFC 5464 METADATA: get the values of `entries` on `mailbox`, or of the
+    // server if `mailbox` is empty
+    pub fn get_metadata(mailbox: &str, entries: &[&str], options: &MetadataOptions) -> Command {
+        let mut args = b"GETMETADATA ".to_vec();
+        let mut opts = Vec::new();
+        if let Some(max_size) = options.max_size {
+            opts.push(format!("MAXSIZE {}", max_size));
+        }
+        match options.depth {
+            MetadataDepth::Zero => {}
+            MetadataDepth::One => opts.push("DEPTH 1".to_string()),
+            MetadataDepth::Infinity => opts.push("DEPTH infinity".to_string()),
+        }
+        if !opts.is_empty() {
+            args.extend(format!("({}) ", opts.join(" ")).as_bytes());
+        }
+        push_string(&mut args, mailbox);
+        args.extend(b" (");
+        for (i, entry) in entries.iter().enumerate() {
+            if i > 0 {
+                args.push(b' ');
+            }
+            push_string(&mut args, entry);
+        }
+        args.push(b')');
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn get_quota(root: &str) -> Command {
+        let mut args = b"GETQUOTA ".to_vec();
+        push_string(&mut args, root);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn get_quota_root(mailbox: &str) -> Command {
+        let mut args = b"GETQUOTAROOT ".to_vec();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    // RFC 2971 ID: identify the client with `fields`, or send `NIL` if empty
+    pub fn id(fields: &[(&str, Option<&str>)]) -> Command {
+        let mut args = b"ID ".to_vec();
+        if fields.is_empty() {
+            args.extend(b"NIL");
+        } else {
+            args.push(b'(');
+            for (i, (key, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    args.push(b' ');
+                }
+                push_string(&mut args, key);
+                args.push(b' ');
+                match value {
+                    Some(value) => push_string(&mut args, value),
+                    None => args.extend(b"NIL"),
+                }
+            }
+            args.push(b')');
+        }
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    // RFC 2177 IDLE command; end it by sending `DONE`
+    pub fn idle() -> Command {
+        let args = b"IDLE".to_vec();
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
     pub fn list(reference: &str, glob: &str) -> Command {
-        let args = format!(
-            "LIST \"{}\" \"{}\"",
-            quoted_string(reference).unwrap(),
-            quoted_string(glob).unwrap()
-        )
-        .into_bytes();
+        let mut args = b"LIST ".to_vec();
+        push_string(&mut args, reference);
+        args.push(b' ');
+        push_string(&mut args, glob);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    // RFC 5258 LIST-EXTENDED: the subscribed mailboxes, with the `\Subscribed`
+    // attribute, including those that no longer exist
+    pub fn list_subscribed(reference: &str, glob: &str) -> Command {
+        let mut args = b"LIST (SUBSCRIBED) ".to_vec();
+        push_string(&mut args, reference);
+        args.push(b' ');
+        push_string(&mut args, glob);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn lsub(reference: &str, glob: &str) -> Command {
+        let mut args = b"LSUB ".to_vec();
+        push_string(&mut args, reference);
+        args.push(b' ');
+        push_string(&mut args, glob);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn list_rights(mailbox: &str, identifier: &str) -> Command {
+        let mut args = b"LISTRIGHTS ".to_vec();
+        push_string(&mut args, mailbox);
+        args.push(b' ');
+        push_string(&mut args, identifier);
         Command {
             args,
             next_state: None,
@@ -53,34 +266,278 @@ impl CommandBuilder {
     }
 
     pub fn login(user_name: &str, password: &str) -> Command {
-        let args = format!(
-            "LOGIN \"{}\" \"{}\"",
-            quoted_string(user_name).unwrap(),
-            quoted_string(password).unwrap()
-        )
-        .into_bytes();
+        let mut args = b"LOGIN ".to_vec();
+        push_string(&mut args, user_name);
+        args.push(b' ');
+        push_string(&mut args, password);
         Command {
             args,
             next_state: Some(State::Authenticated),
         }
     }
 
+    // RFC 5819 LIST-STATUS: `LIST` with the `STATUS` return option, for several
+    // mailbox patterns at once (based on RFC 5258 LIST-EXTENDED)
+    pub fn list_status(reference: &str, patterns: &[&str], items: &[StatusItem]) -> Command {
+        let mut args = b"LIST ".to_vec();
+        push_string(&mut args, reference);
+        args.extend(b" (");
+        for (i, pattern) in patterns.iter().enumerate() {
+            if i > 0 {
+                args.push(b' ');
+            }
+            push_string(&mut args, pattern);
+        }
+        args.extend(b") RETURN (STATUS ");
+        push_status_items(&mut args, items);
+        args.push(b')');
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn logout() -> Command {
+        let args = b"LOGOUT".to_vec();
+        Command {
+            args,
+            next_state: Some(State::Logout),
+        }
+    }
+
+    pub fn my_rights(mailbox: &str) -> Command {
+        let mut args = b"MYRIGHTS ".to_vec();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    // RFC 2342 NAMESPACE command
+    pub fn namespace() -> Command {
+        let args = b"NAMESPACE".to_vec();
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn noop() -> Command {
+        let args = b"NOOP".to_vec();
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn rename(from: &str, to: &str) -> Command {
+        let mut args = b"RENAME ".to_vec();
+        push_string(&mut args, from);
+        args.push(b' ');
+        push_string(&mut args, to);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    /// `criteria` is included as-is, so strings in it must be quoted.
+    pub fn search(criteria: &str) -> SearchCommand {
+        SearchCommand {
+            args: b"SEARCH".to_vec(),
+            criteria: criteria.as_bytes().to_vec(),
+        }
+    }
+
+    /// Strings that are not ASCII are sent as UTF-8, with `CHARSET UTF-8`.
+    pub fn search_criteria(criteria: &SearchCriteria<'_>) -> SearchCommand {
+        SearchCommand {
+            args: b"SEARCH".to_vec(),
+            criteria: search_program(criteria),
+        }
+    }
+
     pub fn select(mailbox: &str) -> SelectCommand<select::NoParams> {
-        let args = format!("SELECT \"{}\"", quoted_string(mailbox).unwrap()).into_bytes();
+        let mut args = b"SELECT ".to_vec();
+        push_string(&mut args, mailbox);
         SelectCommand {
             args,
             state: PhantomData::default(),
         }
     }
 
+    /// Replace, add to or remove from the rights of `identifier` on `mailbox`.
+    pub fn set_acl(mailbox: &str, identifier: &str, mode: StoreMode, rights: &Rights) -> Command {
+        let mut args = b"SETACL ".to_vec();
+        push_string(&mut args, mailbox);
+        args.push(b' ');
+        push_string(&mut args, identifier);
+        args.push(b' ');
+        let rights = match mode {
+            StoreMode::Replace => rights.to_string(),
+            StoreMode::Add => format!("+{}", rights),
+            StoreMode::Remove => format!("-{}", rights),
+        };
+        push_string(&mut args, &rights);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    /// Set the values of entries on `mailbox`, or of the server if `mailbox` is
+    /// empty. Entries with no value are removed.
+    pub fn set_metadata(mailbox: &str, values: &[(&str, Option<&[u8]>)]) -> Command {
+        let mut args = b"SETMETADATA ".to_vec();
+        push_string(&mut args, mailbox);
+        args.extend(b" (");
+        for (i, (entry, value)) in values.iter().enumerate() {
+            if i > 0 {
+                args.push(b' ');
+            }
+            push_string(&mut args, entry);
+            args.push(b' ');
+            match value {
+                Some(value) => push_bytes(&mut args, value),
+                None => args.extend(b"NIL"),
+            }
+        }
+        args.push(b')');
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    /// Set the limits of a quota root, as pairs of resource names and limits.
+    /// Resources not listed no longer have a limit.
+    pub fn set_quota(root: &str, limits: &[(&str, u64)]) -> Command {
+        let mut args = b"SETQUOTA ".to_vec();
+        push_string(&mut args, root);
+        args.extend(b" (");
+        for (i, (resource, limit)) in limits.iter().enumerate() {
+            if i > 0 {
+                args.push(b' ');
+            }
+            args.extend(format!("{} {}", resource, limit).as_bytes());
+        }
+        args.push(b')');
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn status(mailbox: &str, items: &[StatusItem]) -> Command {
+        let mut args = b"STATUS ".to_vec();
+        push_string(&mut args, mailbox);
+        args.push(b' ');
+        push_status_items(&mut args, items);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn store(set: &SequenceSet, mode: StoreMode, flags: &FlagSet) -> StoreCommand {
+        StoreCommand::new(format!("STORE {}", set), mode, flags)
+    }
+
+    pub fn subscribe(mailbox: &str) -> Command {
+        let mut args = b"SUBSCRIBE ".to_vec();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn uid_copy(set: &SequenceSet, mailbox: &str) -> Command {
+        let mut args = format!("UID COPY {} ", set).into_bytes();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    /// `criteria` is included as-is, so strings in it must be quoted.
+    pub fn uid_search(criteria: &str) -> SearchCommand {
+        SearchCommand {
+            args: b"UID SEARCH".to_vec(),
+            criteria: criteria.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn uid_search_criteria(criteria: &SearchCriteria<'_>) -> SearchCommand {
+        SearchCommand {
+            args: b"UID SEARCH".to_vec(),
+            criteria: search_program(criteria),
+        }
+    }
+
+    // RFC 4315 UIDPLUS
+    pub fn uid_expunge(set: &SequenceSet) -> Command {
+        Command {
+            args: format!("UID EXPUNGE {}", set).into_bytes(),
+            next_state: None,
+        }
+    }
+
     pub fn uid_fetch() -> FetchCommand<fetch::Empty> {
         FetchCommand {
             args: b"UID FETCH ".to_vec(),
             state: PhantomData::default(),
         }
     }
+
+    // RFC 6851 MOVE command
+    pub fn uid_move(set: &SequenceSet, mailbox: &str) -> Command {
+        let mut args = format!("UID MOVE {} ", set).into_bytes();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    /// `UID SORT` (RFC 5256): the UIDs of the messages matching `criteria`,
+    /// in the given order. Strings are sent as UTF-8.
+    pub fn uid_sort(order: &[SortCriterion], criteria: &SearchCriteria<'_>) -> Command {
+        let mut args = b"UID SORT (".to_vec();
+        for (i, criterion) in order.iter().enumerate() {
+            if i > 0 {
+                args.push(b' ');
+            }
+            args.extend(criterion.to_string().as_bytes());
+        }
+        args.extend(b") UTF-8 ");
+        match criteria {
+            SearchCriteria::And(keys) if !keys.is_empty() => push_search_keys(&mut args, keys),
+            key => push_search_key(&mut args, key),
+        }
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn unsubscribe(mailbox: &str) -> Command {
+        let mut args = b"UNSUBSCRIBE ".to_vec();
+        push_string(&mut args, mailbox);
+        Command {
+            args,
+            next_state: None,
+        }
+    }
+
+    pub fn uid_store(set: &SequenceSet, mode: StoreMode, flags: &FlagSet) -> StoreCommand {
+        StoreCommand::new(format!("UID STORE {}", set), mode, flags)
+    }
 }
 
+#[derive(Clone)]
 pub struct Command {
     pub args: Vec<u8>,
     pub next_state: Option<State>,
@@ -100,6 +557,26 @@ impl SelectCommand<select::NoParams> {
             state: PhantomData::default(),
         }
     }
+
+    // RFC 7162 QRESYNC parameter: the last known UIDVALIDITY and HIGHESTMODSEQ
+    // values, and optionally the set of UIDs known to the client
+    pub fn qresync(
+        mut self,
+        uid_validity: u32,
+        mod_seq: u64,
+        known_uids: Option<&SequenceSet>,
+    ) -> SelectCommand<select::Params> {
+        self.args
+            .extend(format!(" (QRESYNC ({} {}", uid_validity, mod_seq).as_bytes());
+        if let Some(uids) = known_uids.filter(|uids| !uids.is_empty()) {
+            self.args.extend(format!(" {}", uids).as_bytes());
+        }
+        self.args.push(b')');
+        SelectCommand {
+            args: self.args,
+            state: PhantomData,
+        }
+    }
 }
 
 impl From<SelectCommand<select::NoParams>> for Command {
@@ -121,6 +598,97 @@ impl From<SelectCommand<select::Params>> for Command {
     }
 }
 
+pub struct SearchCommand {
+    args: Vec<u8>,
+    criteria: Vec<u8>,
+}
+
+impl SearchCommand {
+    // RFC 4731 ESEARCH return options
+    pub fn returning(mut self, options: &[SearchReturn]) -> Command {
+        self.args.extend(b" RETURN (");
+        for (i, option) in options.iter().enumerate() {
+            if i > 0 {
+                self.args.push(b' ');
+            }
+            self.args.extend(
+                match option {
+                    SearchReturn::Min => "MIN",
+                    SearchReturn::Max => "MAX",
+                    SearchReturn::All => "ALL",
+                    SearchReturn::Count => "COUNT",
+                }
+                .as_bytes(),
+            );
+        }
+        self.args.push(b')');
+        self.into()
+    }
+}
+
+impl From<SearchCommand> for Command {
+    fn from(mut cmd: SearchCommand) -> Command {
+        cmd.args.push(b' ');
+        cmd.args.extend(cmd.criteria);
+        Command {
+            args: cmd.args,
+            next_state: None,
+        }
+    }
+}
+
+pub struct StoreCommand {
+    args: Vec<u8>,
+    item: &'static str,
+    silent: bool,
+    flags: String,
+}
+
+impl StoreCommand {
+    fn new(args: String, mode: StoreMode, flags: &FlagSet) -> Self {
+        StoreCommand {
+            args: args.into_bytes(),
+            item: match mode {
+                StoreMode::Replace => "FLAGS",
+                StoreMode::Add => "+FLAGS",
+                StoreMode::Remove => "-FLAGS",
+            },
+            silent: false,
+            flags: flags.to_string(),
+        }
+    }
+
+    /// Ask the server not to send the resulting flags (`FLAGS.SILENT`).
+    pub fn silent(mut self) -> Self {
+        self.silent = true;
+        self
+    }
+
+    // RFC 7162 UNCHANGEDSINCE modifier: only change messages of which the
+    // mod-sequence is not greater than `seq`
+    pub fn unchanged_since(mut self, seq: u64) -> Self {
+        self.args
+            .extend(format!(" (UNCHANGEDSINCE {})", seq).as_bytes());
+        self
+    }
+}
+
+impl From<StoreCommand> for Command {
+    fn from(mut cmd: StoreCommand) -> Command {
+        cmd.args.push(b' ');
+        cmd.args.extend(cmd.item.as_bytes());
+        if cmd.silent {
+            cmd.args.extend(b".SILENT");
+        }
+        cmd.args.push(b' ');
+        cmd.args.extend(cmd.flags.as_bytes());
+        Command {
+            args: cmd.args,
+            next_state: None,
+        }
+    }
+}
+
 pub mod select {
     pub struct NoParams;
     pub struct Params;
@@ -162,6 +730,14 @@ impl FetchCommand<fetch::Empty> {
             state: PhantomData::default(),
         }
     }
+
+    pub fn set(mut self, set: &SequenceSet) -> FetchCommand<fetch::Messages> {
+        self.args.extend(set.to_string().as_bytes());
+        FetchCommand {
+            args: self.args,
+            state: PhantomData,
+        }
+    }
 }
 
 impl FetchCommand<fetch::Messages> {
@@ -242,20 +818,42 @@ impl FetchCommand<fetch::Attributes> {
 }
 
 fn push_attr(cmd: &mut Vec<u8>, attr: Attribute) {
-    cmd.extend(
-        match attr {
-            Attribute::Body => "BODY",
-            Attribute::Envelope => "ENVELOPE",
-            Attribute::Flags => "FLAGS",
-            Attribute::InternalDate => "INTERNALDATE",
-            Attribute::ModSeq => "MODSEQ",
-            Attribute::Rfc822 => "RFC822",
-            Attribute::Rfc822Size => "RFC822.SIZE",
-            Attribute::Rfc822Text => "RFC822.TEXT",
-            Attribute::Uid => "UID",
-        }
-        .as_bytes(),
-    );
+    let name = match attr {
+        Attribute::Body => "BODY",
+        Attribute::BodyPeek => "BODY.PEEK[]",
+        Attribute::BodySection(section) => {
+            cmd.extend(format!("BODY[{}]", section).as_bytes());
+            return;
+        }
+        Attribute::BodyPeekSection(section) => {
+            cmd.extend(format!("BODY.PEEK[{}]", section).as_bytes());
+            return;
+        }
+        Attribute::BodyPeekHeaderFields(names) => {
+            cmd.extend(b"BODY.PEEK[HEADER.FIELDS (");
+            for (i, name) in names.iter().enumerate() {
+                if i > 0 {
+                    cmd.push(b' ');
+                }
+                match name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+                    true if !name.is_empty() => cmd.extend(name.as_bytes()),
+                    _ => push_string(cmd, name),
+                }
+            }
+            cmd.extend(b")]");
+            return;
+        }
+        Attribute::BodyStructure => "BODYSTRUCTURE",
+        Attribute::Envelope => "ENVELOPE",
+        Attribute::Flags => "FLAGS",
+        Attribute::InternalDate => "INTERNALDATE",
+        Attribute::ModSeq => "MODSEQ",
+        Attribute::Rfc822 => "RFC822",
+        Attribute::Rfc822Size => "RFC822.SIZE",
+        Attribute::Rfc822Text => "RFC822.TEXT",
+        Attribute::Uid => "UID",
+    };
+    cmd.extend(name.as_bytes());
 }
 
 impl From<FetchCommand<fetch::Attributes>> for Command {
@@ -290,6 +888,170 @@ fn changed_since(cmd: &mut Vec<u8>, seq: u64) {
     cmd.push(b')');
 }
 
+// [SP "CHARSET" SP astring] 1*(SP search-key), without the leading space
+fn search_program(criteria: &SearchCriteria<'_>) -> Vec<u8> {
+    let mut cmd = Vec::new();
+    if !search_is_ascii(criteria) {
+        cmd.extend(b"CHARSET UTF-8 ");
+    }
+    match criteria {
+        SearchCriteria::And(keys) if !keys.is_empty() => push_search_keys(&mut cmd, keys),
+        key => push_search_key(&mut cmd, key),
+    }
+    cmd
+}
+
+fn search_is_ascii(key: &SearchCriteria<'_>) -> bool {
+    use SearchCriteria::*;
+    match key {
+        And(keys) => keys.iter().all(search_is_ascii),
+        Or(a, b) => search_is_ascii(a) && search_is_ascii(b),
+        Not(key) => search_is_ascii(key),
+        Bcc(s) | Body(s) | Cc(s) | From(s) | Subject(s) | Text(s) | To(s) => s.is_ascii(),
+        Header(name, value) => name.is_ascii() && value.is_ascii(),
+        _ => true,
+    }
+}
+
+fn push_search_keys(cmd: &mut Vec<u8>, keys: &[SearchCriteria<'_>]) {
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            cmd.push(b' ');
+        }
+        push_search_key(cmd, key);
+    }
+}
+
+fn push_search_key(cmd: &mut Vec<u8>, key: &SearchCriteria<'_>) {
+    use SearchCriteria::*;
+    let (name, arg) = match key {
+        And(keys) if keys.is_empty() => ("ALL", None),
+        And(keys) => {
+            cmd.push(b'(');
+            push_search_keys(cmd, keys);
+            cmd.push(b')');
+            return;
+        }
+        Or(a, b) => {
+            cmd.extend(b"OR ");
+            push_search_key(cmd, a);
+            cmd.push(b' ');
+            push_search_key(cmd, b);
+            return;
+        }
+        Not(key) => {
+            cmd.extend(b"NOT ");
+            push_search_key(cmd, key);
+            return;
+        }
+        All => ("ALL", None),
+        Answered => ("ANSWERED", None),
+        Deleted => ("DELETED", None),
+        Draft => ("DRAFT", None),
+        Flagged => ("FLAGGED", None),
+        New => ("NEW", None),
+        Old => ("OLD", None),
+        Recent => ("RECENT", None),
+        Seen => ("SEEN", None),
+        Unanswered => ("UNANSWERED", None),
+        Undeleted => ("UNDELETED", None),
+        Undraft => ("UNDRAFT", None),
+        Unflagged => ("UNFLAGGED", None),
+        Unseen => ("UNSEEN", None),
+        Keyword(flag) => ("KEYWORD", Some(flag.to_string())),
+        Unkeyword(flag) => ("UNKEYWORD", Some(flag.to_string())),
+        Bcc(s) => return push_search_string(cmd, "BCC", s),
+        Body(s) => return push_search_string(cmd, "BODY", s),
+        Cc(s) => return push_search_string(cmd, "CC", s),
+        From(s) => return push_search_string(cmd, "FROM", s),
+        Subject(s) => return push_search_string(cmd, "SUBJECT", s),
+        Text(s) => return push_search_string(cmd, "TEXT", s),
+        To(s) => return push_search_string(cmd, "TO", s),
+        Header(name, value) => {
+            push_search_string(cmd, "HEADER", name);
+            cmd.push(b' ');
+            push_bytes(cmd, value);
+            return;
+        }
+        Before(date) => ("BEFORE", Some(date.to_string())),
+        On(date) => ("ON", Some(date.to_string())),
+        Since(date) => ("SINCE", Some(date.to_string())),
+        SentBefore(date) => ("SENTBEFORE", Some(date.to_string())),
+        SentOn(date) => ("SENTON", Some(date.to_string())),
+        SentSince(date) => ("SENTSINCE", Some(date.to_string())),
+        Larger(size) => ("LARGER", Some(size.to_string())),
+        Smaller(size) => ("SMALLER", Some(size.to_string())),
+        Uid(set) => ("UID", Some(set.to_string())),
+        SequenceSet(set) => {
+            cmd.extend(set.to_string().as_bytes());
+            return;
+        }
+    };
+    cmd.extend(name.as_bytes());
+    if let Some(arg) = arg {
+        cmd.push(b' ');
+        cmd.extend(arg.as_bytes());
+    }
+}
+
+fn push_search_string(cmd: &mut Vec<u8>, name: &str, s: &[u8]) {
+    cmd.extend(name.as_bytes());
+    cmd.push(b' ');
+    push_bytes(cmd, s);
+}
+
+fn push_status_items(cmd: &mut Vec<u8>, items: &[StatusItem]) {
+    cmd.push(b'(');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            cmd.push(b' ');
+        }
+        cmd.extend(
+            match item {
+                StatusItem::HighestModSeq => "HIGHESTMODSEQ",
+                StatusItem::Messages => "MESSAGES",
+                StatusItem::Recent => "RECENT",
+                StatusItem::Size => "SIZE",
+                StatusItem::UidNext => "UIDNEXT",
+                StatusItem::UidValidity => "UIDVALIDITY",
+                StatusItem::Unseen => "UNSEEN",
+            }
+            .as_bytes(),
+        );
+    }
+    cmd.push(b')');
+}
+
+/// Appends `s` as a quoted string if possible, or as a literal otherwise.
+///
+/// Quoted strings can only contain 7-bit characters other than CR and LF, so
+/// anything else (for example, a password containing non-ASCII characters) is
+/// sent as a synchronizing literal. Transports are expected to split the command
+/// at literals and wait for the server's continuation request where needed.
+fn push_string(cmd: &mut Vec<u8>, s: &str) {
+    match quoted_string(s) {
+        Ok(quoted) if s.is_ascii() => {
+            cmd.push(b'"');
+            cmd.extend(quoted.as_bytes());
+            cmd.push(b'"');
+        }
+        _ => push_literal(cmd, s.as_bytes()),
+    }
+}
+
+fn push_bytes(cmd: &mut Vec<u8>, data: &[u8]) {
+    match str::from_utf8(data) {
+        Ok(s) => push_string(cmd, s),
+        Err(_) => push_literal(cmd, data),
+    }
+}
+
+/// literal = "{" number "}" CRLF *CHAR8
+fn push_literal(cmd: &mut Vec<u8>, data: &[u8]) {
+    cmd.extend(format!("{{{}}}\r\n", data.len()).as_bytes());
+    cmd.extend(data);
+}
+
 /// Returns an escaped string if necessary for use as a "quoted" string per
 /// the IMAPv4 RFC. Return value does not include surrounding quote characters.
 /// Will return Err if the argument contains illegal characters.
@@ -336,6 +1098,10 @@ fn quoted_string(s: &str) -> Result<Cow<str>, &'static str> {
 #[cfg(test)]
 mod tests {
     use super::{quoted_string, Attribute, Command, CommandBuilder};
+    use crate::types::{
+        FlagSet, MessageSection, MetadataDepth, MetadataOptions, SearchCriteria, SearchReturn,
+        SectionPath, SequenceSet, SortCriterion, SortKey, StatusItem, StoreMode,
+    };
 
     #[test]
     fn login() {
@@ -349,12 +1115,213 @@ mod tests {
         );
     }
 
+    #[test]
+    fn login_literal() {
+        assert_eq!(
+            CommandBuilder::login("djc", "p\u{e4}ss").args,
+            b"LOGIN \"djc\" {5}\r\np\xc3\xa4ss"
+        );
+    }
+
+    #[test]
+    fn append() {
+        assert_eq!(
+            CommandBuilder::append("Drafts", b"Subject: hi\r\n\r\n").args,
+            &b"APPEND \"Drafts\" {15}\r\nSubject: hi\r\n\r\n"[..]
+        );
+        assert_eq!(
+            CommandBuilder::multi_append("Drafts", &[b"a", b"bc"]).args,
+            &b"APPEND \"Drafts\" {1}\r\na {2}\r\nbc"[..]
+        );
+    }
+
     #[test]
     fn select() {
         let cmd = Command::from(CommandBuilder::select("INBOX"));
         assert_eq!(&cmd.args, br#"SELECT "INBOX""#);
         let cmd = Command::from(CommandBuilder::examine("INBOX").cond_store());
         assert_eq!(&cmd.args, br#"EXAMINE "INBOX" (CONDSTORE)"#);
+
+        let known: SequenceSet = (1..=100).collect();
+        let cmd = Command::from(CommandBuilder::select("INBOX").qresync(
+            67890007,
+            90060115194045000,
+            Some(&known),
+        ));
+        assert_eq!(
+            &cmd.args,
+            br#"SELECT "INBOX" (QRESYNC (67890007 90060115194045000 1:100))"#
+        );
+        let cmd = Command::from(CommandBuilder::select("INBOX").qresync(3, 4, None));
+        assert_eq!(&cmd.args, br#"SELECT "INBOX" (QRESYNC (3 4))"#);
+    }
+
+    #[test]
+    fn status() {
+        let items = [StatusItem::Messages, StatusItem::Unseen];
+        let cmd = CommandBuilder::status("INBOX", &items);
+        assert_eq!(cmd.args, &b"STATUS \"INBOX\" (MESSAGES UNSEEN)"[..]);
+        let cmd = CommandBuilder::list_status("", &["INBOX", "Sent"], &items);
+        assert_eq!(
+            cmd.args,
+            &b"LIST \"\" (\"INBOX\" \"Sent\") RETURN (STATUS (MESSAGES UNSEEN))"[..]
+        );
+    }
+
+    #[test]
+    fn subscriptions() {
+        let cmd = CommandBuilder::subscribe("Lists/rust");
+        assert_eq!(cmd.args, &b"SUBSCRIBE \"Lists/rust\""[..]);
+        let cmd = CommandBuilder::unsubscribe("Lists/rust");
+        assert_eq!(cmd.args, &b"UNSUBSCRIBE \"Lists/rust\""[..]);
+        let cmd = CommandBuilder::lsub("", "*");
+        assert_eq!(cmd.args, &b"LSUB \"\" \"*\""[..]);
+        let cmd = CommandBuilder::list_subscribed("", "*");
+        assert_eq!(cmd.args, &b"LIST (SUBSCRIBED) \"\" \"*\""[..]);
+    }
+
+    #[test]
+    fn acl() {
+        let cmd = CommandBuilder::get_acl("Shared");
+        assert_eq!(cmd.args, &b"GETACL \"Shared\""[..]);
+        let rights = "lr".parse().unwrap();
+        let cmd = CommandBuilder::set_acl("Shared", "bob", StoreMode::Add, &rights);
+        assert_eq!(cmd.args, &b"SETACL \"Shared\" \"bob\" \"+lr\""[..]);
+        let cmd = CommandBuilder::set_acl("Shared", "bob", StoreMode::Replace, &rights);
+        assert_eq!(cmd.args, &b"SETACL \"Shared\" \"bob\" \"lr\""[..]);
+        let cmd = CommandBuilder::delete_acl("Shared", "bob");
+        assert_eq!(cmd.args, &b"DELETEACL \"Shared\" \"bob\""[..]);
+        let cmd = CommandBuilder::my_rights("Shared");
+        assert_eq!(cmd.args, &b"MYRIGHTS \"Shared\""[..]);
+        let cmd = CommandBuilder::list_rights("Shared", "bob");
+        assert_eq!(cmd.args, &b"LISTRIGHTS \"Shared\" \"bob\""[..]);
+    }
+
+    #[test]
+    fn id() {
+        let cmd = CommandBuilder::id(&[("name", Some("tokio-imap")), ("os", None)]);
+        assert_eq!(cmd.args, &b"ID (\"name\" \"tokio-imap\" \"os\" NIL)"[..]);
+        let cmd = CommandBuilder::id(&[]);
+        assert_eq!(cmd.args, &b"ID NIL"[..]);
+    }
+
+    #[test]
+    fn metadata() {
+        let options = MetadataOptions::default();
+        let cmd = CommandBuilder::get_metadata("INBOX", &["/shared/comment"], &options);
+        assert_eq!(
+            cmd.args,
+            &b"GETMETADATA \"INBOX\" (\"/shared/comment\")"[..]
+        );
+        let options = MetadataOptions {
+            max_size: Some(1024),
+            depth: MetadataDepth::Infinity,
+        };
+        let cmd = CommandBuilder::get_metadata("", &["/shared", "/private"], &options);
+        assert_eq!(
+            cmd.args,
+            &b"GETMETADATA (MAXSIZE 1024 DEPTH infinity) \"\" (\"/shared\" \"/private\")"[..]
+        );
+        let values = [
+            ("/private/comment", Some(&b"My comment"[..])),
+            ("/shared/comment", None),
+        ];
+        let cmd = CommandBuilder::set_metadata("INBOX", &values);
+        assert_eq!(
+            cmd.args,
+            &b"SETMETADATA \"INBOX\" (\"/private/comment\" \"My comment\" \"/shared/comment\" NIL)"
+                [..]
+        );
+    }
+
+    #[test]
+    fn quota() {
+        let cmd = CommandBuilder::get_quota_root("INBOX");
+        assert_eq!(cmd.args, &b"GETQUOTAROOT \"INBOX\""[..]);
+        let cmd = CommandBuilder::get_quota("");
+        assert_eq!(cmd.args, &b"GETQUOTA \"\""[..]);
+        let cmd = CommandBuilder::set_quota("user.alice", &[("STORAGE", 512), ("MESSAGE", 100)]);
+        assert_eq!(
+            cmd.args,
+            &b"SETQUOTA \"user.alice\" (STORAGE 512 MESSAGE 100)"[..]
+        );
+    }
+
+    #[test]
+    fn mailbox_management() {
+        let cmd = CommandBuilder::create("Archive/2024");
+        assert_eq!(cmd.args, &b"CREATE \"Archive/2024\""[..]);
+        let cmd = CommandBuilder::delete("Archive/2024");
+        assert_eq!(cmd.args, &b"DELETE \"Archive/2024\""[..]);
+        let cmd = CommandBuilder::rename("Archive", "Old Archive");
+        assert_eq!(cmd.args, &b"RENAME \"Archive\" \"Old Archive\""[..]);
+    }
+
+    #[test]
+    fn copy_and_move() {
+        let set: SequenceSet = vec![4, 5, 9].into_iter().collect();
+        let cmd = CommandBuilder::uid_copy(&set, "Archive");
+        assert_eq!(cmd.args, &b"UID COPY 4:5,9 \"Archive\""[..]);
+        let cmd = CommandBuilder::uid_move(&set, "Trash");
+        assert_eq!(cmd.args, &b"UID MOVE 4:5,9 \"Trash\""[..]);
+    }
+
+    #[test]
+    fn store() {
+        let set: SequenceSet = vec![2, 3, 4].into_iter().collect();
+        let flags: FlagSet = vec!["$Junk", "\\Seen"].into_iter().collect();
+        let cmd = Command::from(CommandBuilder::store(&set, StoreMode::Add, &flags));
+        assert_eq!(cmd.args, &b"STORE 2:4 +FLAGS (\\Seen $Junk)"[..]);
+        let cmd = Command::from(
+            CommandBuilder::uid_store(&set, StoreMode::Remove, &flags)
+                .silent()
+                .unchanged_since(320162338),
+        );
+        assert_eq!(
+            cmd.args,
+            &b"UID STORE 2:4 (UNCHANGEDSINCE 320162338) -FLAGS.SILENT (\\Seen $Junk)"[..]
+        );
+        let cmd = CommandBuilder::uid_store(&set, StoreMode::Replace, &FlagSet::new());
+        assert_eq!(Command::from(cmd).args, &b"UID STORE 2:4 FLAGS ()"[..]);
+    }
+
+    #[test]
+    fn expunge() {
+        assert_eq!(CommandBuilder::expunge().args, &b"EXPUNGE"[..]);
+        let set: SequenceSet = vec![3, 4, 9].into_iter().collect();
+        let cmd = CommandBuilder::uid_expunge(&set);
+        assert_eq!(cmd.args, &b"UID EXPUNGE 3:4,9"[..]);
+    }
+
+    #[test]
+    fn search() {
+        let cmd = Command::from(CommandBuilder::search("UNSEEN"));
+        assert_eq!(cmd.args, &b"SEARCH UNSEEN"[..]);
+        let cmd = CommandBuilder::uid_search("SINCE 1-Feb-1994")
+            .returning(&[SearchReturn::Min, SearchReturn::All]);
+        assert_eq!(
+            cmd.args,
+            &b"UID SEARCH RETURN (MIN ALL) SINCE 1-Feb-1994"[..]
+        );
+    }
+
+    #[test]
+    fn sort() {
+        let order = [
+            SortCriterion::reverse(SortKey::Date),
+            SortCriterion::new(SortKey::Subject),
+        ];
+        let cmd = CommandBuilder::uid_sort(&order, &SearchCriteria::Unseen);
+        assert_eq!(
+            cmd.args,
+            &b"UID SORT (REVERSE DATE SUBJECT) UTF-8 UNSEEN"[..]
+        );
+    }
+
+    #[test]
+    fn enable() {
+        let cmd = CommandBuilder::enable(&["QRESYNC", "CONDSTORE"]);
+        assert_eq!(cmd.args, &b"ENABLE QRESYNC CONDSTORE"[..]);
     }
 
     #[test]
@@ -374,6 +1341,42 @@ mod tests {
             .attr(Attribute::ModSeq)
             .into();
         assert_eq!(cmd.args, &b"FETCH 1,2 (UID MODSEQ)"[..]);
+
+        let set: SequenceSet = vec![1, 2, 3, 7].into_iter().collect();
+        let cmd: Command = CommandBuilder::uid_fetch()
+            .set(&set)
+            .attr(Attribute::Uid)
+            .into();
+        assert_eq!(cmd.args, &b"UID FETCH 1:3,7 (UID)"[..]);
+
+        let cmd: Command = CommandBuilder::uid_fetch()
+            .num(42)
+            .attr(Attribute::BodyPeek)
+            .into();
+        assert_eq!(cmd.args, &b"UID FETCH 42 (BODY.PEEK[])"[..]);
+
+        let cmd: Command = CommandBuilder::uid_fetch()
+            .num(42)
+            .attr(Attribute::BodyPeekSection(SectionPath::Part(
+                vec![1, 2],
+                None,
+            )))
+            .attr(Attribute::BodySection(SectionPath::Part(
+                vec![2],
+                Some(MessageSection::Mime),
+            )))
+            .into();
+        assert_eq!(cmd.args, &b"UID FETCH 42 (BODY.PEEK[1.2] BODY[2.MIME])"[..]);
+
+        let names = vec!["From".to_string(), "X-Spam Score".to_string()];
+        let cmd: Command = CommandBuilder::uid_fetch()
+            .num(42)
+            .attr(Attribute::BodyPeekHeaderFields(names))
+            .into();
+        assert_eq!(
+            cmd.args,
+            &b"UID FETCH 42 (BODY.PEEK[HEADER.FIELDS (From \"X-Spam Score\")])"[..]
+        );
     }
 
     #[test]
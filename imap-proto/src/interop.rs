@@ -0,0 +1,247 @@
+//! Conversions into the types of other mail crates, so that data fetched over
+//! IMAP can be passed on to them (for example, to reply over SMTP).
+//!
+//! Each conversion is enabled by the feature named after the crate: `lettre`
+//! or `mail-parser`. Display names and subjects are decoded (RFC 2047).
+
+#[cfg(feature = "lettre")]
+use std::convert::TryFrom;
+
+#[cfg(feature = "lettre")]
+use crate::types::Addresses;
+#[cfg(any(feature = "lettre", feature = "mail-parser"))]
+use crate::types::{Address, Envelope};
+#[cfg(feature = "mail-parser")]
+use crate::types::{AddressEntry, AddressList};
+
+#[cfg(feature = "lettre")]
+impl<'a> TryFrom<&Address<'a>> for lettre::message::Mailbox {
+    type Error = lettre::address::AddressError;
+
+    /// Fails with `AddressError::MissingParts` for group markers.
+    fn try_from(addr: &Address<'a>) -> Result<Self, Self::Error> {
+        let (mailbox, host) = match (&addr.mailbox, &addr.host) {
+            (Some(mailbox), Some(host)) => (mailbox, host),
+            _ => return Err(lettre::address::AddressError::MissingParts),
+        };
+        let email = lettre::Address::new(
+            String::from_utf8_lossy(mailbox),
+            String::from_utf8_lossy(host),
+        )?;
+        Ok(Self::new(addr.display_name(), email))
+    }
+}
+
+#[cfg(feature = "lettre")]
+impl<'a> Envelope<'a> {
+    /// A message builder with the addresses, subject and identifiers of this
+    /// envelope.
+    ///
+    /// Group markers and addresses that lettre considers invalid are left out.
+    pub fn to_message_builder(&self) -> lettre::message::MessageBuilder {
+        let mailboxes = |list: &Option<Addresses<'a>>| {
+            list.iter()
+                .flatten()
+                .filter_map(|addr| lettre::message::Mailbox::try_from(addr).ok())
+                .collect::<Vec<_>>()
+        };
+
+        let mut builder = lettre::message::MessageBuilder::new();
+        for mailbox in mailboxes(&self.from) {
+            builder = builder.from(mailbox);
+        }
+        if let Some(mailbox) = mailboxes(&self.sender).into_iter().next() {
+            builder = builder.sender(mailbox);
+        }
+        for mailbox in mailboxes(&self.reply_to) {
+            builder = builder.reply_to(mailbox);
+        }
+        for mailbox in mailboxes(&self.to) {
+            builder = builder.to(mailbox);
+        }
+        for mailbox in mailboxes(&self.cc) {
+            builder = builder.cc(mailbox);
+        }
+        for mailbox in mailboxes(&self.bcc) {
+            builder = builder.bcc(mailbox);
+        }
+        if let Some(subject) = self.decoded_subject() {
+            builder = builder.subject(subject);
+        }
+        if let Some(id) = &self.in_reply_to {
+            builder = builder.in_reply_to(String::from_utf8_lossy(id).into_owned());
+        }
+        if let Some(id) = &self.message_id {
+            builder = builder.message_id(Some(String::from_utf8_lossy(id).into_owned()));
+        }
+        builder
+    }
+}
+
+#[cfg(feature = "mail-parser")]
+impl<'a> From<&Address<'a>> for mail_parser::Addr<'static> {
+    fn from(addr: &Address<'a>) -> Self {
+        Self {
+            name: addr.display_name().map(Into::into),
+            address: addr.email().map(Into::into),
+        }
+    }
+}
+
+#[cfg(feature = "mail-parser")]
+impl<'a> Envelope<'a> {
+    /// The `From` addresses, in the form used by mail-parser.
+    pub fn from_addresses(&self) -> Option<mail_parser::Address<'static>> {
+        self.from.as_deref().map(address_list)
+    }
+
+    /// The `To` addresses, in the form used by mail-parser.
+    pub fn to_addresses(&self) -> Option<mail_parser::Address<'static>> {
+        self.to.as_deref().map(address_list)
+    }
+
+    /// The `Cc` addresses, in the form used by mail-parser.
+    pub fn cc_addresses(&self) -> Option<mail_parser::Address<'static>> {
+        self.cc.as_deref().map(address_list)
+    }
+
+    /// The `Bcc` addresses, in the form used by mail-parser.
+    pub fn bcc_addresses(&self) -> Option<mail_parser::Address<'static>> {
+        self.bcc.as_deref().map(address_list)
+    }
+
+    /// The `Reply-To` addresses, in the form used by mail-parser.
+    pub fn reply_to_addresses(&self) -> Option<mail_parser::Address<'static>> {
+        self.reply_to.as_deref().map(address_list)
+    }
+}
+
+/// Convert an address list from an envelope, which may contain groups (RFC 3501,
+/// section 7.4.2), into the mail-parser form.
+///
+/// The result is an `Address::Group` if the list contains any group, with the
+/// addresses outside of groups collected in a group without a name.
+#[cfg(feature = "mail-parser")]
+pub fn address_list(list: &[Address<'_>]) -> mail_parser::Address<'static> {
+    use mail_parser::{Addr, Group};
+
+    let mut groups = Vec::new();
+    let mut ungrouped = Vec::new();
+    for entry in AddressList(list).entries() {
+        match entry {
+            AddressEntry::Mailbox(addr) => ungrouped.push(Addr::from(addr)),
+            AddressEntry::Group { name, members } => groups.push(Group {
+                name: Some(String::from_utf8_lossy(name).into_owned().into()),
+                addresses: members.into_iter().map(Addr::from).collect(),
+            }),
+        }
+    }
+
+    if groups.is_empty() {
+        return mail_parser::Address::List(ungrouped);
+    }
+    if !ungrouped.is_empty() {
+        groups.insert(
+            0,
+            Group {
+                name: None,
+                addresses: ungrouped,
+            },
+        );
+    }
+    mail_parser::Address::Group(groups)
+}
+
+#[cfg(all(test, feature = "lettre"))]
+mod lettre_tests {
+    use std::borrow::Cow;
+    use std::convert::TryFrom;
+
+    use crate::types::{Address, Envelope};
+
+    #[test]
+    fn mailbox() {
+        let addr = Address {
+            name: Some(Cow::Borrowed(b"=?ISO-8859-1?Q?Andr=E9?= Pirard")),
+            adl: None,
+            mailbox: Some(Cow::Borrowed(b"pirard")),
+            host: Some(Cow::Borrowed(b"vm1.ulg.ac.be")),
+        };
+        let mailbox = lettre::message::Mailbox::try_from(&addr).unwrap();
+        assert_eq!(mailbox.name.as_deref(), Some("André Pirard"));
+        assert_eq!(mailbox.email.to_string(), "pirard@vm1.ulg.ac.be");
+
+        let envelope = Envelope {
+            date: None,
+            subject: Some(Cow::Borrowed(b"=?utf-8?q?Caf=C3=A9?=")),
+            from: Some(vec![addr.clone()]),
+            sender: None,
+            reply_to: None,
+            to: Some(vec![addr]),
+            cc: None,
+            bcc: None,
+            in_reply_to: None,
+            message_id: Some(Cow::Borrowed(b"<1234@example.com>")),
+        };
+        let message = envelope.to_message_builder().body(String::new()).unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("Subject: =?utf-8?b?Q2Fmw6k=?="));
+        assert!(formatted.contains("Message-ID: <1234@example.com>"));
+    }
+}
+
+#[cfg(all(test, feature = "mail-parser"))]
+mod mail_parser_tests {
+    use std::borrow::Cow;
+
+    use super::address_list;
+    use crate::types::Address;
+
+    #[test]
+    fn groups() {
+        let list = [
+            Address {
+                name: Some(Cow::Borrowed(b"Fred")),
+                adl: None,
+                mailbox: Some(Cow::Borrowed(b"fred")),
+                host: Some(Cow::Borrowed(b"example.com")),
+            },
+            Address {
+                name: None,
+                adl: None,
+                mailbox: Some(Cow::Borrowed(b"friends")),
+                host: None,
+            },
+            Address {
+                name: None,
+                adl: None,
+                mailbox: Some(Cow::Borrowed(b"joe")),
+                host: Some(Cow::Borrowed(b"example.com")),
+            },
+            Address {
+                name: None,
+                adl: None,
+                mailbox: None,
+                host: None,
+            },
+        ];
+        match address_list(&list[..1]) {
+            mail_parser::Address::List(addrs) => {
+                assert_eq!(addrs[0].name.as_deref(), Some("Fred"));
+                assert_eq!(addrs[0].address.as_deref(), Some("fred@example.com"));
+            }
+            addr => panic!("unexpected address: {:?}", addr),
+        }
+        match address_list(&list) {
+            mail_parser::Address::Group(groups) => {
+                assert_eq!(groups.len(), 2);
+                assert_eq!(groups[1].name.as_deref(), Some("friends"));
+                assert_eq!(
+                    groups[1].addresses[0].address.as_deref(),
+                    Some("joe@example.com")
+                );
+            }
+            addr => panic!("unexpected address: {:?}", addr),
+        }
+    }
+}
@@ -1,5 +1,8 @@
 pub mod builders;
+pub mod interop;
+pub mod owned;
 pub mod parser;
+pub mod server;
 pub mod types;
 
 pub use crate::parser::ParseResult;
@@ -0,0 +1,377 @@
+//! Owned versions of the envelope and body structure types, which can be kept
+//! after the buffer a response was parsed from is gone.
+//!
+//! With the `rkyv` feature, these types can be archived with rkyv, so that large
+//! numbers of them can be stored and accessed again (for example, through a
+//! memory map) without parsing.
+
+use std::borrow::Cow;
+
+use crate::types;
+
+#[cfg(feature = "rkyv")]
+use rkyv::{Archive, Deserialize, Serialize};
+
+pub type BodyParams = Option<Vec<(String, String)>>;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+pub struct Envelope {
+    pub date: Option<Vec<u8>>,
+    pub subject: Option<Vec<u8>>,
+    pub from: Option<Vec<Address>>,
+    pub sender: Option<Vec<Address>>,
+    pub reply_to: Option<Vec<Address>>,
+    pub to: Option<Vec<Address>>,
+    pub cc: Option<Vec<Address>>,
+    pub bcc: Option<Vec<Address>>,
+    pub in_reply_to: Option<Vec<u8>>,
+    pub message_id: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+pub struct Address {
+    pub name: Option<Vec<u8>>,
+    pub adl: Option<Vec<u8>>,
+    pub mailbox: Option<Vec<u8>>,
+    pub host: Option<Vec<u8>>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    rkyv(
+        serialize_bounds(
+            __S: rkyv::ser::Writer + rkyv::ser::Allocator,
+            __S::Error: rkyv::rancor::Source,
+        ),
+        deserialize_bounds(__D::Error: rkyv::rancor::Source),
+        bytecheck(bounds(
+            __C: rkyv::validation::ArchiveContext,
+            __C::Error: rkyv::rancor::Source,
+        )),
+    )
+)]
+pub enum BodyStructure {
+    Basic {
+        common: BodyContentCommon,
+        other: BodyContentSinglePart,
+        extension: Option<BodyExtension>,
+    },
+    Text {
+        common: BodyContentCommon,
+        other: BodyContentSinglePart,
+        lines: u32,
+        extension: Option<BodyExtension>,
+    },
+    Message {
+        common: BodyContentCommon,
+        other: BodyContentSinglePart,
+        envelope: Envelope,
+        #[cfg_attr(feature = "rkyv", rkyv(omit_bounds))]
+        body: Box<BodyStructure>,
+        lines: u32,
+        extension: Option<BodyExtension>,
+    },
+    Multipart {
+        common: BodyContentCommon,
+        #[cfg_attr(feature = "rkyv", rkyv(omit_bounds))]
+        bodies: Vec<BodyStructure>,
+        extension: Option<BodyExtension>,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+pub struct BodyContentCommon {
+    pub ty: ContentType,
+    pub disposition: Option<ContentDisposition>,
+    pub language: Option<Vec<String>>,
+    pub location: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+pub struct BodyContentSinglePart {
+    pub id: Option<String>,
+    pub md5: Option<String>,
+    pub description: Option<String>,
+    pub transfer_encoding: ContentEncoding,
+    pub octets: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+pub struct ContentType {
+    pub ty: String,
+    pub subtype: String,
+    pub params: BodyParams,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+pub struct ContentDisposition {
+    pub ty: String,
+    pub params: BodyParams,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+pub enum ContentEncoding {
+    SevenBit,
+    EightBit,
+    Binary,
+    Base64,
+    QuotedPrintable,
+    Other(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "rkyv", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    rkyv(
+        serialize_bounds(
+            __S: rkyv::ser::Writer + rkyv::ser::Allocator,
+            __S::Error: rkyv::rancor::Source,
+        ),
+        deserialize_bounds(__D::Error: rkyv::rancor::Source),
+        bytecheck(bounds(
+            __C: rkyv::validation::ArchiveContext,
+            __C::Error: rkyv::rancor::Source,
+        )),
+    )
+)]
+pub enum BodyExtension {
+    Num(u32),
+    Str(Option<String>),
+    List(#[cfg_attr(feature = "rkyv", rkyv(omit_bounds))] Vec<BodyExtension>),
+}
+
+fn bytes(data: &Option<Cow<'_, [u8]>>) -> Option<Vec<u8>> {
+    data.as_deref().map(<[u8]>::to_vec)
+}
+
+fn string(s: &Option<Cow<'_, str>>) -> Option<String> {
+    s.as_deref().map(str::to_string)
+}
+
+fn addresses(list: &Option<types::Addresses<'_>>) -> Option<Vec<Address>> {
+    list.as_ref()
+        .map(|list| list.iter().map(Address::from).collect())
+}
+
+fn params(params: &types::BodyParams<'_>) -> BodyParams {
+    params.as_ref().map(|params| {
+        params
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    })
+}
+
+impl From<&types::Envelope<'_>> for Envelope {
+    fn from(envelope: &types::Envelope<'_>) -> Self {
+        Self {
+            date: bytes(&envelope.date),
+            subject: bytes(&envelope.subject),
+            from: addresses(&envelope.from),
+            sender: addresses(&envelope.sender),
+            reply_to: addresses(&envelope.reply_to),
+            to: addresses(&envelope.to),
+            cc: addresses(&envelope.cc),
+            bcc: addresses(&envelope.bcc),
+            in_reply_to: bytes(&envelope.in_reply_to),
+            message_id: bytes(&envelope.message_id),
+        }
+    }
+}
+
+impl From<&types::Address<'_>> for Address {
+    fn from(addr: &types::Address<'_>) -> Self {
+        Self {
+            name: bytes(&addr.name),
+            adl: bytes(&addr.adl),
+            mailbox: bytes(&addr.mailbox),
+            host: bytes(&addr.host),
+        }
+    }
+}
+
+impl From<&types::BodyStructure<'_>> for BodyStructure {
+    fn from(body: &types::BodyStructure<'_>) -> Self {
+        let extension = |ext: &Option<types::BodyExtension<'_>>| ext.as_ref().map(Into::into);
+        match body {
+            types::BodyStructure::Basic {
+                common,
+                other,
+                extension: ext,
+            } => BodyStructure::Basic {
+                common: common.into(),
+                other: other.into(),
+                extension: extension(ext),
+            },
+            types::BodyStructure::Text {
+                common,
+                other,
+                lines,
+                extension: ext,
+            } => BodyStructure::Text {
+                common: common.into(),
+                other: other.into(),
+                lines: *lines,
+                extension: extension(ext),
+            },
+            types::BodyStructure::Message {
+                common,
+                other,
+                envelope,
+                body,
+                lines,
+                extension: ext,
+            } => BodyStructure::Message {
+                common: common.into(),
+                other: other.into(),
+                envelope: envelope.into(),
+                body: Box::new((&**body).into()),
+                lines: *lines,
+                extension: extension(ext),
+            },
+            types::BodyStructure::Multipart {
+                common,
+                bodies,
+                extension: ext,
+            } => BodyStructure::Multipart {
+                common: common.into(),
+                bodies: bodies.iter().map(Into::into).collect(),
+                extension: extension(ext),
+            },
+        }
+    }
+}
+
+impl From<&types::BodyContentCommon<'_>> for BodyContentCommon {
+    fn from(common: &types::BodyContentCommon<'_>) -> Self {
+        Self {
+            ty: ContentType {
+                ty: common.ty.ty.to_string(),
+                subtype: common.ty.subtype.to_string(),
+                params: params(&common.ty.params),
+            },
+            disposition: common
+                .disposition
+                .as_ref()
+                .map(|disposition| ContentDisposition {
+                    ty: disposition.ty.to_string(),
+                    params: params(&disposition.params),
+                }),
+            language: common
+                .language
+                .as_ref()
+                .map(|language| language.iter().map(|tag| tag.to_string()).collect()),
+            location: string(&common.location),
+        }
+    }
+}
+
+impl From<&types::BodyContentSinglePart<'_>> for BodyContentSinglePart {
+    fn from(other: &types::BodyContentSinglePart<'_>) -> Self {
+        Self {
+            id: string(&other.id),
+            md5: string(&other.md5),
+            description: string(&other.description),
+            transfer_encoding: match &other.transfer_encoding {
+                types::ContentEncoding::SevenBit => ContentEncoding::SevenBit,
+                types::ContentEncoding::EightBit => ContentEncoding::EightBit,
+                types::ContentEncoding::Binary => ContentEncoding::Binary,
+                types::ContentEncoding::Base64 => ContentEncoding::Base64,
+                types::ContentEncoding::QuotedPrintable => ContentEncoding::QuotedPrintable,
+                types::ContentEncoding::Other(other) => ContentEncoding::Other(other.to_string()),
+            },
+            octets: other.octets,
+        }
+    }
+}
+
+impl From<&types::BodyExtension<'_>> for BodyExtension {
+    fn from(ext: &types::BodyExtension<'_>) -> Self {
+        match ext {
+            types::BodyExtension::Num(n) => BodyExtension::Num(*n),
+            types::BodyExtension::Str(s) => BodyExtension::Str(string(s)),
+            types::BodyExtension::List(list) => {
+                BodyExtension::List(list.iter().map(Into::into).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BodyStructure, ContentEncoding};
+    use crate::parser::parse_response;
+    use crate::types::{AttributeValue, Response};
+
+    const RESPONSE: &[u8] = b"* 1 FETCH (BODYSTRUCTURE (\
+        (\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 12 1 NIL NIL NIL NIL)\
+        (\"MESSAGE\" \"RFC822\" NIL NIL NIL \"7BIT\" 500 \
+        (NIL \"Fwd\" ((NIL NIL \"a\" \"example.com\")) NIL NIL NIL NIL NIL NIL NIL) \
+        (\"TEXT\" \"PLAIN\" (\"CHARSET\" \"us-ascii\") NIL NIL \"BASE64\" 20 1 NIL NIL NIL NIL) \
+        10 NIL NIL NIL NIL) \
+        \"MIXED\" (\"BOUNDARY\" \"b1\") NIL NIL NIL))\r\n";
+
+    fn parse() -> BodyStructure {
+        match parse_response(RESPONSE) {
+            Ok((_, Response::Fetch(_, attrs))) => match &attrs[0] {
+                AttributeValue::BodyStructure(body) => body.into(),
+                attr => panic!("unexpected attribute: {:?}", attr),
+            },
+            rsp => panic!("unexpected response: {:?}", rsp),
+        }
+    }
+
+    #[test]
+    fn into_owned() {
+        let body = parse();
+        let bodies = match &body {
+            BodyStructure::Multipart { bodies, common, .. } => {
+                assert_eq!(common.ty.subtype, "MIXED");
+                bodies
+            }
+            body => panic!("unexpected body: {:?}", body),
+        };
+        match &bodies[1] {
+            BodyStructure::Message { envelope, body, .. } => {
+                assert_eq!(envelope.subject.as_deref(), Some(&b"Fwd"[..]));
+                let from = envelope.from.as_ref().unwrap();
+                assert_eq!(from[0].host.as_deref(), Some(&b"example.com"[..]));
+                match &**body {
+                    BodyStructure::Text { other, .. } => {
+                        assert_eq!(other.transfer_encoding, ContentEncoding::Base64)
+                    }
+                    body => panic!("unexpected body: {:?}", body),
+                }
+            }
+            body => panic!("unexpected body: {:?}", body),
+        }
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archive() {
+        use super::ArchivedBodyStructure;
+        use rkyv::rancor::Error;
+
+        let body = parse();
+        let bytes = rkyv::to_bytes::<Error>(&body).unwrap();
+        let archived = rkyv::access::<ArchivedBodyStructure, Error>(&bytes).unwrap();
+        match archived {
+            ArchivedBodyStructure::Multipart { bodies, .. } => assert_eq!(bodies.len(), 2),
+            _ => panic!("unexpected body"),
+        }
+        let body2 = rkyv::deserialize::<BodyStructure, Error>(archived).unwrap();
+        assert_eq!(body, body2);
+    }
+}
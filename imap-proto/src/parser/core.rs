@@ -2,14 +2,17 @@ use nom::{
     branch::alt,
     bytes::streaming::{escaped, tag, tag_no_case, take, take_while, take_while1},
     character::streaming::{char, digit1, one_of},
-    combinator::{map, map_res},
+    combinator::{map, map_res, opt, recognize},
     multi::{separated_list, separated_nonempty_list},
-    sequence::{delimited, tuple},
+    sequence::{delimited, preceded, tuple},
     IResult,
 };
 
+use std::borrow::Cow;
 use std::str::{from_utf8, FromStr};
 
+use crate::types::SequenceSet;
+
 // ----- number -----
 
 // number          = 1*DIGIT
@@ -38,34 +41,91 @@ pub fn number_64(i: &[u8]) -> IResult<&[u8], u64> {
     }
 }
 
+// ----- sequence set -----
+
+// sequence-set    = (seq-number / seq-range) *("," sequence-set)
+// seq-range       = seq-number ":" seq-number
+//
+// Only numeric sets are accepted, as sent by servers (e.g. in UID sets);
+// the `*` placeholder is only valid in commands.
+pub fn sequence_set(i: &[u8]) -> IResult<&[u8], SequenceSet> {
+    let (i, ranges) =
+        separated_nonempty_list(char(','), tuple((number, opt(preceded(char(':'), number)))))(i)?;
+    let mut set = SequenceSet::new();
+    for (start, end) in ranges {
+        let end = end.unwrap_or(start);
+        set.insert_range(start.min(end)..=start.max(end));
+    }
+    Ok((i, set))
+}
+
 // ----- string -----
 
 // string = quoted / literal
-pub fn string(i: &[u8]) -> IResult<&[u8], &[u8]> {
-    alt((quoted, literal))(i)
+pub fn string(i: &[u8]) -> IResult<&[u8], Cow<'_, [u8]>> {
+    alt((quoted, map(literal, Cow::Borrowed)))(i)
 }
 
 // string bytes as utf8
-pub fn string_utf8(i: &[u8]) -> IResult<&[u8], &str> {
-    map_res(string, from_utf8)(i)
+pub fn string_utf8(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    map_res(string, utf8)(i)
 }
 
 // quoted = DQUOTE *QUOTED-CHAR DQUOTE
-pub fn quoted(i: &[u8]) -> IResult<&[u8], &[u8]> {
-    delimited(
-        char('"'),
-        escaped(
-            take_while1(|byte| is_text_char(byte) && !is_quoted_specials(byte)),
-            '\\',
-            one_of("\\\""),
+//
+// The content is returned with the escaping backslashes removed, borrowed from
+// the input unless it contains any.
+pub fn quoted(i: &[u8]) -> IResult<&[u8], Cow<'_, [u8]>> {
+    // utf8-quoted = DQUOTE *UQUOTED-CHAR DQUOTE (RFC 6855), if accepted
+    let utf8 = super::quirks::current().accepts_utf8_quoted();
+    map(
+        delimited(
+            char('"'),
+            recognize(opt(escaped(
+                take_while1(move |byte| {
+                    (is_text_char(byte) || (utf8 && byte >= 0x80)) && !is_quoted_specials(byte)
+                }),
+                '\\',
+                one_of("\\\""),
+            ))),
+            char('"'),
         ),
-        char('"'),
+        unescape_quoted,
     )(i)
 }
 
 // quoted bytes as utf8
-pub fn quoted_utf8(i: &[u8]) -> IResult<&[u8], &str> {
-    map_res(quoted, from_utf8)(i)
+pub fn quoted_utf8(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    map_res(quoted, utf8)(i)
+}
+
+// QUOTED-CHAR = <any TEXT-CHAR except quoted-specials> / "\" quoted-specials
+fn unescape_quoted(raw: &[u8]) -> Cow<'_, [u8]> {
+    if !raw.contains(&b'\\') {
+        return Cow::Borrowed(raw);
+    }
+    let mut out = Vec::with_capacity(raw.len());
+    let mut escaped = false;
+    for &b in raw {
+        if b == b'\\' && !escaped {
+            escaped = true;
+            continue;
+        }
+        out.push(b);
+        escaped = false;
+    }
+    Cow::Owned(out)
+}
+
+/// Convert the bytes of a string to UTF-8, without copying them if they are
+/// borrowed.
+pub fn utf8(bytes: Cow<[u8]>) -> Result<Cow<str>, std::str::Utf8Error> {
+    match bytes {
+        Cow::Borrowed(bytes) => from_utf8(bytes).map(Cow::Borrowed),
+        Cow::Owned(bytes) => String::from_utf8(bytes)
+            .map(Cow::Owned)
+            .map_err(|e| e.utf8_error()),
+    }
 }
 
 // quoted-specials = DQUOTE / "\"
@@ -73,10 +133,30 @@ pub fn is_quoted_specials(c: u8) -> bool {
     c == b'"' || c == b'\\'
 }
 
+// CRLF = CR LF
+//
+// A bare LF is accepted too if the current quirks allow it.
+pub fn crlf(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    if super::quirks::current().accepts_bare_lf() {
+        alt((tag("\r\n"), tag("\n")))(i)
+    } else {
+        tag("\r\n")(i)
+    }
+}
+
+/// SP, or any run of spaces (none included) with the loose spacing quirk.
+pub fn sp(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    if super::quirks::current().accepts_loose_spacing() {
+        take_while(|c| c == b' ')(i)
+    } else {
+        tag(" ")(i)
+    }
+}
+
 /// literal = "{" number "}" CRLF *CHAR8
 ///            ; Number represents the number of CHAR8s
 pub fn literal(input: &[u8]) -> IResult<&[u8], &[u8]> {
-    let parser = tuple((tag(b"{"), number, tag(b"}"), tag("\r\n")));
+    let parser = tuple((tag(b"{"), number, tag(b"}"), crlf));
 
     let (remaining, (_, count, _, _)) = parser(input)?;
 
@@ -90,6 +170,13 @@ pub fn literal(input: &[u8]) -> IResult<&[u8], &[u8]> {
     Ok((remaining, data))
 }
 
+/// literal8 = "~{" number "}" CRLF *OCTET  (RFC 3516)
+///            ; Number represents the number of OCTETs
+pub fn literal8(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (remaining, (_, count, _, _)) = tuple((tag(b"~{"), number, tag(b"}"), crlf))(input)?;
+    take(count)(remaining)
+}
+
 /// CHAR8 = %x01-ff ; any OCTET except NUL, %x00
 pub fn is_char8(i: u8) -> bool {
     i != 0
@@ -98,13 +185,13 @@ pub fn is_char8(i: u8) -> bool {
 // ----- astring ----- atom (roughly) or string
 
 // astring = 1*ASTRING-CHAR / string
-pub fn astring(i: &[u8]) -> IResult<&[u8], &[u8]> {
-    alt((take_while1(is_astring_char), string))(i)
+pub fn astring(i: &[u8]) -> IResult<&[u8], Cow<'_, [u8]>> {
+    alt((map(take_while1(is_astring_char), Cow::Borrowed), string))(i)
 }
 
 // astring bytes as utf8
-pub fn astring_utf8(i: &[u8]) -> IResult<&[u8], &str> {
-    map_res(astring, from_utf8)(i)
+pub fn astring_utf8(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    map_res(astring, utf8)(i)
 }
 
 // ASTRING-CHAR = ATOM-CHAR / resp-specials
@@ -142,12 +229,12 @@ pub fn atom(i: &[u8]) -> IResult<&[u8], &str> {
 // ----- nstring ----- nil or string
 
 // nstring = string / nil
-pub fn nstring(i: &[u8]) -> IResult<&[u8], Option<&[u8]>> {
+pub fn nstring(i: &[u8]) -> IResult<&[u8], Option<Cow<'_, [u8]>>> {
     alt((map(nil, |_| None), map(string, Some)))(i)
 }
 
 // nstring bytes as utf8
-pub fn nstring_utf8(i: &[u8]) -> IResult<&[u8], Option<&str>> {
+pub fn nstring_utf8(i: &[u8]) -> IResult<&[u8], Option<Cow<'_, str>>> {
     alt((map(nil, |_| None), map(string_utf8, Some)))(i)
 }
 
@@ -163,6 +250,13 @@ pub fn text(i: &[u8]) -> IResult<&[u8], &str> {
     map_res(take_while(is_text_char), from_utf8)(i)
 }
 
+// Human-readable text, as in `resp-text`. Servers send 8-bit text in other
+// charsets and stray control characters too (such as localized Exchange
+// errors), so any byte but CR and LF is accepted and kept as sent.
+pub fn text_8bit(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    take_while(|c| c != b'\r' && c != b'\n')(i)
+}
+
 // TEXT-CHAR = <any CHAR except CR and LF>
 pub fn is_text_char(c: u8) -> bool {
     is_char(c) && c != b'\r' && c != b'\n'
@@ -232,7 +326,8 @@ mod tests {
     fn test_quoted() {
         let (rem, val) = quoted(br#""Hello"???"#).unwrap();
         assert_eq!(rem, b"???");
-        assert_eq!(val, b"Hello");
+        assert_eq!(val, &b"Hello"[..]);
+        assert!(matches!(val, Cow::Borrowed(_)));
 
         // Allowed escapes...
         assert!(quoted(br#""Hello \" "???"#).is_ok());
@@ -245,10 +340,14 @@ mod tests {
 
         let (rem, val) = quoted(br#""Hello \"World\""???"#).unwrap();
         assert_eq!(rem, br#"???"#);
-        // Should it be this (Hello \"World\") ...
-        assert_eq!(val, br#"Hello \"World\""#);
-        // ... or this (Hello "World")?
-        //assert_eq!(val, br#"Hello "World""#); // fails
+        assert_eq!(val, &br#"Hello "World""#[..]);
+
+        let (_, val) = quoted(br#""C:\\Temp\\" "#).unwrap();
+        assert_eq!(val, &br#"C:\Temp\"#[..]);
+        let (_, val) = quoted(br#""""#).unwrap();
+        assert_eq!(val, &b""[..]);
+        let (_, val) = quoted_utf8(br#""\\\"""#).unwrap();
+        assert_eq!(val, r#"\""#);
 
         // Test Incomplete
         assert_matches!(quoted(br#""#), Err(nom::Err::Incomplete(_)));
@@ -263,7 +362,7 @@ mod tests {
     fn test_string_literal() {
         match string(b"{3}\r\nXYZ") {
             Ok((_, value)) => {
-                assert_eq!(value, b"XYZ");
+                assert_eq!(value, &b"XYZ"[..]);
             }
             rsp => panic!("unexpected response {:?}", rsp),
         }
@@ -273,7 +372,7 @@ mod tests {
     fn test_astring() {
         match astring(b"text ") {
             Ok((_, value)) => {
-                assert_eq!(value, b"text");
+                assert_eq!(value, &b"text"[..]);
             }
             rsp => panic!("unexpected response {:?}", rsp),
         }
@@ -2,11 +2,26 @@ use crate::types::Response;
 use nom::{branch::alt, IResult};
 
 pub mod core;
+mod quirks;
 
+pub mod rfc2221;
+pub mod rfc2342;
+pub mod rfc2971;
 pub mod rfc3501;
+pub mod rfc4314;
+pub mod rfc4315;
 pub mod rfc4551;
+pub mod rfc4731;
 pub mod rfc5161;
+pub mod rfc5256;
+pub mod rfc5258;
 pub mod rfc5464;
+pub mod rfc5530;
+pub mod rfc7162;
+pub mod rfc8438;
+pub mod rfc9208;
+
+pub use self::quirks::Quirks;
 
 #[cfg(test)]
 mod tests;
@@ -19,4 +34,15 @@ pub fn parse_response(msg: &[u8]) -> ParseResult {
     ))(msg)
 }
 
+/// Parse a response, accepting the deviations from the grammar selected by
+/// `quirks`.
+///
+/// The quirks are held in a thread-local for the duration of the call, so the
+/// parsers need not pass them along. When the call returns or panics, the
+/// quirks in effect before it are restored: they only apply to this parse, and
+/// calls can be nested.
+pub fn parse_response_with<'a>(msg: &'a [u8], quirks: &Quirks) -> ParseResult<'a> {
+    quirks::with(*quirks, || parse_response(msg))
+}
+
 pub type ParseResult<'a> = IResult<&'a [u8], Response<'a>>;
@@ -0,0 +1,156 @@
+//! Workarounds for servers that do not follow the grammar of RFC 3501.
+//!
+//! The parser is strict by default. `parse_response_with()` parses a response
+//! with some deviations seen in the wild accepted, as selected by `Quirks`.
+
+use std::cell::Cell;
+
+/// The deviations from the grammar accepted by `parse_response_with()`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Quirks {
+    bare_lf: bool,
+    nonstandard_flags: bool,
+    loose_flag_lists: bool,
+    loose_spacing: bool,
+    utf8_quoted: bool,
+    missing_imap4rev: bool,
+}
+
+impl Quirks {
+    /// The strict grammar, as used by `parse_response()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept all the deviations known to this version.
+    pub fn lenient() -> Self {
+        Self {
+            bare_lf: true,
+            nonstandard_flags: true,
+            loose_flag_lists: true,
+            loose_spacing: true,
+            utf8_quoted: true,
+            missing_imap4rev: true,
+        }
+    }
+
+    /// Accept lines terminated by a bare LF rather than CRLF, as sent by some
+    /// gateways and misconfigured servers.
+    pub fn bare_lf(mut self, enabled: bool) -> Self {
+        self.bare_lf = enabled;
+        self
+    }
+
+    pub fn accepts_bare_lf(&self) -> bool {
+        self.bare_lf
+    }
+
+    /// Accept flags containing characters that are not allowed in atoms, such
+    /// as `$Forwarded,` or `\Foo]` sent by some Exchange and Domino versions.
+    /// They are returned as custom flags, as sent by the server.
+    pub fn nonstandard_flags(mut self, enabled: bool) -> Self {
+        self.nonstandard_flags = enabled;
+        self
+    }
+
+    pub fn accepts_nonstandard_flags(&self) -> bool {
+        self.nonstandard_flags
+    }
+
+    /// Accept `NIL` as an empty flag list, and extra spaces around and inside
+    /// flag lists, such as `FLAGS ( )` sent by some Exchange versions.
+    pub fn loose_flag_lists(mut self, enabled: bool) -> Self {
+        self.loose_flag_lists = enabled;
+        self
+    }
+
+    pub fn accepts_loose_flag_lists(&self) -> bool {
+        self.loose_flag_lists
+    }
+
+    /// Accept extra or missing spaces between FETCH attributes and after
+    /// their names, such as `UID 4FLAGS (\Seen)` sent by some Domino and
+    /// hMailServer versions.
+    pub fn loose_spacing(mut self, enabled: bool) -> Self {
+        self.loose_spacing = enabled;
+        self
+    }
+
+    pub fn accepts_loose_spacing(&self) -> bool {
+        self.loose_spacing
+    }
+
+    /// Accept UTF-8 in quoted strings, as sent by servers once `UTF8=ACCEPT`
+    /// is enabled (RFC 6855).
+    pub fn utf8_quoted(mut self, enabled: bool) -> Self {
+        self.utf8_quoted = enabled;
+        self
+    }
+
+    pub fn accepts_utf8_quoted(&self) -> bool {
+        self.utf8_quoted
+    }
+
+    /// Accept capability lists that advertise neither `IMAP4rev1` nor
+    /// `IMAP4rev2`, as sent by some appliances. Without this, such a
+    /// `CAPABILITY` response code is left in the text of the response.
+    pub fn missing_imap4rev(mut self, enabled: bool) -> Self {
+        self.missing_imap4rev = enabled;
+        self
+    }
+
+    pub fn accepts_missing_imap4rev(&self) -> bool {
+        self.missing_imap4rev
+    }
+
+    /// The deviations known to be needed for the server software `name`, as
+    /// sent in the `name` field of its `ID` response (RFC 2971), or none if
+    /// the server is not known to need any.
+    pub fn for_server(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        if name.contains("exchange") {
+            Self::new().nonstandard_flags(true).loose_flag_lists(true)
+        } else if name.contains("domino") {
+            Self::new().nonstandard_flags(true).loose_spacing(true)
+        } else if name.contains("davmail") || name.contains("hmailserver") {
+            Self::new().loose_spacing(true)
+        } else {
+            Self::new()
+        }
+    }
+
+    /// Accept the deviations accepted by either `self` or `other`.
+    pub fn union(self, other: Quirks) -> Self {
+        Self {
+            bare_lf: self.bare_lf || other.bare_lf,
+            nonstandard_flags: self.nonstandard_flags || other.nonstandard_flags,
+            loose_flag_lists: self.loose_flag_lists || other.loose_flag_lists,
+            loose_spacing: self.loose_spacing || other.loose_spacing,
+            utf8_quoted: self.utf8_quoted || other.utf8_quoted,
+            missing_imap4rev: self.missing_imap4rev || other.missing_imap4rev,
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Quirks> = Cell::new(Quirks::new());
+}
+
+/// The quirks accepted by the parse in progress on this thread.
+pub(crate) fn current() -> Quirks {
+    CURRENT.with(Cell::get)
+}
+
+/// Run `f` with `quirks` accepted by the parsers.
+pub(crate) fn with<T>(quirks: Quirks, f: impl FnOnce() -> T) -> T {
+    struct Restore(Quirks);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.with(|current| current.set(self.0));
+        }
+    }
+
+    let _restore = Restore(CURRENT.with(|current| current.replace(quirks)));
+    f()
+}
@@ -0,0 +1,24 @@
+//!
+//! https://tools.ietf.org/html/rfc2221
+//!
+//! IMAP4 Login Referrals
+//!
+
+use nom::{
+    bytes::streaming::{tag_no_case, take_while1},
+    combinator::map_res,
+    sequence::preceded,
+    IResult,
+};
+
+use crate::types::*;
+
+// The REFERRAL response code points the client to another server (or
+// another account) holding the requested data, as an IMAP URL (RFC 5092).
+// [RFC2221 - 4.1 LOGIN and AUTHENTICATE Referrals](https://tools.ietf.org/html/rfc2221#section-4.1)
+pub(crate) fn resp_text_code_referral(i: &[u8]) -> IResult<&[u8], ResponseCode<'_>> {
+    map_res(
+        preceded(tag_no_case("REFERRAL "), take_while1(|c| c != b']')),
+        |url| std::str::from_utf8(url).map(ResponseCode::Referral),
+    )(i)
+}
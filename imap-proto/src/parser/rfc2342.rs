@@ -0,0 +1,91 @@
+//!
+//! https://tools.ietf.org/html/rfc2342
+//!
+//! IMAP4 Namespace
+//!
+
+use std::borrow::Cow;
+
+use nom::{
+    branch::alt,
+    bytes::streaming::tag_no_case,
+    character::streaming::char,
+    combinator::{map, map_opt},
+    multi::{many0, many1},
+    sequence::{delimited, preceded, tuple},
+    IResult,
+};
+
+use crate::parser::core::{nil, parenthesized_nonempty_list, quoted_utf8, string_utf8};
+use crate::types::*;
+
+// The NAMESPACE response lists the personal, other users' and shared
+// namespaces, each of them NIL if there is none.
+// [RFC2342 - 5 NAMESPACE Command](https://tools.ietf.org/html/rfc2342#section-5)
+// [RFC2342 - 6 Formal Syntax](https://tools.ietf.org/html/rfc2342#section-6)
+pub(crate) fn resp_namespace(i: &[u8]) -> IResult<&[u8], Response<'_>> {
+    let (i, (_, personal, _, other, _, shared)) = tuple((
+        tag_no_case("NAMESPACE "),
+        namespace,
+        char(' '),
+        namespace,
+        char(' '),
+        namespace,
+    ))(i)?;
+    Ok((
+        i,
+        Response::Namespace(Namespaces {
+            personal,
+            other,
+            shared,
+        }),
+    ))
+}
+
+// namespace = nil / "(" 1*( "(" string SP (<"> QUOTED_CHAR <"> / nil)
+//             *(Namespace_Response_Extension) ")" ) ")"
+fn namespace(i: &[u8]) -> IResult<&[u8], Vec<Namespace>> {
+    alt((
+        map(nil, |_| Vec::new()),
+        delimited(char('('), many1(namespace_descr), char(')')),
+    ))(i)
+}
+
+fn namespace_descr(i: &[u8]) -> IResult<&[u8], Namespace> {
+    let (i, (_, prefix, _, delimiter, extensions, _)) = tuple((
+        char('('),
+        string_utf8,
+        char(' '),
+        alt((
+            map(nil, |_| None),
+            map(map_opt(quoted_utf8, single_char), Some),
+        )),
+        many0(namespace_response_extension),
+        char(')'),
+    ))(i)?;
+    let namespace = Namespace {
+        prefix: prefix.into_owned(),
+        delimiter,
+        extensions,
+    };
+    Ok((i, namespace))
+}
+
+fn single_char(s: Cow<str>) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+// Namespace_Response_Extension = SP string SP "(" string *(SP string) ")"
+fn namespace_response_extension(i: &[u8]) -> IResult<&[u8], (String, Vec<String>)> {
+    let (i, (name, _, values)) = tuple((
+        preceded(char(' '), string_utf8),
+        char(' '),
+        parenthesized_nonempty_list(string_utf8),
+    ))(i)?;
+    let values = values.into_iter().map(|value| value.into_owned()).collect();
+    Ok((i, (name.into_owned(), values)))
+}
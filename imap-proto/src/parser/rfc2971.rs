@@ -0,0 +1,37 @@
+//!
+//! https://tools.ietf.org/html/rfc2971
+//!
+//! IMAP4 ID extension
+//!
+
+use nom::{
+    branch::alt,
+    bytes::streaming::tag_no_case,
+    character::streaming::char,
+    combinator::map,
+    sequence::{preceded, tuple},
+    IResult,
+};
+
+use crate::parser::core::{nil, nstring_utf8, parenthesized_nonempty_list, string_utf8};
+use crate::types::*;
+
+// The ID response, identifying the server software, or NIL if the server
+// does not tell.
+// [RFC2971 - 3.2 ID Response](https://tools.ietf.org/html/rfc2971#section-3.2)
+// [RFC2971 - 4 Formal Syntax](https://tools.ietf.org/html/rfc2971#section-4)
+pub(crate) fn resp_id(i: &[u8]) -> IResult<&[u8], Response<'_>> {
+    map(
+        preceded(
+            tag_no_case("ID "),
+            alt((
+                map(nil, |_| Vec::new()),
+                parenthesized_nonempty_list(map(
+                    tuple((string_utf8, char(' '), nstring_utf8)),
+                    |(key, _, value)| (key.into_owned(), value.map(|v| v.into_owned())),
+                )),
+            )),
+        ),
+        |fields| Response::Id(Identity::new(fields)),
+    )(i)
+}
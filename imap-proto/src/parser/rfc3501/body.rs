@@ -59,7 +59,7 @@ pub fn msg_att_body_section(i: &[u8]) -> IResult<&[u8], AttributeValue> {
             tag_no_case("BODY"),
             section,
             opt(delimited(char('<'), number, char('>'))),
-            tag(" "),
+            sp,
             nstring,
         )),
         |(_, section, index, _, data)| AttributeValue::BodySection {
@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use nom::{
     branch::alt,
     bytes::streaming::{tag, tag_no_case},
@@ -113,7 +115,7 @@ fn body_encoding(i: &[u8]) -> IResult<&[u8], ContentEncoding> {
     ))(i)
 }
 
-fn body_lang(i: &[u8]) -> IResult<&[u8], Option<Vec<&str>>> {
+fn body_lang(i: &[u8]) -> IResult<&[u8], Option<Vec<Cow<'_, str>>>> {
     alt((
         // body language seems to refer to RFC 3066 language tags, which should be ASCII-only
         map(nstring_utf8, |v| v.map(|s| vec![s])),
@@ -205,7 +207,7 @@ fn body_type_text(i: &[u8]) -> IResult<&[u8], BodyStructure> {
         |(_, _, subtype, _, fields, _, lines, ext)| BodyStructure::Text {
             common: BodyContentCommon {
                 ty: ContentType {
-                    ty: "TEXT",
+                    ty: Cow::Borrowed("TEXT"),
                     subtype,
                     params: fields.param,
                 },
@@ -243,8 +245,8 @@ fn body_type_message(i: &[u8]) -> IResult<&[u8], BodyStructure> {
         |(_, _, fields, _, envelope, _, body, _, lines, ext)| BodyStructure::Message {
             common: BodyContentCommon {
                 ty: ContentType {
-                    ty: "MESSAGE",
-                    subtype: "RFC822",
+                    ty: Cow::Borrowed("MESSAGE"),
+                    subtype: Cow::Borrowed("RFC822"),
                     params: fields.param,
                 },
                 disposition: ext.disposition,
@@ -272,7 +274,7 @@ fn body_type_multipart(i: &[u8]) -> IResult<&[u8], BodyStructure> {
         |(bodies, _, subtype, ext)| BodyStructure::Multipart {
             common: BodyContentCommon {
                 ty: ContentType {
-                    ty: "MULTIPART",
+                    ty: Cow::Borrowed("MULTIPART"),
                     subtype,
                     params: ext.param,
                 },
@@ -295,10 +297,19 @@ pub(crate) fn body(i: &[u8]) -> IResult<&[u8], BodyStructure> {
     )))(i)
 }
 
+// "BODY" ["STRUCTURE"] SP body
+//
+// Both are returned as `BodyStructure`; the non-extensible `BODY` form simply
+// has no extension data.
 pub(crate) fn msg_att_body_structure(i: &[u8]) -> IResult<&[u8], AttributeValue> {
-    map(tuple((tag_no_case("BODYSTRUCTURE "), body)), |(_, body)| {
-        AttributeValue::BodyStructure(body)
-    })(i)
+    map(
+        tuple((
+            alt((tag_no_case("BODYSTRUCTURE"), tag_no_case("BODY"))),
+            sp,
+            body,
+        )),
+        |(_, _, body)| AttributeValue::BodyStructure(body),
+    )(i)
 }
 
 #[cfg(test)]
@@ -310,9 +321,10 @@ mod tests {
 
     // body-fld-param SP body-fld-id SP body-fld-desc SP body-fld-enc SP body-fld-octets
     const BODY_FIELDS: &str = r#"("foo" "bar") "id" "desc" "7BIT" 1337"#;
-    const BODY_FIELD_PARAM_PAIR: (&str, &str) = ("foo", "bar");
-    const BODY_FIELD_ID: Option<&str> = Some("id");
-    const BODY_FIELD_DESC: Option<&str> = Some("desc");
+    const BODY_FIELD_PARAM_PAIR: (Cow<str>, Cow<str>) =
+        (Cow::Borrowed("foo"), Cow::Borrowed("bar"));
+    const BODY_FIELD_ID: Option<Cow<str>> = Some(Cow::Borrowed("id"));
+    const BODY_FIELD_DESC: Option<Cow<str>> = Some(Cow::Borrowed("desc"));
     const BODY_FIELD_ENC: ContentEncoding = ContentEncoding::SevenBit;
     const BODY_FIELD_OCTETS: u32 = 1337;
 
@@ -322,8 +334,8 @@ mod tests {
             BodyStructure::Text {
                 common: BodyContentCommon {
                     ty: ContentType {
-                        ty: "TEXT",
-                        subtype: "PLAIN",
+                        ty: "TEXT".into(),
+                        subtype: "PLAIN".into(),
                         params: Some(vec![BODY_FIELD_PARAM_PAIR]),
                     },
                     disposition: None,
@@ -350,7 +362,7 @@ mod tests {
         assert_matches!(
             body_param(br#"("foo" "bar")"#),
             Ok((EMPTY, Some(param))) => {
-                assert_eq!(param, vec![("foo", "bar")]);
+                assert_eq!(param, vec![("foo".into(), "bar".into())]);
             }
         );
     }
@@ -378,7 +390,7 @@ mod tests {
     fn test_body_extension_data() {
         assert_matches!(
             body_extension(br#""blah""#),
-            Ok((EMPTY, BodyExtension::Str(Some("blah"))))
+            Ok((EMPTY, BodyExtension::Str(Some(s)))) if s == "blah"
         );
 
         assert_matches!(
@@ -389,7 +401,7 @@ mod tests {
         assert_matches!(
             body_extension(br#"("hello")"#),
             Ok((EMPTY, BodyExtension::List(list))) => {
-                assert_eq!(list, vec![BodyExtension::Str(Some("hello"))]);
+                assert_eq!(list, vec![BodyExtension::Str(Some("hello".into()))]);
             }
         );
 
@@ -409,9 +421,9 @@ mod tests {
             body_disposition(br#"("attachment" ("FILENAME" "pages.pdf"))"#),
             Ok((EMPTY, Some(disposition))) => {
                 assert_eq!(disposition, ContentDisposition {
-                    ty: "attachment",
+                    ty: "attachment".into(),
                     params: Some(vec![
-                        ("FILENAME", "pages.pdf")
+                        ("FILENAME".into(), "pages.pdf".into())
                     ])
                 });
             }
@@ -453,13 +465,13 @@ mod tests {
                 assert_eq!(basic, BodyStructure::Basic {
                     common: BodyContentCommon {
                         ty: ContentType {
-                            ty: "APPLICATION",
-                            subtype: "PDF",
-                            params: Some(vec![("NAME", "pages.pdf")])
+                            ty: "APPLICATION".into(),
+                            subtype: "PDF".into(),
+                            params: Some(vec![("NAME".into(), "pages.pdf".into())])
                         },
                         disposition: Some(ContentDisposition {
-                            ty: "attachment",
-                            params: Some(vec![("FILENAME", "pages.pdf")])
+                            ty: "attachment".into(),
+                            params: Some(vec![("FILENAME".into(), "pages.pdf".into())])
                         }),
                         language: None,
                         location: None,
@@ -507,8 +519,8 @@ mod tests {
                 assert_eq!(multipart, BodyStructure::Multipart {
                     common: BodyContentCommon {
                         ty: ContentType {
-                            ty: "MULTIPART",
-                            subtype: "ALTERNATIVE",
+                            ty: "MULTIPART".into(),
+                            subtype: "ALTERNATIVE".into(),
                             params: None
                         },
                         language: None,
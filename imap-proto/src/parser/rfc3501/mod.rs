@@ -4,27 +4,32 @@
 //! INTERNET MESSAGE ACCESS PROTOCOL
 //!
 
+use std::borrow::Cow;
 use std::str::from_utf8;
 
 use nom::{
     branch::alt,
     bytes::streaming::{tag, tag_no_case, take_while, take_while1},
     character::streaming::char,
-    combinator::{map, map_res, opt, recognize},
-    multi::{many0, many1},
+    combinator::{map, map_res, opt, peek, recognize, verify},
+    multi::{many0, many1, separated_list, separated_nonempty_list},
     sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
 
 use crate::{
-    parser::{core::*, rfc3501::body::*, rfc3501::body_structure::*, rfc4551, rfc5161, rfc5464},
+    parser::{
+        core::*, quirks, rfc2221, rfc2342, rfc2971, rfc3501::body::*, rfc3501::body_structure::*,
+        rfc4314, rfc4315, rfc4551, rfc4731, rfc5161, rfc5256, rfc5258, rfc5464, rfc5530, rfc7162,
+        rfc8438, rfc9208,
+    },
     types::*,
 };
 
 pub mod body;
 pub mod body_structure;
 
-fn is_tag_char(c: u8) -> bool {
+pub(crate) fn is_tag_char(c: u8) -> bool {
     c != b'+' && is_astring_char(c)
 }
 
@@ -48,10 +53,10 @@ fn status(i: &[u8]) -> IResult<&[u8], Status> {
     alt((status_ok, status_no, status_bad, status_preauth, status_bye))(i)
 }
 
-fn mailbox(i: &[u8]) -> IResult<&[u8], &str> {
+pub(crate) fn mailbox(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
     map(astring_utf8, |s| {
         if s.eq_ignore_ascii_case("INBOX") {
-            "INBOX"
+            Cow::Borrowed("INBOX")
         } else {
             s
         }
@@ -66,10 +71,18 @@ fn flag_extension(i: &[u8]) -> IResult<&[u8], &str> {
 }
 
 fn flag(i: &[u8]) -> IResult<&[u8], &str> {
+    if quirks::current().accepts_nonstandard_flags() {
+        return map_res(take_while1(is_nonstandard_flag_char), from_utf8)(i);
+    }
     alt((flag_extension, atom))(i)
 }
 
-fn flag_list(i: &[u8]) -> IResult<&[u8], Vec<&str>> {
+// Anything up to the end of the flag list or the next flag
+fn is_nonstandard_flag_char(c: u8) -> bool {
+    c != b' ' && c != b'(' && c != b')' && c >= 0x20 && c != 0x7f
+}
+
+fn flag_list(i: &[u8]) -> IResult<&[u8], FlagList<'_>> {
     // Correct code is
     //   parenthesized_list(flag)(i)
     //
@@ -77,9 +90,28 @@ fn flag_list(i: &[u8]) -> IResult<&[u8], Vec<&str>> {
     // * FLAGS (\Answered \Flagged \Deleted \Seen \Draft \*)
     //
     // As a workaround, "\*" is allowed here.
+    if quirks::current().accepts_loose_flag_lists() {
+        return loose_flag_list(i);
+    }
     parenthesized_list(flag_perm)(i)
 }
 
+// Exchange sends "FLAGS NIL", "FLAGS ( )" and the like
+fn loose_flag_list(i: &[u8]) -> IResult<&[u8], FlagList<'_>> {
+    let spaces = |i| take_while(|c| c == b' ')(i);
+    preceded(
+        spaces,
+        alt((
+            map(nil, |_| FlagList::new()),
+            delimited(
+                pair(char('('), spaces),
+                many0(terminated(flag_perm, spaces)),
+                char(')'),
+            ),
+        )),
+    )(i)
+}
+
 fn flag_perm(i: &[u8]) -> IResult<&[u8], &str> {
     alt((map_res(tag(b"\\*"), from_utf8), flag))(i)
 }
@@ -88,19 +120,32 @@ fn resp_text_code_alert(i: &[u8]) -> IResult<&[u8], ResponseCode> {
     map(tag_no_case(b"ALERT"), |_| ResponseCode::Alert)(i)
 }
 
+// "BADCHARSET" [SP "(" charset *(SP charset) ")" ]
+//
+// Some servers leave out the parentheses.
 fn resp_text_code_badcharset(i: &[u8]) -> IResult<&[u8], ResponseCode> {
     map(
         preceded(
             tag_no_case(b"BADCHARSET"),
             opt(preceded(
                 tag(b" "),
-                parenthesized_nonempty_list(astring_utf8),
+                alt((
+                    parenthesized_nonempty_list(charset),
+                    separated_nonempty_list(char(' '), charset),
+                )),
             )),
         ),
-        ResponseCode::BadCharset,
+        |charsets| ResponseCode::BadCharset(charsets.map(Charsets)),
     )(i)
 }
 
+// charset = atom / quoted
+//
+// Literals are accepted too. Atoms end before `]`, unlike astrings.
+fn charset(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    alt((map(atom, Cow::Borrowed), map_res(string, utf8)))(i)
+}
+
 fn resp_text_code_capability(i: &[u8]) -> IResult<&[u8], ResponseCode> {
     map(capability_data, ResponseCode::Capabilities)(i)
 }
@@ -111,11 +156,8 @@ fn resp_text_code_parse(i: &[u8]) -> IResult<&[u8], ResponseCode> {
 
 fn resp_text_code_permanent_flags(i: &[u8]) -> IResult<&[u8], ResponseCode> {
     map(
-        preceded(
-            tag_no_case(b"PERMANENTFLAGS "),
-            parenthesized_list(flag_perm),
-        ),
-        ResponseCode::PermanentFlags,
+        preceded(tag_no_case(b"PERMANENTFLAGS "), flag_list),
+        |flags| ResponseCode::PermanentFlags(flags.into_iter().collect()),
     )(i)
 }
 
@@ -158,23 +200,99 @@ fn resp_text_code(i: &[u8]) -> IResult<&[u8], ResponseCode> {
     delimited(
         tag(b"["),
         alt((
-            resp_text_code_alert,
-            resp_text_code_badcharset,
-            resp_text_code_capability,
-            resp_text_code_parse,
-            resp_text_code_permanent_flags,
-            resp_text_code_uid_validity,
-            resp_text_code_uid_next,
-            resp_text_code_unseen,
-            resp_text_code_read_only,
-            resp_text_code_read_write,
-            resp_text_code_try_create,
-            rfc4551::resp_text_code_highest_mod_seq,
+            // Known codes must end at the bracket, so that `[ALERTS]` is
+            // unknown rather than a malformed `ALERT`.
+            terminated(
+                alt((
+                    resp_text_code_alert,
+                    resp_text_code_badcharset,
+                    resp_text_code_capability,
+                    resp_text_code_parse,
+                    resp_text_code_permanent_flags,
+                    resp_text_code_uid_validity,
+                    resp_text_code_uid_next,
+                    resp_text_code_unseen,
+                    resp_text_code_read_only,
+                    resp_text_code_read_write,
+                    resp_text_code_try_create,
+                    rfc2221::resp_text_code_referral,
+                    rfc4315::resp_text_code_append_uid,
+                    rfc4315::resp_text_code_copy_uid,
+                    rfc4551::resp_text_code_highest_mod_seq,
+                    rfc5464::resp_text_code_metadata,
+                    rfc5530::resp_text_code,
+                    rfc7162::resp_text_code_closed,
+                )),
+                peek(tag(b"]")),
+            ),
+            resp_text_code_other,
         )),
         tag(b"]"),
     )(i)
 }
 
+// resp-text-code =/ atom [SP 1*<any TEXT-CHAR except "]">]
+//
+// Codes from extensions this parser does not know are kept as sent, so that
+// their arguments do not end up in the text of the response.
+fn resp_text_code_other(i: &[u8]) -> IResult<&[u8], ResponseCode<'_>> {
+    map(
+        verify(
+            map_res(
+                recognize(pair(
+                    atom,
+                    opt(pair(sp, take_while1(|c| is_text_char(c) && c != b']'))),
+                )),
+                from_utf8,
+            ),
+            |content: &str| !is_known_resp_text_code(content),
+        ),
+        ResponseCode::Other,
+    )(i)
+}
+
+// A malformed known code is left in the text rather than returned as unknown
+fn is_known_resp_text_code(content: &str) -> bool {
+    const KNOWN: &[&str] = &[
+        "ALERT",
+        "ALREADYEXISTS",
+        "APPENDUID",
+        "AUTHENTICATIONFAILED",
+        "AUTHORIZATIONFAILED",
+        "BADCHARSET",
+        "CANNOT",
+        "CAPABILITY",
+        "CLIENTBUG",
+        "CLOSED",
+        "CONTACTADMIN",
+        "COPYUID",
+        "CORRUPTION",
+        "EXPIRED",
+        "EXPUNGEISSUED",
+        "HIGHESTMODSEQ",
+        "INUSE",
+        "LIMIT",
+        "METADATA",
+        "NONEXISTENT",
+        "NOPERM",
+        "OVERQUOTA",
+        "PARSE",
+        "PERMANENTFLAGS",
+        "PRIVACYREQUIRED",
+        "READ-ONLY",
+        "READ-WRITE",
+        "REFERRAL",
+        "SERVERBUG",
+        "TRYCREATE",
+        "UIDNEXT",
+        "UIDVALIDITY",
+        "UNAVAILABLE",
+        "UNSEEN",
+    ];
+    let name = content.split(' ').next().unwrap_or(content);
+    KNOWN.iter().any(|known| known.eq_ignore_ascii_case(name))
+}
+
 fn capability(i: &[u8]) -> IResult<&[u8], Capability> {
     alt((
         map(tag_no_case(b"IMAP4rev1"), |_| Capability::Imap4rev1),
@@ -183,10 +301,17 @@ fn capability(i: &[u8]) -> IResult<&[u8], Capability> {
     ))(i)
 }
 
+// IMAP4rev2 servers (RFC 9051) need not advertise IMAP4rev1, and some
+// appliances advertise neither; the latter is only accepted as a quirk.
 fn ensure_capabilities_contains_imap4rev<'a>(
     capabilities: Vec<Capability<'a>>,
 ) -> Result<Vec<Capability<'a>>, ()> {
-    if capabilities.contains(&Capability::Imap4rev1) {
+    let imap4rev = capabilities.iter().any(|capability| match capability {
+        Capability::Imap4rev1 => true,
+        Capability::Atom(name) => name.eq_ignore_ascii_case("IMAP4rev2"),
+        Capability::Auth(_) => false,
+    });
+    if imap4rev || quirks::current().accepts_missing_imap4rev() {
         Ok(capabilities)
     } else {
         Err(())
@@ -203,10 +328,19 @@ fn capability_data(i: &[u8]) -> IResult<&[u8], Vec<Capability>> {
     )(i)
 }
 
+// mailbox-data =/ "SEARCH" [1*(SP nz-number) SP search-sort-mod-seq]
+//
+// search-sort-mod-seq = "(" "MODSEQ" SP mod-sequence-value ")"  (RFC 4551)
 fn mailbox_data_search(i: &[u8]) -> IResult<&[u8], MailboxDatum> {
     map(
-        preceded(tag_no_case(b"SEARCH"), many0(preceded(tag(" "), number))),
-        MailboxDatum::Search,
+        preceded(
+            tag_no_case(b"SEARCH"),
+            pair(
+                many0(preceded(tag(" "), number)),
+                opt(delimited(tag_no_case(" (MODSEQ "), number_64, tag(")"))),
+            ),
+        ),
+        |(ids, mod_seq)| MailboxDatum::Search { ids, mod_seq },
     )(i)
 }
 
@@ -225,7 +359,10 @@ fn mailbox_data_exists(i: &[u8]) -> IResult<&[u8], MailboxDatum> {
 }
 
 #[allow(clippy::type_complexity)]
-fn mailbox_list(i: &[u8]) -> IResult<&[u8], (Vec<&str>, Option<&str>, &str)> {
+// mailbox-list = "(" [mbx-list-flags] ")" SP
+//                (DQUOTE QUOTED-CHAR DQUOTE / nil) SP mailbox
+//                [SP mbox-list-extended]  (RFC 5258)
+fn mailbox_list(i: &[u8]) -> IResult<&[u8], MailboxDatum<'_>> {
     map(
         tuple((
             flag_list,
@@ -233,29 +370,23 @@ fn mailbox_list(i: &[u8]) -> IResult<&[u8], (Vec<&str>, Option<&str>, &str)> {
             alt((map(quoted_utf8, Some), map(nil, |_| None))),
             tag(b" "),
             mailbox,
+            opt(preceded(tag(b" "), rfc5258::mbox_list_extended)),
         )),
-        |(flags, _, delimiter, _, name)| (flags, delimiter, name),
+        |(flags, _, delimiter, _, name, extensions)| MailboxDatum::List {
+            flags,
+            delimiter,
+            name,
+            extensions: extensions.unwrap_or_default(),
+        },
     )(i)
 }
 
 fn mailbox_data_list(i: &[u8]) -> IResult<&[u8], MailboxDatum> {
-    map(preceded(tag_no_case("LIST "), mailbox_list), |data| {
-        MailboxDatum::List {
-            flags: data.0,
-            delimiter: data.1,
-            name: data.2,
-        }
-    })(i)
+    preceded(tag_no_case("LIST "), mailbox_list)(i)
 }
 
 fn mailbox_data_lsub(i: &[u8]) -> IResult<&[u8], MailboxDatum> {
-    map(preceded(tag_no_case("LSUB "), mailbox_list), |data| {
-        MailboxDatum::List {
-            flags: data.0,
-            delimiter: data.1,
-            name: data.2,
-        }
-    })(i)
+    preceded(tag_no_case("LSUB "), mailbox_list)(i)
 }
 
 // Unlike `status_att` in the RFC syntax, this includes the value,
@@ -263,6 +394,7 @@ fn mailbox_data_lsub(i: &[u8]) -> IResult<&[u8], MailboxDatum> {
 fn status_att(i: &[u8]) -> IResult<&[u8], StatusAttribute> {
     alt((
         rfc4551::status_att_val_highest_mod_seq,
+        rfc8438::status_att_val_size,
         map(
             preceded(tag_no_case("MESSAGES "), number),
             StatusAttribute::Messages,
@@ -283,9 +415,18 @@ fn status_att(i: &[u8]) -> IResult<&[u8], StatusAttribute> {
             preceded(tag_no_case("UNSEEN "), number),
             StatusAttribute::Unseen,
         ),
+        status_att_other,
     ))(i)
 }
 
+// Attributes we don't know (vendor extensions like `X-GUID`, or later RFCs)
+// are kept with their value, rather than failing the whole STATUS response.
+fn status_att_other(i: &[u8]) -> IResult<&[u8], StatusAttribute<'_>> {
+    map(tuple((atom, tag(" "), astring_utf8)), |(name, _, value)| {
+        StatusAttribute::Other(Cow::Borrowed(name), value)
+    })(i)
+}
+
 fn status_att_list(i: &[u8]) -> IResult<&[u8], Vec<StatusAttribute>> {
     parenthesized_nonempty_list(status_att)(i)
 }
@@ -313,6 +454,13 @@ fn mailbox_data(i: &[u8]) -> IResult<&[u8], MailboxDatum> {
         mailbox_data_status,
         mailbox_data_recent,
         mailbox_data_search,
+        rfc4731::mailbox_data_esearch,
+        rfc5256::mailbox_data_sort,
+        rfc4314::mailbox_data_acl,
+        rfc4314::mailbox_data_list_rights,
+        rfc4314::mailbox_data_my_rights,
+        rfc9208::mailbox_data_quota,
+        rfc9208::mailbox_data_quota_root,
     ))(i)
 }
 
@@ -338,16 +486,55 @@ fn address(i: &[u8]) -> IResult<&[u8], Address> {
     ))(i)
 }
 
-fn opt_addresses(i: &[u8]) -> IResult<&[u8], Option<Vec<Address>>> {
+// Groups (RFC 5322, section 3.4) are sent as a start marker with the group
+// name as mailbox and a NIL host, the members, and an end marker with a NIL
+// mailbox and host (RFC 3501, section 7.4.2).
+fn opt_addresses(i: &[u8]) -> IResult<&[u8], Option<Addresses<'_>>> {
     alt((
         map(nil, |_s| None),
         map(
             paren_delimited(many1(terminated(address, opt(char(' '))))),
-            Some,
+            |list| Some(balance_groups(list)),
         ),
     ))(i)
 }
 
+// Some servers leave groups open or send stray end markers; close open groups
+// before the next one and at the end of the list, and drop stray end markers,
+// so that markers always come in pairs.
+fn balance_groups(list: Addresses) -> Addresses {
+    if !list.iter().any(|addr| addr.host.is_none()) {
+        return list;
+    }
+
+    let end = || Address {
+        name: None,
+        adl: None,
+        mailbox: None,
+        host: None,
+    };
+    let mut balanced = Addresses::with_capacity(list.len());
+    let mut open = false;
+    for addr in list {
+        if addr.is_group_end() {
+            if !open {
+                continue;
+            }
+            open = false;
+        } else if addr.is_group_start() {
+            if open {
+                balanced.push(end());
+            }
+            open = true;
+        }
+        balanced.push(addr);
+    }
+    if open {
+        balanced.push(end());
+    }
+    balanced
+}
+
 // envelope        = "(" env-date SP env-subject SP env-from SP
 //                   env-sender SP env-reply-to SP env-to SP env-cc SP
 //                   env-bcc SP env-in-reply-to SP env-message-id ")"
@@ -430,28 +617,29 @@ pub(crate) fn envelope(i: &[u8]) -> IResult<&[u8], Envelope> {
 }
 
 fn msg_att_envelope(i: &[u8]) -> IResult<&[u8], AttributeValue> {
-    map(preceded(tag_no_case("ENVELOPE "), envelope), |envelope| {
-        AttributeValue::Envelope(Box::new(envelope))
-    })(i)
+    map(
+        preceded(pair(tag_no_case("ENVELOPE"), sp), envelope),
+        |envelope| AttributeValue::Envelope(Box::new(envelope)),
+    )(i)
 }
 
 fn msg_att_internal_date(i: &[u8]) -> IResult<&[u8], AttributeValue> {
     map(
-        preceded(tag_no_case("INTERNALDATE "), nstring_utf8),
+        preceded(pair(tag_no_case("INTERNALDATE"), sp), nstring_utf8),
         |date| AttributeValue::InternalDate(date.unwrap()),
     )(i)
 }
 
 fn msg_att_flags(i: &[u8]) -> IResult<&[u8], AttributeValue> {
     map(
-        preceded(tag_no_case("FLAGS "), flag_list),
+        preceded(pair(tag_no_case("FLAGS"), sp), flag_list),
         AttributeValue::Flags,
     )(i)
 }
 
 fn msg_att_rfc822(i: &[u8]) -> IResult<&[u8], AttributeValue> {
     map(
-        preceded(tag_no_case("RFC822 "), nstring),
+        preceded(pair(tag_no_case("RFC822"), sp), nstring),
         AttributeValue::Rfc822,
     )(i)
 }
@@ -459,27 +647,86 @@ fn msg_att_rfc822(i: &[u8]) -> IResult<&[u8], AttributeValue> {
 fn msg_att_rfc822_header(i: &[u8]) -> IResult<&[u8], AttributeValue> {
     // extra space workaround for DavMail
     map(
-        tuple((tag_no_case("RFC822.HEADER "), opt(tag(b" ")), nstring)),
-        |(_, _, raw)| AttributeValue::Rfc822Header(raw),
+        tuple((tag_no_case("RFC822.HEADER"), sp, opt(tag(b" ")), nstring)),
+        |(_, _, _, raw)| AttributeValue::Rfc822Header(raw),
     )(i)
 }
 
 fn msg_att_rfc822_size(i: &[u8]) -> IResult<&[u8], AttributeValue> {
     map(
-        preceded(tag_no_case("RFC822.SIZE "), number),
+        preceded(pair(tag_no_case("RFC822.SIZE"), sp), number),
         AttributeValue::Rfc822Size,
     )(i)
 }
 
 fn msg_att_rfc822_text(i: &[u8]) -> IResult<&[u8], AttributeValue> {
     map(
-        preceded(tag_no_case("RFC822.TEXT "), nstring),
+        preceded(pair(tag_no_case("RFC822.TEXT"), sp), nstring),
         AttributeValue::Rfc822Text,
     )(i)
 }
 
 fn msg_att_uid(i: &[u8]) -> IResult<&[u8], AttributeValue> {
-    map(preceded(tag_no_case("UID "), number), AttributeValue::Uid)(i)
+    map(
+        preceded(pair(tag_no_case("UID"), sp), number),
+        AttributeValue::Uid,
+    )(i)
+}
+
+// Attributes from extensions this parser does not know. The value is
+// recognized as any nstring, literal8 (as in `BINARY[1] ~{3}`), number, atom
+// or parenthesized list of these.
+fn msg_att_unknown(i: &[u8]) -> IResult<&[u8], AttributeValue<'_>> {
+    map(
+        tuple((
+            verify(
+                map_res(
+                    recognize(tuple((
+                        take_while1(|c| is_atom_char(c) && c != b'['),
+                        opt(delimited(char('['), take_while(|c| c != b']'), char(']'))),
+                        opt(delimited(char('<'), number, char('>'))),
+                    ))),
+                    from_utf8,
+                ),
+                |name: &str| !is_known_msg_att(name),
+            ),
+            sp,
+            unknown_value,
+        )),
+        |(name, _, raw)| AttributeValue::Unknown { name, raw },
+    )(i)
+}
+
+// A malformed known attribute is an error rather than an unknown attribute
+fn is_known_msg_att(name: &str) -> bool {
+    const KNOWN: &[&str] = &[
+        "BODY",
+        "BODYSTRUCTURE",
+        "ENVELOPE",
+        "FLAGS",
+        "INTERNALDATE",
+        "MODSEQ",
+        "RFC822",
+        "RFC822.HEADER",
+        "RFC822.SIZE",
+        "RFC822.TEXT",
+        "UID",
+    ];
+    let base = name.split(['[', '<']).next().unwrap_or(name);
+    KNOWN.iter().any(|known| known.eq_ignore_ascii_case(base))
+}
+
+fn unknown_value(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    alt((
+        recognize(string),
+        recognize(literal8),
+        recognize(delimited(
+            char('('),
+            separated_list(char(' '), unknown_value),
+            char(')'),
+        )),
+        take_while1(|c| c > b' ' && c != b'(' && c != b')' && c != 0x7f),
+    ))(i)
 }
 
 // msg-att         = "(" (msg-att-dynamic / msg-att-static)
@@ -508,18 +755,27 @@ fn msg_att(i: &[u8]) -> IResult<&[u8], AttributeValue> {
         msg_att_rfc822_size,
         msg_att_rfc822_text,
         msg_att_uid,
+        msg_att_unknown,
     ))(i)
 }
 
 fn msg_att_list(i: &[u8]) -> IResult<&[u8], Vec<AttributeValue>> {
+    if quirks::current().accepts_loose_spacing() {
+        // Domino and hMailServer add or drop spaces between attributes
+        return delimited(
+            pair(char('('), sp),
+            many1(terminated(msg_att, sp)),
+            char(')'),
+        )(i);
+    }
     parenthesized_nonempty_list(msg_att)(i)
 }
 
 // message-data    = nz-number SP ("EXPUNGE" / ("FETCH" SP msg-att))
 fn message_data_fetch(i: &[u8]) -> IResult<&[u8], Response> {
     map(
-        tuple((number, tag_no_case(" FETCH "), msg_att_list)),
-        |(num, _, attrs)| Response::Fetch(num, attrs),
+        tuple((number, sp, tag_no_case("FETCH"), sp, msg_att_list)),
+        |(num, _, _, _, attrs)| Response::Fetch(num, attrs),
     )(i)
 }
 
@@ -529,35 +785,39 @@ fn message_data_expunge(i: &[u8]) -> IResult<&[u8], u32> {
 }
 
 // tag             = 1*<any ASTRING-CHAR except "+">
-fn imap_tag(i: &[u8]) -> IResult<&[u8], RequestId> {
-    map(map_res(take_while1(is_tag_char), from_utf8), |s| {
-        RequestId(s.to_string())
-    })(i)
+pub(crate) fn imap_tag(i: &[u8]) -> IResult<&[u8], RequestId> {
+    map(map_res(take_while1(is_tag_char), from_utf8), RequestId::new)(i)
 }
 
 // This is not quite according to spec, which mandates the following:
 //     ["[" resp-text-code "]" SP] text
 // However, examples in RFC 4551 (Conditional STORE) counteract this by giving
 // examples of `resp-text` that do not include the trailing space and text.
-fn resp_text(i: &[u8]) -> IResult<&[u8], (Option<ResponseCode>, Option<&str>)> {
-    map(tuple((opt(resp_text_code), text)), |(code, text)| {
+fn resp_text(i: &[u8]) -> IResult<&[u8], (Option<ResponseCode<'_>>, Option<Text<'_>>)> {
+    map(tuple((opt(resp_text_code), text_8bit)), |(code, text)| {
+        // Some servers leave out the space after the code, so only a space
+        // is dropped rather than whatever comes first.
+        let text = match (&code, text) {
+            (Some(_), [b' ', rest @ ..]) => rest,
+            _ => text,
+        };
         let res = if text.is_empty() {
             None
-        } else if code.is_some() {
-            Some(&text[1..])
         } else {
-            Some(text)
+            Some(Text(text))
         };
         (code, res)
     })(i)
 }
 
 // continue-req    = "+" SP (resp-text / base64) CRLF
+//
+// Base64 data such as a SASL challenge cannot be told apart from text (`+ YWJj`
+// is both), so it is kept as text; see `Response::decode_challenge()`.
 pub(crate) fn continue_req(i: &[u8]) -> IResult<&[u8], Response> {
     // Some servers do not send the space :/
-    // TODO: base64
     map(
-        tuple((tag("+"), opt(tag(" ")), resp_text, tag("\r\n"))),
+        tuple((tag("+"), opt(tag(" ")), resp_text, crlf)),
         |(_, _, text, _)| Response::Continue {
             code: text.0,
             information: text.1,
@@ -571,14 +831,7 @@ pub(crate) fn continue_req(i: &[u8]) -> IResult<&[u8], Response> {
 //                     ; Status condition
 pub(crate) fn response_tagged(i: &[u8]) -> IResult<&[u8], Response> {
     map(
-        tuple((
-            imap_tag,
-            tag(b" "),
-            status,
-            tag(b" "),
-            resp_text,
-            tag(b"\r\n"),
-        )),
+        tuple((imap_tag, tag(b" "), status, tag(b" "), resp_text, crlf)),
         |(tag, _, status, _, text, _)| Response::Done {
             tag,
             status,
@@ -617,11 +870,14 @@ pub(crate) fn response_data(i: &[u8]) -> IResult<&[u8], Response> {
             map(message_data_expunge, Response::Expunge),
             message_data_fetch,
             map(capability_data, Response::Capabilities),
+            rfc2342::resp_namespace,
+            rfc2971::resp_id,
             rfc5161::resp_enabled,
             rfc5464::metadata_solicited,
             rfc5464::metadata_unsolicited,
+            rfc7162::resp_vanished,
         )),
-        tag(b"\r\n"),
+        crlf,
     )(i)
 }
 
@@ -668,6 +924,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_opt_addresses_groups() {
+        let addr = br#"((NIL NIL "friends" NIL)(NIL NIL "jane" "example.com")(NIL NIL NIL NIL)(NIL NIL NIL NIL)(NIL NIL "john" "example.com")(NIL NIL "undisclosed-recipients" NIL))"#;
+        let (_, list) = super::opt_addresses(addr).unwrap();
+        let list = list.unwrap();
+        let markers = list
+            .iter()
+            .map(|addr| (addr.is_group_start(), addr.is_group_end()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            markers,
+            [
+                (true, false),
+                (false, false),
+                (false, true),
+                (false, false),
+                (true, false),
+                (false, true),
+            ]
+        );
+        assert_eq!(
+            crate::types::AddressList(&list).to_string(),
+            "friends: jane@example.com;, john@example.com, undisclosed-recipients:;"
+        );
+    }
+
     #[test]
     fn test_addresses() {
         match super::address(b"(\"John Klensin\" NIL \"KLENSIN\" \"MIT.EDU\") ") {
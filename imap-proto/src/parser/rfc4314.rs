@@ -0,0 +1,74 @@
+//!
+//! https://tools.ietf.org/html/rfc4314
+//!
+//! IMAP4 Access Control List (ACL) Extension
+//!
+
+use nom::{
+    bytes::streaming::tag_no_case,
+    character::streaming::char,
+    combinator::{map, map_opt},
+    multi::many0,
+    sequence::{preceded, tuple},
+    IResult,
+};
+
+use crate::parser::core::astring_utf8;
+use crate::parser::rfc3501::mailbox;
+use crate::types::*;
+
+// [RFC4314 - 9 Formal Syntax](https://tools.ietf.org/html/rfc4314#section-9)
+fn rights(i: &[u8]) -> IResult<&[u8], Rights> {
+    map_opt(astring_utf8, |s| s.parse().ok())(i)
+}
+
+// The ACL response, listing the identifiers with rights on a mailbox.
+// [RFC4314 - 3.6 ACL Response](https://tools.ietf.org/html/rfc4314#section-3.6)
+pub(crate) fn mailbox_data_acl(i: &[u8]) -> IResult<&[u8], MailboxDatum<'_>> {
+    map(
+        tuple((
+            tag_no_case("ACL "),
+            mailbox,
+            many0(map(
+                tuple((char(' '), astring_utf8, char(' '), rights)),
+                |(_, identifier, _, rights)| AclEntry {
+                    identifier: identifier.into_owned(),
+                    rights,
+                },
+            )),
+        )),
+        |(_, mailbox, entries)| MailboxDatum::Acl { mailbox, entries },
+    )(i)
+}
+
+// The LISTRIGHTS response: the rights always granted to an identifier, and
+// the groups of rights that may be granted to it.
+// [RFC4314 - 3.7 LISTRIGHTS Response](https://tools.ietf.org/html/rfc4314#section-3.7)
+pub(crate) fn mailbox_data_list_rights(i: &[u8]) -> IResult<&[u8], MailboxDatum<'_>> {
+    map(
+        tuple((
+            tag_no_case("LISTRIGHTS "),
+            mailbox,
+            char(' '),
+            astring_utf8,
+            char(' '),
+            rights,
+            many0(preceded(char(' '), rights)),
+        )),
+        |(_, mailbox, _, identifier, _, required, optional)| MailboxDatum::ListRights {
+            mailbox,
+            identifier,
+            required,
+            optional,
+        },
+    )(i)
+}
+
+// The MYRIGHTS response, giving the rights of the user on a mailbox.
+// [RFC4314 - 3.8 MYRIGHTS Response](https://tools.ietf.org/html/rfc4314#section-3.8)
+pub(crate) fn mailbox_data_my_rights(i: &[u8]) -> IResult<&[u8], MailboxDatum<'_>> {
+    map(
+        tuple((tag_no_case("MYRIGHTS "), mailbox, char(' '), rights)),
+        |(_, mailbox, _, rights)| MailboxDatum::MyRights { mailbox, rights },
+    )(i)
+}
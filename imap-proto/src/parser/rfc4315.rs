@@ -0,0 +1,61 @@
+//!
+//! https://tools.ietf.org/html/rfc4315
+//!
+//! The IMAP UIDPLUS Extension
+//!
+
+use nom::{
+    bytes::streaming::tag_no_case,
+    character::streaming::char,
+    combinator::{map, opt},
+    multi::separated_nonempty_list,
+    sequence::{preceded, tuple},
+    IResult,
+};
+
+use crate::parser::core::number;
+use crate::types::*;
+
+// The APPENDUID response code reports the UIDs assigned to appended messages,
+// several of them for MULTIAPPEND (RFC 3502).
+// [RFC4315 - 3 Additional Response Codes](https://tools.ietf.org/html/rfc4315#section-3)
+// [RFC4315 - 4 Formal Syntax - resp-code-apnd](https://tools.ietf.org/html/rfc4315#section-4)
+pub(crate) fn resp_text_code_append_uid(i: &[u8]) -> IResult<&[u8], ResponseCode<'_>> {
+    let (i, (_, uid_validity, _, uids)) =
+        tuple((tag_no_case("APPENDUID "), number, char(' '), uid_set))(i)?;
+    Ok((i, ResponseCode::AppendUid(uid_validity, uids)))
+}
+
+// The COPYUID response code reports the UIDs assigned to messages copied to
+// the destination mailbox, in the order of the source UIDs.
+// [RFC4315 - 3 Additional Response Codes](https://tools.ietf.org/html/rfc4315#section-3)
+// [RFC4315 - 4 Formal Syntax - resp-code-copy](https://tools.ietf.org/html/rfc4315#section-4)
+pub(crate) fn resp_text_code_copy_uid(i: &[u8]) -> IResult<&[u8], ResponseCode<'_>> {
+    let (i, (_, uid_validity, _, source, _, dest)) = tuple((
+        tag_no_case("COPYUID "),
+        number,
+        char(' '),
+        uid_set,
+        char(' '),
+        uid_set,
+    ))(i)?;
+    Ok((i, ResponseCode::CopyUid(uid_validity, source, dest)))
+}
+
+// uid-set         = (uniqueid / uid-range) *("," uid-set)
+// uid-range       = (uniqueid ":" uniqueid)
+//                   ; two uniqueid values and all values
+//                   ; between these two regardless of order.
+// [RFC4315 - 4 Formal Syntax](https://tools.ietf.org/html/rfc4315#section-4)
+fn uid_set(i: &[u8]) -> IResult<&[u8], Vec<UidSetMember>> {
+    separated_nonempty_list(
+        char(','),
+        map(
+            tuple((number, opt(preceded(char(':'), number)))),
+            |(start, end)| match end {
+                Some(end) => UidSetMember::UidRange(start.min(end)..=start.max(end)),
+                None => UidSetMember::Uid(start),
+            },
+        ),
+    )(i)
+}
@@ -8,7 +8,7 @@
 use nom::{bytes::streaming::tag_no_case, sequence::tuple, IResult};
 
 use crate::{
-    parser::core::{number_64, paren_delimited},
+    parser::core::{number_64, paren_delimited, sp},
     types::*,
 };
 
@@ -30,7 +30,7 @@ pub(crate) fn status_att_val_highest_mod_seq(i: &[u8]) -> IResult<&[u8], StatusA
 }
 
 // [RFC4551 - 4. Formal Syntax - fetch-mod-resp](https://tools.ietf.org/html/rfc4551#section-4)
-pub(crate) fn msg_att_mod_seq(i: &[u8]) -> IResult<&[u8], AttributeValue> {
-    let (i, (_, num)) = tuple((tag_no_case("MODSEQ "), paren_delimited(number_64)))(i)?;
+pub(crate) fn msg_att_mod_seq(i: &[u8]) -> IResult<&[u8], AttributeValue<'_>> {
+    let (i, (_, _, num)) = tuple((tag_no_case("MODSEQ"), sp, paren_delimited(number_64)))(i)?;
     Ok((i, AttributeValue::ModSeq(num)))
 }
@@ -0,0 +1,88 @@
+//!
+//! https://tools.ietf.org/html/rfc4731
+//!
+//! IMAP4 Extension to SEARCH Command for Controlling What Kind of Information Is Returned
+//!
+
+use std::borrow::Cow;
+
+use nom::{
+    branch::alt,
+    bytes::streaming::tag_no_case,
+    character::streaming::char,
+    combinator::{map, opt},
+    multi::many0,
+    sequence::{delimited, preceded, tuple},
+    IResult,
+};
+
+use crate::parser::core::{number, number_64, quoted_utf8, sequence_set};
+use crate::types::*;
+
+enum SearchReturnData {
+    Min(u32),
+    Max(u32),
+    All(SequenceSet),
+    Count(u32),
+    ModSeq(u64),
+}
+
+// The ESEARCH response, sent instead of SEARCH if return options were specified.
+// [RFC4731 - 3.1 The ESEARCH Response](https://tools.ietf.org/html/rfc4731#section-3.1)
+// [RFC4731 - 5 Formal Syntax - esearch-response](https://tools.ietf.org/html/rfc4731#section-5)
+pub(crate) fn mailbox_data_esearch(i: &[u8]) -> IResult<&[u8], MailboxDatum<'_>> {
+    let (i, (_, tag, uid, data)) = tuple((
+        tag_no_case("ESEARCH"),
+        opt(search_correlator),
+        map(opt(tag_no_case(" UID")), |uid| uid.is_some()),
+        many0(preceded(char(' '), search_return_data)),
+    ))(i)?;
+
+    let mut esearch = ESearch {
+        tag,
+        uid,
+        min: None,
+        max: None,
+        all: None,
+        count: None,
+        mod_seq: None,
+    };
+    for item in data {
+        match item {
+            SearchReturnData::Min(n) => esearch.min = Some(n),
+            SearchReturnData::Max(n) => esearch.max = Some(n),
+            SearchReturnData::All(set) => esearch.all = Some(set),
+            SearchReturnData::Count(n) => esearch.count = Some(n),
+            SearchReturnData::ModSeq(n) => esearch.mod_seq = Some(n),
+        }
+    }
+    Ok((i, MailboxDatum::ESearch(esearch)))
+}
+
+// search-correlator = SP "(" "TAG" SP tag-string ")"
+fn search_correlator(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    delimited(tag_no_case(" (TAG "), quoted_utf8, char(')'))(i)
+}
+
+// search-return-data = "MIN" SP nz-number / "MAX" SP nz-number /
+//                      "ALL" SP sequence-set / "COUNT" SP number /
+//                      search-ret-data-ext
+// MODSEQ is added by [RFC7162 - 3.1.5](https://tools.ietf.org/html/rfc7162#section-3.1.5)
+fn search_return_data(i: &[u8]) -> IResult<&[u8], SearchReturnData> {
+    alt((
+        map(preceded(tag_no_case("MIN "), number), SearchReturnData::Min),
+        map(preceded(tag_no_case("MAX "), number), SearchReturnData::Max),
+        map(
+            preceded(tag_no_case("ALL "), sequence_set),
+            SearchReturnData::All,
+        ),
+        map(
+            preceded(tag_no_case("COUNT "), number),
+            SearchReturnData::Count,
+        ),
+        map(
+            preceded(tag_no_case("MODSEQ "), number_64),
+            SearchReturnData::ModSeq,
+        ),
+    ))(i)
+}
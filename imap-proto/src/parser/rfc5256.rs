@@ -0,0 +1,22 @@
+//!
+//! https://tools.ietf.org/html/rfc5256
+//!
+//! IMAP SORT and THREAD Extensions
+//!
+
+use nom::{
+    bytes::streaming::tag_no_case, character::streaming::char, combinator::map, multi::many0,
+    sequence::preceded, IResult,
+};
+
+use crate::parser::core::number;
+use crate::types::*;
+
+// The SORT response, listing the messages in the requested order.
+// [RFC5256 - 4 Additional Responses](https://tools.ietf.org/html/rfc5256#section-4)
+pub(crate) fn mailbox_data_sort(i: &[u8]) -> IResult<&[u8], MailboxDatum<'_>> {
+    map(
+        preceded(tag_no_case("SORT"), many0(preceded(char(' '), number))),
+        MailboxDatum::Sort,
+    )(i)
+}
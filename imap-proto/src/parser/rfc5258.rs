@@ -0,0 +1,71 @@
+//!
+//! https://tools.ietf.org/html/rfc5258
+//!
+//! IMAP4 - LIST Command Extensions
+//!
+
+use std::borrow::Cow;
+
+use nom::{
+    branch::alt,
+    character::streaming::{char, one_of},
+    combinator::{map, not},
+    multi::separated_list,
+    sequence::{delimited, terminated, tuple},
+    IResult,
+};
+
+use crate::parser::core::{astring_utf8, number_64, sequence_set};
+use crate::types::*;
+
+// mbox-list-extended = "(" [mbox-list-extended-item
+//                      *(SP mbox-list-extended-item)] ")"
+//
+// mbox-list-extended-item = mbox-list-extended-item-tag SP tagged-ext-val
+pub(crate) fn mbox_list_extended(
+    i: &[u8],
+) -> IResult<&[u8], Vec<(Cow<'_, str>, TaggedExtValue<'_>)>> {
+    delimited(
+        char('('),
+        separated_list(
+            char(' '),
+            map(
+                tuple((astring_utf8, char(' '), tagged_ext_val)),
+                |(tag, _, value)| (tag, value),
+            ),
+        ),
+        char(')'),
+    )(i)
+}
+
+// tagged-ext-val = tagged-ext-simple / "(" [tagged-ext-comp] ")"
+//
+// tagged-ext-simple = sequence-set / number / number64  (RFC 4466)
+fn tagged_ext_val(i: &[u8]) -> IResult<&[u8], TaggedExtValue<'_>> {
+    alt((
+        map(
+            delimited(char('('), tagged_ext_comp, char(')')),
+            TaggedExtValue::List,
+        ),
+        map(
+            terminated(number_64, not(one_of(",:"))),
+            TaggedExtValue::Number,
+        ),
+        map(sequence_set, TaggedExtValue::SequenceSet),
+    ))(i)
+}
+
+// tagged-ext-comp = astring / tagged-ext-comp *(SP tagged-ext-comp) /
+//                   "(" tagged-ext-comp ")"
+fn tagged_ext_comp(i: &[u8]) -> IResult<&[u8], Vec<TaggedExtValue<'_>>> {
+    separated_list(
+        char(' '),
+        alt((
+            map(
+                delimited(char('('), tagged_ext_comp, char(')')),
+                TaggedExtValue::List,
+            ),
+            map(astring_utf8, TaggedExtValue::Str),
+        )),
+    )(i)
+}
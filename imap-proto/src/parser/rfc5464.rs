@@ -4,12 +4,14 @@
 //! IMAP METADATA extension
 //!
 
+use std::borrow::Cow;
+
 use nom::{
     branch::alt,
     bytes::streaming::{tag, tag_no_case},
-    combinator::{map, map_opt},
+    combinator::map,
     multi::separated_list,
-    sequence::tuple,
+    sequence::{preceded, tuple},
     IResult,
 };
 
@@ -66,7 +68,7 @@ fn check_vendor_comment(i: &[u8], l: usize) -> EntryParseStage {
 }
 
 fn check_path(i: &[u8], l: usize) -> EntryParseStage {
-    if i.len() == l || i[l] == b' ' || i[l] == b'\r' {
+    if i.len() == l || i[l] == b' ' || i[l] == b'\r' || i[l] == b'\n' {
         return EntryParseStage::Done(l);
     } else if i[l] != b'/' {
         return EntryParseStage::Fail(nom::Err::Error(b"Entry name path is corrupted"));
@@ -112,45 +114,42 @@ fn check_entry_name(i: &[u8]) -> IResult<&[u8], &[u8]> {
     }
 }
 
-fn entry_name(i: &[u8]) -> IResult<&[u8], &[u8]> {
-    let astring_res = astring(i)?;
-    check_entry_name(astring_res.1)?;
-    Ok(astring_res)
+fn entry_name(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    let (rest, name) = astring(i)?;
+    match check_entry_name(&name) {
+        Ok(_) => Ok((rest, cow_to_str(name))),
+        Err(nom::Err::Incomplete(needed)) => Err(nom::Err::Incomplete(needed)),
+        Err(_) => Err(nom::Err::Error((i, nom::error::ErrorKind::Verify))),
+    }
 }
 
-fn slice_to_str(i: &[u8]) -> &str {
-    std::str::from_utf8(i).unwrap()
+fn cow_to_str(i: Cow<[u8]>) -> Cow<str> {
+    utf8(i).unwrap()
 }
 
 fn nil_value(i: &[u8]) -> IResult<&[u8], Option<String>> {
-    map_opt(tag_no_case("NIL"), |_| None)(i)
+    map(tag_no_case("NIL"), |_| None)(i)
 }
 
 fn string_value(i: &[u8]) -> IResult<&[u8], Option<String>> {
-    map(alt((quoted, literal)), |s| {
-        Some(slice_to_str(s).to_string())
-    })(i)
+    map(string, |s| Some(cow_to_str(s).into_owned()))(i)
 }
 
 fn keyval_list(i: &[u8]) -> IResult<&[u8], Vec<Metadata>> {
     parenthesized_nonempty_list(map(
-        tuple((
-            map(entry_name, slice_to_str),
-            tag(" "),
-            alt((nil_value, string_value)),
-        )),
+        tuple((entry_name, tag(" "), alt((nil_value, string_value)))),
         |(key, _, value)| Metadata {
-            entry: key.to_string(),
+            entry: key.into_owned(),
             value,
         },
     ))(i)
 }
 
-fn entry_list(i: &[u8]) -> IResult<&[u8], Vec<&str>> {
-    separated_list(tag(" "), map(entry_name, slice_to_str))(i)
+fn entry_list(i: &[u8]) -> IResult<&[u8], Vec<Cow<'_, str>>> {
+    separated_list(tag(" "), entry_name)(i)
 }
 
-fn metadata_common(i: &[u8]) -> IResult<&[u8], &[u8]> {
+fn metadata_common(i: &[u8]) -> IResult<&[u8], Cow<'_, [u8]>> {
     let (i, (_, mbox, _)) = tuple((tag_no_case("METADATA "), quoted, tag(" ")))(i)?;
     Ok((i, mbox))
 }
@@ -161,7 +160,7 @@ pub(crate) fn metadata_solicited(i: &[u8]) -> IResult<&[u8], Response> {
     Ok((
         i,
         Response::MailboxData(MailboxDatum::MetadataSolicited {
-            mailbox: slice_to_str(mailbox),
+            mailbox: cow_to_str(mailbox),
             values,
         }),
     ))
@@ -173,12 +172,35 @@ pub(crate) fn metadata_unsolicited(i: &[u8]) -> IResult<&[u8], Response> {
     Ok((
         i,
         Response::MailboxData(MailboxDatum::MetadataUnsolicited {
-            mailbox: slice_to_str(mailbox),
+            mailbox: cow_to_str(mailbox),
             values,
         }),
     ))
 }
 
+// The METADATA response codes, sent for GETMETADATA entries that were left
+// out and for SETMETADATA commands that failed.
+// [RFC5464 - 4.2.1 GETMETADATA MAXSIZE](https://tools.ietf.org/html/rfc5464#section-4.2.1)
+// [RFC5464 - 4.3 SETMETADATA Command](https://tools.ietf.org/html/rfc5464#section-4.3)
+pub(crate) fn resp_text_code_metadata(i: &[u8]) -> IResult<&[u8], ResponseCode<'_>> {
+    map(
+        preceded(
+            tag_no_case("METADATA "),
+            alt((
+                map(preceded(tag_no_case("LONGENTRIES "), number), |n| {
+                    MetadataCode::LongEntries(n)
+                }),
+                map(preceded(tag_no_case("MAXSIZE "), number), |n| {
+                    MetadataCode::MaxSize(n)
+                }),
+                map(tag_no_case("TOOMANY"), |_| MetadataCode::TooMany),
+                map(tag_no_case("NOPRIVATE"), |_| MetadataCode::NoPrivate),
+            )),
+        ),
+        ResponseCode::Metadata,
+    )(i)
+}
+
 #[cfg(test)]
 mod tests {
     use super::{metadata_solicited, metadata_unsolicited};
@@ -253,6 +275,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_nil_value() {
+        match metadata_solicited(b"METADATA \"INBOX\" (/private/comment NIL)\r\n") {
+            Ok((_, Response::MailboxData(MailboxDatum::MetadataSolicited { values, .. }))) => {
+                assert_eq!(values.len(), 1);
+                assert_eq!(values[0].entry, "/private/comment");
+                assert_eq!(values[0].value, None);
+            }
+            rsp => panic!("Unexpected response: {:?}", rsp),
+        }
+    }
+
     #[test]
     fn test_literal_success() {
         // match metadata_solicited(b"METADATA \"\" (/shared/vendor/vendor.coi/a \"AAA\")\r\n")
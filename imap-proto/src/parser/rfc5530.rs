@@ -0,0 +1,44 @@
+//!
+//! https://tools.ietf.org/html/rfc5530
+//!
+//! IMAP Response Codes
+//!
+
+use nom::{branch::alt, bytes::streaming::tag_no_case, combinator::map, IResult};
+
+use crate::types::*;
+
+// Response codes describing why a command failed, so that clients can tell
+// whether and how to recover.
+// [RFC5530 - 3. Response Codes](https://tools.ietf.org/html/rfc5530#section-3)
+pub(crate) fn resp_text_code(i: &[u8]) -> IResult<&[u8], ResponseCode<'_>> {
+    alt((
+        map(tag_no_case("ALREADYEXISTS"), |_| {
+            ResponseCode::AlreadyExists
+        }),
+        map(tag_no_case("AUTHENTICATIONFAILED"), |_| {
+            ResponseCode::AuthenticationFailed
+        }),
+        map(tag_no_case("AUTHORIZATIONFAILED"), |_| {
+            ResponseCode::AuthorizationFailed
+        }),
+        map(tag_no_case("CANNOT"), |_| ResponseCode::Cannot),
+        map(tag_no_case("CLIENTBUG"), |_| ResponseCode::ClientBug),
+        map(tag_no_case("CONTACTADMIN"), |_| ResponseCode::ContactAdmin),
+        map(tag_no_case("CORRUPTION"), |_| ResponseCode::Corruption),
+        map(tag_no_case("EXPIRED"), |_| ResponseCode::Expired),
+        map(tag_no_case("EXPUNGEISSUED"), |_| {
+            ResponseCode::ExpungeIssued
+        }),
+        map(tag_no_case("INUSE"), |_| ResponseCode::InUse),
+        map(tag_no_case("LIMIT"), |_| ResponseCode::Limit),
+        map(tag_no_case("NONEXISTENT"), |_| ResponseCode::NonExistent),
+        map(tag_no_case("NOPERM"), |_| ResponseCode::NoPerm),
+        map(tag_no_case("OVERQUOTA"), |_| ResponseCode::OverQuota),
+        map(tag_no_case("PRIVACYREQUIRED"), |_| {
+            ResponseCode::PrivacyRequired
+        }),
+        map(tag_no_case("SERVERBUG"), |_| ResponseCode::ServerBug),
+        map(tag_no_case("UNAVAILABLE"), |_| ResponseCode::Unavailable),
+    ))(i)
+}
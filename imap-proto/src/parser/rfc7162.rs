@@ -0,0 +1,34 @@
+//!
+//! https://tools.ietf.org/html/rfc7162
+//!
+//! The IMAP QRESYNC Extensions
+//!
+
+use nom::{
+    bytes::streaming::tag_no_case,
+    combinator::{map, opt},
+    sequence::tuple,
+    IResult,
+};
+
+use crate::parser::core::sequence_set;
+use crate::types::*;
+
+// The VANISHED response reports that the specified UIDs have been
+// permanently removed from the mailbox.
+// [RFC7162 - 3.2.10 VANISHED Response](https://tools.ietf.org/html/rfc7162#section-3.2.10)
+pub(crate) fn resp_vanished(i: &[u8]) -> IResult<&[u8], Response<'_>> {
+    let (i, (_, earlier, uids)) = tuple((
+        tag_no_case("VANISHED "),
+        map(opt(tag_no_case("(EARLIER) ")), |earlier| earlier.is_some()),
+        sequence_set,
+    ))(i)?;
+    Ok((i, Response::Vanished { earlier, uids }))
+}
+
+// The CLOSED response code signals that the previously selected mailbox
+// has been closed, when selecting another mailbox.
+// [RFC7162 - 3.2.11 CLOSED Response Code](https://tools.ietf.org/html/rfc7162#section-3.2.11)
+pub(crate) fn resp_text_code_closed(i: &[u8]) -> IResult<&[u8], ResponseCode<'_>> {
+    map(tag_no_case("CLOSED"), |_| ResponseCode::Closed)(i)
+}
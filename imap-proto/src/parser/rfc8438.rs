@@ -0,0 +1,18 @@
+//!
+//! https://tools.ietf.org/html/rfc8438
+//!
+//! IMAP Extension for STATUS=SIZE
+//!
+
+use nom::{bytes::streaming::tag_no_case, sequence::tuple, IResult};
+
+use crate::{parser::core::number_64, types::*};
+
+// The total size of the mailbox in octets.
+// Extends status-att-val defined in rfc3501.
+// [RFC8438 - 2. The STATUS=SIZE Extension](https://tools.ietf.org/html/rfc8438#section-2)
+// [RFC8438 - 4. Formal Syntax](https://tools.ietf.org/html/rfc8438#section-4)
+pub(crate) fn status_att_val_size(i: &[u8]) -> IResult<&[u8], StatusAttribute<'_>> {
+    let (i, (_, num)) = tuple((tag_no_case("SIZE "), number_64))(i)?;
+    Ok((i, StatusAttribute::Size(num)))
+}
@@ -0,0 +1,58 @@
+//!
+//! https://tools.ietf.org/html/rfc9208
+//!
+//! IMAP QUOTA Extension
+//!
+
+use nom::{
+    bytes::streaming::tag_no_case,
+    character::streaming::char,
+    combinator::map,
+    multi::many0,
+    sequence::{preceded, tuple},
+    IResult,
+};
+
+use crate::parser::core::{astring_utf8, atom, number_64, parenthesized_list};
+use crate::parser::rfc3501::mailbox;
+use crate::types::*;
+
+// The QUOTA response, giving the usage and limits of the resources in a quota
+// root.
+// [RFC9208 - 5.1 QUOTA Response](https://tools.ietf.org/html/rfc9208#section-5.1)
+pub(crate) fn mailbox_data_quota(i: &[u8]) -> IResult<&[u8], MailboxDatum<'_>> {
+    map(
+        tuple((
+            tag_no_case("QUOTA "),
+            astring_utf8,
+            char(' '),
+            parenthesized_list(quota_resource),
+        )),
+        |(_, root, _, resources)| MailboxDatum::Quota { root, resources },
+    )(i)
+}
+
+// [RFC9208 - 7 Formal Syntax](https://tools.ietf.org/html/rfc9208#section-7)
+fn quota_resource(i: &[u8]) -> IResult<&[u8], QuotaResource> {
+    map(
+        tuple((atom, char(' '), number_64, char(' '), number_64)),
+        |(name, _, usage, _, limit)| QuotaResource {
+            name: name.to_ascii_uppercase(),
+            usage,
+            limit,
+        },
+    )(i)
+}
+
+// The QUOTAROOT response, listing the quota roots of a mailbox.
+// [RFC9208 - 5.2 QUOTAROOT Response](https://tools.ietf.org/html/rfc9208#section-5.2)
+pub(crate) fn mailbox_data_quota_root(i: &[u8]) -> IResult<&[u8], MailboxDatum<'_>> {
+    map(
+        tuple((
+            tag_no_case("QUOTAROOT "),
+            mailbox,
+            many0(preceded(char(' '), astring_utf8)),
+        )),
+        |(_, mailbox, roots)| MailboxDatum::QuotaRoot { mailbox, roots },
+    )(i)
+}
@@ -1,5 +1,6 @@
-use super::parse_response;
+use super::{parse_response, parse_response_with, Quirks};
 use crate::types::*;
+use std::borrow::Cow;
 
 #[test]
 fn test_mailbox_data_response() {
@@ -9,6 +10,83 @@ fn test_mailbox_data_response() {
     }
 }
 
+#[test]
+fn test_list_extended() {
+    match parse_response(b"* LIST () \"/\" Foo (\"CHILDINFO\" (\"SUBSCRIBED\" (a b)) X-ID 4:7)\r\n")
+    {
+        Ok((
+            _,
+            Response::MailboxData(MailboxDatum::List {
+                name, extensions, ..
+            }),
+        )) => {
+            assert_eq!(name, "Foo");
+            assert_eq!(
+                extensions,
+                [
+                    (
+                        Cow::Borrowed("CHILDINFO"),
+                        TaggedExtValue::List(vec![
+                            TaggedExtValue::Str("SUBSCRIBED".into()),
+                            TaggedExtValue::List(vec![
+                                TaggedExtValue::Str("a".into()),
+                                TaggedExtValue::Str("b".into()),
+                            ]),
+                        ])
+                    ),
+                    (
+                        Cow::Borrowed("X-ID"),
+                        TaggedExtValue::SequenceSet((4..=7).into())
+                    ),
+                ]
+            );
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+    match parse_response(b"* LIST (\\Noselect) \"/\" bar (OLDNAME (\"baz\") X-SIZE 12)\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::List { extensions, .. }))) => {
+            assert_eq!(
+                extensions,
+                [
+                    (
+                        Cow::Borrowed("OLDNAME"),
+                        TaggedExtValue::List(vec![TaggedExtValue::Str("baz".into())])
+                    ),
+                    (Cow::Borrowed("X-SIZE"), TaggedExtValue::Number(12)),
+                ]
+            );
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_quoted_escapes() {
+    match parse_response(b"* LIST () \"\\\\\" \"Say \\\"hi\\\"\\\\x\"\r\n") {
+        Ok((
+            _,
+            Response::MailboxData(MailboxDatum::List {
+                delimiter, name, ..
+            }),
+        )) => {
+            assert_eq!(delimiter.as_deref(), Some("\\"));
+            assert_eq!(name, "Say \"hi\"\\x");
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    const RESPONSE: &[u8] = b"* 1 FETCH (BODYSTRUCTURE (\"APPLICATION\" \"PDF\" (\"NAME\" \"a \\\"b\\\".pdf\") NIL NIL \"BASE64\" 10 NIL NIL NIL NIL))\r\n";
+    match parse_response(RESPONSE) {
+        Ok((_, Response::Fetch(_, attrs))) => match &attrs[0] {
+            AttributeValue::BodyStructure(BodyStructure::Basic { common, .. }) => {
+                assert_eq!(common.ty.param("name"), Some("a \"b\".pdf"));
+            }
+            attr => panic!("unexpected attribute {:?}", attr),
+        },
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+}
+
 #[test]
 fn test_number_overflow() {
     match parse_response(b"* 2222222222222222222222222222222222222222222C\r\n") {
@@ -25,7 +103,7 @@ fn test_unseen() {
             Response::Data {
                 status: Status::Ok,
                 code: Some(ResponseCode::Unseen(3)),
-                information: Some("Message 3 is first unseen"),
+                information: Some(Text(b"Message 3 is first unseen")),
             },
         ) => {}
         rsp => panic!("unexpected response {:?}", rsp),
@@ -42,7 +120,7 @@ fn test_body_text() {
                 &AttributeValue::BodySection {
                     section: Some(SectionPath::Full(MessageSection::Text)),
                     index: None,
-                    data: Some(b"foo"),
+                    data: Some(Cow::Borrowed(b"foo")),
                 },
                 "body = {:?}",
                 body
@@ -72,6 +150,25 @@ fn test_body_structure() {
     }
 }
 
+#[test]
+fn test_fetch_attributes_any_case_and_order() {
+    const RESPONSE: &[u8] = b"* 15 fetch (Body (\"TEXT\" \"PLAIN\" NIL NIL NIL \"7BIT\" 0 0) \
+        flags (\\Seen) Rfc822.Size 12 uid 4 Flags (\\Seen \\Answered))\r\n";
+    match parse_response(RESPONSE) {
+        Ok((_, Response::Fetch(15, attrs))) => {
+            match &attrs[0] {
+                AttributeValue::BodyStructure(BodyStructure::Text { .. }) => {}
+                attr => panic!("unexpected attribute {:?}", attr),
+            }
+            assert_eq!(attrs[1], AttributeValue::Flags(vec!["\\Seen"]));
+            assert_eq!(attrs[2], AttributeValue::Rfc822Size(12));
+            assert_eq!(attrs[3], AttributeValue::Uid(4));
+            assert_eq!(attrs.len(), 5);
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+}
+
 #[test]
 fn test_status() {
     match parse_response(b"* STATUS blurdybloop (MESSAGES 231 UIDNEXT 44292)\r\n") {
@@ -87,6 +184,33 @@ fn test_status() {
         }
         rsp => panic!("unexpected response {:?}", rsp),
     }
+    match parse_response(b"* STATUS INBOX (MESSAGES 2 SIZE 12345678901)\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::Status { status, .. }))) => {
+            assert_eq!(
+                status,
+                [
+                    StatusAttribute::Messages(2),
+                    StatusAttribute::Size(12_345_678_901),
+                ]
+            );
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+    match parse_response(b"* STATUS INBOX (MESSAGES 2 X-GUID 3f2a0c1e X-NAME \"a b\" UNSEEN 1)\r\n")
+    {
+        Ok((_, Response::MailboxData(MailboxDatum::Status { status, .. }))) => {
+            assert_eq!(
+                status,
+                [
+                    StatusAttribute::Messages(2),
+                    StatusAttribute::Other("X-GUID".into(), "3f2a0c1e".into()),
+                    StatusAttribute::Other("X-NAME".into(), "a b".into()),
+                    StatusAttribute::Unseen(1),
+                ]
+            );
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
 }
 
 #[test]
@@ -104,7 +228,7 @@ fn test_notify() {
             _,
             Response::Continue {
                 code: None,
-                information: Some("idling"),
+                information: Some(Text(b"idling")),
             },
         )) => {}
         rsp => panic!("unexpected response {:?}", rsp),
@@ -114,18 +238,26 @@ fn test_notify() {
 #[test]
 fn test_search() {
     match parse_response(b"* SEARCH\r\n") {
-        Ok((_, Response::MailboxData(MailboxDatum::Search(ids)))) => {
+        Ok((_, Response::MailboxData(MailboxDatum::Search { ids, mod_seq }))) => {
             assert!(ids.is_empty());
+            assert_eq!(mod_seq, None);
         }
         rsp => panic!("unexpected response {:?}", rsp),
     }
     match parse_response(b"* SEARCH 12345 67890\r\n") {
-        Ok((_, Response::MailboxData(MailboxDatum::Search(ids)))) => {
+        Ok((_, Response::MailboxData(MailboxDatum::Search { ids, .. }))) => {
             assert_eq!(ids[0], 12345);
             assert_eq!(ids[1], 67890);
         }
         rsp => panic!("unexpected response {:?}", rsp),
     }
+    match parse_response(b"* SEARCH 2 5 6 (MODSEQ 917162500)\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::Search { ids, mod_seq }))) => {
+            assert_eq!(ids, [2, 5, 6]);
+            assert_eq!(mod_seq, Some(917_162_500));
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
 }
 
 #[test]
@@ -155,6 +287,34 @@ fn test_header_fields() {
     }
 }
 
+#[test]
+fn test_resp_text() {
+    // Localized Exchange errors, with brackets, tabs and 8-bit text
+    match parse_response(b"A1 NO Fehler ] beim\tAnmelden: \xe4\x01\x00\x7f [x]\r\n") {
+        Ok((
+            _,
+            Response::Done {
+                code: None,
+                information: Some(Text(text)),
+                ..
+            },
+        )) => assert_eq!(text, b"Fehler ] beim\tAnmelden: \xe4\x01\x00\x7f [x]"),
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    match parse_response(b"* OK [ALERT]Mailbox ] almost full\r\n") {
+        Ok((
+            _,
+            Response::Data {
+                code: Some(ResponseCode::Alert),
+                information: Some(Text(b"Mailbox ] almost full")),
+                ..
+            },
+        )) => {}
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+}
+
 #[test]
 fn test_response_codes() {
     match parse_response(b"* OK [ALERT] Alert!\r\n") {
@@ -163,7 +323,7 @@ fn test_response_codes() {
             Response::Data {
                 status: Status::Ok,
                 code: Some(ResponseCode::Alert),
-                information: Some("Alert!"),
+                information: Some(Text(b"Alert!")),
             },
         )) => {}
         rsp => panic!("unexpected response {:?}", rsp),
@@ -175,7 +335,7 @@ fn test_response_codes() {
             Response::Data {
                 status: Status::No,
                 code: Some(ResponseCode::Parse),
-                information: Some("Something"),
+                information: Some(Text(b"Something")),
             },
         )) => {}
         rsp => panic!("unexpected response {:?}", rsp),
@@ -187,7 +347,7 @@ fn test_response_codes() {
             Response::Data {
                 status: Status::Ok,
                 code: Some(ResponseCode::Capabilities(c)),
-                information: Some("Logged in"),
+                information: Some(Text(b"Logged in")),
             },
         )) => {
             assert_eq!(c.len(), 2);
@@ -203,7 +363,7 @@ fn test_response_codes() {
             Response::Data {
                 status: Status::Ok,
                 code: Some(ResponseCode::Capabilities(c)),
-                information: Some("Logged in"),
+                information: Some(Text(b"Logged in")),
             },
         )) => {
             assert_eq!(c.len(), 3);
@@ -221,19 +381,41 @@ fn test_response_codes() {
             Response::Data {
                 status: Status::Ok,
                 code: None,
-                information: Some("[CAPABILITY UIDPLUS IDLE] Logged in"),
+                information: Some(Text(b"[CAPABILITY UIDPLUS IDLE] Logged in")),
             },
         )) => {}
         rsp => panic!("unexpected response {:?}", rsp),
     }
 
+    match parse_response(b"* CAPABILITY IMAP4rev2 IDLE\r\n") {
+        Ok((_, Response::Capabilities(c))) => {
+            assert_eq!(c, [Capability::Atom("IMAP4rev2"), Capability::Atom("IDLE")]);
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    let quirks = Quirks::new().missing_imap4rev(true);
+    match parse_response_with(b"* OK [CAPABILITY UIDPLUS IDLE] Logged in\r\n", &quirks) {
+        Ok((
+            _,
+            Response::Data {
+                code: Some(ResponseCode::Capabilities(c)),
+                information: Some(Text(b"Logged in")),
+                ..
+            },
+        )) => {
+            assert_eq!(c, [Capability::Atom("UIDPLUS"), Capability::Atom("IDLE")]);
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
     match parse_response(b"* NO [BADCHARSET] error\r\n") {
         Ok((
             _,
             Response::Data {
                 status: Status::No,
                 code: Some(ResponseCode::BadCharset(None)),
-                information: Some("error"),
+                information: Some(Text(b"error")),
             },
         )) => {}
         rsp => panic!("unexpected response {:?}", rsp),
@@ -245,7 +427,7 @@ fn test_response_codes() {
             Response::Data {
                 status: Status::No,
                 code: Some(ResponseCode::BadCharset(Some(v))),
-                information: Some("error"),
+                information: Some(Text(b"error")),
             },
         )) => {
             assert_eq!(v.len(), 2);
@@ -255,13 +437,74 @@ fn test_response_codes() {
         rsp => panic!("unexpected response {:?}", rsp),
     }
 
+    match parse_response(b"* NO [BADCHARSET \"UTF-8\" US-ASCII] error\r\n") {
+        Ok((
+            _,
+            Response::Data {
+                status: Status::No,
+                code: Some(ResponseCode::BadCharset(Some(v))),
+                information: Some(Text(b"error")),
+            },
+        )) => {
+            assert_eq!(&v[..], ["UTF-8", "US-ASCII"]);
+            assert_eq!(v.select(&["utf-8"]), Some("UTF-8"));
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    match parse_response(b"* NO [BADCHARSET (\"ISO-8859-1\" KOI8-R)] error\r\n") {
+        Ok((
+            _,
+            Response::Data {
+                code: Some(ResponseCode::BadCharset(Some(v))),
+                ..
+            },
+        )) => assert_eq!(&v[..], ["ISO-8859-1", "KOI8-R"]),
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
     match parse_response(b"* NO [BADCHARSET ()] error\r\n") {
         Ok((
             _,
             Response::Data {
                 status: Status::No,
                 code: None,
-                information: Some("[BADCHARSET ()] error"),
+                information: Some(Text(b"[BADCHARSET ()] error")),
+            },
+        )) => {}
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    match parse_response(b"* OK [XPROXYREUSE] Proxied\r\n") {
+        Ok((
+            _,
+            Response::Data {
+                code: Some(ResponseCode::Other("XPROXYREUSE")),
+                information: Some(Text(b"Proxied")),
+                ..
+            },
+        )) => {}
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    match parse_response(b"A1 NO [UNDEFINED-FILTER foo (bar)] Unknown filter\r\n") {
+        Ok((
+            _,
+            Response::Done {
+                code: Some(ResponseCode::Other("UNDEFINED-FILTER foo (bar)")),
+                information: Some(Text(b"Unknown filter")),
+                ..
+            },
+        )) => {}
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    match parse_response(b"* OK [ALERTS] Read them\r\n") {
+        Ok((
+            _,
+            Response::Data {
+                code: Some(ResponseCode::Other("ALERTS")),
+                ..
             },
         )) => {}
         rsp => panic!("unexpected response {:?}", rsp),
@@ -301,6 +544,36 @@ fn test_continuation() {
         )) => {}
         rsp => panic!("unexpected response {:?}", rsp),
     }
+
+    // SASL challenges are kept as text, to be decoded during AUTHENTICATE
+    match parse_response(b"+ YWJj\r\n") {
+        Ok((
+            _,
+            rsp @ Response::Continue {
+                information: Some(Text(b"YWJj")),
+                ..
+            },
+        )) => assert_eq!(rsp.decode_challenge().unwrap(), b"abc"),
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+    match parse_response(b"+ cj1meWtvK3Jnai8=\r\n") {
+        Ok((_, rsp)) => assert_eq!(rsp.decode_challenge().unwrap(), b"r=fyko+rgj/"),
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+    match parse_response(b"+ \r\n") {
+        Ok((_, rsp)) => assert_eq!(rsp.decode_challenge().unwrap(), b""),
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+    for invalid in &[
+        &b"+ Ready for literal data\r\n"[..],
+        b"+ YWJ\r\n",
+        b"+ YQ=j\r\n",
+    ] {
+        match parse_response(invalid) {
+            Ok((_, rsp @ Response::Continue { .. })) => assert!(rsp.decode_challenge().is_none()),
+            rsp => panic!("unexpected response {:?}", rsp),
+        }
+    }
 }
 
 #[test]
@@ -337,3 +610,585 @@ fn test_flags() {
         rsp => panic!("Unexpected response: {:?}", rsp),
     }
 }
+
+#[test]
+fn test_vanished() {
+    match parse_response(b"* VANISHED (EARLIER) 41,43:116,118,120:211,214:540\r\n") {
+        Ok((_, Response::Vanished { earlier, uids })) => {
+            assert!(earlier);
+            assert_eq!(uids.to_string(), "41,43:116,118,120:211,214:540");
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+
+    match parse_response(b"* VANISHED 405,407,410:409\r\n") {
+        Ok((_, Response::Vanished { earlier, uids })) => {
+            assert!(!earlier);
+            assert_eq!(uids.to_string(), "405,407,409:410");
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_referral() {
+    match parse_response(b"A1 NO [REFERRAL IMAP://user;AUTH=*@SERVER2/] Remote Server\r\n") {
+        Ok((
+            _,
+            Response::Done {
+                code: Some(ResponseCode::Referral(url)),
+                ..
+            },
+        )) => assert_eq!(url, "IMAP://user;AUTH=*@SERVER2/"),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_rfc5530_codes() {
+    match parse_response(b"A1 NO [UNAVAILABLE] User's backend down for maintenance\r\n") {
+        Ok((
+            _,
+            Response::Done {
+                code: Some(ResponseCode::Unavailable),
+                information: Some(Text(b"User's backend down for maintenance")),
+                ..
+            },
+        )) => {}
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"A2 NO [INUSE] Mailbox in use\r\n") {
+        Ok((
+            _,
+            Response::Done {
+                code: Some(ResponseCode::InUse),
+                ..
+            },
+        )) => {}
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_closed() {
+    match parse_response(b"* OK [CLOSED] Previous mailbox closed\r\n") {
+        Ok((
+            _,
+            Response::Data {
+                code: Some(ResponseCode::Closed),
+                ..
+            },
+        )) => {}
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_append_uid() {
+    match parse_response(b"A003 OK [APPENDUID 38505 3955] APPEND completed\r\n") {
+        Ok((
+            _,
+            Response::Done {
+                code: Some(ResponseCode::AppendUid(38505, uids)),
+                ..
+            },
+        )) => assert_eq!(uids, vec![UidSetMember::Uid(3955)]),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_copy_uid() {
+    match parse_response(b"A003 OK [COPYUID 38505 304,319:320 3956:3958] Done\r\n") {
+        Ok((
+            _,
+            Response::Done {
+                code: Some(ResponseCode::CopyUid(38505, source, dest)),
+                ..
+            },
+        )) => {
+            assert_eq!(
+                source,
+                vec![UidSetMember::Uid(304), UidSetMember::UidRange(319..=320)]
+            );
+            assert_eq!(dest, vec![UidSetMember::UidRange(3956..=3958)]);
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_esearch() {
+    match parse_response(b"* ESEARCH (TAG \"A282\") UID MIN 2 COUNT 3 ALL 2,10:11\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::ESearch(esearch)))) => {
+            assert_eq!(esearch.tag.as_deref(), Some("A282"));
+            assert!(esearch.uid);
+            assert_eq!(esearch.min, Some(2));
+            assert_eq!(esearch.max, None);
+            assert_eq!(esearch.count, Some(3));
+            assert_eq!(esearch.all.unwrap().to_string(), "2,10:11");
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+
+    match parse_response(b"* ESEARCH (TAG \"A283\")\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::ESearch(esearch)))) => {
+            assert!(!esearch.uid);
+            assert_eq!(esearch.all, None);
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_namespace() {
+    match parse_response(
+        b"* NAMESPACE ((\"INBOX.\" \".\")) NIL ((\"#shared/\" \"/\" \"X-PARAM\" (\"a\" \"b\"))(\"#ftp\" NIL))\r\n",
+    ) {
+        Ok((_, Response::Namespace(namespaces))) => {
+            assert_eq!(namespaces.personal, [Namespace::new("INBOX.", Some('.'))]);
+            assert!(namespaces.other.is_empty());
+            assert_eq!(namespaces.shared.len(), 2);
+            assert_eq!(
+                namespaces.shared[0].extensions,
+                [("X-PARAM".to_string(), vec!["a".to_string(), "b".to_string()])]
+            );
+            assert_eq!(namespaces.shared[1], Namespace::new("#ftp", None));
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_acl() {
+    match parse_response(b"* ACL Shared alice lrswipkxtea \"anyone\" lr\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::Acl { mailbox, entries }))) => {
+            assert_eq!(mailbox, "Shared");
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].identifier, "alice");
+            assert!(entries[0].rights.contains(Right::Administer));
+            assert_eq!(entries[1].rights.to_string(), "lr");
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"* LISTRIGHTS ~/Mail/saved smith la r swicdkxte\r\n") {
+        Ok((
+            _,
+            Response::MailboxData(MailboxDatum::ListRights {
+                required, optional, ..
+            }),
+        )) => {
+            assert!(required.contains(Right::Lookup));
+            assert_eq!(optional.len(), 2);
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"* MYRIGHTS INBOX rwiptsldaex\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::MyRights { mailbox, rights }))) => {
+            assert_eq!(mailbox, "INBOX");
+            assert!(rights.contains(Right::Expunge));
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_metadata_codes() {
+    match parse_response(b"A1 OK [METADATA LONGENTRIES 2199] GETMETADATA complete\r\n") {
+        Ok((_, Response::Done { code, .. })) => {
+            assert_eq!(
+                code,
+                Some(ResponseCode::Metadata(MetadataCode::LongEntries(2199)))
+            );
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"A2 NO [METADATA MAXSIZE 1024] Annotation too large\r\n") {
+        Ok((_, Response::Done { code, .. })) => {
+            assert_eq!(
+                code,
+                Some(ResponseCode::Metadata(MetadataCode::MaxSize(1024)))
+            );
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"A3 NO [METADATA NOPRIVATE] Private annotations not supported\r\n") {
+        Ok((_, Response::Done { code, .. })) => {
+            assert_eq!(code, Some(ResponseCode::Metadata(MetadataCode::NoPrivate)));
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_id() {
+    match parse_response(b"* ID (\"name\" \"Cyrus\" \"version\" \"1.5\" \"support-url\" NIL)\r\n") {
+        Ok((_, Response::Id(identity))) => {
+            assert_eq!(identity.name(), Some("Cyrus"));
+            assert_eq!(identity.version(), Some("1.5"));
+            assert_eq!(identity.fields.len(), 3);
+            assert_eq!(identity.get("support-url"), None);
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"* ID NIL\r\n") {
+        Ok((_, Response::Id(identity))) => assert!(identity.is_empty()),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_quota() {
+    match parse_response(b"* QUOTAROOT inbox \"\" user.alice\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::QuotaRoot { mailbox, roots }))) => {
+            assert_eq!(mailbox, "INBOX");
+            assert_eq!(roots, ["", "user.alice"]);
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"* QUOTA \"\" (storage 10 512 MESSAGE 3 100)\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::Quota { root, resources }))) => {
+            assert_eq!(root, "");
+            assert_eq!(resources.len(), 2);
+            assert_eq!(resources[0].name, "STORAGE");
+            assert_eq!(resources[0].remaining(), 502);
+            assert_eq!((resources[1].usage, resources[1].limit), (3, 100));
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"* QUOTA user ()\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::Quota { resources, .. }))) => {
+            assert!(resources.is_empty())
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_sort() {
+    match parse_response(b"* SORT 2 84 882\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::Sort(ids)))) => assert_eq!(ids, [2, 84, 882]),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    match parse_response(b"* SORT\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::Sort(ids)))) => assert!(ids.is_empty()),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_bare_lf() {
+    const RESPONSES: &[u8] =
+        b"* 1 FETCH (UID 4 BODY[] {3}\nfoo)\n* OK [UIDNEXT 5] Predicted\nA1 OK done\n";
+    assert!(parse_response(RESPONSES).is_err());
+
+    let lenient = Quirks::lenient();
+    let (rest, rsp) = parse_response_with(RESPONSES, &lenient).unwrap();
+    match rsp {
+        Response::Fetch(1, attrs) => assert_eq!(
+            attrs[1],
+            AttributeValue::BodySection {
+                section: None,
+                index: None,
+                data: Some(Cow::Borrowed(b"foo")),
+            }
+        ),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+    let (rest, rsp) = parse_response_with(rest, &lenient).unwrap();
+    assert!(matches!(
+        rsp,
+        Response::Data {
+            status: Status::Ok,
+            ..
+        }
+    ));
+    let (rest, rsp) = parse_response_with(rest, &lenient).unwrap();
+    assert!(matches!(
+        rsp,
+        Response::Done {
+            status: Status::Ok,
+            ..
+        }
+    ));
+    assert!(rest.is_empty());
+
+    // A CR still needs its LF, and the quirks only apply to the parse
+    match parse_response_with(b"A1 OK done\r", &lenient) {
+        Err(nom::Err::Incomplete(_)) => {}
+        rsp => panic!("should be incomplete: {:?}", rsp),
+    }
+    assert!(parse_response(b"A1 OK done\n").is_err());
+}
+
+#[test]
+fn test_nested_quirks() {
+    use super::quirks::{current, with};
+
+    let lenient = Quirks::lenient();
+    let loose = Quirks::new().loose_spacing(true);
+    with(lenient, || {
+        with(loose, || assert_eq!(current(), loose));
+        assert_eq!(current(), lenient);
+        assert!(parse_response_with(b"A1 OK done\r\n", &Quirks::new()).is_ok());
+        assert_eq!(current(), lenient);
+    });
+    assert_eq!(current(), Quirks::new());
+
+    // The quirks are restored even if the parse panics
+    let unwound = std::panic::catch_unwind(|| with(lenient, || panic!("parse failed")));
+    assert!(unwound.is_err());
+    assert_eq!(current(), Quirks::new());
+    assert!(parse_response(b"A1 OK done\n").is_err());
+}
+
+#[test]
+fn test_nonstandard_flags() {
+    const RESPONSE: &[u8] = b"* 3 FETCH (UID 7 FLAGS (\\Seen $Forwarded, \\Foo]))\r\n";
+    assert!(parse_response(RESPONSE).is_err());
+
+    let quirks = Quirks::new().nonstandard_flags(true);
+    match parse_response_with(RESPONSE, &quirks) {
+        Ok((_, Response::Fetch(3, attrs))) => match &attrs[1] {
+            AttributeValue::Flags(flags) => {
+                assert_eq!(&flags[..], &["\\Seen", "$Forwarded,", "\\Foo]"])
+            }
+            attr => panic!("Unexpected attribute: {:?}", attr),
+        },
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+
+    match parse_response_with(b"* OK [PERMANENTFLAGS (\\Seen \\*)] Limited\r\n", &quirks) {
+        Ok((_, Response::Data { code, .. })) => assert_eq!(
+            code,
+            Some(ResponseCode::PermanentFlags(PermanentFlags {
+                flags: vec!["\\Seen"].into_iter().collect(),
+                allows_new_keywords: true,
+            }))
+        ),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_quirks_for_server() {
+    let quirks = Quirks::for_server("IBM Domino IMAP Server");
+    assert!(quirks.accepts_nonstandard_flags() && quirks.accepts_loose_spacing());
+    assert!(!quirks.accepts_bare_lf());
+    assert_eq!(Quirks::for_server("Dovecot"), Quirks::new());
+
+    let quirks = Quirks::new()
+        .bare_lf(true)
+        .union(Quirks::for_server("DavMail"));
+    assert!(quirks.accepts_bare_lf() && quirks.accepts_loose_spacing());
+}
+
+#[test]
+fn test_8bit_text() {
+    match parse_response(b"A1 NO [TRYCREATE] Pas de bo\xeete\r\n") {
+        Ok((_, Response::Done { information, .. })) => {
+            let information = information.unwrap();
+            assert_eq!(information.as_bytes(), b"Pas de bo\xeete");
+            assert_eq!(information.as_str(), None);
+            assert_eq!(information.to_str_lossy(), "Pas de bo\u{fffd}te");
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+
+    match parse_response(b"* OK Bo\xc3\xaete pleine\r\n") {
+        Ok((_, Response::Data { information, .. })) => {
+            assert_eq!(information.unwrap().as_str(), Some("Boîte pleine"))
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_loose_flag_lists() {
+    match parse_response(b"* 1 FETCH (FLAGS NIL)\r\n") {
+        Err(nom::Err::Error(_)) => {}
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+
+    let quirks = Quirks::new().loose_flag_lists(true);
+    for input in &[
+        &b"* 1 FETCH (FLAGS NIL UID 4)\r\n"[..],
+        b"* 1 FETCH (FLAGS ( ) UID 4)\r\n",
+        b"* 1 FETCH (FLAGS  () UID 4)\r\n",
+    ] {
+        match parse_response_with(input, &quirks) {
+            Ok((_, Response::Fetch(1, attrs))) => {
+                assert_eq!(
+                    attrs,
+                    vec![AttributeValue::Flags(vec![]), AttributeValue::Uid(4)]
+                )
+            }
+            rsp => panic!("Unexpected response: {:?}", rsp),
+        }
+    }
+
+    match parse_response_with(b"* 1 FETCH (FLAGS ( \\Seen  \\Answered ))\r\n", &quirks) {
+        Ok((_, Response::Fetch(1, attrs))) => assert_eq!(
+            attrs,
+            vec![AttributeValue::Flags(vec!["\\Seen", "\\Answered"])]
+        ),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+
+    match parse_response_with(b"* OK [PERMANENTFLAGS NIL] No flags\r\n", &quirks) {
+        Ok((_, Response::Data { code, .. })) => {
+            assert_eq!(
+                code,
+                Some(ResponseCode::PermanentFlags(PermanentFlags::default()))
+            )
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_loose_spacing() {
+    match parse_response(b"* 1 FETCH (UID 4FLAGS (\\Seen))\r\n") {
+        Err(nom::Err::Error(_)) => {}
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+
+    let quirks = Quirks::new().loose_spacing(true);
+    for input in &[
+        &b"* 1 FETCH (UID 4FLAGS (\\Seen))\r\n"[..],
+        b"* 1 FETCH ( UID  4 FLAGS (\\Seen) )\r\n",
+        b"* 1  FETCH (UID4 FLAGS(\\Seen))\r\n",
+    ] {
+        match parse_response_with(input, &quirks) {
+            Ok((_, Response::Fetch(1, attrs))) => assert_eq!(
+                attrs,
+                vec![
+                    AttributeValue::Uid(4),
+                    AttributeValue::Flags(vec!["\\Seen"])
+                ]
+            ),
+            rsp => panic!("Unexpected response: {:?}", rsp),
+        }
+    }
+
+    match parse_response_with(b"* 1 FETCH (RFC822.SIZE 3BODY[]  {3}\r\nabc)\r\n", &quirks) {
+        Ok((_, Response::Fetch(1, attrs))) => {
+            assert_eq!(attrs[0], AttributeValue::Rfc822Size(3));
+            match &attrs[1] {
+                AttributeValue::BodySection {
+                    data: Some(data), ..
+                } => assert_eq!(&data[..], b"abc"),
+                attr => panic!("Unexpected attribute: {:?}", attr),
+            }
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_utf8_quoted() {
+    const RESPONSE: &[u8] = "* LIST () \"/\" \"Entwürfe\"\r\n".as_bytes();
+    match parse_response(RESPONSE) {
+        Err(nom::Err::Error(_)) => {}
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+
+    let quirks = Quirks::new().utf8_quoted(true);
+    match parse_response_with(RESPONSE, &quirks) {
+        Ok((_, Response::MailboxData(MailboxDatum::List { name, .. }))) => {
+            assert_eq!(name, "Entwürfe")
+        }
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_empty_literals() {
+    const RESPONSE: &[u8] = b"* 1 FETCH (BODY[HEADER] {0}\r\n BODY[1] {0}\r\n \
+        ENVELOPE (NIL {0}\r\n NIL NIL NIL NIL NIL NIL NIL NIL) BINARY[2] ~{0}\r\n UID 3)\r\n";
+    match parse_response(RESPONSE) {
+        Ok((_, Response::Fetch(1, attrs))) => {
+            assert_eq!(
+                attrs[0],
+                AttributeValue::BodySection {
+                    section: Some(SectionPath::Full(MessageSection::Header)),
+                    index: None,
+                    data: Some(Cow::Borrowed(b"")),
+                }
+            );
+            assert_eq!(
+                attrs[1],
+                AttributeValue::BodySection {
+                    section: Some(SectionPath::Part(vec![1], None)),
+                    index: None,
+                    data: Some(Cow::Borrowed(b"")),
+                }
+            );
+            match &attrs[2] {
+                AttributeValue::Envelope(envelope) => {
+                    assert_eq!(envelope.subject.as_deref(), Some(&b""[..]))
+                }
+                attr => panic!("unexpected attribute {:?}", attr),
+            }
+            assert_eq!(
+                attrs[3],
+                AttributeValue::Unknown {
+                    name: "BINARY[2]",
+                    raw: b"~{0}\r\n",
+                }
+            );
+            assert_eq!(attrs[4], AttributeValue::Uid(3));
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    match parse_response(b"* 1 FETCH (RFC822.TEXT {0}\r\n)\r\n") {
+        Ok((_, Response::Fetch(1, attrs))) => {
+            assert_eq!(
+                attrs,
+                [AttributeValue::Rfc822Text(Some(Cow::Borrowed(b"")))]
+            );
+        }
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    match parse_response(b"* LIST () \"/\" {0}\r\n\r\n") {
+        Ok((_, Response::MailboxData(MailboxDatum::List { name, .. }))) => assert_eq!(name, ""),
+        rsp => panic!("unexpected response {:?}", rsp),
+    }
+
+    // The empty literal is complete, but the rest of the response is missing
+    match parse_response(b"* 1 FETCH (BODY[1] {0}\r\n") {
+        Err(nom::Err::Incomplete(_)) => {}
+        rsp => panic!("should be incomplete: {:?}", rsp),
+    }
+}
+
+#[test]
+fn test_unknown_fetch_attribute() {
+    const RESPONSE: &[u8] = b"* 1 FETCH (UID 4 X-MSG-ID \"abc\" X-LABELS (\"\\\\Inbox\" foo) \
+        BINARY[1] {3}\r\nabc FLAGS (\\Seen))\r\n";
+
+    match parse_response(RESPONSE) {
+        Ok((_, Response::Fetch(1, attrs))) => assert_eq!(
+            attrs,
+            vec![
+                AttributeValue::Uid(4),
+                AttributeValue::Unknown {
+                    name: "X-MSG-ID",
+                    raw: b"\"abc\"",
+                },
+                AttributeValue::Unknown {
+                    name: "X-LABELS",
+                    raw: b"(\"\\\\Inbox\" foo)",
+                },
+                AttributeValue::Unknown {
+                    name: "BINARY[1]",
+                    raw: b"{3}\r\nabc",
+                },
+                AttributeValue::Flags(vec!["\\Seen"]),
+            ]
+        ),
+        rsp => panic!("Unexpected response: {:?}", rsp),
+    }
+}
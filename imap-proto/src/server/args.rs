@@ -0,0 +1,96 @@
+// Parsers for the arguments of commands. Unlike those in `parser::core`, they
+// expect the arguments to be complete (as in `CommandLine::args`), rather than
+// asking for more data at the end of the input.
+
+use std::borrow::Cow;
+use std::str;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take, take_while1},
+    character::complete::{char, digit1},
+    combinator::{map, map_res, opt, recognize, verify},
+    multi::{separated_list, separated_nonempty_list},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::parser::core::{is_astring_char, is_atom_char, quoted, utf8};
+use crate::types::{FlagSet, SeqNumber, SequenceSpec};
+
+pub(crate) fn atom(i: &[u8]) -> IResult<&[u8], &str> {
+    map_res(take_while1(is_atom_char), str::from_utf8)(i)
+}
+
+// astring = 1*ASTRING-CHAR / string
+pub(crate) fn astring(i: &[u8]) -> IResult<&[u8], Cow<'_, [u8]>> {
+    alt((
+        map(take_while1(is_astring_char), Cow::Borrowed),
+        quoted,
+        map(literal, Cow::Borrowed),
+    ))(i)
+}
+
+// literal = "{" number ["+"] "}" CRLF *CHAR8, with the non-synchronizing form
+// from RFC 7888
+pub(crate) fn literal(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (i, len) = terminated(
+        delimited(char('{'), number, pair(opt(char('+')), char('}'))),
+        tag("\r\n"),
+    )(i)?;
+    verify(take(len), |data: &[u8]| !data.contains(&0))(i)
+}
+
+// mailbox = "INBOX" / astring, with INBOX in any case
+pub(crate) fn mailbox(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    map(map_res(astring, utf8), |name| {
+        match name.eq_ignore_ascii_case("INBOX") {
+            true => Cow::Borrowed("INBOX"),
+            false => name,
+        }
+    })(i)
+}
+
+// flag = "\Answered" / "\Flagged" / "\Deleted" / "\Seen" / "\Draft" /
+//        flag-keyword / flag-extension
+pub(crate) fn flag(i: &[u8]) -> IResult<&[u8], &str> {
+    map_res(recognize(pair(opt(char('\\')), atom)), str::from_utf8)(i)
+}
+
+// flag-list = "(" [flag *(SP flag)] ")"
+pub(crate) fn flag_list(i: &[u8]) -> IResult<&[u8], FlagSet> {
+    map(
+        delimited(char('('), separated_list(char(' '), flag), char(')')),
+        |flags| flags.into_iter().collect(),
+    )(i)
+}
+
+pub(crate) fn number(i: &[u8]) -> IResult<&[u8], u32> {
+    map_res(map_res(digit1, str::from_utf8), str::parse)(i)
+}
+
+pub(crate) fn number_64(i: &[u8]) -> IResult<&[u8], u64> {
+    map_res(map_res(digit1, str::from_utf8), str::parse)(i)
+}
+
+// seq-number = nz-number / "*"
+fn seq_number(i: &[u8]) -> IResult<&[u8], SeqNumber> {
+    alt((
+        map(verify(number, |&num| num > 0), SeqNumber::Value),
+        map(char('*'), |_| SeqNumber::Largest),
+    ))(i)
+}
+
+// sequence-set = (seq-number / seq-range) *("," sequence-set)
+// seq-range    = seq-number ":" seq-number
+pub(crate) fn sequence_spec(i: &[u8]) -> IResult<&[u8], SequenceSpec> {
+    let (i, ranges) = separated_nonempty_list(
+        char(','),
+        tuple((seq_number, opt(preceded(char(':'), seq_number)))),
+    )(i)?;
+    let mut set = SequenceSpec::new();
+    for (start, end) in ranges {
+        set.push(start, end.unwrap_or(start));
+    }
+    Ok((i, set))
+}
@@ -0,0 +1,296 @@
+use std::str;
+
+use crate::server::command::CommandLine;
+use crate::server::{encode_challenge, encode_response, ResponseBuilder};
+use crate::types::{decode_base64, RequestId};
+
+/// What a SASL mechanism answers to a client response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SaslStep {
+    /// Send the challenge and wait for the next client response.
+    Challenge(Vec<u8>),
+    /// The client is authenticated.
+    Success,
+    /// The credentials were rejected.
+    Failure,
+}
+
+/// The server side of a SASL mechanism, such as a backend checking `PLAIN`
+/// credentials.
+pub trait SaslMechanism {
+    /// Process a client response. The first call gets the initial response
+    /// sent with the command (RFC 4959), or `None` if the client sent none.
+    fn step(&mut self, response: Option<&[u8]>) -> SaslStep;
+}
+
+impl<F> SaslMechanism for F
+where
+    F: FnMut(Option<&[u8]>) -> SaslStep,
+{
+    fn step(&mut self, response: Option<&[u8]>) -> SaslStep {
+        self(response)
+    }
+}
+
+/// The state of an `AuthenticateExchange`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AuthOutcome {
+    /// A challenge was sent; pass the next line from the client to
+    /// `AuthenticateExchange::respond()`.
+    Pending,
+    Authenticated,
+    /// The mechanism rejected the credentials, or the client sent invalid
+    /// data.
+    Failed,
+    /// The client cancelled the exchange with `*`.
+    Aborted,
+}
+
+/// The exchange of challenges and responses of an `AUTHENTICATE` command.
+///
+/// The exchange writes the continuation requests and the tagged completion of
+/// the command, and decodes the client responses for the `SaslMechanism`:
+///
+/// ```
+/// use imap_proto::server::{parse_command, AuthOutcome, AuthenticateExchange, SaslStep};
+///
+/// let (_, cmd) = parse_command(b"A1 AUTHENTICATE PLAIN\r\n").unwrap();
+/// let mut auth = AuthenticateExchange::new(&cmd).unwrap();
+/// assert_eq!(auth.mechanism(), "PLAIN");
+///
+/// let mut plain = |response: Option<&[u8]>| match response {
+///     None => SaslStep::Challenge(Vec::new()),
+///     Some(b"\0joe\0secret") => SaslStep::Success,
+///     Some(_) => SaslStep::Failure,
+/// };
+/// let mut out = Vec::new();
+/// assert_eq!(auth.start(&mut plain, &mut out), AuthOutcome::Pending);
+/// assert_eq!(out, b"+ \r\n");
+///
+/// out.clear();
+/// let outcome = auth.respond(b"AGpvZQBzZWNyZXQ=\r\n", &mut plain, &mut out);
+/// assert_eq!(outcome, AuthOutcome::Authenticated);
+/// assert_eq!(out, b"A1 OK Authenticated\r\n");
+/// ```
+#[derive(Debug)]
+pub struct AuthenticateExchange {
+    tag: RequestId,
+    mechanism: String,
+    initial: Option<Vec<u8>>,
+    outcome: AuthOutcome,
+}
+
+impl AuthenticateExchange {
+    /// Read the mechanism and the initial response of an `AUTHENTICATE`
+    /// command. Fails if the arguments are invalid, which should be answered
+    /// with `BAD`.
+    //
+    // authenticate = "AUTHENTICATE" SP auth-type [SP (base64 / "=")]
+    pub fn new(cmd: &CommandLine<'_>) -> Result<Self, &'static str> {
+        if cmd.name != "AUTHENTICATE" || cmd.uid {
+            return Err("not an AUTHENTICATE command");
+        }
+        let args = str::from_utf8(cmd.args).map_err(|_| "invalid mechanism")?;
+        let mut parts = args.split(' ');
+        let mechanism = parts.next().unwrap_or_default();
+        if mechanism.is_empty() || !mechanism.bytes().all(crate::parser::core::is_atom_char) {
+            return Err("invalid mechanism");
+        }
+        let initial = match parts.next() {
+            Some("=") => Some(Vec::new()),
+            Some(data) => Some(decode_base64(data).ok_or("invalid base64 initial response")?),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return Err("unexpected arguments");
+        }
+        Ok(Self {
+            tag: cmd.tag.clone(),
+            mechanism: mechanism.to_ascii_uppercase(),
+            initial,
+            outcome: AuthOutcome::Pending,
+        })
+    }
+
+    pub fn tag(&self) -> &RequestId {
+        &self.tag
+    }
+
+    /// The name of the mechanism in upper case, such as `PLAIN`.
+    pub fn mechanism(&self) -> &str {
+        &self.mechanism
+    }
+
+    /// Whether the client sent an initial response with the command.
+    pub fn has_initial_response(&self) -> bool {
+        self.initial.is_some()
+    }
+
+    /// Answer the command with `NO`, for mechanisms the server does not
+    /// support.
+    pub fn unsupported(self, out: &mut Vec<u8>) {
+        let rsp = ResponseBuilder::no(self.tag, None, "Unsupported authentication mechanism");
+        encode_response(&rsp, out);
+    }
+
+    /// Start the exchange, passing the initial response to `mechanism`.
+    pub fn start(&mut self, mechanism: &mut impl SaslMechanism, out: &mut Vec<u8>) -> AuthOutcome {
+        let initial = self.initial.take();
+        let step = mechanism.step(initial.as_deref());
+        self.step(step, out)
+    }
+
+    /// Process a line received from the client while the exchange is pending:
+    /// a base64-encoded response, or `*` to cancel.
+    pub fn respond(
+        &mut self,
+        line: &[u8],
+        mechanism: &mut impl SaslMechanism,
+        out: &mut Vec<u8>,
+    ) -> AuthOutcome {
+        if self.outcome != AuthOutcome::Pending {
+            return self.outcome;
+        }
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line == b"*" {
+            self.complete(AuthOutcome::Aborted, out);
+            return self.outcome;
+        }
+        match str::from_utf8(line).ok().and_then(decode_base64) {
+            Some(response) => {
+                let step = mechanism.step(Some(&response));
+                self.step(step, out)
+            }
+            None => {
+                self.complete(AuthOutcome::Failed, out);
+                self.outcome
+            }
+        }
+    }
+
+    pub fn outcome(&self) -> AuthOutcome {
+        self.outcome
+    }
+
+    fn step(&mut self, step: SaslStep, out: &mut Vec<u8>) -> AuthOutcome {
+        match step {
+            SaslStep::Challenge(data) => {
+                encode_challenge(&data, out);
+            }
+            SaslStep::Success => self.complete(AuthOutcome::Authenticated, out),
+            SaslStep::Failure => self.complete(AuthOutcome::Failed, out),
+        }
+        self.outcome
+    }
+
+    fn complete(&mut self, outcome: AuthOutcome, out: &mut Vec<u8>) {
+        let tag = self.tag.clone();
+        let rsp = match outcome {
+            AuthOutcome::Authenticated => ResponseBuilder::ok(tag, None, "Authenticated"),
+            AuthOutcome::Aborted => ResponseBuilder::bad(tag, None, "Authentication cancelled"),
+            _ => ResponseBuilder::no(tag, None, "Authentication failed"),
+        };
+        encode_response(&rsp, out);
+        self.outcome = outcome;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::parse_command;
+
+    fn exchange(line: &[u8]) -> AuthenticateExchange {
+        let (_, cmd) = parse_command(line).unwrap();
+        AuthenticateExchange::new(&cmd).unwrap()
+    }
+
+    #[test]
+    fn initial_response() {
+        let mut auth = exchange(b"A1 AUTHENTICATE plain AGpvZQBzZWNyZXQ=\r\n");
+        assert_eq!(auth.mechanism(), "PLAIN");
+        assert!(auth.has_initial_response());
+        let mut out = Vec::new();
+        let mut plain = |response: Option<&[u8]>| match response {
+            Some(b"\0joe\0secret") => SaslStep::Success,
+            _ => SaslStep::Failure,
+        };
+        assert_eq!(auth.start(&mut plain, &mut out), AuthOutcome::Authenticated);
+        assert_eq!(out, b"A1 OK Authenticated\r\n");
+
+        let mut auth = exchange(b"A2 AUTHENTICATE PLAIN =\r\n");
+        out.clear();
+        assert_eq!(auth.start(&mut plain, &mut out), AuthOutcome::Failed);
+        assert_eq!(out, b"A2 NO Authentication failed\r\n");
+    }
+
+    #[test]
+    fn challenges() {
+        let mut auth = exchange(b"A1 AUTHENTICATE X-TWO-STEP\r\n");
+        let mut steps = 0;
+        let mut two_step = |response: Option<&[u8]>| {
+            steps += 1;
+            match (steps, response) {
+                (1, None) => SaslStep::Challenge(b"first".to_vec()),
+                (2, Some(b"one")) => SaslStep::Challenge(b"second".to_vec()),
+                (3, Some(b"two")) => SaslStep::Success,
+                _ => SaslStep::Failure,
+            }
+        };
+        let mut out = Vec::new();
+        assert_eq!(auth.start(&mut two_step, &mut out), AuthOutcome::Pending);
+        assert_eq!(
+            auth.respond(b"b25l\r\n", &mut two_step, &mut out),
+            AuthOutcome::Pending
+        );
+        assert_eq!(
+            auth.respond(b"dHdv\r\n", &mut two_step, &mut out),
+            AuthOutcome::Authenticated
+        );
+        assert_eq!(
+            out,
+            &b"+ Zmlyc3Q=\r\n+ c2Vjb25k\r\nA1 OK Authenticated\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn abort_and_errors() {
+        let mut never = |_: Option<&[u8]>| SaslStep::Challenge(Vec::new());
+        let mut out = Vec::new();
+        let mut auth = exchange(b"A1 AUTHENTICATE PLAIN\r\n");
+        auth.start(&mut never, &mut out);
+        out.clear();
+        assert_eq!(
+            auth.respond(b"*\r\n", &mut never, &mut out),
+            AuthOutcome::Aborted
+        );
+        assert_eq!(out, b"A1 BAD Authentication cancelled\r\n");
+        assert_eq!(
+            auth.respond(b"AAAA\r\n", &mut never, &mut out),
+            AuthOutcome::Aborted
+        );
+
+        let mut auth = exchange(b"A2 AUTHENTICATE PLAIN\r\n");
+        auth.start(&mut never, &mut out);
+        out.clear();
+        assert_eq!(
+            auth.respond(b"not base64!\r\n", &mut never, &mut out),
+            AuthOutcome::Failed
+        );
+        assert_eq!(out, b"A2 NO Authentication failed\r\n");
+
+        out.clear();
+        exchange(b"A3 AUTHENTICATE GSSAPI\r\n").unsupported(&mut out);
+        assert_eq!(out, b"A3 NO Unsupported authentication mechanism\r\n");
+
+        for line in &[
+            &b"A4 AUTHENTICATE PLAIN !!!\r\n"[..],
+            b"A4 AUTHENTICATE PLAIN = x\r\n",
+            b"A4 LOGIN a b\r\n",
+        ] {
+            let (_, cmd) = parse_command(line).unwrap();
+            assert!(AuthenticateExchange::new(&cmd).is_err());
+        }
+    }
+}
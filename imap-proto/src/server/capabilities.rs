@@ -0,0 +1,141 @@
+use crate::parser::core::is_atom_char;
+use crate::types::{Capability, Response, ResponseCode};
+
+/// The capabilities a server advertises, in the `CAPABILITY` response or as
+/// response code of the greeting.
+///
+/// The set always includes `IMAP4rev1`, which servers are required to
+/// advertise, and only accepts capabilities that clients can parse:
+///
+/// ```
+/// use imap_proto::server::CapabilitySet;
+/// use imap_proto::types::Capability;
+///
+/// let mut caps = CapabilitySet::new();
+/// caps.insert(Capability::Atom("IDLE")).unwrap();
+/// caps.insert(Capability::Auth("PLAIN")).unwrap();
+/// assert!(caps.insert(Capability::Atom("AUTH=PLAIN")).is_err());
+/// assert!(caps.contains("auth=plain"));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CapabilitySet<'a> {
+    caps: Vec<Capability<'a>>,
+}
+
+impl<'a> CapabilitySet<'a> {
+    pub fn new() -> Self {
+        Self {
+            caps: vec![Capability::Imap4rev1],
+        }
+    }
+
+    /// The capabilities advertised by a server, as parsed by a client or a
+    /// proxy. Invalid capabilities are skipped.
+    pub fn from_capabilities(caps: &[Capability<'a>]) -> Self {
+        let mut set = Self::new();
+        for cap in caps {
+            let _ = set.insert(cap.clone());
+        }
+        set
+    }
+
+    /// Add `cap` to the set, unless it is already in it.
+    ///
+    /// Fails if the capability is not an atom, or if it is an `AUTH=`
+    /// capability given as `Capability::Atom` rather than `Capability::Auth`.
+    pub fn insert(&mut self, cap: Capability<'a>) -> Result<(), &'static str> {
+        let cap = match cap {
+            Capability::Atom(name) if name.eq_ignore_ascii_case("IMAP4rev1") => {
+                Capability::Imap4rev1
+            }
+            Capability::Atom(name)
+                if name.len() >= 5 && name[..5].eq_ignore_ascii_case("AUTH=") =>
+            {
+                return Err("AUTH= capabilities must be given as Capability::Auth");
+            }
+            Capability::Atom(name) | Capability::Auth(name) if !is_atom(name) => {
+                return Err("capability is not an atom");
+            }
+            cap => cap,
+        };
+        if !self.contains(&cap.name()) {
+            self.caps.push(cap);
+        }
+        Ok(())
+    }
+
+    /// Remove the capability with the given name (compared case-insensitively),
+    /// such as `STARTTLS` once TLS is active. `IMAP4rev1` cannot be removed.
+    pub fn remove(&mut self, name: &str) {
+        self.caps
+            .retain(|cap| *cap == Capability::Imap4rev1 || !cap.name().eq_ignore_ascii_case(name));
+    }
+
+    /// Whether the capability with the given name (compared case-insensitively)
+    /// is in the set.
+    pub fn contains(&self, name: &str) -> bool {
+        self.caps
+            .iter()
+            .any(|cap| cap.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Capability<'a>> {
+        self.caps.iter()
+    }
+
+    /// The `CAPABILITY` response.
+    pub fn response(&self) -> Response<'a> {
+        Response::Capabilities(self.caps.clone())
+    }
+
+    /// The `CAPABILITY` response code, as included in the greeting.
+    pub fn code(&self) -> ResponseCode<'a> {
+        ResponseCode::Capabilities(self.caps.clone())
+    }
+}
+
+impl<'a> Default for CapabilitySet<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_atom(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(is_atom_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CapabilitySet;
+    use crate::server::{encode_response, ResponseBuilder};
+    use crate::types::Capability;
+
+    #[test]
+    fn capabilities() {
+        let mut caps = CapabilitySet::from_capabilities(&[
+            Capability::Atom("IDLE"),
+            Capability::Atom("imap4rev1"),
+            Capability::Atom("STARTTLS"),
+            Capability::Atom("idle"),
+        ]);
+        assert!(caps.insert(Capability::Auth("PLAIN")).is_ok());
+        assert!(caps.insert(Capability::Auth("")).is_err());
+        assert!(caps.insert(Capability::Atom("X (Y)")).is_err());
+        assert!(caps.insert(Capability::Atom("auth=LOGIN")).is_err());
+
+        caps.remove("starttls");
+        caps.remove("IMAP4rev1");
+
+        let mut out = Vec::new();
+        encode_response(&caps.response(), &mut out);
+        encode_response(
+            &ResponseBuilder::greeting(Some(caps.code()), "ready"),
+            &mut out,
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "* CAPABILITY IMAP4rev1 IDLE AUTH=PLAIN\r\n\
+             * OK [CAPABILITY IMAP4rev1 IDLE AUTH=PLAIN] ready\r\n"
+        );
+    }
+}
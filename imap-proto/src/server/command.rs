@@ -0,0 +1,192 @@
+use nom::{
+    bytes::streaming::tag,
+    combinator::{map, opt},
+    sequence::{preceded, tuple},
+    IResult, Needed,
+};
+
+use crate::parser::core::{atom, crlf};
+use crate::parser::rfc3501::imap_tag;
+use crate::types::RequestId;
+
+/// A command line sent by a client: the tag, the command name and the raw
+/// arguments.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommandLine<'a> {
+    pub tag: RequestId,
+    /// The command name in upper case, such as `FETCH`. For `UID` commands,
+    /// this is the name of the command following `UID`.
+    pub name: String,
+    /// Whether the command was prefixed with `UID`.
+    pub uid: bool,
+    /// The arguments following the name, including any literals, without the
+    /// final line terminator.
+    pub args: &'a [u8],
+}
+
+// command = tag SP (command-any / command-auth / command-nonauth /
+//           command-select) CRLF
+pub fn parse_command(i: &[u8]) -> IResult<&[u8], CommandLine<'_>> {
+    let (i, (id, _, name)) = tuple((imap_tag, tag(" "), atom))(i)?;
+    let (i, uid_name) = match name.eq_ignore_ascii_case("UID") {
+        true => map(preceded(tag(" "), atom), Some)(i)?,
+        false => (i, None),
+    };
+    let (i, args) = opt(preceded(tag(" "), args))(i)?;
+    let (i, _) = crlf(i)?;
+    Ok((
+        i,
+        CommandLine {
+            tag: id,
+            name: uid_name.unwrap_or(name).to_ascii_uppercase(),
+            uid: uid_name.is_some(),
+            args: args.unwrap_or_default(),
+        },
+    ))
+}
+
+// The rest of the command up to the final line terminator, including the
+// literals announced at the end of each line
+fn args(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    let mut pos = 0;
+    loop {
+        let eol = match i[pos..].iter().position(|&b| b == b'\r' || b == b'\n') {
+            Some(eol) => pos + eol,
+            None => return Err(nom::Err::Incomplete(Needed::Unknown)),
+        };
+        let (len, _) = match literal_header(&i[..eol]) {
+            Some(header) => header,
+            None => return Ok((&i[eol..], &i[..eol])),
+        };
+        let (rest, _) = crlf(&i[eol..])?;
+        let start = i.len() - rest.len();
+        let end = start
+            .checked_add(len)
+            .ok_or(nom::Err::Failure((i, nom::error::ErrorKind::TooLarge)))?;
+        if end > i.len() {
+            return Err(nom::Err::Incomplete(Needed::Size(end - i.len())));
+        }
+        pos = end;
+    }
+}
+
+/// The size of the literal announced at the end of `line`, and whether it is
+/// non-synchronizing (`{n+}`, RFC 7888).
+fn literal_header(line: &[u8]) -> Option<(usize, bool)> {
+    let line = line.strip_suffix(b"}")?;
+    let (line, plus) = match line.strip_suffix(b"+") {
+        Some(line) => (line, true),
+        None => (line, false),
+    };
+    let open = line.iter().rposition(|&b| b == b'{')?;
+    let digits = &line[open + 1..];
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some((std::str::from_utf8(digits).ok()?.parse().ok()?, plus))
+}
+
+/// What `CommandDecoder::decode()` found in the data received.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Decoded {
+    /// A complete command of the given length, including its literals, to be
+    /// parsed with `parse_command()` or handed to a `Dispatcher`.
+    Command(usize),
+    /// The command continues with a literal of `len` bytes, not received yet.
+    /// If `synchronizing`, the client waits for a continuation request (such
+    /// as `ResponseBuilder::continuation()`) before sending it.
+    ///
+    /// Reported once per literal, so that servers can also reject literals
+    /// that are too large by answering the command with `BAD` or `NO`.
+    Literal { len: usize, synchronizing: bool },
+    /// More data is needed.
+    Incomplete,
+}
+
+/// Finds the end of the commands received by a server, which may span several
+/// lines joined by literals, and reports the literals awaiting a continuation
+/// request.
+///
+/// The decoder keeps track of the data already examined: pass the same buffer
+/// (with more data appended) until a command is complete, then remove the
+/// command from the buffer before the next call.
+#[derive(Debug, Default)]
+pub struct CommandDecoder {
+    /// The start of the line of the command not examined yet.
+    scanned: usize,
+    /// The offset of the last literal reported.
+    reported: Option<usize>,
+}
+
+impl CommandDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decode(&mut self, buf: &[u8]) -> Decoded {
+        loop {
+            let eol = match buf[self.scanned..].iter().position(|&b| b == b'\n') {
+                Some(lf) => self.scanned + lf,
+                None => return Decoded::Incomplete,
+            };
+            let line = &buf[..eol];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            let (len, plus) = match literal_header(&line[self.scanned..]) {
+                Some(header) => header,
+                None => {
+                    let len = eol + 1;
+                    *self = Self::default();
+                    return Decoded::Command(len);
+                }
+            };
+            let start = eol + 1;
+            if buf.len() - start >= len {
+                self.scanned = start + len;
+                continue;
+            }
+            if self.reported == Some(start) {
+                return Decoded::Incomplete;
+            }
+            self.reported = Some(start);
+            return Decoded::Literal {
+                len,
+                synchronizing: !plus,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_command, CommandDecoder, Decoded};
+
+    #[test]
+    fn literals() {
+        let mut decoder = CommandDecoder::new();
+        let mut buf = b"A1 LOGIN {3}\r\n".to_vec();
+        let literal = Decoded::Literal {
+            len: 3,
+            synchronizing: true,
+        };
+        assert_eq!(decoder.decode(&buf), literal);
+        assert_eq!(decoder.decode(&buf), Decoded::Incomplete);
+
+        buf.extend_from_slice(b"joe {6+}\r\nsec");
+        let literal = Decoded::Literal {
+            len: 6,
+            synchronizing: false,
+        };
+        assert_eq!(decoder.decode(&buf), literal);
+
+        buf.extend_from_slice(b"ret\r\nA2 NOOP\r\n");
+        assert_eq!(decoder.decode(&buf), Decoded::Command(buf.len() - 9));
+
+        let (rest, cmd) = parse_command(&buf).unwrap();
+        assert_eq!(rest, b"A2 NOOP\r\n");
+        assert_eq!(cmd.name, "LOGIN");
+        assert_eq!(cmd.args, b"{3}\r\njoe {6+}\r\nsecret");
+
+        let buf = rest;
+        assert_eq!(decoder.decode(buf), Decoded::Command(buf.len()));
+    }
+}
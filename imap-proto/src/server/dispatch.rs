@@ -0,0 +1,188 @@
+use crate::parser::rfc3501::imap_tag;
+use crate::server::command::{parse_command, CommandLine};
+use crate::server::{encode_response, ResponseBuilder};
+use crate::types::{Response, State, Status, Text};
+
+/// The states in which a command is valid, or `None` for unknown commands.
+///
+/// Covers the commands of RFC 3501 and of the extensions used by tokio-imap.
+pub fn valid_states(cmd: &CommandLine<'_>) -> Option<&'static [State]> {
+    use State::*;
+    const ANY: &[State] = &[NotAuthenticated, Authenticated, Selected];
+    const NOT_AUTHENTICATED: &[State] = &[NotAuthenticated];
+    const AUTHENTICATED: &[State] = &[Authenticated, Selected];
+    const SELECTED: &[State] = &[Selected];
+
+    if cmd.uid {
+        return match cmd.name.as_str() {
+            "COPY" | "EXPUNGE" | "FETCH" | "MOVE" | "SEARCH" | "STORE" => Some(SELECTED),
+            _ => None,
+        };
+    }
+    Some(match cmd.name.as_str() {
+        "CAPABILITY" | "ID" | "LOGOUT" | "NOOP" => ANY,
+        "AUTHENTICATE" | "LOGIN" | "STARTTLS" => NOT_AUTHENTICATED,
+        "APPEND" | "CREATE" | "DELETE" | "ENABLE" | "EXAMINE" | "GETMETADATA" | "IDLE" | "LIST"
+        | "LSUB" | "NAMESPACE" | "RENAME" | "SELECT" | "SETMETADATA" | "STATUS" | "SUBSCRIBE"
+        | "UNSUBSCRIBE" => AUTHENTICATED,
+        "CHECK" | "CLOSE" | "COPY" | "EXPUNGE" | "FETCH" | "MOVE" | "SEARCH" | "STORE"
+        | "UNSELECT" => SELECTED,
+        _ => return None,
+    })
+}
+
+/// Handles the commands valid in the current state of a connection.
+pub trait Handler {
+    /// Process `cmd`, appending the responses to send (including the tagged
+    /// completion) to `out`.
+    ///
+    /// Returns the state the connection enters, if the command changes it,
+    /// such as `State::Selected` after a successful `SELECT`.
+    fn handle(&mut self, cmd: &CommandLine<'_>, out: &mut Vec<u8>) -> Option<State>;
+}
+
+impl<F> Handler for F
+where
+    F: FnMut(&CommandLine<'_>, &mut Vec<u8>) -> Option<State>,
+{
+    fn handle(&mut self, cmd: &CommandLine<'_>, out: &mut Vec<u8>) -> Option<State> {
+        self(cmd, out)
+    }
+}
+
+/// Validates the commands of a connection against its state before handing
+/// them to a `Handler`.
+///
+/// Commands that cannot be parsed, are unknown or are not valid in the
+/// current state are answered with `BAD` without calling the handler.
+#[derive(Debug)]
+pub struct Dispatcher {
+    state: State,
+}
+
+impl Dispatcher {
+    /// A dispatcher for a new connection, not authenticated yet.
+    pub fn new() -> Self {
+        Self::with_state(State::NotAuthenticated)
+    }
+
+    /// A dispatcher for a connection in `state`, such as `State::Authenticated`
+    /// after a `PREAUTH` greeting.
+    pub fn with_state(state: State) -> Self {
+        Self { state }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Dispatch the command line `line`, appending the responses to `out`.
+    ///
+    /// Returns the number of bytes of `line` consumed, or `None` if it does
+    /// not hold a complete line yet.
+    pub fn dispatch<H: Handler>(
+        &mut self,
+        line: &[u8],
+        out: &mut Vec<u8>,
+        handler: &mut H,
+    ) -> Option<usize> {
+        let (rest, cmd) = match parse_command(line) {
+            Ok(parsed) => parsed,
+            Err(nom::Err::Incomplete(_)) => return None,
+            Err(_) => {
+                let len = line.iter().position(|&b| b == b'\n')? + 1;
+                let rsp = match imap_tag(line) {
+                    Ok((_, tag)) => ResponseBuilder::bad(tag, None, "Invalid command syntax"),
+                    Err(_) => Response::Data {
+                        status: Status::Bad,
+                        code: None,
+                        information: Some(Text(b"Invalid tag")),
+                    },
+                };
+                encode_response(&rsp, out);
+                return Some(len);
+            }
+        };
+
+        let consumed = line.len() - rest.len();
+        let text = match (valid_states(&cmd), self.state) {
+            (_, State::Logout) => "Connection is logging out",
+            (None, _) => "Unknown command",
+            (Some(states), state) if states.contains(&state) => {
+                if let Some(state) = handler.handle(&cmd, out) {
+                    self.state = state;
+                }
+                return Some(consumed);
+            }
+            (Some(_), State::NotAuthenticated) => "Not authenticated",
+            (Some(states), _) if states.contains(&State::Selected) => "No mailbox selected",
+            (Some(_), _) => "Already authenticated",
+        };
+        encode_response(&ResponseBuilder::bad(cmd.tag, None, text), out);
+        Some(consumed)
+    }
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dispatcher;
+    use crate::server::{encode_response, CommandLine, ResponseBuilder};
+    use crate::types::State;
+
+    #[test]
+    fn dispatch() {
+        let mut handled = Vec::new();
+        let mut handler = |cmd: &CommandLine<'_>, out: &mut Vec<u8>| {
+            handled.push(cmd.name.clone());
+            encode_response(&ResponseBuilder::ok(cmd.tag.clone(), None, "done"), out);
+            match cmd.name.as_str() {
+                "LOGIN" => Some(State::Authenticated),
+                "SELECT" => Some(State::Selected),
+                _ => None,
+            }
+        };
+
+        let mut dispatcher = Dispatcher::new();
+        let mut out = Vec::new();
+        let script: &[(&[u8], &[u8])] = &[
+            (b"A1 FETCH 1 FLAGS\r\n", b"A1 BAD Not authenticated\r\n"),
+            (b"A2 login joe secret\r\n", b"A2 OK done\r\n"),
+            (
+                b"A3 LOGIN joe secret\r\n",
+                b"A3 BAD Already authenticated\r\n",
+            ),
+            (
+                b"A4 UID FETCH 1 FLAGS\r\n",
+                b"A4 BAD No mailbox selected\r\n",
+            ),
+            (b"A5 SELECT INBOX\r\n", b"A5 OK done\r\n"),
+            (b"A6 UID FETCH 1 FLAGS\r\n", b"A6 OK done\r\n"),
+            (b"A7 FROBNICATE\r\n", b"A7 BAD Unknown command\r\n"),
+            (b"A8\r\n", b"A8 BAD Invalid command syntax\r\n"),
+            (b"(\r\n", b"* BAD Invalid tag\r\n"),
+        ];
+        for (line, expected) in script {
+            out.clear();
+            assert_eq!(
+                dispatcher.dispatch(line, &mut out, &mut handler),
+                Some(line.len())
+            );
+            assert_eq!(
+                String::from_utf8_lossy(&out),
+                String::from_utf8_lossy(expected)
+            );
+        }
+        assert_eq!(dispatcher.state(), State::Selected);
+        assert_eq!(
+            dispatcher.dispatch(b"A9 NOOP", &mut out, &mut handler),
+            None
+        );
+        assert_eq!(handled, vec!["LOGIN", "SELECT", "FETCH"]);
+    }
+}
@@ -0,0 +1,805 @@
+use std::fmt;
+
+use crate::parser::core::is_astring_char;
+use crate::types::*;
+
+/// Append `rsp` to `out` as sent by a server, including the final CRLF.
+///
+/// Strings are written as atoms, quoted strings or literals, whichever the
+/// content allows, so the output may differ from the response as originally
+/// received but parses to the same value.
+pub fn encode_response(rsp: &Response<'_>, out: &mut Vec<u8>) {
+    match rsp {
+        Response::Capabilities(caps) => {
+            out.extend_from_slice(b"* ");
+            capabilities(out, caps);
+        }
+        Response::Continue { code, information } => {
+            out.extend_from_slice(b"+ ");
+            resp_text(out, code, information);
+        }
+        Response::Done {
+            tag,
+            status: st,
+            code,
+            information,
+        } => {
+            out.extend_from_slice(tag.as_bytes());
+            out.push(b' ');
+            status(out, *st);
+            out.push(b' ');
+            resp_text(out, code, information);
+        }
+        Response::Data {
+            status: st,
+            code,
+            information,
+        } => {
+            out.extend_from_slice(b"* ");
+            status(out, *st);
+            out.push(b' ');
+            resp_text(out, code, information);
+        }
+        Response::Expunge(seq) => {
+            display(out, format_args!("* {} EXPUNGE", seq));
+        }
+        Response::Fetch(seq, attrs) => {
+            display(out, format_args!("* {} FETCH (", seq));
+            for (i, attr) in attrs.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                attribute_value(out, attr);
+            }
+            out.push(b')');
+        }
+        Response::Id(identity) => {
+            out.extend_from_slice(b"* ID ");
+            if identity.is_empty() {
+                out.extend_from_slice(b"NIL");
+            } else {
+                list(out, &identity.fields, |out, (key, value)| {
+                    string(out, key.as_bytes());
+                    out.push(b' ');
+                    nstring(out, value.as_ref().map(|v| v.as_bytes()));
+                });
+            }
+        }
+        Response::MailboxData(datum) => {
+            out.extend_from_slice(b"* ");
+            mailbox_datum(out, datum);
+        }
+        Response::Namespace(namespaces) => {
+            out.extend_from_slice(b"* NAMESPACE ");
+            namespace(out, &namespaces.personal);
+            out.push(b' ');
+            namespace(out, &namespaces.other);
+            out.push(b' ');
+            namespace(out, &namespaces.shared);
+        }
+        Response::Vanished { earlier, uids } => {
+            out.extend_from_slice(b"* VANISHED ");
+            if *earlier {
+                out.extend_from_slice(b"(EARLIER) ");
+            }
+            display(out, uids);
+        }
+    }
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Append a continuation request carrying the SASL challenge `data`, encoded
+/// as base64, to `out`, including the final CRLF.
+pub fn encode_challenge(data: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(b"+ ");
+    out.extend_from_slice(encode_base64(data).as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+fn display(out: &mut Vec<u8>, value: impl fmt::Display) {
+    out.extend_from_slice(value.to_string().as_bytes());
+}
+
+fn status(out: &mut Vec<u8>, status: Status) {
+    out.extend_from_slice(match status {
+        Status::Ok => b"OK",
+        Status::No => b"NO",
+        Status::Bad => b"BAD",
+        Status::PreAuth => b"PREAUTH",
+        Status::Bye => b"BYE",
+    });
+}
+
+fn resp_text(out: &mut Vec<u8>, code: &Option<ResponseCode<'_>>, text: &Option<Text<'_>>) {
+    if let Some(code) = code {
+        out.push(b'[');
+        response_code(out, code);
+        out.push(b']');
+        if text.is_some() {
+            out.push(b' ');
+        }
+    }
+    if let Some(text) = text {
+        out.extend_from_slice(text.as_bytes());
+    }
+}
+
+fn response_code(out: &mut Vec<u8>, code: &ResponseCode<'_>) {
+    let name: &[u8] = match code {
+        ResponseCode::Alert => b"ALERT",
+        ResponseCode::AlreadyExists => b"ALREADYEXISTS",
+        ResponseCode::AuthenticationFailed => b"AUTHENTICATIONFAILED",
+        ResponseCode::AuthorizationFailed => b"AUTHORIZATIONFAILED",
+        ResponseCode::BadCharset(charsets) => {
+            out.extend_from_slice(b"BADCHARSET");
+            if let Some(charsets) = charsets {
+                out.push(b' ');
+                list(out, &charsets[..], |out, charset| astring(out, charset));
+            }
+            return;
+        }
+        ResponseCode::AppendUid(validity, uids) => {
+            display(out, format_args!("APPENDUID {} ", validity));
+            uid_set(out, uids);
+            return;
+        }
+        ResponseCode::Cannot => b"CANNOT",
+        ResponseCode::Capabilities(caps) => return capabilities(out, caps),
+        ResponseCode::ClientBug => b"CLIENTBUG",
+        ResponseCode::Closed => b"CLOSED",
+        ResponseCode::ContactAdmin => b"CONTACTADMIN",
+        ResponseCode::CopyUid(validity, src, dst) => {
+            display(out, format_args!("COPYUID {} ", validity));
+            uid_set(out, src);
+            out.push(b' ');
+            uid_set(out, dst);
+            return;
+        }
+        ResponseCode::Corruption => b"CORRUPTION",
+        ResponseCode::Expired => b"EXPIRED",
+        ResponseCode::ExpungeIssued => b"EXPUNGEISSUED",
+        ResponseCode::HighestModSeq(seq) => {
+            return display(out, format_args!("HIGHESTMODSEQ {}", seq));
+        }
+        ResponseCode::InUse => b"INUSE",
+        ResponseCode::Limit => b"LIMIT",
+        ResponseCode::Metadata(code) => {
+            return match code {
+                MetadataCode::LongEntries(n) => {
+                    display(out, format_args!("METADATA LONGENTRIES {}", n))
+                }
+                MetadataCode::MaxSize(n) => display(out, format_args!("METADATA MAXSIZE {}", n)),
+                MetadataCode::TooMany => out.extend_from_slice(b"METADATA TOOMANY"),
+                MetadataCode::NoPrivate => out.extend_from_slice(b"METADATA NOPRIVATE"),
+            };
+        }
+        ResponseCode::NonExistent => b"NONEXISTENT",
+        ResponseCode::NoPerm => b"NOPERM",
+        ResponseCode::OverQuota => b"OVERQUOTA",
+        ResponseCode::Parse => b"PARSE",
+        ResponseCode::PermanentFlags(flags) => {
+            return display(out, format_args!("PERMANENTFLAGS {}", flags));
+        }
+        ResponseCode::PrivacyRequired => b"PRIVACYREQUIRED",
+        ResponseCode::ReadOnly => b"READ-ONLY",
+        ResponseCode::ReadWrite => b"READ-WRITE",
+        ResponseCode::Referral(url) => {
+            return display(out, format_args!("REFERRAL {}", url));
+        }
+        ResponseCode::ServerBug => b"SERVERBUG",
+        ResponseCode::TryCreate => b"TRYCREATE",
+        ResponseCode::UidNext(uid) => return display(out, format_args!("UIDNEXT {}", uid)),
+        ResponseCode::UidValidity(validity) => {
+            return display(out, format_args!("UIDVALIDITY {}", validity));
+        }
+        ResponseCode::Unavailable => b"UNAVAILABLE",
+        ResponseCode::Unseen(seq) => return display(out, format_args!("UNSEEN {}", seq)),
+        ResponseCode::Other(content) => content.as_bytes(),
+    };
+    out.extend_from_slice(name);
+}
+
+fn capabilities(out: &mut Vec<u8>, caps: &[Capability<'_>]) {
+    out.extend_from_slice(b"CAPABILITY");
+    for cap in caps {
+        out.push(b' ');
+        match cap {
+            Capability::Imap4rev1 => out.extend_from_slice(b"IMAP4rev1"),
+            Capability::Auth(mechanism) => display(out, format_args!("AUTH={}", mechanism)),
+            Capability::Atom(atom) => out.extend_from_slice(atom.as_bytes()),
+        }
+    }
+}
+
+fn uid_set(out: &mut Vec<u8>, members: &[UidSetMember]) {
+    for (i, member) in members.iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+        match member {
+            UidSetMember::Uid(uid) => display(out, uid),
+            UidSetMember::UidRange(range) => {
+                display(out, format_args!("{}:{}", range.start(), range.end()))
+            }
+        }
+    }
+}
+
+fn flag_list(out: &mut Vec<u8>, flags: &[&str]) {
+    list(out, flags, |out, flag| {
+        out.extend_from_slice(flag.as_bytes())
+    });
+}
+
+fn mailbox_datum(out: &mut Vec<u8>, datum: &MailboxDatum<'_>) {
+    match datum {
+        MailboxDatum::Exists(n) => display(out, format_args!("{} EXISTS", n)),
+        MailboxDatum::Recent(n) => display(out, format_args!("{} RECENT", n)),
+        MailboxDatum::Flags(flags) => {
+            out.extend_from_slice(b"FLAGS ");
+            flag_list(out, flags);
+        }
+        MailboxDatum::List {
+            flags,
+            delimiter,
+            name,
+            extensions,
+        } => {
+            out.extend_from_slice(b"LIST ");
+            flag_list(out, flags);
+            out.push(b' ');
+            match delimiter {
+                Some(delimiter) => quoted(out, delimiter.as_bytes()),
+                None => out.extend_from_slice(b"NIL"),
+            }
+            out.push(b' ');
+            astring(out, name);
+            if !extensions.is_empty() {
+                out.push(b' ');
+                list(out, extensions, |out, (tag, value)| {
+                    astring(out, tag);
+                    out.push(b' ');
+                    tagged_ext_value(out, value);
+                });
+            }
+        }
+        MailboxDatum::Search { ids, mod_seq } => {
+            out.extend_from_slice(b"SEARCH");
+            for id in ids {
+                display(out, format_args!(" {}", id));
+            }
+            if let Some(mod_seq) = mod_seq {
+                display(out, format_args!(" (MODSEQ {})", mod_seq));
+            }
+        }
+        MailboxDatum::Sort(ids) => {
+            out.extend_from_slice(b"SORT");
+            for id in ids {
+                display(out, format_args!(" {}", id));
+            }
+        }
+        MailboxDatum::ESearch(esearch) => {
+            out.extend_from_slice(b"ESEARCH");
+            if let Some(tag) = &esearch.tag {
+                out.extend_from_slice(b" (TAG ");
+                quoted(out, tag.as_bytes());
+                out.push(b')');
+            }
+            if esearch.uid {
+                out.extend_from_slice(b" UID");
+            }
+            if let Some(min) = esearch.min {
+                display(out, format_args!(" MIN {}", min));
+            }
+            if let Some(max) = esearch.max {
+                display(out, format_args!(" MAX {}", max));
+            }
+            if let Some(all) = &esearch.all {
+                display(out, format_args!(" ALL {}", all));
+            }
+            if let Some(count) = esearch.count {
+                display(out, format_args!(" COUNT {}", count));
+            }
+            if let Some(mod_seq) = esearch.mod_seq {
+                display(out, format_args!(" MODSEQ {}", mod_seq));
+            }
+        }
+        MailboxDatum::Status { mailbox, status } => {
+            out.extend_from_slice(b"STATUS ");
+            astring(out, mailbox);
+            out.push(b' ');
+            list(out, status, |out, attr| {
+                let (name, value): (&str, u64) = match *attr {
+                    StatusAttribute::Other(ref name, ref value) => {
+                        out.extend_from_slice(name.as_bytes());
+                        out.push(b' ');
+                        astring(out, value);
+                        return;
+                    }
+                    StatusAttribute::HighestModSeq(n) => ("HIGHESTMODSEQ", n),
+                    StatusAttribute::Messages(n) => ("MESSAGES", n.into()),
+                    StatusAttribute::Recent(n) => ("RECENT", n.into()),
+                    StatusAttribute::Size(n) => ("SIZE", n),
+                    StatusAttribute::UidNext(n) => ("UIDNEXT", n.into()),
+                    StatusAttribute::UidValidity(n) => ("UIDVALIDITY", n.into()),
+                    StatusAttribute::Unseen(n) => ("UNSEEN", n.into()),
+                };
+                display(out, format_args!("{} {}", name, value));
+            });
+        }
+        MailboxDatum::MetadataSolicited { mailbox, values } => {
+            out.extend_from_slice(b"METADATA ");
+            quoted(out, mailbox.as_bytes());
+            out.push(b' ');
+            list(out, values, |out, metadata| {
+                astring(out, &metadata.entry);
+                out.push(b' ');
+                nstring(out, metadata.value.as_ref().map(|v| v.as_bytes()));
+            });
+        }
+        MailboxDatum::MetadataUnsolicited { mailbox, values } => {
+            out.extend_from_slice(b"METADATA ");
+            quoted(out, mailbox.as_bytes());
+            for entry in values {
+                out.push(b' ');
+                astring(out, entry);
+            }
+        }
+        MailboxDatum::Acl { mailbox, entries } => {
+            out.extend_from_slice(b"ACL ");
+            astring(out, mailbox);
+            for entry in entries {
+                out.push(b' ');
+                astring(out, &entry.identifier);
+                out.push(b' ');
+                astring(out, &entry.rights.to_string());
+            }
+        }
+        MailboxDatum::ListRights {
+            mailbox,
+            identifier,
+            required,
+            optional,
+        } => {
+            out.extend_from_slice(b"LISTRIGHTS ");
+            astring(out, mailbox);
+            out.push(b' ');
+            astring(out, identifier);
+            out.push(b' ');
+            astring(out, &required.to_string());
+            for rights in optional {
+                out.push(b' ');
+                astring(out, &rights.to_string());
+            }
+        }
+        MailboxDatum::MyRights { mailbox, rights } => {
+            out.extend_from_slice(b"MYRIGHTS ");
+            astring(out, mailbox);
+            out.push(b' ');
+            astring(out, &rights.to_string());
+        }
+        MailboxDatum::Quota { root, resources } => {
+            out.extend_from_slice(b"QUOTA ");
+            astring(out, root);
+            out.push(b' ');
+            list(out, resources, |out, resource| {
+                display(
+                    out,
+                    format_args!("{} {} {}", resource.name, resource.usage, resource.limit),
+                );
+            });
+        }
+        MailboxDatum::QuotaRoot { mailbox, roots } => {
+            out.extend_from_slice(b"QUOTAROOT ");
+            astring(out, mailbox);
+            for root in roots {
+                out.push(b' ');
+                astring(out, root);
+            }
+        }
+    }
+}
+
+fn namespace(out: &mut Vec<u8>, namespaces: &[Namespace]) {
+    if namespaces.is_empty() {
+        return out.extend_from_slice(b"NIL");
+    }
+    out.push(b'(');
+    for ns in namespaces {
+        out.push(b'(');
+        string(out, ns.prefix.as_bytes());
+        out.push(b' ');
+        match ns.delimiter {
+            Some(delimiter) => quoted(out, delimiter.to_string().as_bytes()),
+            None => out.extend_from_slice(b"NIL"),
+        }
+        for (name, values) in &ns.extensions {
+            out.push(b' ');
+            string(out, name.as_bytes());
+            out.push(b' ');
+            list(out, values, |out, value| string(out, value.as_bytes()));
+        }
+        out.push(b')');
+    }
+    out.push(b')');
+}
+
+fn attribute_value(out: &mut Vec<u8>, attr: &AttributeValue<'_>) {
+    match attr {
+        AttributeValue::BodySection {
+            section,
+            index,
+            data,
+        } => {
+            out.extend_from_slice(b"BODY[");
+            if let Some(section) = section {
+                display(out, section);
+            }
+            out.push(b']');
+            if let Some(index) = index {
+                display(out, format_args!("<{}>", index));
+            }
+            out.push(b' ');
+            nstring(out, data.as_deref());
+        }
+        AttributeValue::BodyStructure(body) => {
+            out.extend_from_slice(b"BODYSTRUCTURE ");
+            body_structure(out, body);
+        }
+        AttributeValue::Envelope(env) => {
+            out.extend_from_slice(b"ENVELOPE ");
+            envelope(out, env);
+        }
+        AttributeValue::Flags(flags) => {
+            out.extend_from_slice(b"FLAGS ");
+            flag_list(out, flags);
+        }
+        AttributeValue::InternalDate(date) => {
+            out.extend_from_slice(b"INTERNALDATE ");
+            quoted(out, date.as_bytes());
+        }
+        AttributeValue::ModSeq(seq) => display(out, format_args!("MODSEQ ({})", seq)),
+        AttributeValue::Rfc822(data) => {
+            out.extend_from_slice(b"RFC822 ");
+            nstring(out, data.as_deref());
+        }
+        AttributeValue::Rfc822Header(data) => {
+            out.extend_from_slice(b"RFC822.HEADER ");
+            nstring(out, data.as_deref());
+        }
+        AttributeValue::Rfc822Size(size) => display(out, format_args!("RFC822.SIZE {}", size)),
+        AttributeValue::Rfc822Text(data) => {
+            out.extend_from_slice(b"RFC822.TEXT ");
+            nstring(out, data.as_deref());
+        }
+        AttributeValue::Uid(uid) => display(out, format_args!("UID {}", uid)),
+        AttributeValue::Unknown { name, raw } => {
+            out.extend_from_slice(name.as_bytes());
+            out.push(b' ');
+            out.extend_from_slice(raw);
+        }
+    }
+}
+
+fn envelope(out: &mut Vec<u8>, env: &Envelope<'_>) {
+    out.push(b'(');
+    nstring(out, env.date.as_deref());
+    out.push(b' ');
+    nstring(out, env.subject.as_deref());
+    for addresses in &[
+        &env.from,
+        &env.sender,
+        &env.reply_to,
+        &env.to,
+        &env.cc,
+        &env.bcc,
+    ] {
+        out.push(b' ');
+        match addresses {
+            Some(addresses) => {
+                out.push(b'(');
+                for addr in addresses.iter() {
+                    out.push(b'(');
+                    nstring(out, addr.name.as_deref());
+                    out.push(b' ');
+                    nstring(out, addr.adl.as_deref());
+                    out.push(b' ');
+                    nstring(out, addr.mailbox.as_deref());
+                    out.push(b' ');
+                    nstring(out, addr.host.as_deref());
+                    out.push(b')');
+                }
+                out.push(b')');
+            }
+            None => out.extend_from_slice(b"NIL"),
+        }
+    }
+    out.push(b' ');
+    nstring(out, env.in_reply_to.as_deref());
+    out.push(b' ');
+    nstring(out, env.message_id.as_deref());
+    out.push(b')');
+}
+
+fn body_structure(out: &mut Vec<u8>, body: &BodyStructure<'_>) {
+    out.push(b'(');
+    match body {
+        BodyStructure::Basic {
+            common,
+            other,
+            extension,
+        } => {
+            body_fields(out, common, other);
+            ext_1part(out, common, other, extension);
+        }
+        BodyStructure::Text {
+            common,
+            other,
+            lines,
+            extension,
+        } => {
+            body_fields(out, common, other);
+            display(out, format_args!(" {}", lines));
+            ext_1part(out, common, other, extension);
+        }
+        BodyStructure::Message {
+            common,
+            other,
+            envelope: env,
+            body,
+            lines,
+            extension,
+        } => {
+            body_fields(out, common, other);
+            out.push(b' ');
+            envelope(out, env);
+            out.push(b' ');
+            body_structure(out, body);
+            display(out, format_args!(" {}", lines));
+            ext_1part(out, common, other, extension);
+        }
+        BodyStructure::Multipart {
+            common,
+            bodies,
+            extension,
+        } => {
+            for body in bodies {
+                body_structure(out, body);
+            }
+            out.push(b' ');
+            string(out, common.ty.subtype.as_bytes());
+            let depth = ext_depth(common, extension);
+            if depth > 0 || common.ty.params.is_some() {
+                out.push(b' ');
+                body_params(out, &common.ty.params);
+                ext_common(out, common, extension, depth);
+            }
+        }
+    }
+    out.push(b')');
+}
+
+// media-type SP body-fields, with the media type quoted
+fn body_fields(
+    out: &mut Vec<u8>,
+    common: &BodyContentCommon<'_>,
+    other: &BodyContentSinglePart<'_>,
+) {
+    quoted(out, common.ty.ty.as_bytes());
+    out.push(b' ');
+    quoted(out, common.ty.subtype.as_bytes());
+    out.push(b' ');
+    body_params(out, &common.ty.params);
+    out.push(b' ');
+    nstring(out, other.id.as_ref().map(|s| s.as_bytes()));
+    out.push(b' ');
+    nstring(out, other.description.as_ref().map(|s| s.as_bytes()));
+    out.push(b' ');
+    match &other.transfer_encoding {
+        ContentEncoding::SevenBit => out.extend_from_slice(b"\"7BIT\""),
+        ContentEncoding::EightBit => out.extend_from_slice(b"\"8BIT\""),
+        ContentEncoding::Binary => out.extend_from_slice(b"\"BINARY\""),
+        ContentEncoding::Base64 => out.extend_from_slice(b"\"BASE64\""),
+        ContentEncoding::QuotedPrintable => out.extend_from_slice(b"\"QUOTED-PRINTABLE\""),
+        ContentEncoding::Other(other) => string(out, other.as_bytes()),
+    }
+    display(out, format_args!(" {}", other.octets));
+}
+
+fn ext_1part(
+    out: &mut Vec<u8>,
+    common: &BodyContentCommon<'_>,
+    other: &BodyContentSinglePart<'_>,
+    extension: &Option<BodyExtension<'_>>,
+) {
+    let depth = ext_depth(common, extension);
+    if depth > 0 || other.md5.is_some() {
+        out.push(b' ');
+        nstring(out, other.md5.as_ref().map(|s| s.as_bytes()));
+        ext_common(out, common, extension, depth);
+    }
+}
+
+// The number of extension fields following the MD5 or parameters that need
+// to be written so that none of the present ones is lost.
+fn ext_depth(common: &BodyContentCommon<'_>, extension: &Option<BodyExtension<'_>>) -> usize {
+    if extension.is_some() {
+        4
+    } else if common.location.is_some() {
+        3
+    } else if common.language.is_some() {
+        2
+    } else if common.disposition.is_some() {
+        1
+    } else {
+        0
+    }
+}
+
+fn ext_common(
+    out: &mut Vec<u8>,
+    common: &BodyContentCommon<'_>,
+    extension: &Option<BodyExtension<'_>>,
+    depth: usize,
+) {
+    if depth >= 1 {
+        out.push(b' ');
+        match &common.disposition {
+            Some(disposition) => {
+                out.push(b'(');
+                string(out, disposition.ty.as_bytes());
+                out.push(b' ');
+                body_params(out, &disposition.params);
+                out.push(b')');
+            }
+            None => out.extend_from_slice(b"NIL"),
+        }
+    }
+    if depth >= 2 {
+        out.push(b' ');
+        match &common.language {
+            Some(languages) if languages.len() == 1 => string(out, languages[0].as_bytes()),
+            Some(languages) => list(out, languages, |out, lang| string(out, lang.as_bytes())),
+            None => out.extend_from_slice(b"NIL"),
+        }
+    }
+    if depth >= 3 {
+        out.push(b' ');
+        nstring(out, common.location.as_ref().map(|s| s.as_bytes()));
+    }
+    if let (4, Some(extension)) = (depth, extension) {
+        out.push(b' ');
+        body_extension(out, extension);
+    }
+}
+
+fn body_extension(out: &mut Vec<u8>, extension: &BodyExtension<'_>) {
+    match extension {
+        BodyExtension::Num(n) => display(out, n),
+        BodyExtension::Str(s) => nstring(out, s.as_ref().map(|s| s.as_bytes())),
+        BodyExtension::List(items) => list(out, items, body_extension),
+    }
+}
+
+fn body_params(out: &mut Vec<u8>, params: &BodyParams<'_>) {
+    match params {
+        Some(params) => list(out, params, |out, (key, value)| {
+            string(out, key.as_bytes());
+            out.push(b' ');
+            string(out, value.as_bytes());
+        }),
+        None => out.extend_from_slice(b"NIL"),
+    }
+}
+
+fn tagged_ext_value(out: &mut Vec<u8>, value: &TaggedExtValue<'_>) {
+    match value {
+        TaggedExtValue::Number(n) => display(out, n),
+        TaggedExtValue::SequenceSet(set) => display(out, set),
+        TaggedExtValue::Str(s) => astring(out, s),
+        TaggedExtValue::List(items) => list(out, items, tagged_ext_value),
+    }
+}
+
+fn list<T>(out: &mut Vec<u8>, items: &[T], mut f: impl FnMut(&mut Vec<u8>, &T)) {
+    out.push(b'(');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(b' ');
+        }
+        f(out, item);
+    }
+    out.push(b')');
+}
+
+fn astring(out: &mut Vec<u8>, s: &str) {
+    let atom = !s.is_empty() && s.bytes().all(is_astring_char) && !s.eq_ignore_ascii_case("NIL");
+    match atom {
+        true => out.extend_from_slice(s.as_bytes()),
+        false => string(out, s.as_bytes()),
+    }
+}
+
+fn nstring(out: &mut Vec<u8>, data: Option<&[u8]>) {
+    match data {
+        Some(data) => string(out, data),
+        None => out.extend_from_slice(b"NIL"),
+    }
+}
+
+// A quoted string if the data can be quoted, a literal otherwise
+fn string(out: &mut Vec<u8>, data: &[u8]) {
+    if data
+        .iter()
+        .all(|&b| b != 0 && b != b'\r' && b != b'\n' && b < 0x80)
+    {
+        quoted(out, data);
+    } else {
+        display(out, format_args!("{{{}}}\r\n", data.len()));
+        out.extend_from_slice(data);
+    }
+}
+
+fn quoted(out: &mut Vec<u8>, data: &[u8]) {
+    out.push(b'"');
+    for &b in data {
+        if b == b'"' || b == b'\\' {
+            out.push(b'\\');
+        }
+        out.push(b);
+    }
+    out.push(b'"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_challenge, encode_response};
+    use crate::parser::parse_response;
+
+    #[test]
+    fn round_trip() {
+        let responses: &[&[u8]] = &[
+            b"* OK [CAPABILITY IMAP4rev1 AUTH=PLAIN IDLE] ready\r\n",
+            b"A1 NO [TRYCREATE] no such mailbox\r\n",
+            b"+ send more\r\n",
+            b"* 12 EXISTS\r\n",
+            b"* FLAGS (\\Seen \\Draft $Forwarded)\r\n",
+            b"* LIST (\\HasNoChildren) \"/\" \"a \\\"b\\\"\"\r\n",
+            b"* STATUS INBOX (MESSAGES 3 UIDNEXT 7)\r\n",
+            b"* ESEARCH (TAG \"A2\") UID MIN 1 COUNT 3\r\n",
+            b"* SORT 2 84 882\r\n",
+            b"* ACL INBOX alice aeilrswx anyone lr\r\n",
+            b"* LISTRIGHTS INBOX bob \"\" l r s w i p k x t e a 0 1\r\n",
+            b"* MYRIGHTS INBOX aeilrswx\r\n",
+            b"* QUOTAROOT INBOX \"\" user\r\n",
+            b"* QUOTA \"\" (STORAGE 10 512 MESSAGE 3 100)\r\n",
+            b"* NAMESPACE ((\"\" \"/\")) NIL ((\"#shared/\" \"/\" \"X-PARAM\" (\"a\")))\r\n",
+            b"* VANISHED (EARLIER) 1:3,5\r\n",
+            b"* ID (\"name\" \"Cyrus\" \"version\" \"1.5\" \"os\" NIL)\r\n",
+            b"* ID NIL\r\n",
+            b"A3 OK [COPYUID 9 1:2,4 10:12] done\r\n",
+            b"A4 OK [APPENDUID 9 13:14] done\r\n",
+            b"A5 OK [METADATA LONGENTRIES 2199] done\r\n",
+            b"A6 NO [METADATA TOOMANY] too many\r\n",
+            b"* 1 FETCH (UID 4 FLAGS (\\Seen) BODY[1.HEADER]<0> {5}\r\nhel\r\n)\r\n",
+            b"* 2 FETCH (ENVELOPE (\"date\" NIL ((\"A\" NIL \"a\" \"example.com\")) NIL NIL NIL NIL NIL NIL \"<id>\"))\r\n",
+            b"* 3 FETCH (BODYSTRUCTURE ((\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 12 1 NIL \
+              (\"inline\" NIL))(\"IMAGE\" \"PNG\" NIL \"<c>\" NIL \"BASE64\" 40) \"MIXED\" (\"BOUNDARY\" \"x\")))\r\n",
+        ];
+        for &raw in responses {
+            let mut out = Vec::new();
+            let (_, rsp) = parse_response(raw).unwrap();
+            encode_response(&rsp, &mut out);
+            assert_eq!(String::from_utf8_lossy(&out), String::from_utf8_lossy(raw));
+            assert_eq!(parse_response(&out).unwrap().1, rsp);
+        }
+    }
+    #[test]
+    fn challenge() {
+        let mut out = Vec::new();
+        encode_challenge(b"r=fyko+rgj/", &mut out);
+        assert_eq!(out, b"+ cj1meWtvK3Jnai8=\r\n");
+        let (_, rsp) = parse_response(&out).unwrap();
+        assert_eq!(rsp.decode_challenge().unwrap(), b"r=fyko+rgj/");
+    }
+}
@@ -0,0 +1,338 @@
+use std::str;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag_no_case, take_while, take_while1},
+    character::complete::char,
+    combinator::{all_consuming, complete, map, map_res, opt, recognize, verify},
+    multi::{many0, separated_nonempty_list},
+    sequence::{delimited, preceded, separated_pair, tuple},
+    IResult,
+};
+
+use super::args::{astring, number, number_64, sequence_spec};
+use crate::parser::core::{is_atom_char, utf8};
+use crate::types::{AttrMacro, FetchItem, FetchItems, FetchSection, SectionText, SequenceSpec};
+
+/// The arguments of a `FETCH` command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FetchArgs<'a> {
+    pub set: SequenceSpec,
+    pub items: FetchItems<'a>,
+    /// The `CHANGEDSINCE` modifier (RFC 7162).
+    pub changed_since: Option<u64>,
+    /// The `VANISHED` modifier of `UID FETCH` (RFC 7162).
+    pub vanished: bool,
+}
+
+/// Parse the arguments of a `FETCH` or `UID FETCH` command, as in
+/// `CommandLine::args`.
+///
+/// ```
+/// use imap_proto::server::parse_fetch;
+/// use imap_proto::types::FetchItem;
+///
+/// let (_, fetch) = parse_fetch(b"1:* (UID BODY.PEEK[HEADER]<0.1024>)").unwrap();
+/// assert_eq!(fetch.items.items()[0], FetchItem::Uid);
+/// assert!(!fetch.items.sets_seen());
+/// assert_eq!(fetch.items.to_string(), "(UID BODY.PEEK[HEADER]<0.1024>)");
+/// ```
+//
+// fetch = "FETCH" SP sequence-set SP ("ALL" / "FULL" / "FAST" /
+//         fetch-att / "(" fetch-att *(SP fetch-att) ")")
+//         [SP "(" fetch-modifier *(SP fetch-modifier) ")"]
+pub fn parse_fetch(args: &[u8]) -> IResult<&[u8], FetchArgs<'_>> {
+    let (i, (set, _, items, modifiers)) = all_consuming(complete(tuple((
+        sequence_spec,
+        char(' '),
+        fetch_items,
+        opt(preceded(
+            char(' '),
+            delimited(
+                char('('),
+                separated_nonempty_list(char(' '), fetch_modifier),
+                char(')'),
+            ),
+        )),
+    ))))(args)?;
+    let mut fetch = FetchArgs {
+        set,
+        items,
+        changed_since: None,
+        vanished: false,
+    };
+    for modifier in modifiers.unwrap_or_default() {
+        match modifier {
+            Modifier::ChangedSince(mod_seq) => fetch.changed_since = Some(mod_seq),
+            Modifier::Vanished => fetch.vanished = true,
+        }
+    }
+    Ok((i, fetch))
+}
+
+enum Modifier {
+    ChangedSince(u64),
+    Vanished,
+}
+
+fn fetch_modifier(i: &[u8]) -> IResult<&[u8], Modifier> {
+    alt((
+        map(
+            preceded(tag_no_case("CHANGEDSINCE "), number_64),
+            Modifier::ChangedSince,
+        ),
+        map(tag_no_case("VANISHED"), |_| Modifier::Vanished),
+    ))(i)
+}
+
+fn fetch_items(i: &[u8]) -> IResult<&[u8], FetchItems<'_>> {
+    alt((
+        map(
+            delimited(
+                char('('),
+                separated_nonempty_list(char(' '), fetch_att),
+                char(')'),
+            ),
+            FetchItems::Items,
+        ),
+        map(fetch_att, |item| FetchItems::Items(vec![item])),
+        fetch_macro,
+    ))(i)
+}
+
+fn fetch_macro(i: &[u8]) -> IResult<&[u8], FetchItems<'_>> {
+    map(
+        alt((
+            map(tag_no_case("ALL"), |_| AttrMacro::All),
+            map(tag_no_case("FAST"), |_| AttrMacro::Fast),
+            map(tag_no_case("FULL"), |_| AttrMacro::Full),
+        )),
+        FetchItems::Macro,
+    )(i)
+}
+
+// The name of an item, up to its section or partial specifier
+fn att_name(i: &[u8]) -> IResult<&[u8], &str> {
+    map_res(
+        take_while1(|c| is_atom_char(c) && c != b'[' && c != b'<'),
+        str::from_utf8,
+    )(i)
+}
+
+// fetch-att = "ENVELOPE" / "FLAGS" / "INTERNALDATE" /
+//             "RFC822" [".HEADER" / ".SIZE" / ".TEXT"] /
+//             "BODY" ["STRUCTURE"] / "UID" /
+//             "BODY" section ["<" number "." nz-number ">"] /
+//             "BODY.PEEK" section ["<" number "." nz-number ">"]
+fn fetch_att(i: &[u8]) -> IResult<&[u8], FetchItem<'_>> {
+    let (rest, name) = att_name(i)?;
+    let name = name.to_ascii_uppercase();
+    let item = match name.as_str() {
+        "BODY" | "BODY.PEEK" if rest.starts_with(b"[") => {
+            let peek = name == "BODY.PEEK";
+            return map(tuple((section, opt(partial))), |(section, partial)| {
+                FetchItem::BodySection {
+                    section,
+                    partial,
+                    peek,
+                }
+            })(rest);
+        }
+        "BODY" => FetchItem::Body,
+        "BODYSTRUCTURE" => FetchItem::BodyStructure,
+        "ENVELOPE" => FetchItem::Envelope,
+        "FLAGS" => FetchItem::Flags,
+        "INTERNALDATE" => FetchItem::InternalDate,
+        "MODSEQ" => FetchItem::ModSeq,
+        "RFC822" => FetchItem::Rfc822,
+        "RFC822.HEADER" => FetchItem::Rfc822Header,
+        "RFC822.SIZE" => FetchItem::Rfc822Size,
+        "RFC822.TEXT" => FetchItem::Rfc822Text,
+        "UID" => FetchItem::Uid,
+        // The macros are not items, and not valid in lists
+        "ALL" | "FAST" | "FULL" | "BODY.PEEK" => {
+            return Err(nom::Err::Error((i, nom::error::ErrorKind::Tag)))
+        }
+        _ => return map(map_res(extension_att, str::from_utf8), FetchItem::Extension)(i),
+    };
+    Ok((rest, item))
+}
+
+// An item defined by an extension: a name, with an optional section and
+// partial specifier, such as `BINARY.PEEK[1]<0.100>`
+fn extension_att(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    recognize(tuple((
+        att_name,
+        opt(delimited(char('['), take_while(|c| c != b']'), char(']'))),
+        opt(delimited(
+            char('<'),
+            take_while1(|c: u8| c.is_ascii_digit() || c == b'.'),
+            char('>'),
+        )),
+    )))(i)
+}
+
+// section = "[" [section-spec] "]"
+fn section(i: &[u8]) -> IResult<&[u8], FetchSection<'_>> {
+    map(
+        delimited(char('['), opt(section_spec), char(']')),
+        Option::unwrap_or_default,
+    )(i)
+}
+
+// section-spec = section-msgtext / (section-part ["." section-text])
+fn section_spec(i: &[u8]) -> IResult<&[u8], FetchSection<'_>> {
+    alt((
+        map(section_msgtext, |text| FetchSection {
+            part: Vec::new(),
+            text: Some(text),
+        }),
+        map(
+            tuple((
+                section_part,
+                opt(preceded(
+                    char('.'),
+                    alt((
+                        section_msgtext,
+                        map(tag_no_case("MIME"), |_| SectionText::Mime),
+                    )),
+                )),
+            )),
+            |(part, text)| FetchSection { part, text },
+        ),
+    ))(i)
+}
+
+// section-part = nz-number *("." nz-number)
+fn section_part(i: &[u8]) -> IResult<&[u8], Vec<u32>> {
+    let nz_number = || verify(number, |&num| num > 0);
+    let (i, (first, mut rest)) = tuple((nz_number(), many0(preceded(char('.'), nz_number()))))(i)?;
+    rest.insert(0, first);
+    Ok((i, rest))
+}
+
+// section-msgtext = "HEADER" / "HEADER.FIELDS" [".NOT"] SP header-list / "TEXT"
+// header-list     = "(" header-fld-name *(SP header-fld-name) ")"
+fn section_msgtext(i: &[u8]) -> IResult<&[u8], SectionText<'_>> {
+    alt((
+        map(
+            tuple((
+                tag_no_case("HEADER.FIELDS"),
+                opt(tag_no_case(".NOT")),
+                char(' '),
+                delimited(
+                    char('('),
+                    separated_nonempty_list(char(' '), map_res(astring, utf8)),
+                    char(')'),
+                ),
+            )),
+            |(_, not, _, fields)| SectionText::HeaderFields {
+                not: not.is_some(),
+                fields,
+            },
+        ),
+        map(tag_no_case("HEADER"), |_| SectionText::Header),
+        map(tag_no_case("TEXT"), |_| SectionText::Text),
+    ))(i)
+}
+
+// partial = "<" number "." nz-number ">"
+fn partial(i: &[u8]) -> IResult<&[u8], (u32, u32)> {
+    delimited(
+        char('<'),
+        separated_pair(number, char('.'), verify(number, |&len| len > 0)),
+        char('>'),
+    )(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_fetch;
+    use crate::types::*;
+
+    #[test]
+    fn fetch() {
+        let (_, fetch) = parse_fetch(
+            b"2,4:* (uid Flags BODY[1.2.HEADER.FIELDS.NOT (Subject \"From\")] \
+              BODY.PEEK[]<0.512> BODY[3.MIME] X-GM-MSGID BINARY.PEEK[1]<0.10>) \
+              (CHANGEDSINCE 12345)",
+        )
+        .unwrap();
+        assert_eq!(fetch.set.to_string(), "2,4:*");
+        assert_eq!(fetch.changed_since, Some(12345));
+        assert!(!fetch.vanished);
+        assert_eq!(
+            fetch.items,
+            FetchItems::Items(vec![
+                FetchItem::Uid,
+                FetchItem::Flags,
+                FetchItem::BodySection {
+                    section: FetchSection {
+                        part: vec![1, 2],
+                        text: Some(SectionText::HeaderFields {
+                            not: true,
+                            fields: vec!["Subject".into(), "From".into()],
+                        }),
+                    },
+                    partial: None,
+                    peek: false,
+                },
+                FetchItem::BodySection {
+                    section: FetchSection::default(),
+                    partial: Some((0, 512)),
+                    peek: true,
+                },
+                FetchItem::BodySection {
+                    section: FetchSection {
+                        part: vec![3],
+                        text: Some(SectionText::Mime),
+                    },
+                    partial: None,
+                    peek: false,
+                },
+                FetchItem::Extension("X-GM-MSGID"),
+                FetchItem::Extension("BINARY.PEEK[1]<0.10>"),
+            ])
+        );
+        assert!(fetch.items.sets_seen());
+        assert_eq!(
+            fetch.items.to_string(),
+            "(UID FLAGS BODY[1.2.HEADER.FIELDS.NOT (Subject From)] BODY.PEEK[]<0.512> \
+             BODY[3.MIME] X-GM-MSGID BINARY.PEEK[1]<0.10>)"
+        );
+    }
+
+    #[test]
+    fn fetch_macros() {
+        let (_, fetch) = parse_fetch(b"1 full").unwrap();
+        assert_eq!(fetch.items, FetchItems::Macro(AttrMacro::Full));
+        assert_eq!(fetch.items.items().len(), 5);
+        assert!(!fetch.items.sets_seen());
+
+        let (_, fetch) = parse_fetch(b"1:4 BODY (VANISHED CHANGEDSINCE 7)").unwrap();
+        assert_eq!(fetch.items, FetchItems::Items(vec![FetchItem::Body]));
+        assert!(fetch.vanished);
+
+        let (_, fetch) = parse_fetch(b"1 BODY[TEXT]").unwrap();
+        assert!(fetch.items.sets_seen());
+    }
+
+    #[test]
+    fn fetch_invalid() {
+        for args in &[
+            &b"1"[..],
+            b"1 ",
+            b"1 (ALL)",
+            b"1 ()",
+            b"1 (UID",
+            b"1 BODY[",
+            b"1 BODY.PEEK",
+            b"1 BODY[0]",
+            b"1 BODY[]<0.0>",
+            b"1 BODY[HEADER.FIELDS ()]",
+            b"1 UID (CHANGEDSINCE)",
+        ] {
+            assert!(parse_fetch(args).is_err(), "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::borrow::Cow;
+use std::str;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag_no_case, take_while_m_n},
+    character::complete::{char, one_of},
+    combinator::{all_consuming, complete, map, map_res, opt, recognize},
+    multi::separated_nonempty_list,
+    sequence::{delimited, pair, preceded, tuple},
+    IResult,
+};
+
+use super::args::{flag, flag_list, literal, mailbox, number_64, sequence_spec};
+use crate::types::{FlagSet, SequenceSpec, StoreMode};
+
+/// The arguments of a `STORE` command.
+#[derive(Clone, Debug)]
+pub struct StoreArgs {
+    pub set: SequenceSpec,
+    /// The `UNCHANGEDSINCE` modifier (RFC 7162).
+    pub unchanged_since: Option<u64>,
+    pub mode: StoreMode,
+    /// Whether the server should not send the new flags (`FLAGS.SILENT`).
+    pub silent: bool,
+    pub flags: FlagSet,
+}
+
+/// Parse the arguments of a `STORE` or `UID STORE` command, as in
+/// `CommandLine::args`. The flags may be given as a list or separated by
+/// spaces.
+//
+// store = "STORE" SP sequence-set [SP "(" "UNCHANGEDSINCE" SP mod-seq ")"]
+//         SP store-att-flags
+// store-att-flags = (["+" / "-"] "FLAGS" [".SILENT"]) SP
+//                   (flag-list / (flag *(SP flag)))
+pub fn parse_store(args: &[u8]) -> IResult<&[u8], StoreArgs> {
+    let (i, (set, unchanged_since, _, mode, _, silent, _, flags)) =
+        all_consuming(complete(tuple((
+            sequence_spec,
+            opt(preceded(
+                tag_no_case(" (UNCHANGEDSINCE "),
+                map(pair(number_64, char(')')), |(mod_seq, _)| mod_seq),
+            )),
+            char(' '),
+            map(opt(one_of("+-")), |sign| match sign {
+                Some('+') => StoreMode::Add,
+                Some(_) => StoreMode::Remove,
+                None => StoreMode::Replace,
+            }),
+            tag_no_case("FLAGS"),
+            map(opt(tag_no_case(".SILENT")), |silent| silent.is_some()),
+            char(' '),
+            alt((
+                flag_list,
+                map(separated_nonempty_list(char(' '), flag), |flags| {
+                    flags.into_iter().collect()
+                }),
+            )),
+        ))))(args)?;
+    Ok((
+        i,
+        StoreArgs {
+            set,
+            unchanged_since,
+            mode,
+            silent,
+            flags,
+        },
+    ))
+}
+
+/// The arguments of a `COPY` or `MOVE` command (RFC 6851).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CopyArgs<'a> {
+    pub set: SequenceSpec,
+    /// The destination mailbox, with `INBOX` in upper case.
+    pub mailbox: Cow<'a, str>,
+}
+
+/// Parse the arguments of a `COPY` or `MOVE` command, with or without `UID`,
+/// as in `CommandLine::args`.
+//
+// copy = "COPY" SP sequence-set SP mailbox
+// move = "MOVE" SP sequence-set SP mailbox
+pub fn parse_copy(args: &[u8]) -> IResult<&[u8], CopyArgs<'_>> {
+    let (i, (set, _, mailbox)) =
+        all_consuming(complete(tuple((sequence_spec, char(' '), mailbox))))(args)?;
+    Ok((i, CopyArgs { set, mailbox }))
+}
+
+/// The arguments of an `APPEND` command.
+#[derive(Clone, Debug)]
+pub struct AppendArgs<'a> {
+    /// The mailbox to add the message to, with `INBOX` in upper case.
+    pub mailbox: Cow<'a, str>,
+    /// The flags to set on the message, if any were given.
+    pub flags: Option<FlagSet>,
+    /// The internal date to set on the message, such as
+    /// `17-Jul-1996 02:44:25 -0700`.
+    pub date: Option<&'a str>,
+    pub message: &'a [u8],
+}
+
+/// Parse the arguments of an `APPEND` command, as in `CommandLine::args`; the
+/// message is the final literal.
+///
+/// ```
+/// use imap_proto::server::parse_append;
+///
+/// let args = b"Drafts (\\Draft) \"17-Jul-1996 02:44:25 -0700\" {11}\r\nSubject: hi";
+/// let (_, append) = parse_append(args).unwrap();
+/// assert_eq!(append.mailbox, "Drafts");
+/// assert!(append.flags.unwrap().contains("\\draft"));
+/// assert_eq!(append.date, Some("17-Jul-1996 02:44:25 -0700"));
+/// assert_eq!(append.message, b"Subject: hi");
+/// ```
+//
+// append = "APPEND" SP mailbox [SP flag-list] [SP date-time] SP literal
+pub fn parse_append(args: &[u8]) -> IResult<&[u8], AppendArgs<'_>> {
+    let (i, (mailbox, flags, date, _, message)) = all_consuming(complete(tuple((
+        mailbox,
+        opt(preceded(char(' '), flag_list)),
+        opt(preceded(char(' '), date_time)),
+        char(' '),
+        literal,
+    ))))(args)?;
+    Ok((
+        i,
+        AppendArgs {
+            mailbox,
+            flags,
+            date,
+            message,
+        },
+    ))
+}
+
+// date-time = DQUOTE date-day-fixed "-" date-month "-" date-year
+//             SP time SP zone DQUOTE
+fn date_time(i: &[u8]) -> IResult<&[u8], &str> {
+    let digits = |n| take_while_m_n(n, n, |c: u8| c.is_ascii_digit());
+    let alpha = |n| take_while_m_n(n, n, |c: u8| c.is_ascii_alphabetic());
+    delimited(
+        char('"'),
+        map_res(
+            recognize(tuple((
+                alt((recognize(pair(char(' '), digits(1))), digits(2))),
+                char('-'),
+                alpha(3),
+                char('-'),
+                digits(4),
+                char(' '),
+                tuple((digits(2), char(':'), digits(2), char(':'), digits(2))),
+                char(' '),
+                one_of("+-"),
+                digits(4),
+            ))),
+            str::from_utf8,
+        ),
+        char('"'),
+    )(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SeqNumber;
+
+    #[test]
+    fn store() {
+        let (_, store) = parse_store(b"1:* +FLAGS.SILENT (\\Deleted $Junk)").unwrap();
+        assert_eq!(
+            store.set.ranges().next(),
+            Some((SeqNumber::Value(1), SeqNumber::Largest))
+        );
+        assert_eq!(store.mode, StoreMode::Add);
+        assert!(store.silent);
+        assert_eq!(
+            store.flags.iter().collect::<Vec<_>>(),
+            ["\\Deleted", "$Junk"]
+        );
+        assert_eq!(store.unchanged_since, None);
+
+        let (_, store) =
+            parse_store(b"4 (UNCHANGEDSINCE 320162338) -flags \\Seen \\Flagged").unwrap();
+        assert_eq!(store.unchanged_since, Some(320162338));
+        assert_eq!(store.mode, StoreMode::Remove);
+        assert!(!store.silent);
+        assert_eq!(store.flags.len(), 2);
+
+        let (_, store) = parse_store(b"4 FLAGS ()").unwrap();
+        assert_eq!(store.mode, StoreMode::Replace);
+        assert!(store.flags.is_empty());
+
+        for args in &[
+            &b"4 FLAGS"[..],
+            b"4 *FLAGS (\\Seen)",
+            b"4 FLAGS.QUIET ()",
+            b"4 FLAGS (",
+        ] {
+            assert!(parse_store(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn copy() {
+        let (_, copy) = parse_copy(b"2:4 inbox").unwrap();
+        assert_eq!(copy.mailbox, "INBOX");
+        assert_eq!(copy.set.to_string(), "2:4");
+        let (_, copy) = parse_copy(b"2 {10+}\r\nSent Items").unwrap();
+        assert_eq!(copy.mailbox, "Sent Items");
+        assert!(parse_copy(b"2").is_err());
+        assert!(parse_copy(b"2 \"Sent").is_err());
+    }
+
+    #[test]
+    fn append() {
+        let (_, append) = parse_append(b"INBOX {5+}\r\nhello").unwrap();
+        assert_eq!(append.mailbox, "INBOX");
+        assert!(append.flags.is_none());
+        assert_eq!(append.date, None);
+        assert_eq!(append.message, b"hello");
+
+        let (_, append) = parse_append(b"INBOX \" 7-Jul-1996 02:44:25 +0000\" {0}\r\n").unwrap();
+        assert_eq!(append.date, Some(" 7-Jul-1996 02:44:25 +0000"));
+        assert_eq!(append.message, b"");
+
+        for args in &[
+            &b"INBOX"[..],
+            b"INBOX \"hello\"",
+            b"INBOX {6}\r\nhello",
+            b"INBOX \"7-Jul-1996 02:44:25 +0000\" {5}\r\nhello",
+        ] {
+            assert!(parse_append(args).is_err(), "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Building blocks for IMAP servers, and for test doubles of them.
+//!
+//! `ResponseBuilder` constructs the responses a server sends from the types in
+//! this crate, and `encode_response()` writes them in the wire format, as
+//! `encode_challenge()` does for SASL challenges.
+//! `CapabilitySet` holds the capabilities to advertise. `CommandDecoder` finds
+//! the commands in the data received from a client, including their literals,
+//! and `Dispatcher` hands those valid in the state of the connection to a
+//! `Handler`.
+//!
+//! The arguments of commands are parsed with the `parse_*()` functions, such as
+//! `parse_fetch()` and `parse_search()`. `AuthenticateExchange` runs the
+//! exchange of an `AUTHENTICATE` command with a `SaslMechanism`.
+//!
+//! Proxies forward the responses of a server with `Passthrough`, and multiplex
+//! client connections with `TagMap`.
+
+mod args;
+mod authenticate;
+pub use self::authenticate::{AuthOutcome, AuthenticateExchange, SaslMechanism, SaslStep};
+mod capabilities;
+pub use self::capabilities::CapabilitySet;
+mod command;
+pub use self::command::{parse_command, CommandDecoder, CommandLine, Decoded};
+mod dispatch;
+pub use self::dispatch::{valid_states, Dispatcher, Handler};
+mod encode;
+pub use self::encode::{encode_challenge, encode_response};
+mod fetch;
+pub use self::fetch::{parse_fetch, FetchArgs};
+mod messages;
+pub use self::messages::{parse_append, parse_copy, parse_store, AppendArgs, CopyArgs, StoreArgs};
+mod proxy;
+pub use self::proxy::Passthrough;
+mod response;
+pub use self::response::ResponseBuilder;
+mod search;
+pub use self::search::{parse_search, SearchArgs};
+mod tags;
+pub use self::tags::{is_valid_tag, rewrite_tag, InFlightTags, TagMap};
@@ -0,0 +1,138 @@
+use crate::parser::{parse_response_with, Quirks};
+use crate::server::encode_response;
+use crate::types::{Capability, RequestId, Response, ResponseCode};
+
+/// A response received from a server and forwarded to a client, as done by
+/// proxies.
+///
+/// The response is written as received unless it was modified, in which case
+/// it is encoded again from the parsed response. This keeps the responses a
+/// proxy does not touch byte for byte identical, including any deviations from
+/// the protocol that the client may rely on.
+#[derive(Clone, Debug)]
+pub struct Passthrough<'a> {
+    raw: &'a [u8],
+    response: Response<'a>,
+    modified: bool,
+}
+
+impl<'a> Passthrough<'a> {
+    /// Parse the first response in `buf`, returning the rest of the data.
+    pub fn parse(buf: &'a [u8], quirks: &Quirks) -> nom::IResult<&'a [u8], Self> {
+        let (rest, response) = parse_response_with(buf, quirks)?;
+        let raw = &buf[..buf.len() - rest.len()];
+        let passthrough = Self {
+            raw,
+            response,
+            modified: false,
+        };
+        Ok((rest, passthrough))
+    }
+
+    pub fn response(&self) -> &Response<'a> {
+        &self.response
+    }
+
+    /// The response, to be modified: it is encoded again when written.
+    pub fn response_mut(&mut self) -> &mut Response<'a> {
+        self.modified = true;
+        &mut self.response
+    }
+
+    /// The response as received.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Replace the tag of a tagged response, such as when the proxy sent the
+    /// command under another tag than the client.
+    pub fn rewrite_tag(&mut self, tag: RequestId) {
+        if let Response::Done { tag: old, .. } = &self.response {
+            if *old != tag {
+                if let Response::Done { tag: old, .. } = self.response_mut() {
+                    *old = tag;
+                }
+            }
+        }
+    }
+
+    /// Remove the capabilities for which `keep` returns false from a
+    /// `CAPABILITY` response or response code, such as extensions the proxy
+    /// does not support. The response is only modified if it advertised any
+    /// of them.
+    pub fn filter_capabilities(&mut self, mut keep: impl FnMut(&Capability<'a>) -> bool) {
+        let caps = match &self.response {
+            Response::Capabilities(caps) => caps,
+            Response::Data {
+                code: Some(ResponseCode::Capabilities(caps)),
+                ..
+            }
+            | Response::Done {
+                code: Some(ResponseCode::Capabilities(caps)),
+                ..
+            } => caps,
+            _ => return,
+        };
+        if caps.iter().all(&mut keep) {
+            return;
+        }
+        match self.response_mut() {
+            Response::Capabilities(caps)
+            | Response::Data {
+                code: Some(ResponseCode::Capabilities(caps)),
+                ..
+            }
+            | Response::Done {
+                code: Some(ResponseCode::Capabilities(caps)),
+                ..
+            } => caps.retain(|cap| keep(cap)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Append the response to `out`: as received if it was not modified.
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        match self.modified {
+            true => encode_response(&self.response, out),
+            false => out.extend_from_slice(self.raw),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Passthrough;
+    use crate::parser::Quirks;
+    use crate::types::{Capability, RequestId};
+
+    #[test]
+    fn passthrough() {
+        let mut buf = b"* ok [CAPABILITY IMAP4rev1 COMPRESS=DEFLATE IDLE]  Hello\r\n".to_vec();
+        buf.extend_from_slice(b"* 1 fetch (UID 4)\r\nP7 OK done\r\n");
+
+        let mut out = Vec::new();
+        let (rest, mut rsp) = Passthrough::parse(&buf, &Quirks::new()).unwrap();
+        rsp.filter_capabilities(|cap| *cap != Capability::Atom("COMPRESS=DEFLATE"));
+        rsp.write_to(&mut out);
+        assert_eq!(out, b"* OK [CAPABILITY IMAP4rev1 IDLE]  Hello\r\n");
+
+        out.clear();
+        let (rest, mut rsp) = Passthrough::parse(rest, &Quirks::new()).unwrap();
+        rsp.filter_capabilities(|_| false);
+        rsp.rewrite_tag(RequestId::from("A1"));
+        assert!(!rsp.is_modified());
+        rsp.write_to(&mut out);
+        assert_eq!(out, b"* 1 fetch (UID 4)\r\n");
+
+        out.clear();
+        let (rest, mut rsp) = Passthrough::parse(rest, &Quirks::new()).unwrap();
+        rsp.rewrite_tag(RequestId::from("A1"));
+        rsp.write_to(&mut out);
+        assert_eq!(out, b"A1 OK done\r\n");
+        assert!(rest.is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+use std::borrow::Cow;
+
+use crate::types::*;
+
+/// Constructors for the responses a server sends, to be written with
+/// `encode_response()`.
+///
+/// ```
+/// use imap_proto::server::{encode_response, ResponseBuilder};
+/// use imap_proto::types::{RequestId, ResponseCode};
+///
+/// let mut out = Vec::new();
+/// encode_response(&ResponseBuilder::exists(3), &mut out);
+/// encode_response(
+///     &ResponseBuilder::ok(RequestId::from("A1"), Some(ResponseCode::ReadWrite), "SELECT done"),
+///     &mut out,
+/// );
+/// assert_eq!(out, b"* 3 EXISTS\r\nA1 OK [READ-WRITE] SELECT done\r\n");
+/// ```
+pub struct ResponseBuilder {}
+
+impl ResponseBuilder {
+    /// The `OK` greeting, typically with the capabilities as response code.
+    pub fn greeting<'a>(code: Option<ResponseCode<'a>>, text: &'a str) -> Response<'a> {
+        Self::data(Status::Ok, code, text)
+    }
+
+    /// The `PREAUTH` greeting, for connections that are already authenticated.
+    pub fn preauth<'a>(code: Option<ResponseCode<'a>>, text: &'a str) -> Response<'a> {
+        Self::data(Status::PreAuth, code, text)
+    }
+
+    /// The `BYE` response sent before closing the connection.
+    pub fn bye(text: &str) -> Response<'_> {
+        Self::data(Status::Bye, None, text)
+    }
+
+    /// An untagged status response, such as `* OK [UIDNEXT 4] Predicted`.
+    pub fn data<'a>(status: Status, code: Option<ResponseCode<'a>>, text: &'a str) -> Response<'a> {
+        Response::Data {
+            status,
+            code,
+            information: Some(Text(text.as_bytes())),
+        }
+    }
+
+    /// The tagged `OK` completing the command with the given tag.
+    pub fn ok<'a>(tag: RequestId, code: Option<ResponseCode<'a>>, text: &'a str) -> Response<'a> {
+        Self::done(tag, Status::Ok, code, text)
+    }
+
+    /// The tagged `NO` failing the command with the given tag.
+    pub fn no<'a>(tag: RequestId, code: Option<ResponseCode<'a>>, text: &'a str) -> Response<'a> {
+        Self::done(tag, Status::No, code, text)
+    }
+
+    /// The tagged `BAD` rejecting the command with the given tag.
+    pub fn bad<'a>(tag: RequestId, code: Option<ResponseCode<'a>>, text: &'a str) -> Response<'a> {
+        Self::done(tag, Status::Bad, code, text)
+    }
+
+    fn done<'a>(
+        tag: RequestId,
+        status: Status,
+        code: Option<ResponseCode<'a>>,
+        text: &'a str,
+    ) -> Response<'a> {
+        Response::Done {
+            tag,
+            status,
+            code,
+            information: Some(Text(text.as_bytes())),
+        }
+    }
+
+    /// A continuation request, such as `+ Ready for literal data`.
+    pub fn continuation(text: &str) -> Response<'_> {
+        Response::Continue {
+            code: None,
+            information: Some(Text(text.as_bytes())),
+        }
+    }
+
+    pub fn capabilities(caps: Vec<Capability<'_>>) -> Response<'_> {
+        Response::Capabilities(caps)
+    }
+
+    pub fn exists(count: u32) -> Response<'static> {
+        Response::MailboxData(MailboxDatum::Exists(count))
+    }
+
+    pub fn recent(count: u32) -> Response<'static> {
+        Response::MailboxData(MailboxDatum::Recent(count))
+    }
+
+    pub fn expunge(seq: u32) -> Response<'static> {
+        Response::Expunge(seq)
+    }
+
+    /// The `FLAGS` response listing the flags defined in the mailbox.
+    pub fn flags<'a>(flags: &[&'a str]) -> Response<'a> {
+        Response::MailboxData(MailboxDatum::Flags(flags.to_vec()))
+    }
+
+    pub fn fetch(seq: u32, attrs: Vec<AttributeValue<'_>>) -> Response<'_> {
+        Response::Fetch(seq, attrs)
+    }
+
+    /// A `LIST` response for one mailbox; `delimiter` is `None` for a flat
+    /// hierarchy.
+    pub fn list<'a>(flags: &[&'a str], delimiter: Option<&'a str>, name: &'a str) -> Response<'a> {
+        Response::MailboxData(MailboxDatum::List {
+            flags: flags.to_vec(),
+            delimiter: delimiter.map(Cow::Borrowed),
+            name: Cow::Borrowed(name),
+            extensions: Vec::new(),
+        })
+    }
+
+    pub fn search(ids: Vec<u32>) -> Response<'static> {
+        Response::MailboxData(MailboxDatum::Search { ids, mod_seq: None })
+    }
+
+    pub fn status<'a>(mailbox: &'a str, status: Vec<StatusAttribute<'a>>) -> Response<'a> {
+        Response::MailboxData(MailboxDatum::Status {
+            mailbox: Cow::Borrowed(mailbox),
+            status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResponseBuilder;
+    use crate::server::encode_response;
+    use crate::types::*;
+
+    fn encode(rsp: Response<'_>) -> String {
+        let mut out = Vec::new();
+        encode_response(&rsp, &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn responses() {
+        let caps = vec![Capability::Imap4rev1, Capability::Auth("PLAIN")];
+        assert_eq!(
+            encode(ResponseBuilder::greeting(
+                Some(ResponseCode::Capabilities(caps)),
+                "ready"
+            )),
+            "* OK [CAPABILITY IMAP4rev1 AUTH=PLAIN] ready\r\n"
+        );
+        assert_eq!(
+            encode(ResponseBuilder::no(
+                RequestId::from("A2"),
+                Some(ResponseCode::TryCreate),
+                "no such mailbox"
+            )),
+            "A2 NO [TRYCREATE] no such mailbox\r\n"
+        );
+        assert_eq!(
+            encode(ResponseBuilder::list(
+                &["\\HasNoChildren"],
+                Some("/"),
+                "Sent Items"
+            )),
+            "* LIST (\\HasNoChildren) \"/\" \"Sent Items\"\r\n"
+        );
+        assert_eq!(
+            encode(ResponseBuilder::fetch(
+                2,
+                vec![
+                    AttributeValue::Uid(7),
+                    AttributeValue::Flags(vec!["\\Seen"])
+                ]
+            )),
+            "* 2 FETCH (UID 7 FLAGS (\\Seen))\r\n"
+        );
+        assert_eq!(
+            encode(ResponseBuilder::continuation("go ahead")),
+            "+ go ahead\r\n"
+        );
+    }
+}
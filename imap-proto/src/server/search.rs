@@ -0,0 +1,277 @@
+use std::borrow::Cow;
+use std::str;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag_no_case, take_while_m_n},
+    character::complete::char,
+    combinator::{all_consuming, complete, map, map_opt, map_res, opt},
+    error::ErrorKind,
+    multi::{separated_list, separated_nonempty_list},
+    sequence::{delimited, preceded, terminated, tuple},
+    IResult,
+};
+
+use super::args::{astring, atom, number, sequence_spec};
+use crate::parser::core::utf8;
+use crate::types::{Date, SearchCriteria, SearchReturn};
+
+/// Nesting allowed in search criteria (with parentheses, `NOT` and `OR`), to
+/// bound the recursion of the parser on input from clients.
+const MAX_DEPTH: usize = 64;
+
+/// The arguments of a `SEARCH` command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SearchArgs<'a> {
+    /// The return options (RFC 4731), if any were given: the results are then
+    /// sent in an `ESEARCH` response. An empty list stands for `ALL`.
+    pub returning: Option<Vec<SearchReturn>>,
+    pub charset: Option<Cow<'a, str>>,
+    /// The criteria, combined in `SearchCriteria::And` if there are several.
+    pub criteria: SearchCriteria<'a>,
+}
+
+/// Parse the arguments of a `SEARCH` or `UID SEARCH` command, as in
+/// `CommandLine::args`.
+///
+/// ```
+/// use imap_proto::server::parse_search;
+/// use imap_proto::types::SearchCriteria;
+///
+/// let (_, search) = parse_search(b"OR SEEN (UNDELETED FROM \"joe\")").unwrap();
+/// match search.criteria {
+///     SearchCriteria::Or(a, b) => {
+///         assert_eq!(*a, SearchCriteria::Seen);
+///         assert!(matches!(*b, SearchCriteria::And(keys) if keys.len() == 2));
+///     }
+///     _ => panic!("expected OR"),
+/// }
+/// ```
+//
+// search = "SEARCH" [search-return-opts] [SP "CHARSET" SP astring]
+//          1*(SP search-key)
+pub fn parse_search(args: &[u8]) -> IResult<&[u8], SearchArgs<'_>> {
+    let (i, (returning, charset, mut keys)) = all_consuming(complete(tuple((
+        opt(delimited(
+            tag_no_case("RETURN ("),
+            separated_list(char(' '), search_return),
+            tag_no_case(") "),
+        )),
+        opt(delimited(
+            tag_no_case("CHARSET "),
+            map_res(astring, utf8),
+            char(' '),
+        )),
+        |i| search_keys(i, 0),
+    ))))(args)?;
+    let criteria = match keys.len() {
+        1 => keys.remove(0),
+        _ => SearchCriteria::And(keys),
+    };
+    Ok((
+        i,
+        SearchArgs {
+            returning,
+            charset,
+            criteria,
+        },
+    ))
+}
+
+// search-return-opt = "MIN" / "MAX" / "ALL" / "COUNT"
+fn search_return(i: &[u8]) -> IResult<&[u8], SearchReturn> {
+    alt((
+        map(tag_no_case("MIN"), |_| SearchReturn::Min),
+        map(tag_no_case("MAX"), |_| SearchReturn::Max),
+        map(tag_no_case("ALL"), |_| SearchReturn::All),
+        map(tag_no_case("COUNT"), |_| SearchReturn::Count),
+    ))(i)
+}
+
+fn search_keys(i: &[u8], depth: usize) -> IResult<&[u8], Vec<SearchCriteria<'_>>> {
+    separated_nonempty_list(char(' '), |i| search_key(i, depth))(i)
+}
+
+// search-key = "ALL" / "ANSWERED" / "BCC" SP astring / ... /
+//              sequence-set / "(" search-key *(SP search-key) ")"
+fn search_key(i: &[u8], depth: usize) -> IResult<&[u8], SearchCriteria<'_>> {
+    use SearchCriteria::*;
+    if depth > MAX_DEPTH {
+        return Err(nom::Err::Failure((i, ErrorKind::TooLarge)));
+    }
+    match i.first() {
+        Some(b'(') => {
+            return map(
+                delimited(char('('), |i| search_keys(i, depth + 1), char(')')),
+                And,
+            )(i)
+        }
+        Some(c) if c.is_ascii_digit() || *c == b'*' => return map(sequence_spec, SequenceSet)(i),
+        _ => {}
+    }
+
+    let (rest, name) = atom(i)?;
+    let key = |i| search_key(i, depth + 1);
+    let simple = |key| Ok((rest, key));
+    match name.to_ascii_uppercase().as_str() {
+        "ALL" => simple(All),
+        "ANSWERED" => simple(Answered),
+        "DELETED" => simple(Deleted),
+        "DRAFT" => simple(Draft),
+        "FLAGGED" => simple(Flagged),
+        "NEW" => simple(New),
+        "OLD" => simple(Old),
+        "RECENT" => simple(Recent),
+        "SEEN" => simple(Seen),
+        "UNANSWERED" => simple(Unanswered),
+        "UNDELETED" => simple(Undeleted),
+        "UNDRAFT" => simple(Undraft),
+        "UNFLAGGED" => simple(Unflagged),
+        "UNSEEN" => simple(Unseen),
+        "KEYWORD" => map(arg(atom), Keyword)(rest),
+        "UNKEYWORD" => map(arg(atom), Unkeyword)(rest),
+        "BCC" => map(arg(astring), Bcc)(rest),
+        "BODY" => map(arg(astring), Body)(rest),
+        "CC" => map(arg(astring), Cc)(rest),
+        "FROM" => map(arg(astring), From)(rest),
+        "SUBJECT" => map(arg(astring), Subject)(rest),
+        "TEXT" => map(arg(astring), Text)(rest),
+        "TO" => map(arg(astring), To)(rest),
+        "HEADER" => map(tuple((arg(astring), arg(astring))), |(name, value)| {
+            Header(name, value)
+        })(rest),
+        "BEFORE" => map(arg(date), Before)(rest),
+        "ON" => map(arg(date), On)(rest),
+        "SINCE" => map(arg(date), Since)(rest),
+        "SENTBEFORE" => map(arg(date), SentBefore)(rest),
+        "SENTON" => map(arg(date), SentOn)(rest),
+        "SENTSINCE" => map(arg(date), SentSince)(rest),
+        "LARGER" => map(arg(number), Larger)(rest),
+        "SMALLER" => map(arg(number), Smaller)(rest),
+        "UID" => map(arg(sequence_spec), Uid)(rest),
+        "NOT" => map(arg(key), |key| Not(Box::new(key)))(rest),
+        "OR" => map(tuple((arg(key), arg(key))), |(a, b)| {
+            Or(Box::new(a), Box::new(b))
+        })(rest),
+        _ => Err(nom::Err::Error((i, ErrorKind::Tag))),
+    }
+}
+
+// SP followed by an argument
+fn arg<'a, O>(
+    f: impl Fn(&'a [u8]) -> IResult<&'a [u8], O>,
+) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], O> {
+    preceded(char(' '), f)
+}
+
+// date      = date-text / DQUOTE date-text DQUOTE
+// date-text = date-day "-" date-month "-" date-year
+fn date(i: &[u8]) -> IResult<&[u8], Date> {
+    alt((delimited(char('"'), date_text, char('"')), date_text))(i)
+}
+
+fn date_text(i: &[u8]) -> IResult<&[u8], Date> {
+    map_opt(
+        tuple((
+            terminated(take_while_m_n(1, 2, |c: u8| c.is_ascii_digit()), char('-')),
+            terminated(
+                take_while_m_n(3, 3, |c: u8| c.is_ascii_alphabetic()),
+                char('-'),
+            ),
+            take_while_m_n(4, 4, |c: u8| c.is_ascii_digit()),
+        )),
+        |(day, month, year)| {
+            let year = str::from_utf8(year).ok()?.parse().ok()?;
+            let day = str::from_utf8(day).ok()?.parse().ok()?;
+            Date::new(year, Date::month(str::from_utf8(month).ok()?)?, day)
+        },
+    )(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_search;
+    use crate::builders::command::{Command, CommandBuilder};
+    use crate::types::*;
+
+    #[test]
+    fn search() {
+        let (_, search) = parse_search(
+            b"RETURN (MIN COUNT) CHARSET UTF-8 1:3,7:* uid 4 NOT (SEEN deleted) \
+              OR HEADER X-Mailer {4}\r\nmutt SENTSINCE \"1-Feb-1994\" LARGER 1000",
+        )
+        .unwrap();
+        assert_eq!(
+            search.returning,
+            Some(vec![SearchReturn::Min, SearchReturn::Count])
+        );
+        assert_eq!(search.charset.as_deref(), Some("UTF-8"));
+
+        let mut seqs = SequenceSpec::new();
+        seqs.push(SeqNumber::Value(1), SeqNumber::Value(3));
+        seqs.push(SeqNumber::Value(7), SeqNumber::Largest);
+        assert_eq!(seqs.resolve(8).to_string(), "1:3,7:8");
+        let uids = SequenceSpec::from(&SequenceSet::from(4..=4));
+        assert_eq!(
+            search.criteria,
+            SearchCriteria::And(vec![
+                SearchCriteria::SequenceSet(seqs),
+                SearchCriteria::Uid(uids),
+                SearchCriteria::Not(Box::new(SearchCriteria::And(vec![
+                    SearchCriteria::Seen,
+                    SearchCriteria::Deleted,
+                ]))),
+                SearchCriteria::Or(
+                    Box::new(SearchCriteria::Header(
+                        b"X-Mailer"[..].into(),
+                        b"mutt"[..].into()
+                    )),
+                    Box::new(SearchCriteria::SentSince(Date::new(1994, 2, 1).unwrap())),
+                ),
+                SearchCriteria::Larger(1000),
+            ])
+        );
+
+        let cmd = Command::from(CommandBuilder::search_criteria(&search.criteria));
+        assert_eq!(
+            cmd.args,
+            &b"SEARCH 1:3,7:* UID 4 NOT (SEEN DELETED) \
+               OR HEADER \"X-Mailer\" \"mutt\" SENTSINCE 1-Feb-1994 LARGER 1000"[..]
+        );
+        let (_, parsed) = parse_search(&cmd.args[7..]).unwrap();
+        assert_eq!(parsed.criteria, search.criteria);
+    }
+
+    #[test]
+    fn search_charset() {
+        let criteria = SearchCriteria::Subject("R\u{e9}sum\u{e9}".as_bytes().into());
+        let cmd = Command::from(CommandBuilder::uid_search_criteria(&criteria));
+        assert_eq!(
+            cmd.args,
+            &b"UID SEARCH CHARSET UTF-8 SUBJECT {8}\r\nR\xc3\xa9sum\xc3\xa9"[..]
+        );
+        let (_, search) = parse_search(&cmd.args[11..]).unwrap();
+        assert_eq!(search.criteria, criteria);
+    }
+
+    #[test]
+    fn search_invalid() {
+        for args in &[
+            &b""[..],
+            b"SEEN ",
+            b"UNKNOWN",
+            b"(SEEN",
+            b"FROM \"joe",
+            b"0:4",
+            b"BEFORE 1-Foo-1994",
+            b"BEFORE 32-Jan-1994",
+            b"LARGER",
+        ] {
+            assert!(parse_search(args).is_err(), "{:?}", args);
+        }
+        let nested = "NOT ".repeat(100) + "SEEN";
+        assert!(parse_search(nested.as_bytes()).is_err());
+        let nested = "NOT ".repeat(50) + "SEEN";
+        assert!(parse_search(nested.as_bytes()).is_ok());
+    }
+}
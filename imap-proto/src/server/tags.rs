@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::parser::rfc3501::is_tag_char;
+use crate::types::RequestId;
+
+/// Whether `tag` is valid as the tag of a command: one or more `ASTRING-CHAR`
+/// other than `+`.
+pub fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty() && tag.bytes().all(is_tag_char)
+}
+
+/// Replace the tag at the start of a command line or tagged response line.
+///
+/// Returns `None` if the line does not start with a valid tag followed by a
+/// space.
+pub fn rewrite_tag(line: &[u8], tag: &RequestId) -> Option<Vec<u8>> {
+    let end = line.iter().position(|&b| !is_tag_char(b))?;
+    if end == 0 || line[end] != b' ' {
+        return None;
+    }
+    let mut out = Vec::with_capacity(tag.as_bytes().len() + line.len() - end);
+    out.extend_from_slice(tag.as_bytes());
+    out.extend_from_slice(&line[end..]);
+    Some(out)
+}
+
+/// The tags of the commands in progress on a connection.
+///
+/// Clients must not reuse the tag of a command that has not completed yet, as
+/// the server could not tell which command a tagged response completes.
+#[derive(Clone, Debug, Default)]
+pub struct InFlightTags {
+    tags: HashSet<RequestId>,
+}
+
+impl InFlightTags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the command with `tag` started.
+    ///
+    /// Fails if the tag is invalid or already used by a command in progress.
+    pub fn insert(&mut self, tag: RequestId) -> Result<(), &'static str> {
+        if !is_valid_tag(tag.as_str()) {
+            return Err("invalid tag");
+        }
+        match self.tags.insert(tag) {
+            true => Ok(()),
+            false => Err("tag already in use"),
+        }
+    }
+
+    /// Record that the command with `tag` completed; returns whether it was in
+    /// progress.
+    pub fn remove(&mut self, tag: &RequestId) -> bool {
+        self.tags.remove(tag)
+    }
+
+    pub fn contains(&self, tag: &RequestId) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+}
+
+/// The tags of the commands a proxy forwards from several client connections
+/// onto one upstream connection.
+///
+/// Each command gets a tag unique on the upstream connection, mapped to the
+/// client connection (identified by `K`) and the tag it used there, so that
+/// the tagged response can be returned to the right client under its own tag:
+///
+/// ```
+/// use imap_proto::server::TagMap;
+/// use imap_proto::types::RequestId;
+///
+/// let mut tags = TagMap::new("P");
+/// let a = tags.insert(1, RequestId::from("A1")).unwrap();
+/// let b = tags.insert(2, RequestId::from("A1")).unwrap();
+/// assert_ne!(a, b);
+/// assert!(tags.insert(1, RequestId::from("A1")).is_err());
+/// assert_eq!(tags.complete(&b), Some((2, RequestId::from("A1"))));
+/// ```
+#[derive(Clone, Debug)]
+pub struct TagMap<K> {
+    prefix: String,
+    next: u64,
+    upstream: HashMap<RequestId, (K, RequestId)>,
+    client: HashMap<(K, RequestId), RequestId>,
+}
+
+impl<K: Clone + Eq + Hash> TagMap<K> {
+    /// A map generating upstream tags made of `prefix` and a counter.
+    ///
+    /// # Panics
+    ///
+    /// If the prefix is not valid in a tag.
+    pub fn new(prefix: &str) -> Self {
+        assert!(is_valid_tag(prefix), "invalid tag prefix");
+        Self {
+            prefix: prefix.to_owned(),
+            next: 0,
+            upstream: HashMap::new(),
+            client: HashMap::new(),
+        }
+    }
+
+    /// Map the command of `client` with `tag` to a new upstream tag.
+    ///
+    /// Fails if the tag is invalid or already used by a command of the same
+    /// client in progress.
+    pub fn insert(&mut self, client: K, tag: RequestId) -> Result<RequestId, &'static str> {
+        if !is_valid_tag(tag.as_str()) {
+            return Err("invalid tag");
+        }
+        let key = (client, tag);
+        if self.client.contains_key(&key) {
+            return Err("tag already in use");
+        }
+        self.next += 1;
+        let upstream = RequestId::new(&format!("{}{}", self.prefix, self.next));
+        self.upstream.insert(upstream.clone(), key.clone());
+        self.client.insert(key, upstream.clone());
+        Ok(upstream)
+    }
+
+    /// The upstream tag of the command of `client` with `tag`.
+    pub fn upstream(&self, client: &K, tag: &RequestId) -> Option<&RequestId> {
+        self.client.get(&(client.clone(), tag.clone()))
+    }
+
+    /// The client and its tag for the command with the `upstream` tag.
+    pub fn client(&self, upstream: &RequestId) -> Option<(&K, &RequestId)> {
+        self.upstream
+            .get(upstream)
+            .map(|(client, tag)| (client, tag))
+    }
+
+    /// Remove the mapping of the command with the `upstream` tag once its
+    /// tagged response arrived, returning the client and its tag.
+    pub fn complete(&mut self, upstream: &RequestId) -> Option<(K, RequestId)> {
+        let key = self.upstream.remove(upstream)?;
+        self.client.remove(&key);
+        Some(key)
+    }
+
+    /// Remove the mappings of the commands of `client`, such as when its
+    /// connection closed. The upstream tags of the removed commands are
+    /// returned, as their responses are still to be received.
+    pub fn remove_client(&mut self, client: &K) -> Vec<RequestId> {
+        let removed = self
+            .upstream
+            .iter()
+            .filter(|(_, (c, _))| c == client)
+            .map(|(upstream, _)| upstream.clone())
+            .collect::<Vec<_>>();
+        for upstream in &removed {
+            self.complete(upstream);
+        }
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.upstream.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.upstream.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        assert!(is_valid_tag("A001"));
+        assert!(is_valid_tag("a.b]c"));
+        assert!(!is_valid_tag(""));
+        assert!(!is_valid_tag("A+1"));
+        assert!(!is_valid_tag("A 1"));
+        assert!(!is_valid_tag("A{1"));
+
+        let mut tags = InFlightTags::new();
+        assert_eq!(tags.insert(RequestId::from("A1")), Ok(()));
+        assert!(tags.insert(RequestId::from("A1")).is_err());
+        assert!(tags.insert(RequestId::from("*")).is_err());
+        assert!(tags.remove(&RequestId::from("A1")));
+        assert_eq!(tags.insert(RequestId::from("A1")), Ok(()));
+    }
+
+    #[test]
+    fn rewrite() {
+        let tag = RequestId::from("P12");
+        assert_eq!(
+            rewrite_tag(b"a1 SELECT INBOX\r\n", &tag).unwrap(),
+            b"P12 SELECT INBOX\r\n"
+        );
+        assert_eq!(
+            rewrite_tag(b"P12 OK done\r\n", &RequestId::from("a1")).unwrap(),
+            b"a1 OK done\r\n"
+        );
+        assert_eq!(rewrite_tag(b"* OK hello\r\n", &tag), None);
+        assert_eq!(rewrite_tag(b"+ go ahead\r\n", &tag), None);
+        assert_eq!(rewrite_tag(b"A1\r\n", &tag), None);
+    }
+
+    #[test]
+    fn map() {
+        let mut tags = TagMap::new("P");
+        let a = tags.insert("alice", RequestId::from("1")).unwrap();
+        let b = tags.insert("bob", RequestId::from("1")).unwrap();
+        let c = tags.insert("alice", RequestId::from("2")).unwrap();
+        assert_eq!(a.as_str(), "P1");
+        assert_eq!(tags.upstream(&"bob", &RequestId::from("1")), Some(&b));
+        assert_eq!(tags.client(&c), Some((&"alice", &RequestId::from("2"))));
+        assert_eq!(
+            tags.insert("bob", RequestId::from("1")),
+            Err("tag already in use")
+        );
+
+        assert_eq!(tags.complete(&b), Some(("bob", RequestId::from("1"))));
+        assert_eq!(tags.complete(&b), None);
+        assert!(tags.insert("bob", RequestId::from("1")).is_ok());
+
+        let mut removed = tags.remove_client(&"alice");
+        removed.sort_by(|x, y| x.as_str().cmp(y.as_str()));
+        assert_eq!(removed, vec![a, c]);
+        assert_eq!(tags.len(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::iter::FromIterator;
+
+/// An access right on a mailbox (RFC 4314, section 2.1).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Right {
+    /// `l`: the mailbox is visible to `LIST` and `LSUB`.
+    Lookup,
+    /// `r`: select the mailbox, and read and search its messages.
+    Read,
+    /// `s`: keep the `\Seen` flag across sessions.
+    Seen,
+    /// `w`: set flags other than `\Seen` and `\Deleted`.
+    Write,
+    /// `i`: append and copy messages into the mailbox.
+    Insert,
+    /// `p`: send mail to the submission address of the mailbox.
+    Post,
+    /// `k`: create mailboxes below the mailbox.
+    CreateMailbox,
+    /// `x`: delete or rename the mailbox.
+    DeleteMailbox,
+    /// `t`: set or clear the `\Deleted` flag.
+    DeleteMessages,
+    /// `e`: expunge messages.
+    Expunge,
+    /// `a`: administer the access control list of the mailbox.
+    Administer,
+}
+
+impl Right {
+    pub fn as_char(self) -> char {
+        match self {
+            Right::Lookup => 'l',
+            Right::Read => 'r',
+            Right::Seen => 's',
+            Right::Write => 'w',
+            Right::Insert => 'i',
+            Right::Post => 'p',
+            Right::CreateMailbox => 'k',
+            Right::DeleteMailbox => 'x',
+            Right::DeleteMessages => 't',
+            Right::Expunge => 'e',
+            Right::Administer => 'a',
+        }
+    }
+}
+
+/// A set of access rights, as letters. Letters not defined by RFC 4314, such as
+/// the obsolete `c` and `d` rights or those of extensions, are kept as-is.
+///
+/// ```
+/// use imap_proto::types::{Right, Rights};
+///
+/// let rights: Rights = "lrswi".parse().unwrap();
+/// assert!(rights.contains(Right::Insert));
+/// assert!(!rights.contains(Right::Administer));
+/// assert_eq!(rights.to_string(), "ilrsw");
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Rights(BTreeSet<char>);
+
+impl Rights {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, right: Right) -> bool {
+        self.0.contains(&right.as_char())
+    }
+
+    pub fn insert(&mut self, right: Right) {
+        self.0.insert(right.as_char());
+    }
+
+    pub fn remove(&mut self, right: Right) {
+        self.0.remove(&right.as_char());
+    }
+
+    /// The letters of the rights, in alphabetical order.
+    pub fn letters(&self) -> impl Iterator<Item = char> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromIterator<Right> for Rights {
+    fn from_iter<I: IntoIterator<Item = Right>>(iter: I) -> Self {
+        Rights(iter.into_iter().map(Right::as_char).collect())
+    }
+}
+
+impl std::str::FromStr for Rights {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(Rights(s.chars().collect()))
+        } else {
+            Err("rights must be letters or digits")
+        }
+    }
+}
+
+impl fmt::Display for Rights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|c| write!(f, "{}", c))
+    }
+}
+
+/// An entry of an access control list: the rights granted to an identifier,
+/// such as a user name or `anyone`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AclEntry {
+    pub identifier: String,
+    pub rights: Rights,
+}
@@ -0,0 +1,447 @@
+use std::fmt;
+
+use super::{Address, Envelope};
+
+impl<'a> Address<'a> {
+    /// The display name, with MIME encoded-words (RFC 2047) decoded.
+    pub fn display_name(&self) -> Option<String> {
+        self.name.as_deref().map(decode_words)
+    }
+
+    /// The address in `mailbox@host` form, or `None` for group markers.
+    pub fn email(&self) -> Option<String> {
+        match (&self.mailbox, &self.host) {
+            (Some(mailbox), Some(host)) => Some(format!(
+                "{}@{}",
+                String::from_utf8_lossy(mailbox),
+                String::from_utf8_lossy(host)
+            )),
+            _ => None,
+        }
+    }
+
+    /// Whether this entry starts a group (RFC 3501, section 7.4.2), in which case
+    /// `mailbox` holds the group name.
+    pub fn is_group_start(&self) -> bool {
+        self.host.is_none() && self.mailbox.is_some()
+    }
+
+    /// Whether this entry ends a group.
+    pub fn is_group_end(&self) -> bool {
+        self.host.is_none() && self.mailbox.is_none()
+    }
+}
+
+/// Formats the address as in a message header (RFC 5322, section 3.4): for example
+/// `"Doe, John" <john@example.com>`, or `john@example.com` without a name.
+///
+/// The name is written as sent by the server, so that non-ASCII names remain
+/// encoded (RFC 2047) as required in headers. The alternate form (`{:#}`) decodes
+/// the name for display instead. Group markers are written as `name:` and `;`.
+impl<'a> fmt::Display for Address<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_group_end() {
+            return f.write_str(";");
+        } else if self.is_group_start() {
+            let name = String::from_utf8_lossy(self.mailbox.as_deref().unwrap_or_default());
+            return write!(f, "{}:", Phrase(&name));
+        }
+
+        let name = if f.alternate() {
+            self.display_name()
+        } else {
+            self.name
+                .as_deref()
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+        };
+        let mailbox = String::from_utf8_lossy(self.mailbox.as_deref().unwrap_or_default());
+        let host = String::from_utf8_lossy(self.host.as_deref().unwrap_or_default());
+        match name {
+            Some(name) if !name.trim().is_empty() => {
+                write!(f, "{} <{}@{}>", Phrase(&name), LocalPart(&mailbox), host)
+            }
+            _ => write!(f, "{}@{}", LocalPart(&mailbox), host),
+        }
+    }
+}
+
+/// Formats a list of addresses as in a message header, separated by commas
+/// and with groups written as `name: member, member;`.
+///
+/// Like `Address`, supports the alternate form (`{:#}`) to decode names.
+pub struct AddressList<'b, 'a>(pub &'b [Address<'a>]);
+
+impl<'b, 'a> fmt::Display for AddressList<'b, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut separator = "";
+        for addr in self.0 {
+            if addr.is_group_end() {
+                f.write_str(";")?;
+                separator = ", ";
+                continue;
+            }
+
+            f.write_str(separator)?;
+            if f.alternate() {
+                write!(f, "{:#}", addr)?;
+            } else {
+                write!(f, "{}", addr)?;
+            }
+            separator = if addr.is_group_start() { " " } else { ", " };
+        }
+        Ok(())
+    }
+}
+
+impl<'b, 'a> AddressList<'b, 'a> {
+    /// The entries of the list, with the addresses between group markers
+    /// collected into `AddressEntry::Group`.
+    pub fn entries(&self) -> Vec<AddressEntry<'b, 'a>> {
+        let mut entries = Vec::new();
+        let mut group = None;
+        for addr in self.0 {
+            if addr.is_group_start() {
+                entries.extend(group.take());
+                group = Some(AddressEntry::Group {
+                    name: addr.mailbox.as_deref().unwrap_or_default(),
+                    members: Vec::new(),
+                });
+            } else if addr.is_group_end() {
+                entries.extend(group.take());
+            } else if let Some(AddressEntry::Group { members, .. }) = &mut group {
+                members.push(addr);
+            } else {
+                entries.push(AddressEntry::Mailbox(addr));
+            }
+        }
+        entries.extend(group);
+        entries
+    }
+}
+
+/// An entry of an address list: a single address, or a group of addresses
+/// (RFC 5322, section 3.4) such as `undisclosed-recipients:;`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AddressEntry<'b, 'a> {
+    Mailbox(&'b Address<'a>),
+    Group {
+        name: &'b [u8],
+        members: Vec<&'b Address<'a>>,
+    },
+}
+
+/// A display name, quoted unless it consists of atoms (RFC 5322, section 3.2.5).
+struct Phrase<'s>(&'s str);
+
+impl<'s> fmt::Display for Phrase<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self
+            .0
+            .split(' ')
+            .all(|word| !word.is_empty() && word.chars().all(is_atext))
+        {
+            f.write_str(self.0)
+        } else {
+            quote(f, self.0)
+        }
+    }
+}
+
+/// The local part of an address, quoted unless it is a dot-atom.
+struct LocalPart<'s>(&'s str);
+
+impl<'s> fmt::Display for LocalPart<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self
+            .0
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+        {
+            f.write_str(self.0)
+        } else {
+            quote(f, self.0)
+        }
+    }
+}
+
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || !c.is_ascii() || "!#$%&'*+-/=?^_`{|}~".contains(c)
+}
+
+fn quote(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            f.write_str("\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    f.write_str("\"")
+}
+
+impl<'a> Envelope<'a> {
+    /// The subject, with MIME encoded-words (RFC 2047) decoded.
+    pub fn decoded_subject(&self) -> Option<String> {
+        self.subject.as_deref().map(decode_words)
+    }
+}
+
+/// Decode the MIME encoded-words (RFC 2047) in a header value.
+///
+/// Words in charsets other than UTF-8, US-ASCII and ISO-8859-1 are left as is.
+pub fn decode_words(raw: &[u8]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    let mut rest = &*text;
+    let mut out = String::with_capacity(rest.len());
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        match decode_word(candidate) {
+            Some((decoded, len)) => {
+                // Whitespace between adjacent encoded-words is not displayed
+                if !after_word || !before.trim().is_empty() {
+                    out.push_str(before);
+                }
+                out.push_str(&decoded);
+                rest = &candidate[len..];
+                after_word = true;
+            }
+            None => {
+                out.push_str(before);
+                out.push_str("=?");
+                rest = &candidate[2..];
+                after_word = false;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Decode the encoded-word at the start of `s`, returning the decoded text and
+/// the length of the word.
+fn decode_word(s: &str) -> Option<(String, usize)> {
+    let mut parts = s[2..].splitn(3, '?');
+    let charset = parts.next()?;
+    let encoding = parts.next()?;
+    let rest = parts.next()?;
+    let end = rest.find("?=")?;
+    let text = &rest[..end];
+    let len = 2 + charset.len() + 1 + encoding.len() + 1 + end + 2;
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let bytes = match encoding {
+        "B" | "b" => decode_base64(text)?,
+        "Q" | "q" => decode_q(text)?,
+        _ => return None,
+    };
+    Some((decode_charset(charset, &bytes)?, len))
+}
+
+/// Decode text in one of the supported charsets.
+pub(crate) fn decode_charset(charset: &str, bytes: &[u8]) -> Option<String> {
+    // Strip the language (RFC 2231, section 5)
+    let charset = charset.split('*').next().unwrap_or_default();
+    if charset.eq_ignore_ascii_case("utf-8") || charset.eq_ignore_ascii_case("us-ascii") {
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.iter().map(|&b| char::from(b)).collect())
+    } else {
+        None
+    }
+}
+
+fn decode_q(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'_' => out.push(b' '),
+            b'=' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b => out.push(b),
+        }
+    }
+    Some(out)
+}
+
+pub(crate) fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len() / 3 * 4 + 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub(crate) fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for b in text.bytes().take_while(|&b| b != b'=') {
+        let value = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{decode_words, encode_base64, AddressEntry, AddressList};
+    use crate::types::Address;
+
+    fn addr<'a>(
+        name: Option<&'a str>,
+        mailbox: Option<&'a str>,
+        host: Option<&'a str>,
+    ) -> Address<'a> {
+        Address {
+            name: name.map(|s| Cow::Borrowed(s.as_bytes())),
+            adl: None,
+            mailbox: mailbox.map(|s| Cow::Borrowed(s.as_bytes())),
+            host: host.map(|s| Cow::Borrowed(s.as_bytes())),
+        }
+    }
+
+    #[test]
+    fn encoded_words() {
+        assert_eq!(decode_words(b"plain text"), "plain text");
+        assert_eq!(
+            decode_words(b"=?UTF-8?B?SsO2cmc=?= =?utf-8?q?M=C3=BCller?="),
+            "JörgMüller"
+        );
+        assert_eq!(
+            decode_words(b"=?ISO-8859-1?Q?Andr=E9?= Pirard"),
+            "André Pirard"
+        );
+        assert_eq!(decode_words(b"=?UTF-8?Q?a_b?="), "a b");
+        assert_eq!(decode_words(b"=?KOI8-R?B?aGk=?= x"), "=?KOI8-R?B?aGk=?= x");
+        assert_eq!(decode_words(b"50% =? off"), "50% =? off");
+    }
+
+    #[test]
+    fn address() {
+        let addr = Address {
+            name: Some(Cow::Borrowed(b"=?utf-8?q?Dirkjan_Ochtman?=")),
+            adl: None,
+            mailbox: Some(Cow::Borrowed(b"dirkjan")),
+            host: Some(Cow::Borrowed(b"ochtman.nl")),
+        };
+        assert_eq!(addr.display_name().as_deref(), Some("Dirkjan Ochtman"));
+        assert_eq!(addr.email().as_deref(), Some("dirkjan@ochtman.nl"));
+
+        let group = Address {
+            name: None,
+            adl: None,
+            mailbox: Some(Cow::Borrowed(b"undisclosed-recipients")),
+            host: None,
+        };
+        assert!(group.is_group_start());
+        assert_eq!(group.email(), None);
+    }
+
+    #[test]
+    fn display() {
+        let john = addr(Some("Doe, John"), Some("john"), Some("example.com"));
+        assert_eq!(john.to_string(), "\"Doe, John\" <john@example.com>");
+        let bare = addr(None, Some("jane"), Some("example.com"));
+        assert_eq!(bare.to_string(), "jane@example.com");
+        let quoted = addr(
+            Some("Jane Q. \"Public\""),
+            Some("jane doe"),
+            Some("example.com"),
+        );
+        assert_eq!(
+            quoted.to_string(),
+            "\"Jane Q. \\\"Public\\\"\" <\"jane doe\"@example.com>"
+        );
+
+        let encoded = addr(
+            Some("=?utf-8?q?J=C3=B6rg?="),
+            Some("joerg"),
+            Some("example.com"),
+        );
+        assert_eq!(
+            encoded.to_string(),
+            "=?utf-8?q?J=C3=B6rg?= <joerg@example.com>"
+        );
+        assert_eq!(format!("{:#}", encoded), "Jörg <joerg@example.com>");
+
+        let list = [
+            john,
+            addr(None, Some("friends"), None),
+            bare,
+            encoded,
+            addr(None, None, None),
+            addr(None, Some("undisclosed-recipients"), None),
+            addr(None, None, None),
+        ];
+        assert_eq!(
+            format!("{:#}", AddressList(&list)),
+            "\"Doe, John\" <john@example.com>, friends: jane@example.com, \
+             Jörg <joerg@example.com>;, undisclosed-recipients:;"
+        );
+    }
+
+    #[test]
+    fn entries() {
+        let list = [
+            addr(None, Some("jane"), Some("example.com")),
+            addr(None, Some("friends"), None),
+            addr(None, Some("john"), Some("example.com")),
+            addr(None, None, None),
+            addr(None, Some("undisclosed-recipients"), None),
+            addr(None, None, None),
+        ];
+        assert_eq!(
+            AddressList(&list).entries(),
+            vec![
+                AddressEntry::Mailbox(&list[0]),
+                AddressEntry::Group {
+                    name: b"friends",
+                    members: vec![&list[2]],
+                },
+                AddressEntry::Group {
+                    name: b"undisclosed-recipients",
+                    members: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"h"), "aA==");
+        assert_eq!(encode_base64(b"he"), "aGU=");
+        assert_eq!(encode_base64(b"hel"), "aGVs");
+        assert_eq!(encode_base64(b"hello\r\n"), "aGVsbG8NCg==");
+    }
+}
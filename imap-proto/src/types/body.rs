@@ -0,0 +1,518 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use super::address::{decode_base64, decode_charset, decode_words};
+use super::{
+    BodyContentCommon, BodyContentSinglePart, BodyParams, BodyStructure, ContentEncoding,
+    ContentType, MessageSection, SectionPath,
+};
+
+impl<'a> BodyStructure<'a> {
+    pub fn common(&self) -> &BodyContentCommon<'a> {
+        match self {
+            BodyStructure::Basic { common, .. }
+            | BodyStructure::Text { common, .. }
+            | BodyStructure::Message { common, .. }
+            | BodyStructure::Multipart { common, .. } => common,
+        }
+    }
+
+    /// The fields of a non-multipart body, or `None` for a multipart body.
+    pub fn single_part(&self) -> Option<&BodyContentSinglePart<'a>> {
+        match self {
+            BodyStructure::Basic { other, .. }
+            | BodyStructure::Text { other, .. }
+            | BodyStructure::Message { other, .. } => Some(other),
+            BodyStructure::Multipart { .. } => None,
+        }
+    }
+
+    /// Iterate over this body and all its parts (depth-first, in order), with the
+    /// section of each part (RFC 3501, section 6.4.5).
+    ///
+    /// A multipart message itself has the section `TEXT`, and the body of an
+    /// attached multipart message has the section `<part>.TEXT`.
+    pub fn parts(&self) -> BodyParts<'_, 'a> {
+        BodyParts {
+            stack: vec![(root_section(self), self)],
+        }
+    }
+
+    /// The part with the given section, as found by `parts()`.
+    ///
+    /// Use this to check that a section to fetch (for example with
+    /// `Attribute::BodyPeekSection`) refers to the expected part.
+    pub fn part(&self, section: &SectionPath) -> Option<&BodyStructure<'a>> {
+        self.parts()
+            .find(|(found, _)| found == section)
+            .map(|(_, body)| body)
+    }
+
+    /// The `text/plain` body to display, preferring the last alternative of a
+    /// `multipart/alternative` part (RFC 2046, section 5.1.4).
+    ///
+    /// Attachments and attached messages are not considered.
+    pub fn text_body(&self) -> Option<(SectionPath, &BodyStructure<'a>)> {
+        find_body(self, root_section(self), "plain")
+    }
+
+    /// The `text/html` body to display, following the same rules as
+    /// `text_body()`.
+    pub fn html_body(&self) -> Option<(SectionPath, &BodyStructure<'a>)> {
+        find_body(self, root_section(self), "html")
+    }
+
+    /// The decoded file name of the part (RFC 2047 and RFC 2231), taken from the
+    /// disposition or else from the `name` parameter of the content type.
+    pub fn filename(&self) -> Option<String> {
+        filename(self.common())
+    }
+
+    /// The attachments, including attached messages (but not the parts of those).
+    ///
+    /// A part is considered an attachment if its disposition is `attachment`, or
+    /// if it has a file name and is neither plain text nor HTML.
+    pub fn attachments(&self) -> Vec<Attachment<'a>> {
+        let mut attachments = Vec::new();
+        collect_attachments(self, root_section(self), &mut attachments);
+        attachments
+    }
+}
+
+/// An attachment found by `BodyStructure::attachments()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Attachment<'a> {
+    pub section: SectionPath,
+    /// The decoded file name (RFC 2047 and RFC 2231), taken from the
+    /// disposition or else from the `name` parameter of the content type.
+    pub filename: Option<String>,
+    pub content_type: ContentType<'a>,
+    pub transfer_encoding: ContentEncoding<'a>,
+    /// The size in octets of the part in its transfer encoding.
+    pub size: u32,
+}
+
+/// Iterator returned by `BodyStructure::parts()`.
+pub struct BodyParts<'b, 'a> {
+    stack: Vec<(SectionPath, &'b BodyStructure<'a>)>,
+}
+
+impl<'b, 'a> Iterator for BodyParts<'b, 'a> {
+    type Item = (SectionPath, &'b BodyStructure<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (section, body) = self.stack.pop()?;
+        let children = children(&section, body);
+        self.stack.extend(children.into_iter().rev());
+        Some((section, body))
+    }
+}
+
+impl<'a> ContentType<'a> {
+    /// Whether this is the given type and subtype, compared case-insensitively.
+    pub fn is(&self, ty: &str, subtype: &str) -> bool {
+        self.ty.eq_ignore_ascii_case(ty) && self.subtype.eq_ignore_ascii_case(subtype)
+    }
+
+    /// The value of the given parameter, looked up case-insensitively.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        find_param(&self.params, name)
+    }
+}
+
+impl<'a> ContentEncoding<'a> {
+    /// Decode `data` from this transfer encoding (RFC 2045, section 6), or
+    /// return `None` if the encoding is unknown or `data` is not valid base64.
+    ///
+    /// ```
+    /// use imap_proto::types::ContentEncoding;
+    ///
+    /// let data = ContentEncoding::QuotedPrintable.decode(b"caf=C3=A9 =\r\nau lait");
+    /// assert_eq!(data.as_deref(), Some("café au lait".as_bytes()));
+    /// let data = ContentEncoding::Base64.decode(b"aGVs\r\nbG8=\r\n");
+    /// assert_eq!(data.as_deref(), Some(&b"hello"[..]));
+    /// ```
+    pub fn decode<'d>(&self, data: &'d [u8]) -> Option<Cow<'d, [u8]>> {
+        match self {
+            ContentEncoding::SevenBit | ContentEncoding::EightBit | ContentEncoding::Binary => {
+                Some(Cow::Borrowed(data))
+            }
+            ContentEncoding::Base64 => {
+                let text = data
+                    .iter()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .map(|&b| char::from(b))
+                    .collect::<String>();
+                decode_base64(&text).map(Cow::Owned)
+            }
+            ContentEncoding::QuotedPrintable => Some(Cow::Owned(decode_quoted_printable(data))),
+            ContentEncoding::Other(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for SectionPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = |text: &MessageSection| match text {
+            MessageSection::Header => "HEADER",
+            MessageSection::Mime => "MIME",
+            MessageSection::Text => "TEXT",
+        };
+        match self {
+            SectionPath::Full(section) => write!(f, "{}", text(section)),
+            SectionPath::Part(parts, section) => {
+                for (i, part) in parts.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    write!(f, "{}", part)?;
+                }
+                match section {
+                    Some(section) => write!(f, ".{}", text(section)),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+fn root_section(body: &BodyStructure<'_>) -> SectionPath {
+    match body {
+        BodyStructure::Multipart { .. } => SectionPath::Full(MessageSection::Text),
+        _ => SectionPath::Part(vec![1], None),
+    }
+}
+
+/// The parts of `body`, which has the given section, with their sections.
+fn children<'b, 'a>(
+    section: &SectionPath,
+    body: &'b BodyStructure<'a>,
+) -> Vec<(SectionPath, &'b BodyStructure<'a>)> {
+    let parts = match section {
+        SectionPath::Full(_) => &[][..],
+        SectionPath::Part(parts, _) => &parts[..],
+    };
+    match body {
+        BodyStructure::Multipart { bodies, .. } => bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let mut parts = parts.to_vec();
+                parts.push(i as u32 + 1);
+                (SectionPath::Part(parts, None), body)
+            })
+            .collect(),
+        BodyStructure::Message { body, .. } => {
+            let section = match **body {
+                BodyStructure::Multipart { .. } => {
+                    SectionPath::Part(parts.to_vec(), Some(MessageSection::Text))
+                }
+                _ => {
+                    let mut parts = parts.to_vec();
+                    parts.push(1);
+                    SectionPath::Part(parts, None)
+                }
+            };
+            vec![(section, &**body)]
+        }
+        _ => vec![],
+    }
+}
+
+fn find_body<'b, 'a>(
+    body: &'b BodyStructure<'a>,
+    section: SectionPath,
+    subtype: &str,
+) -> Option<(SectionPath, &'b BodyStructure<'a>)> {
+    match body {
+        BodyStructure::Multipart { common, .. } => {
+            let children = children(&section, body);
+            let mut children = children.into_iter();
+            if common.ty.subtype.eq_ignore_ascii_case("alternative") {
+                children
+                    .rev()
+                    .find_map(|(section, body)| find_body(body, section, subtype))
+            } else {
+                children.find_map(|(section, body)| find_body(body, section, subtype))
+            }
+        }
+        BodyStructure::Text { common, .. }
+            if common.ty.is("text", subtype) && !is_attachment(common) =>
+        {
+            Some((section, body))
+        }
+        _ => None,
+    }
+}
+
+fn collect_attachments<'a>(
+    body: &BodyStructure<'a>,
+    section: SectionPath,
+    attachments: &mut Vec<Attachment<'a>>,
+) {
+    let (common, other) = match body {
+        BodyStructure::Multipart { .. } => {
+            for (section, body) in children(&section, body) {
+                collect_attachments(body, section, attachments);
+            }
+            return;
+        }
+        BodyStructure::Basic { common, other, .. }
+        | BodyStructure::Text { common, other, .. }
+        | BodyStructure::Message { common, other, .. } => (common, other),
+    };
+
+    let filename = filename(common);
+    let attached = is_attachment(common)
+        || common.ty.is("message", "rfc822")
+        || (filename.is_some() && !common.ty.is("text", "plain") && !common.ty.is("text", "html"));
+    if attached {
+        attachments.push(Attachment {
+            section,
+            filename,
+            content_type: common.ty.clone(),
+            transfer_encoding: other.transfer_encoding.clone(),
+            size: other.octets,
+        });
+    }
+}
+
+fn is_attachment(common: &BodyContentCommon<'_>) -> bool {
+    match &common.disposition {
+        Some(disposition) => disposition.ty.eq_ignore_ascii_case("attachment"),
+        None => false,
+    }
+}
+
+fn filename(common: &BodyContentCommon<'_>) -> Option<String> {
+    let disposition = common.disposition.as_ref();
+    disposition
+        .and_then(|disposition| extended_param(&disposition.params, "filename"))
+        .or_else(|| extended_param(&common.ty.params, "name"))
+}
+
+fn find_param<'p>(params: &'p BodyParams<'_>, name: &str) -> Option<&'p str> {
+    params
+        .iter()
+        .flatten()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| &**value)
+}
+
+/// Look up a parameter, which may be encoded and split in several sections
+/// (RFC 2231), or contain encoded-words (RFC 2047).
+fn extended_param(params: &BodyParams<'_>, name: &str) -> Option<String> {
+    if let Some(value) = find_param(params, &format!("{}*", name)) {
+        return decode_extended(value);
+    }
+
+    let mut value = String::new();
+    let mut charset = None;
+    for i in 0.. {
+        if let Some(section) = find_param(params, &format!("{}*{}*", name, i)) {
+            if i == 0 {
+                let mut parts = section.splitn(3, '\'');
+                charset = parts.next();
+                let _language = parts.next();
+                value.push_str(parts.next().unwrap_or_default());
+            } else {
+                value.push_str(section);
+            }
+        } else if let Some(section) = find_param(params, &format!("{}*{}", name, i)) {
+            value.push_str(section);
+        } else {
+            break;
+        }
+    }
+    match (value.is_empty(), charset) {
+        (true, _) => find_param(params, name).map(|value| decode_words(value.as_bytes())),
+        (false, Some(charset)) => decode_charset(charset, &percent_decode(&value)),
+        (false, None) => Some(value),
+    }
+}
+
+/// Decode a parameter value of the form `charset'language'value` (RFC 2231).
+fn decode_extended(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let (charset, _language, value) = (parts.next()?, parts.next()?, parts.next()?);
+    decode_charset(charset, &percent_decode(value))
+}
+
+/// Decode quoted-printable data (RFC 2045, section 6.7), leaving invalid
+/// escapes as they are.
+fn decode_quoted_printable(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != b'=' {
+            out.push(data[i]);
+            i += 1;
+            continue;
+        }
+
+        // A soft line break, possibly after trailing whitespace
+        let rest = &data[i + 1..];
+        let blank = rest
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        if rest[blank..].starts_with(b"\r\n") {
+            i += 1 + blank + 2;
+            continue;
+        } else if rest[blank..].starts_with(b"\n") {
+            i += 1 + blank + 1;
+            continue;
+        }
+
+        let hex = rest.get(..2).and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(b'=');
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(s.len());
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(b) if bytes[i] == b'%' => {
+                out.push(b);
+                i += 3;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_response;
+    use crate::types::*;
+
+    const RESPONSE: &[u8] = b"* 1 FETCH (BODYSTRUCTURE (\
+        ((\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 12 1 NIL NIL NIL NIL)\
+        (\"TEXT\" \"HTML\" (\"CHARSET\" \"utf-8\") NIL NIL \"QUOTED-PRINTABLE\" 40 2 NIL NIL NIL NIL) \
+        \"ALTERNATIVE\" (\"BOUNDARY\" \"b2\") NIL NIL NIL)\
+        (\"APPLICATION\" \"PDF\" (\"NAME\" \"=?utf-8?q?R=C3=A9sum=C3=A9.pdf?=\") NIL NIL \"BASE64\" 4000 \
+        NIL (\"ATTACHMENT\" (\"FILENAME*\" \"utf-8''R%C3%A9sum%C3%A9.pdf\")) NIL NIL)\
+        (\"MESSAGE\" \"RFC822\" NIL NIL NIL \"7BIT\" 500 \
+        (NIL \"Fwd\" ((NIL NIL \"a\" \"example.com\")) NIL NIL NIL NIL NIL NIL NIL) \
+        (\"TEXT\" \"PLAIN\" (\"CHARSET\" \"us-ascii\") NIL NIL \"7BIT\" 20 1 NIL NIL NIL NIL) \
+        10 NIL NIL NIL NIL) \
+        \"MIXED\" (\"BOUNDARY\" \"b1\") NIL NIL NIL))\r\n";
+
+    fn with_body<F: FnOnce(&BodyStructure)>(f: F) {
+        match parse_response(RESPONSE) {
+            Ok((_, Response::Fetch(_, attrs))) => match &attrs[0] {
+                AttributeValue::BodyStructure(body) => f(body),
+                attr => panic!("unexpected attribute: {:?}", attr),
+            },
+            rsp => panic!("unexpected response: {:?}", rsp),
+        }
+    }
+
+    fn part(parts: &[u32]) -> SectionPath {
+        SectionPath::Part(parts.to_vec(), None)
+    }
+
+    #[test]
+    fn parts() {
+        with_body(|body| {
+            let sections = body.parts().map(|(section, _)| section).collect::<Vec<_>>();
+            assert_eq!(
+                sections,
+                vec![
+                    SectionPath::Full(MessageSection::Text),
+                    part(&[1]),
+                    part(&[1, 1]),
+                    part(&[1, 2]),
+                    part(&[2]),
+                    part(&[3]),
+                    part(&[3, 1]),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn text_and_html() {
+        with_body(|body| {
+            let (section, text) = body.text_body().unwrap();
+            assert_eq!(section, part(&[1, 1]));
+            assert_eq!(text.single_part().unwrap().octets, 12);
+            let (section, html) = body.html_body().unwrap();
+            assert_eq!(section, part(&[1, 2]));
+            assert!(html.common().ty.is("text", "html"));
+        });
+    }
+
+    #[test]
+    fn section() {
+        assert_eq!(part(&[1, 2]).to_string(), "1.2");
+        assert_eq!(
+            SectionPath::Part(vec![3], Some(MessageSection::Text)).to_string(),
+            "3.TEXT"
+        );
+        assert_eq!(SectionPath::Full(MessageSection::Text).to_string(), "TEXT");
+
+        with_body(|body| {
+            let (section, _) = body.html_body().unwrap();
+            let html = body.part(&section).unwrap();
+            assert!(html.common().ty.is("text", "html"));
+            assert!(body.part(&part(&[1, 3])).is_none());
+        });
+    }
+
+    #[test]
+    fn attachments() {
+        with_body(|body| {
+            let attachments = body.attachments();
+            assert_eq!(attachments.len(), 2);
+            assert_eq!(attachments[0].section, part(&[2]));
+            assert_eq!(attachments[0].filename.as_deref(), Some("Résumé.pdf"));
+            assert_eq!(attachments[0].size, 4000);
+            assert_eq!(attachments[0].transfer_encoding, ContentEncoding::Base64);
+            assert!(attachments[1].content_type.is("message", "rfc822"));
+            assert_eq!(attachments[1].section, part(&[3]));
+        });
+    }
+
+    #[test]
+    fn decode_transfer_encoding() {
+        let qp = ContentEncoding::QuotedPrintable;
+        assert_eq!(
+            qp.decode(b"a=3Db =  \r\nc=\nd =XY=").as_deref(),
+            Some(&b"a=b cd =XY="[..])
+        );
+        assert_eq!(
+            ContentEncoding::Base64.decode(b"aGk=").as_deref(),
+            Some(&b"hi"[..])
+        );
+        assert_eq!(ContentEncoding::Base64.decode(b"a?Gk="), None);
+        assert_eq!(
+            ContentEncoding::Other("x-uuencode".into()).decode(b"x"),
+            None
+        );
+        with_body(|body| {
+            let attachment = body.part(&part(&[2])).unwrap();
+            assert_eq!(attachment.filename().as_deref(), Some("Résumé.pdf"));
+        });
+    }
+}
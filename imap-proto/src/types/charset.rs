@@ -0,0 +1,45 @@
+use std::borrow::Cow;
+use std::ops::Deref;
+
+/// The charsets a server supports for `SEARCH`, as listed in a `BADCHARSET`
+/// response code (RFC 3501, section 7.1).
+///
+/// ```
+/// use std::borrow::Cow;
+/// use imap_proto::types::Charsets;
+///
+/// let charsets = Charsets(vec![Cow::Borrowed("US-ASCII"), Cow::Borrowed("ISO-8859-1")]);
+/// assert!(charsets.contains("iso-8859-1"));
+/// assert_eq!(charsets.select(&["UTF-8", "ISO-8859-1"]), Some("ISO-8859-1"));
+/// assert_eq!(charsets.select(&["UTF-8"]), None);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct Charsets<'a>(pub Vec<Cow<'a, str>>);
+
+impl<'a> Charsets<'a> {
+    /// Whether the server supports `charset` (compared case-insensitively).
+    pub fn contains(&self, charset: &str) -> bool {
+        self.0.iter().any(|c| c.eq_ignore_ascii_case(charset))
+    }
+
+    /// The first of the charsets `supported` by the client that the server
+    /// supports too, to retry the search with, as spelled by the server.
+    pub fn select(&self, supported: &[&str]) -> Option<&str> {
+        supported.iter().find_map(|charset| {
+            self.0
+                .iter()
+                .find(|c| c.eq_ignore_ascii_case(charset))
+                .map(|c| &**c)
+        })
+    }
+}
+
+impl<'a> Deref for Charsets<'a> {
+    type Target = [Cow<'a, str>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
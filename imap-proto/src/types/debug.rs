@@ -0,0 +1,119 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::AttributeValue;
+
+static FULL_DEBUG: AtomicBool = AtomicBool::new(false);
+
+/// Include message content in `Debug` output.
+///
+/// By default, the message data in fetch responses (such as `BODY[]` or
+/// `RFC822`) is summarized by its length and a hash, to keep logs readable and
+/// free of mail content. Enabling this affects the whole process, and is meant
+/// for debugging the parser.
+pub fn set_full_debug(enabled: bool) {
+    FULL_DEBUG.store(enabled, Ordering::Relaxed);
+}
+
+/// Bytes that may contain message content, formatted according to
+/// `set_full_debug()`: either as `<7 bytes, hash 86dc7588>` or as a byte string.
+///
+/// The hash (32-bit FNV-1a) allows recognizing identical content across logs.
+pub struct Content<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Debug for Content<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !FULL_DEBUG.load(Ordering::Relaxed) {
+            return write!(f, "<{} bytes, hash {:08x}>", self.0.len(), fnv1a(self.0));
+        }
+
+        f.write_str("b\"")?;
+        for &b in self.0 {
+            write!(f, "{}", std::ascii::escape_default(b))?;
+        }
+        f.write_str("\"")
+    }
+}
+
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, &b| {
+        (hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+    })
+}
+
+impl<'a> fmt::Debug for AttributeValue<'a> {
+    fn fmt<'b>(&'b self, f: &mut fmt::Formatter) -> fmt::Result {
+        let content = |data: &'b Option<Cow<'a, [u8]>>| data.as_deref().map(Content);
+        match self {
+            AttributeValue::BodySection {
+                section,
+                index,
+                data,
+            } => f
+                .debug_struct("BodySection")
+                .field("section", section)
+                .field("index", index)
+                .field("data", &content(data))
+                .finish(),
+            AttributeValue::BodyStructure(body) => {
+                f.debug_tuple("BodyStructure").field(body).finish()
+            }
+            AttributeValue::Envelope(envelope) => {
+                f.debug_tuple("Envelope").field(envelope).finish()
+            }
+            AttributeValue::Flags(flags) => f.debug_tuple("Flags").field(flags).finish(),
+            AttributeValue::InternalDate(date) => {
+                f.debug_tuple("InternalDate").field(date).finish()
+            }
+            AttributeValue::ModSeq(seq) => f.debug_tuple("ModSeq").field(seq).finish(),
+            AttributeValue::Rfc822(data) => f.debug_tuple("Rfc822").field(&content(data)).finish(),
+            AttributeValue::Rfc822Header(data) => {
+                f.debug_tuple("Rfc822Header").field(&content(data)).finish()
+            }
+            AttributeValue::Rfc822Size(size) => f.debug_tuple("Rfc822Size").field(size).finish(),
+            AttributeValue::Rfc822Text(data) => {
+                f.debug_tuple("Rfc822Text").field(&content(data)).finish()
+            }
+            AttributeValue::Uid(uid) => f.debug_tuple("Uid").field(uid).finish(),
+            AttributeValue::Unknown { name, raw } => f
+                .debug_struct("Unknown")
+                .field("name", name)
+                .field("raw", &Content(raw))
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::set_full_debug;
+    use crate::types::AttributeValue;
+
+    #[test]
+    fn redact_content() {
+        let attr = AttributeValue::BodySection {
+            section: None,
+            index: None,
+            data: Some(Cow::Borrowed(b"hello\r\n")),
+        };
+        assert_eq!(
+            format!("{:?}", attr),
+            "BodySection { section: None, index: None, data: Some(<7 bytes, hash 86dc7588>) }"
+        );
+        assert_eq!(
+            format!("{:?}", AttributeValue::Rfc822Size(7)),
+            "Rfc822Size(7)"
+        );
+
+        set_full_debug(true);
+        let full = format!(
+            "{:?}",
+            AttributeValue::Rfc822(Some(Cow::Borrowed(b"hello\r\n")))
+        );
+        set_full_debug(false);
+        assert_eq!(full, "Rfc822(Some(b\"hello\\r\\n\"))");
+    }
+}
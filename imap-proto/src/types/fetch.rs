@@ -0,0 +1,424 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::{
+    AttrMacro, AttributeValue, BodyStructure, Envelope, FlagList, MessageSection, Response,
+    SectionPath,
+};
+
+/// The data items requested by a `FETCH` command, as parsed by servers with
+/// `server::parse_fetch()`. The `Display` implementation writes them back in
+/// the syntax of the command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FetchItems<'a> {
+    Macro(AttrMacro),
+    /// A single item, or a parenthesized list.
+    Items(Vec<FetchItem<'a>>),
+}
+
+impl<'a> FetchItems<'a> {
+    /// The requested items, with macros expanded.
+    pub fn items(&self) -> Vec<FetchItem<'a>> {
+        use FetchItem::*;
+        match self {
+            FetchItems::Macro(AttrMacro::Fast) => vec![Flags, InternalDate, Rfc822Size],
+            FetchItems::Macro(AttrMacro::All) => vec![Flags, InternalDate, Rfc822Size, Envelope],
+            FetchItems::Macro(AttrMacro::Full) => {
+                vec![Flags, InternalDate, Rfc822Size, Envelope, Body]
+            }
+            FetchItems::Items(items) => items.clone(),
+        }
+    }
+
+    /// Whether fetching the items sets the `\Seen` flag of the messages.
+    pub fn sets_seen(&self) -> bool {
+        match self {
+            FetchItems::Macro(_) => false,
+            FetchItems::Items(items) => items.iter().any(|item| match item {
+                FetchItem::Rfc822 | FetchItem::Rfc822Text => true,
+                FetchItem::BodySection { peek, .. } => !peek,
+                _ => false,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for FetchItems<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchItems::Macro(AttrMacro::All) => f.write_str("ALL"),
+            FetchItems::Macro(AttrMacro::Fast) => f.write_str("FAST"),
+            FetchItems::Macro(AttrMacro::Full) => f.write_str("FULL"),
+            FetchItems::Items(items) if items.len() == 1 => write!(f, "{}", items[0]),
+            FetchItems::Items(items) => {
+                f.write_str("(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// A data item requested by a `FETCH` command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FetchItem<'a> {
+    /// `BODY`: the body structure without extension data.
+    Body,
+    BodyStructure,
+    Envelope,
+    Flags,
+    InternalDate,
+    ModSeq, // RFC 7162
+    Rfc822,
+    Rfc822Header,
+    Rfc822Size,
+    Rfc822Text,
+    Uid,
+    /// `BODY[section]<origin.length>`, or `BODY.PEEK[...]` which does not set
+    /// the `\Seen` flag.
+    BodySection {
+        section: FetchSection<'a>,
+        /// The origin and length of the requested part of the section.
+        partial: Option<(u32, u32)>,
+        peek: bool,
+    },
+    /// An item defined by an extension, such as `BINARY[1]` (RFC 3516), as
+    /// sent.
+    Extension(&'a str),
+}
+
+impl fmt::Display for FetchItem<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FetchItem::Body => "BODY",
+            FetchItem::BodyStructure => "BODYSTRUCTURE",
+            FetchItem::Envelope => "ENVELOPE",
+            FetchItem::Flags => "FLAGS",
+            FetchItem::InternalDate => "INTERNALDATE",
+            FetchItem::ModSeq => "MODSEQ",
+            FetchItem::Rfc822 => "RFC822",
+            FetchItem::Rfc822Header => "RFC822.HEADER",
+            FetchItem::Rfc822Size => "RFC822.SIZE",
+            FetchItem::Rfc822Text => "RFC822.TEXT",
+            FetchItem::Uid => "UID",
+            FetchItem::BodySection {
+                section,
+                partial,
+                peek,
+            } => {
+                let name = if *peek { "BODY.PEEK" } else { "BODY" };
+                write!(f, "{}[{}]", name, section)?;
+                if let Some((origin, len)) = partial {
+                    write!(f, "<{}.{}>", origin, len)?;
+                }
+                return Ok(());
+            }
+            FetchItem::Extension(raw) => raw,
+        };
+        f.write_str(name)
+    }
+}
+
+/// The section of a `BODY[section]` item: the part of the message (all of it
+/// if `part` is empty), and the text of the part requested.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FetchSection<'a> {
+    pub part: Vec<u32>,
+    pub text: Option<SectionText<'a>>,
+}
+
+impl fmt::Display for FetchSection<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, num) in self.part.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", num)?;
+        }
+        if let Some(text) = &self.text {
+            if !self.part.is_empty() {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", text)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SectionText<'a> {
+    Header,
+    /// `HEADER.FIELDS (...)`, or `HEADER.FIELDS.NOT (...)` if `not`.
+    HeaderFields {
+        not: bool,
+        fields: Vec<Cow<'a, str>>,
+    },
+    Text,
+    /// The MIME header of a part; not valid for the whole message.
+    Mime,
+}
+
+impl fmt::Display for SectionText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SectionText::Header => f.write_str("HEADER"),
+            SectionText::HeaderFields { not, fields } => {
+                f.write_str(if *not {
+                    "HEADER.FIELDS.NOT ("
+                } else {
+                    "HEADER.FIELDS ("
+                })?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    f.write_str(field)?;
+                }
+                f.write_str(")")
+            }
+            SectionText::Text => f.write_str("TEXT"),
+            SectionText::Mime => f.write_str("MIME"),
+        }
+    }
+}
+
+/// The attributes of a message sent in `FETCH` responses, by name.
+///
+/// ```
+/// use imap_proto::types::{FetchedMessage, Response};
+///
+/// let (_, rsp) = Response::from_bytes(b"* 3 FETCH (UID 8 FLAGS (\\Seen) BODY[] {2}\r\nhi)\r\n")
+///     .unwrap();
+/// let msg = FetchedMessage::from_response(&rsp).unwrap();
+/// assert_eq!(msg.uid, Some(8));
+/// assert_eq!(msg.flags.as_deref(), Some(&["\\Seen"][..]));
+/// assert_eq!(msg.body(), Some(&b"hi"[..]));
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FetchedMessage<'a> {
+    pub seq: u32,
+    pub uid: Option<u32>,
+    pub flags: Option<FlagList<'a>>,
+    pub envelope: Option<Box<Envelope<'a>>>,
+    pub internal_date: Option<Cow<'a, str>>,
+    /// `RFC822.SIZE`
+    pub size: Option<u32>,
+    pub mod_seq: Option<u64>,
+    pub body_structure: Option<BodyStructure<'a>>,
+    /// The content of the sections received, with `None` for the whole
+    /// message (`BODY[]`). `RFC822`, `RFC822.HEADER` and `RFC822.TEXT` are
+    /// stored as the equivalent sections. Partial content is stored under the
+    /// section it is part of, and sections sent as `NIL` are left out.
+    pub sections: HashMap<Option<SectionPath>, Cow<'a, [u8]>>,
+    /// Attributes the parser does not know, with their value as sent.
+    pub unknown: Vec<(&'a str, &'a [u8])>,
+}
+
+impl<'a> FetchedMessage<'a> {
+    /// The message in a `FETCH` response, or `None` for other responses.
+    pub fn from_response(rsp: &Response<'a>) -> Option<Self> {
+        match rsp {
+            Response::Fetch(seq, attrs) => Some(Self::from_attributes(*seq, attrs)),
+            _ => None,
+        }
+    }
+
+    pub fn from_attributes(seq: u32, attrs: &[AttributeValue<'a>]) -> Self {
+        let mut msg = Self {
+            seq,
+            ..Self::default()
+        };
+        msg.update(attrs);
+        msg
+    }
+
+    /// Add the attributes of another `FETCH` response for the same message,
+    /// replacing those received before.
+    pub fn update(&mut self, attrs: &[AttributeValue<'a>]) {
+        for attr in attrs {
+            match attr {
+                AttributeValue::BodySection { section, data, .. } => {
+                    self.set_section(section.clone(), data)
+                }
+                AttributeValue::BodyStructure(body) => self.body_structure = Some(body.clone()),
+                AttributeValue::Envelope(envelope) => self.envelope = Some(envelope.clone()),
+                AttributeValue::Flags(flags) => self.flags = Some(flags.clone()),
+                AttributeValue::InternalDate(date) => self.internal_date = Some(date.clone()),
+                AttributeValue::ModSeq(mod_seq) => self.mod_seq = Some(*mod_seq),
+                AttributeValue::Rfc822(data) => self.set_section(None, data),
+                AttributeValue::Rfc822Header(data) => {
+                    self.set_section(Some(SectionPath::Full(MessageSection::Header)), data)
+                }
+                AttributeValue::Rfc822Size(size) => self.size = Some(*size),
+                AttributeValue::Rfc822Text(data) => {
+                    self.set_section(Some(SectionPath::Full(MessageSection::Text)), data)
+                }
+                AttributeValue::Uid(uid) => self.uid = Some(*uid),
+                AttributeValue::Unknown { name, raw } => self.unknown.push((name, raw)),
+            }
+        }
+    }
+
+    fn set_section(&mut self, section: Option<SectionPath>, data: &Option<Cow<'a, [u8]>>) {
+        if let Some(data) = data {
+            self.sections.insert(section, data.clone());
+        }
+    }
+
+    /// Collect the messages in `FETCH` responses by UID, merging the responses
+    /// for the same message. Messages without UID are left out.
+    pub fn by_uid<'r>(
+        responses: impl IntoIterator<Item = &'r Response<'a>>,
+    ) -> BTreeMap<u32, FetchedMessage<'a>>
+    where
+        'a: 'r,
+    {
+        let mut messages = BTreeMap::<u32, FetchedMessage<'a>>::new();
+        for rsp in responses {
+            let (seq, attrs) = match rsp {
+                Response::Fetch(seq, attrs) => (*seq, attrs),
+                _ => continue,
+            };
+            let uid = attrs.iter().find_map(|attr| match attr {
+                AttributeValue::Uid(uid) => Some(*uid),
+                _ => None,
+            });
+            if let Some(uid) = uid {
+                let msg = messages.entry(uid).or_default();
+                msg.seq = seq;
+                msg.update(attrs);
+            }
+        }
+        messages
+    }
+
+    /// The content of `section`, or of the whole message for `None`.
+    pub fn section(&self, section: Option<&SectionPath>) -> Option<&[u8]> {
+        self.sections.get(&section.cloned()).map(|data| &data[..])
+    }
+
+    /// The whole message (`BODY[]` or `RFC822`).
+    pub fn body(&self) -> Option<&[u8]> {
+        self.section(None)
+    }
+}
+
+/// The kind of an attribute in a `FETCH` response, to look it up with
+/// `Response::attribute()` whatever its position.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum AttributeKind {
+    BodySection,
+    BodyStructure,
+    Envelope,
+    Flags,
+    InternalDate,
+    ModSeq,
+    Rfc822,
+    Rfc822Header,
+    Rfc822Size,
+    Rfc822Text,
+    Uid,
+    Unknown,
+}
+
+impl AttributeValue<'_> {
+    pub fn kind(&self) -> AttributeKind {
+        match self {
+            AttributeValue::BodySection { .. } => AttributeKind::BodySection,
+            AttributeValue::BodyStructure(_) => AttributeKind::BodyStructure,
+            AttributeValue::Envelope(_) => AttributeKind::Envelope,
+            AttributeValue::Flags(_) => AttributeKind::Flags,
+            AttributeValue::InternalDate(_) => AttributeKind::InternalDate,
+            AttributeValue::ModSeq(_) => AttributeKind::ModSeq,
+            AttributeValue::Rfc822(_) => AttributeKind::Rfc822,
+            AttributeValue::Rfc822Header(_) => AttributeKind::Rfc822Header,
+            AttributeValue::Rfc822Size(_) => AttributeKind::Rfc822Size,
+            AttributeValue::Rfc822Text(_) => AttributeKind::Rfc822Text,
+            AttributeValue::Uid(_) => AttributeKind::Uid,
+            AttributeValue::Unknown { .. } => AttributeKind::Unknown,
+        }
+    }
+}
+
+impl<'a> Response<'a> {
+    /// The attribute of kind `kind` in a `FETCH` response, or `None` if there
+    /// is none or this is another response. Servers order the attributes
+    /// differently, and some repeat them; the last one sent is returned, as
+    /// in `FetchedMessage`.
+    pub fn attribute(&self, kind: AttributeKind) -> Option<&AttributeValue<'a>> {
+        self.attributes(kind).last()
+    }
+
+    /// All the attributes of kind `kind` in a `FETCH` response, in the order
+    /// they were sent.
+    pub fn attributes(&self, kind: AttributeKind) -> impl Iterator<Item = &AttributeValue<'a>> {
+        let attrs = match self {
+            Response::Fetch(_, attrs) => &attrs[..],
+            _ => &[],
+        };
+        attrs.iter().filter(move |attr| attr.kind() == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttributeKind, FetchedMessage};
+    use crate::types::{AttributeValue, MessageSection, Response, SectionPath};
+
+    #[test]
+    fn attribute_by_kind() {
+        let (_, rsp) =
+            Response::from_bytes(b"* 1 fetch (flags (\\Seen) uid 4 Rfc822.Size 12 UID 4)\r\n")
+                .unwrap();
+        assert_eq!(
+            rsp.attribute(AttributeKind::Uid),
+            Some(&AttributeValue::Uid(4))
+        );
+        assert_eq!(rsp.attributes(AttributeKind::Uid).count(), 2);
+        assert_eq!(
+            rsp.attribute(AttributeKind::Rfc822Size),
+            Some(&AttributeValue::Rfc822Size(12))
+        );
+        assert_eq!(rsp.attribute(AttributeKind::Envelope), None);
+
+        let (_, rsp) = Response::from_bytes(b"* 3 EXISTS\r\n").unwrap();
+        assert_eq!(rsp.attribute(AttributeKind::Uid), None);
+    }
+
+    #[test]
+    fn by_uid() {
+        let input: &[&[u8]] = &[
+            b"* 1 FETCH (UID 4 RFC822.SIZE 12)\r\n",
+            b"* 2 FETCH (FLAGS (\\Seen))\r\n",
+            b"* 1 FETCH (RFC822.HEADER {2}\r\nhi UID 4)\r\n",
+            b"* 3 EXISTS\r\n",
+        ];
+        let responses = input
+            .iter()
+            .map(|buf| Response::from_bytes(buf).unwrap().1)
+            .collect::<Vec<_>>();
+        let messages = FetchedMessage::by_uid(&responses);
+        assert_eq!(messages.len(), 1);
+        let msg = &messages[&4];
+        assert_eq!((msg.seq, msg.size), (1, Some(12)));
+        let header = SectionPath::Full(MessageSection::Header);
+        assert_eq!(msg.section(Some(&header)), Some(&b"hi"[..]));
+        assert_eq!(msg.body(), None);
+    }
+}
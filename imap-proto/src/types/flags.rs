@@ -0,0 +1,334 @@
+use std::fmt;
+use std::iter::FromIterator;
+
+bitflags::bitflags! {
+    /// The system flags defined by RFC 3501 (section 2.3.2).
+    #[derive(Default)]
+    pub struct SystemFlags: u8 {
+        const SEEN = 1;
+        const ANSWERED = 1 << 1;
+        const FLAGGED = 1 << 2;
+        const DELETED = 1 << 3;
+        const DRAFT = 1 << 4;
+        const RECENT = 1 << 5;
+    }
+}
+
+const SYSTEM_FLAGS: [(SystemFlags, &str); 6] = [
+    (SystemFlags::SEEN, "\\Seen"),
+    (SystemFlags::ANSWERED, "\\Answered"),
+    (SystemFlags::FLAGGED, "\\Flagged"),
+    (SystemFlags::DELETED, "\\Deleted"),
+    (SystemFlags::DRAFT, "\\Draft"),
+    (SystemFlags::RECENT, "\\Recent"),
+];
+
+impl SystemFlags {
+    /// The system flag named `name`, ignoring case as flags are atoms.
+    pub fn from_name(name: &str) -> Option<Self> {
+        SYSTEM_FLAGS
+            .iter()
+            .find(|(_, known)| known.eq_ignore_ascii_case(name))
+            .map(|&(flag, _)| flag)
+    }
+}
+
+/// A set of message flags: the system flags, stored as bits, and keywords (such
+/// as `$Forwarded` or `$Junk`), kept in a small sorted list.
+///
+/// Flag names are compared case-insensitively. Keywords keep the case in which
+/// they were first inserted.
+#[derive(Clone, Debug, Default)]
+pub struct FlagSet {
+    system: SystemFlags,
+    keywords: Vec<String>,
+}
+
+impl FlagSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn system(&self) -> SystemFlags {
+        self.system
+    }
+
+    pub fn keywords(&self) -> impl Iterator<Item = &str> {
+        self.keywords.iter().map(String::as_str)
+    }
+
+    /// All flags, with the system flags first.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        SYSTEM_FLAGS
+            .iter()
+            .filter(move |(flag, _)| self.system.contains(*flag))
+            .map(|&(_, name)| name)
+            .chain(self.keywords())
+    }
+
+    pub fn len(&self) -> usize {
+        self.system.bits().count_ones() as usize + self.keywords.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.system.is_empty() && self.keywords.is_empty()
+    }
+
+    pub fn contains(&self, flag: &str) -> bool {
+        match SystemFlags::from_name(flag) {
+            Some(system) => self.system.contains(system),
+            None => self.find(flag).is_ok(),
+        }
+    }
+
+    /// Returns whether the flag was not in the set yet.
+    pub fn insert(&mut self, flag: &str) -> bool {
+        if let Some(system) = SystemFlags::from_name(flag) {
+            let inserted = !self.system.contains(system);
+            self.system.insert(system);
+            return inserted;
+        }
+
+        match self.find(flag) {
+            Ok(_) => false,
+            Err(i) => {
+                self.keywords.insert(i, flag.to_string());
+                true
+            }
+        }
+    }
+
+    /// Returns whether the flag was in the set.
+    pub fn remove(&mut self, flag: &str) -> bool {
+        if let Some(system) = SystemFlags::from_name(flag) {
+            let removed = self.system.contains(system);
+            self.system.remove(system);
+            return removed;
+        }
+
+        match self.find(flag) {
+            Ok(i) => {
+                self.keywords.remove(i);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// The flags in either set.
+    pub fn union(&self, other: &FlagSet) -> FlagSet {
+        let mut union = self.clone();
+        union.system |= other.system;
+        for keyword in &other.keywords {
+            union.insert(keyword);
+        }
+        union
+    }
+
+    /// The flags in this set that are not in `other`.
+    pub fn difference(&self, other: &FlagSet) -> FlagSet {
+        FlagSet {
+            system: self.system - other.system,
+            keywords: self
+                .keywords
+                .iter()
+                .filter(|keyword| other.find(keyword).is_err())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    fn find(&self, keyword: &str) -> Result<usize, usize> {
+        self.keywords
+            .binary_search_by(|probe| cmp_ignore_case(probe, keyword))
+    }
+}
+
+fn cmp_ignore_case(a: &str, b: &str) -> std::cmp::Ordering {
+    a.bytes()
+        .map(|b| b.to_ascii_lowercase())
+        .cmp(b.bytes().map(|b| b.to_ascii_lowercase()))
+}
+
+impl PartialEq for FlagSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.system == other.system
+            && self.keywords.len() == other.keywords.len()
+            && self
+                .keywords
+                .iter()
+                .zip(&other.keywords)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for FlagSet {}
+
+impl From<SystemFlags> for FlagSet {
+    fn from(system: SystemFlags) -> Self {
+        FlagSet {
+            system,
+            keywords: Vec::new(),
+        }
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for FlagSet {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        let mut set = FlagSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<S: AsRef<str>> Extend<S> for FlagSet {
+    fn extend<T: IntoIterator<Item = S>>(&mut self, iter: T) {
+        for flag in iter {
+            self.insert(flag.as_ref());
+        }
+    }
+}
+
+/// Formats the set as a parenthesized flag list, as used in `STORE` and `APPEND`.
+impl fmt::Display for FlagSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("(")?;
+        for (i, flag) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(flag)?;
+        }
+        f.write_str(")")
+    }
+}
+
+// Derived from the flag names it contains, to keep keywords sorted
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for FlagSet {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut set = FlagSet::new();
+        for flag in u.arbitrary_iter::<&str>()? {
+            set.insert(flag?);
+        }
+        Ok(set)
+    }
+}
+
+// Serialized as a list of flag names
+#[cfg(feature = "serde")]
+impl serde::Serialize for FlagSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// The flags that can be changed permanently in the selected mailbox, from a
+/// `PERMANENTFLAGS` response code (RFC 3501, section 7.1).
+///
+/// ```
+/// use imap_proto::types::PermanentFlags;
+///
+/// let permanent: PermanentFlags = vec!["\\Seen", "$Junk", "\\*"].into_iter().collect();
+/// assert!(permanent.allows_new_keywords);
+/// assert!(permanent.allows("$Phishing"));
+/// assert!(!permanent.allows("\\Deleted"));
+/// assert_eq!(permanent.to_string(), "(\\Seen $Junk \\*)");
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PermanentFlags {
+    pub flags: FlagSet,
+    /// Whether new keywords can be created by storing them (`\*`).
+    pub allows_new_keywords: bool,
+}
+
+impl PermanentFlags {
+    /// Whether `flag` can be changed permanently: it is listed, or it is a
+    /// keyword and new keywords are allowed.
+    pub fn allows(&self, flag: &str) -> bool {
+        self.flags.contains(flag) || (self.allows_new_keywords && !flag.starts_with('\\'))
+    }
+}
+
+/// Collects flag names, with `\*` setting `allows_new_keywords`.
+impl<S: AsRef<str>> FromIterator<S> for PermanentFlags {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        let mut permanent = PermanentFlags::default();
+        for flag in iter {
+            match flag.as_ref() {
+                "\\*" => permanent.allows_new_keywords = true,
+                flag => {
+                    permanent.flags.insert(flag);
+                }
+            }
+        }
+        permanent
+    }
+}
+
+/// Formats the flags as in a `PERMANENTFLAGS` response code, with `\*` last.
+impl fmt::Display for PermanentFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("(")?;
+        let star = Some("\\*").filter(|_| self.allows_new_keywords);
+        for (i, flag) in self.flags.iter().chain(star).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(flag)?;
+        }
+        f.write_str(")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlagSet, PermanentFlags, SystemFlags};
+
+    #[test]
+    fn insert_and_remove() {
+        let mut set: FlagSet = vec!["\\Seen", "$Junk", "\\flagged", "$junk"]
+            .into_iter()
+            .collect();
+        assert_eq!(set.system(), SystemFlags::SEEN | SystemFlags::FLAGGED);
+        assert_eq!(set.len(), 3);
+        assert!(set.contains("\\SEEN"));
+        assert!(set.contains("$JUNK"));
+        assert_eq!(set.to_string(), "(\\Seen \\Flagged $Junk)");
+
+        assert!(set.insert("$Forwarded"));
+        assert!(!set.insert("\\Seen"));
+        assert!(set.remove("$junk"));
+        assert!(!set.remove("\\Deleted"));
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            vec!["\\Seen", "\\Flagged", "$Forwarded"]
+        );
+    }
+
+    #[test]
+    fn union_and_difference() {
+        let old: FlagSet = vec!["\\Seen", "$Junk", "Work"].into_iter().collect();
+        let new: FlagSet = vec!["\\Seen", "\\Answered", "work"].into_iter().collect();
+        assert_eq!(new.difference(&old), FlagSet::from(SystemFlags::ANSWERED));
+        assert_eq!(old.difference(&new).to_string(), "($Junk)");
+        assert_eq!(
+            old.union(&new).to_string(),
+            "(\\Seen \\Answered $Junk Work)"
+        );
+        assert_eq!(old.union(&new), new.union(&old));
+        assert!(old.difference(&old).is_empty());
+    }
+
+    #[test]
+    fn permanent_flags() {
+        let permanent: PermanentFlags = vec!["\\Seen", "\\Deleted"].into_iter().collect();
+        assert!(!permanent.allows_new_keywords);
+        assert!(permanent.allows("\\seen"));
+        assert!(!permanent.allows("$Junk"));
+        assert_eq!(permanent.to_string(), "(\\Seen \\Deleted)");
+        assert_eq!(PermanentFlags::default().to_string(), "()");
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+
+use super::decode_words;
+
+/// The fields of a message header, such as the content of a
+/// `BODY[HEADER.FIELDS (...)]` attribute, with their values unfolded and MIME
+/// encoded-words (RFC 2047) decoded.
+///
+/// ```
+/// use imap_proto::types::Headers;
+///
+/// let headers = Headers::parse(b"Subject: =?UTF-8?Q?caf=C3=A9?=\r\n and more\r\nFrom: a@b.c\r\n\r\n");
+/// assert_eq!(headers.get("subject"), Some("caf\u{e9} and more"));
+/// assert_eq!(headers.get("From"), Some("a@b.c"));
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    /// Parse the fields up to the empty line ending the header, if any. Lines
+    /// that are not fields are skipped.
+    pub fn parse(raw: &[u8]) -> Self {
+        let mut fields = Vec::<(String, Vec<u8>)>::new();
+        for line in raw.split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            match line.first() {
+                None => break,
+                // A continuation of the previous field
+                Some(b' ') | Some(b'\t') => {
+                    if let Some((_, value)) = fields.last_mut() {
+                        value.extend(line);
+                    }
+                }
+                Some(_) => {
+                    if let Some(colon) = line.iter().position(|&b| b == b':') {
+                        let name = String::from_utf8_lossy(&line[..colon]).trim().to_string();
+                        fields.push((name, line[colon + 1..].to_vec()));
+                    }
+                }
+            }
+        }
+        let fields = fields
+            .into_iter()
+            .map(|(name, value)| (name, decode_words(&value).trim().to_string()))
+            .collect();
+        Self { fields }
+    }
+
+    /// The value of the first field with the given name, compared
+    /// case-insensitively.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| &value[..])
+    }
+
+    /// The values of all the fields with the given name, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| &value[..])
+    }
+
+    /// The names and values of the fields, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (&name[..], &value[..]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl fmt::Display for Headers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.fields {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Headers;
+
+    #[test]
+    fn parse() {
+        let headers = Headers::parse(
+            b"Received: from a\r\n\tby b\r\nreceived: from c\r\nnot a field\r\n\
+              X-Empty:\r\n\r\nBody: text\r\n",
+        );
+        assert_eq!(headers.len(), 3);
+        assert_eq!(
+            headers.get_all("RECEIVED").collect::<Vec<_>>(),
+            ["from a\tby b", "from c"]
+        );
+        assert_eq!(headers.get("x-empty"), Some(""));
+        assert_eq!(headers.get("Body"), None);
+        assert!(Headers::parse(b"").is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+/// The fields of an `ID` command or response (RFC 2971), identifying the
+/// client or server software, such as its `name`, `version` and `vendor`.
+///
+/// ```
+/// use imap_proto::types::Identity;
+///
+/// let id = Identity::new(vec![
+///     ("name".to_string(), Some("Dovecot".to_string())),
+///     ("version".to_string(), None),
+/// ]);
+/// assert_eq!(id.name(), Some("Dovecot"));
+/// assert_eq!(id.get("Name"), Some("Dovecot"));
+/// assert_eq!(id.version(), None);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Identity {
+    /// The fields in the order sent; fields sent as `NIL` have no value.
+    pub fields: Vec<(String, Option<String>)>,
+}
+
+impl Identity {
+    pub fn new(fields: Vec<(String, Option<String>)>) -> Self {
+        Self { fields }
+    }
+
+    /// The value of the field `key`, compared case-insensitively.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get("name")
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.get("version")
+    }
+
+    pub fn vendor(&self) -> Option<&str> {
+        self.get("vendor")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
@@ -0,0 +1,69 @@
+use std::borrow::Cow;
+
+use serde::Serializer;
+
+use super::encode_base64;
+
+#[cfg(feature = "json")]
+use super::Response;
+
+/// Serialize bytes as a base64 string, since they may not be valid UTF-8.
+pub(crate) fn bytes<S: Serializer>(data: &&[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode_base64(data))
+}
+
+// The signature is imposed by `serialize_with`
+#[allow(clippy::ptr_arg)]
+pub(crate) fn cow_bytes<S: Serializer>(data: &Cow<[u8]>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode_base64(data))
+}
+
+pub(crate) fn opt_bytes<S: Serializer>(
+    data: &Option<Cow<[u8]>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match data {
+        Some(data) => serializer.serialize_some(&encode_base64(data)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Text is serialized as a string, decoded lossily.
+impl<'a> serde::Serialize for super::Text<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_str_lossy())
+    }
+}
+
+#[cfg(feature = "json")]
+impl<'a> Response<'a> {
+    /// A JSON representation of the parsed response, for debugging tools and bug
+    /// reports.
+    ///
+    /// Enum variants are written as objects with the variant name as the only
+    /// key, and byte strings (such as message content) as base64. The output only
+    /// changes when the types in this crate change.
+    pub fn to_debug_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("responses serialize to JSON")
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use crate::parser::parse_response;
+
+    #[test]
+    fn debug_json() {
+        let (_, rsp) = parse_response(b"* 12 FETCH (UID 7 BODY[] {7}\r\nhello\r\n)\r\n").unwrap();
+        let json: serde_json::Value = serde_json::from_str(&rsp.to_debug_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "Fetch": [12, [
+                    { "Uid": 7 },
+                    { "BodySection": { "section": null, "index": null, "data": "aGVsbG8NCg==" } },
+                ]]
+            })
+        );
+    }
+}
@@ -1,43 +1,126 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::RangeInclusive;
+use std::str;
+
+mod acl;
+pub use self::acl::{AclEntry, Right, Rights};
+mod address;
+pub(crate) use self::address::{decode_base64, encode_base64};
+pub use self::address::{decode_words, AddressEntry, AddressList};
+mod body;
+pub use self::body::{Attachment, BodyParts};
+mod charset;
+pub use self::charset::Charsets;
+mod debug;
+mod fetch;
+mod headers;
+mod id;
+pub use self::fetch::{
+    AttributeKind, FetchItem, FetchItems, FetchSection, FetchedMessage, SectionText,
+};
+pub use self::headers::Headers;
+pub use self::id::Identity;
+mod flags;
+pub use self::flags::{FlagSet, PermanentFlags, SystemFlags};
+#[cfg(feature = "serde")]
+mod json;
+mod namespace;
+pub use self::namespace::{Namespace, Namespaces};
+mod search;
+pub use self::debug::{set_full_debug, Content};
+pub use self::search::{Date, SearchCriteria, SortCriterion, SortKey};
+mod utf7;
+pub use self::utf7::{decode_utf7, encode_utf7};
+
+/// A list of flags.
+pub type FlagList<'a> = Vec<&'a str>;
+
+/// An address list in an `Envelope`.
+pub type Addresses<'a> = Vec<Address<'a>>;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Request<'a>(pub &'a [u8], pub &'a [u8]);
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Request<'a>(
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::bytes"))] pub &'a [u8],
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::bytes"))] pub &'a [u8],
+);
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum AttrMacro {
     All,
     Fast,
     Full,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Response<'a> {
     Capabilities(Vec<Capability<'a>>),
     Continue {
         code: Option<ResponseCode<'a>>,
-        information: Option<&'a str>,
+        information: Option<Text<'a>>,
     },
     Done {
         tag: RequestId,
         status: Status,
         code: Option<ResponseCode<'a>>,
-        information: Option<&'a str>,
+        information: Option<Text<'a>>,
     },
     Data {
         status: Status,
         code: Option<ResponseCode<'a>>,
-        information: Option<&'a str>,
+        information: Option<Text<'a>>,
     },
     Expunge(u32),
     Fetch(u32, Vec<AttributeValue<'a>>),
+    Id(Identity), // RFC 2971, empty if sent as NIL
     MailboxData(MailboxDatum<'a>),
+    Namespace(Namespaces), // RFC 2342
+    // RFC 7162, section 3.2.10
+    Vanished {
+        earlier: bool,
+        uids: SequenceSet,
+    },
 }
 
 impl<'a> Response<'a> {
     pub fn from_bytes(buf: &'a [u8]) -> crate::ParseResult {
         crate::parser::parse_response(buf)
     }
+
+    /// The SASL challenge carried by a continuation request received during
+    /// `AUTHENTICATE`, decoded from base64, or `None` if this is another
+    /// response or the data is not valid base64.
+    pub fn decode_challenge(&self) -> Option<Vec<u8>> {
+        let data = match self {
+            Response::Continue {
+                code: None,
+                information,
+            } => information.map(|text| text.as_bytes()).unwrap_or_default(),
+            _ => return None,
+        };
+
+        // base64 = *(4base64-char) [base64-terminal]
+        let padding = data.iter().rev().take_while(|&&b| b == b'=').count();
+        let valid =
+            data.len() % 4 == 0 && padding <= 2 && !data[..data.len() - padding].contains(&b'=');
+        match valid {
+            true => decode_base64(str::from_utf8(data).ok()?),
+            false => None,
+        }
+    }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Status {
     Ok,
     No,
@@ -46,73 +129,352 @@ pub enum Status {
     Bye,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// Human-readable text in a response, such as the `information` of a `NO`
+/// response.
+///
+/// Servers may send text in other charsets than UTF-8 (for example, Latin-1
+/// alerts or localized error messages), so it is kept as bytes. `Debug` and
+/// `Display` decode it lossily.
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Text<'a>(pub &'a [u8]);
+
+impl<'a> Text<'a> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// The text, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        str::from_utf8(self.0).ok()
+    }
+
+    /// The text, with invalid UTF-8 sequences replaced by U+FFFD.
+    pub fn to_str_lossy(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.0)
+    }
+}
+
+impl<'a> PartialEq<str> for Text<'a> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b str> for Text<'a> {
+    fn eq(&self, other: &&'b str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl<'a> fmt::Debug for Text<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_str_lossy(), f)
+    }
+}
+
+impl<'a> fmt::Display for Text<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_str_lossy())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ResponseCode<'a> {
     Alert,
-    BadCharset(Option<Vec<&'a str>>),
+    AlreadyExists,                     // RFC 5530, section 3
+    AppendUid(u32, Vec<UidSetMember>), // RFC 4315, section 3
+    AuthenticationFailed,              // RFC 5530, section 3
+    AuthorizationFailed,               // RFC 5530, section 3
+    BadCharset(Option<Charsets<'a>>),
+    Cannot, // RFC 5530, section 3
     Capabilities(Vec<Capability<'a>>),
-    HighestModSeq(u64), // RFC 4551, section 3.1.1
+    ClientBug,                                          // RFC 5530, section 3
+    Closed,                                             // RFC 7162, section 3.2.11
+    ContactAdmin,                                       // RFC 5530, section 3
+    CopyUid(u32, Vec<UidSetMember>, Vec<UidSetMember>), // RFC 4315, section 3
+    Corruption,                                         // RFC 5530, section 3
+    Expired,                                            // RFC 5530, section 3
+    ExpungeIssued,                                      // RFC 5530, section 3
+    HighestModSeq(u64),                                 // RFC 4551, section 3.1.1
+    InUse,                                              // RFC 5530, section 3
+    Limit,                                              // RFC 5530, section 3
+    Metadata(MetadataCode),                             // RFC 5464, section 4.2.1
+    NonExistent,                                        // RFC 5530, section 3
+    NoPerm,                                             // RFC 5530, section 3
+    OverQuota,                                          // RFC 5530, section 3
     Parse,
-    PermanentFlags(Vec<&'a str>),
+    PermanentFlags(PermanentFlags),
+    PrivacyRequired, // RFC 5530, section 3
     ReadOnly,
     ReadWrite,
+    Referral(&'a str), // RFC 2221, section 4.1
+    ServerBug,         // RFC 5530, section 3
     TryCreate,
     UidNext(u32),
     UidValidity(u32),
+    Unavailable, // RFC 5530, section 3
     Unseen(u32),
+    /// A code this crate does not know, such as `XPROXYREUSE` or
+    /// `UNDEFINED-FILTER foo`, with the content between the brackets as sent.
+    Other(&'a str),
+}
+
+/// A `SEARCH` return option (RFC 4731).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SearchReturn {
+    Min,
+    Max,
+    All,
+    Count,
+}
+
+/// The results of a `SEARCH` command with return options (RFC 4731). Only the
+/// requested results are included.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ESearch<'a> {
+    /// The tag of the command the results belong to.
+    pub tag: Option<Cow<'a, str>>,
+    /// Whether the results are UIDs rather than sequence numbers.
+    pub uid: bool,
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    pub all: Option<SequenceSet>,
+    pub count: Option<u32>,
+    pub mod_seq: Option<u64>, // RFC 7162
 }
 
-#[derive(Debug, Eq, PartialEq)]
-pub enum StatusAttribute {
+/// A member of a UID set in a UIDPLUS response code (RFC 4315). The order of the
+/// members is significant, so they are kept as sent by the server.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum UidSetMember {
+    UidRange(RangeInclusive<u32>),
+    Uid(u32),
+}
+
+impl UidSetMember {
+    pub fn iter(&self) -> RangeInclusive<u32> {
+        match self {
+            UidSetMember::UidRange(range) => range.clone(),
+            UidSetMember::Uid(uid) => *uid..=*uid,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum StatusAttribute<'a> {
     HighestModSeq(u64), // RFC 4551
     Messages(u32),
     Recent(u32),
+    Size(u64), // RFC 8438
     UidNext(u32),
     UidValidity(u32),
     Unseen(u32),
+    /// An attribute this crate does not know, such as a vendor extension
+    /// (`X-GUID`), with its name and value as sent by the server.
+    Other(Cow<'a, str>, Cow<'a, str>),
+}
+
+/// How a `STORE` command changes the flags of messages.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum StoreMode {
+    /// Replace the flags (`FLAGS`).
+    Replace,
+    /// Add to the flags (`+FLAGS`).
+    Add,
+    /// Remove from the flags (`-FLAGS`).
+    Remove,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum StatusItem {
+    HighestModSeq, // RFC 4551
+    Messages,
+    Recent,
+    Size, // RFC 8438
+    UidNext,
+    UidValidity,
+    Unseen,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Metadata {
     pub entry: String,
     pub value: Option<String>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// The `METADATA` response codes (RFC 5464), describing why entries were not
+/// returned or could not be set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum MetadataCode {
+    /// Entries larger than the requested `MAXSIZE` were left out; the size of
+    /// the largest of them.
+    LongEntries(u32),
+    /// A value is larger than the server allows; the maximum size.
+    MaxSize(u32),
+    /// Setting the entries would exceed the number of entries allowed.
+    TooMany,
+    /// The server does not support private entries on the mailbox.
+    NoPrivate,
+}
+
+/// How deep below the requested entries `GETMETADATA` returns entries.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum MetadataDepth {
+    /// Only the requested entries (the default).
+    #[default]
+    Zero,
+    /// The requested entries and their immediate children.
+    One,
+    /// The requested entries and all entries below them.
+    Infinity,
+}
+
+/// Options of a `GETMETADATA` command.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MetadataOptions {
+    /// Leave out values larger than this many bytes, reporting the size of
+    /// the largest of them with `MetadataCode::LongEntries`.
+    pub max_size: Option<u32>,
+    pub depth: MetadataDepth,
+}
+
+/// The usage and limit of a resource in a quota root (RFC 9208), such as
+/// `STORAGE` (in units of 1024 octets) or `MESSAGE` (a number of messages).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct QuotaResource {
+    /// The resource name, in upper case.
+    pub name: String,
+    pub usage: u64,
+    pub limit: u64,
+}
+
+impl QuotaResource {
+    /// The usage still allowed before the limit is reached.
+    pub fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.usage)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MailboxDatum<'a> {
     Exists(u32),
-    Flags(Vec<&'a str>),
+    Flags(FlagList<'a>),
     List {
-        flags: Vec<&'a str>,
-        delimiter: Option<&'a str>,
-        name: &'a str,
+        flags: FlagList<'a>,
+        delimiter: Option<Cow<'a, str>>,
+        name: Cow<'a, str>,
+        /// Extended data items following the name, such as `CHILDINFO` or
+        /// `OLDNAME` (RFC 5258), keyed by their tag.
+        extensions: Vec<(Cow<'a, str>, TaggedExtValue<'a>)>,
+    },
+    Search {
+        ids: Vec<u32>,
+        /// The highest mod-sequence of the matching messages, if the search
+        /// used `MODSEQ` (RFC 4551, section 3.5).
+        mod_seq: Option<u64>,
     },
-    Search(Vec<u32>),
+    ESearch(ESearch<'a>), // RFC 4731
+    Sort(Vec<u32>),       // RFC 5256
     Status {
-        mailbox: &'a str,
-        status: Vec<StatusAttribute>,
+        mailbox: Cow<'a, str>,
+        status: Vec<StatusAttribute<'a>>,
     },
     Recent(u32),
     MetadataSolicited {
-        mailbox: &'a str,
+        mailbox: Cow<'a, str>,
         values: Vec<Metadata>,
     },
     MetadataUnsolicited {
-        mailbox: &'a str,
-        values: Vec<&'a str>,
+        mailbox: Cow<'a, str>,
+        values: Vec<Cow<'a, str>>,
+    },
+    // RFC 4314, section 3.6
+    Acl {
+        mailbox: Cow<'a, str>,
+        entries: Vec<AclEntry>,
+    },
+    // RFC 4314, section 3.7
+    ListRights {
+        mailbox: Cow<'a, str>,
+        identifier: Cow<'a, str>,
+        required: Rights,
+        optional: Vec<Rights>,
+    },
+    // RFC 4314, section 3.8
+    MyRights {
+        mailbox: Cow<'a, str>,
+        rights: Rights,
+    },
+    // RFC 9208, section 5.1
+    Quota {
+        root: Cow<'a, str>,
+        resources: Vec<QuotaResource>,
+    },
+    // RFC 9208, section 5.2
+    QuotaRoot {
+        mailbox: Cow<'a, str>,
+        roots: Vec<Cow<'a, str>>,
     },
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Capability<'a> {
     Imap4rev1,
     Auth(&'a str),
     Atom(&'a str),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+impl<'a> Capability<'a> {
+    /// The capability as advertised, in upper case, such as `AUTH=PLAIN`.
+    pub fn name(&self) -> String {
+        match self {
+            Capability::Imap4rev1 => "IMAP4REV1".to_string(),
+            Capability::Auth(mechanism) => format!("AUTH={}", mechanism.to_ascii_uppercase()),
+            Capability::Atom(name) => name.to_ascii_uppercase(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Attribute {
     Body,
+    BodyPeek,                     // BODY.PEEK[], which does not set \Seen
+    BodySection(SectionPath),     // BODY[1.2], with sections from BodyStructure::parts()
+    BodyPeekSection(SectionPath), // BODY.PEEK[1.2]
+    /// `BODY.PEEK[HEADER.FIELDS (...)]`: the header fields with the given names.
+    BodyPeekHeaderFields(Vec<String>),
+    BodyStructure,
     Envelope,
     Flags,
     InternalDate,
@@ -123,41 +485,67 @@ pub enum Attribute {
     Uid,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MessageSection {
     Header,
     Mime,
     Text,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum SectionPath {
     Full(MessageSection),
     Part(Vec<u32>, Option<MessageSection>),
 }
 
+/// The `Debug` output summarizes message content, see `set_full_debug()`.
 #[allow(clippy::large_enum_variant)]
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum AttributeValue<'a> {
     BodySection {
         section: Option<SectionPath>,
         index: Option<u32>,
-        data: Option<&'a [u8]>,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+        data: Option<Cow<'a, [u8]>>,
     },
     BodyStructure(BodyStructure<'a>),
     Envelope(Box<Envelope<'a>>),
-    Flags(Vec<&'a str>),
-    InternalDate(&'a str),
+    Flags(FlagList<'a>),
+    InternalDate(Cow<'a, str>),
     ModSeq(u64), // RFC 4551, section 3.3.2
-    Rfc822(Option<&'a [u8]>),
-    Rfc822Header(Option<&'a [u8]>),
+    Rfc822(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+        Option<Cow<'a, [u8]>>,
+    ),
+    Rfc822Header(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+        Option<Cow<'a, [u8]>>,
+    ),
     Rfc822Size(u32),
-    Rfc822Text(Option<&'a [u8]>),
+    Rfc822Text(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+        Option<Cow<'a, [u8]>>,
+    ),
     Uid(u32),
+    /// An attribute the parser does not know, such as a vendor extension,
+    /// with its value as sent by the server.
+    Unknown {
+        name: &'a str,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "json::bytes"))]
+        raw: &'a [u8],
+    },
 }
 
 #[allow(clippy::large_enum_variant)]
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BodyStructure<'a> {
     Basic {
         common: BodyContentCommon<'a>,
@@ -185,87 +573,492 @@ pub enum BodyStructure<'a> {
     },
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BodyContentCommon<'a> {
     pub ty: ContentType<'a>,
     pub disposition: Option<ContentDisposition<'a>>,
-    pub language: Option<Vec<&'a str>>,
-    pub location: Option<&'a str>,
+    pub language: Option<Vec<Cow<'a, str>>>,
+    pub location: Option<Cow<'a, str>>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BodyContentSinglePart<'a> {
-    pub id: Option<&'a str>,
-    pub md5: Option<&'a str>,
-    pub description: Option<&'a str>,
+    pub id: Option<Cow<'a, str>>,
+    pub md5: Option<Cow<'a, str>>,
+    pub description: Option<Cow<'a, str>>,
     pub transfer_encoding: ContentEncoding<'a>,
     pub octets: u32,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ContentType<'a> {
-    pub ty: &'a str,
-    pub subtype: &'a str,
+    pub ty: Cow<'a, str>,
+    pub subtype: Cow<'a, str>,
     pub params: BodyParams<'a>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ContentDisposition<'a> {
-    pub ty: &'a str,
+    pub ty: Cow<'a, str>,
     pub params: BodyParams<'a>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ContentEncoding<'a> {
     SevenBit,
     EightBit,
     Binary,
     Base64,
     QuotedPrintable,
-    Other(&'a str),
+    Other(Cow<'a, str>),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BodyExtension<'a> {
     Num(u32),
-    Str(Option<&'a str>),
+    Str(Option<Cow<'a, str>>),
     List(Vec<BodyExtension<'a>>),
 }
 
-pub type BodyParams<'a> = Option<Vec<(&'a str, &'a str)>>;
+/// The value of an extension data item (`tagged-ext-val`, RFC 4466), as used in
+/// the extended data of `LIST` responses.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TaggedExtValue<'a> {
+    Number(u64),
+    SequenceSet(SequenceSet),
+    Str(Cow<'a, str>),
+    List(Vec<TaggedExtValue<'a>>),
+}
+
+pub type BodyParams<'a> = Option<Vec<(Cow<'a, str>, Cow<'a, str>)>>;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Envelope<'a> {
-    pub date: Option<&'a [u8]>,
-    pub subject: Option<&'a [u8]>,
-    pub from: Option<Vec<Address<'a>>>,
-    pub sender: Option<Vec<Address<'a>>>,
-    pub reply_to: Option<Vec<Address<'a>>>,
-    pub to: Option<Vec<Address<'a>>>,
-    pub cc: Option<Vec<Address<'a>>>,
-    pub bcc: Option<Vec<Address<'a>>>,
-    pub in_reply_to: Option<&'a [u8]>,
-    pub message_id: Option<&'a [u8]>,
-}
-
-#[derive(Debug, Eq, PartialEq)]
-pub struct Address<'a> {
-    pub name: Option<&'a [u8]>,
-    pub adl: Option<&'a [u8]>,
-    pub mailbox: Option<&'a [u8]>,
-    pub host: Option<&'a [u8]>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+    pub date: Option<Cow<'a, [u8]>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+    pub subject: Option<Cow<'a, [u8]>>,
+    pub from: Option<Addresses<'a>>,
+    pub sender: Option<Addresses<'a>>,
+    pub reply_to: Option<Addresses<'a>>,
+    pub to: Option<Addresses<'a>>,
+    pub cc: Option<Addresses<'a>>,
+    pub bcc: Option<Addresses<'a>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+    pub in_reply_to: Option<Cow<'a, [u8]>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+    pub message_id: Option<Cow<'a, [u8]>>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct RequestId(pub String);
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Address<'a> {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+    pub name: Option<Cow<'a, [u8]>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+    pub adl: Option<Cow<'a, [u8]>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+    pub mailbox: Option<Cow<'a, [u8]>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "json::opt_bytes"))]
+    pub host: Option<Cow<'a, [u8]>>,
+}
+
+/// The tag of a command. Tags of up to 22 bytes (including all tags generated
+/// by tokio-imap) are stored inline, so that parsing a tagged response or
+/// sending a command does not allocate.
+#[derive(Clone)]
+pub struct RequestId(Tag);
+
+const INLINE_TAG_LEN: usize = 22;
+
+#[derive(Clone)]
+enum Tag {
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_TAG_LEN],
+    },
+    Heap(Box<str>),
+}
 
 impl RequestId {
+    pub fn new(tag: &str) -> Self {
+        if tag.len() > INLINE_TAG_LEN {
+            return RequestId(Tag::Heap(tag.into()));
+        }
+
+        let mut bytes = [0; INLINE_TAG_LEN];
+        bytes[..tag.len()].copy_from_slice(tag.as_bytes());
+        RequestId(Tag::Inline {
+            len: tag.len() as u8,
+            bytes,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // safe: copied from a `str`
+            Tag::Inline { len, bytes } => str::from_utf8(&bytes[..*len as usize]).unwrap(),
+            Tag::Heap(tag) => tag,
+        }
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
-        self.0.as_bytes()
+        self.as_str().as_bytes()
+    }
+}
+
+impl PartialEq for RequestId {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for RequestId {}
+
+impl Hash for RequestId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl From<&str> for RequestId {
+    fn from(tag: &str) -> Self {
+        Self::new(tag)
+    }
+}
+
+impl From<String> for RequestId {
+    fn from(tag: String) -> Self {
+        Self::new(&tag)
+    }
+}
+
+impl fmt::Debug for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RequestId").field(&self.as_str()).finish()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for RequestId {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(RequestId::new(<&str>::arbitrary(u)?))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for RequestId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// A set of message sequence numbers or UIDs, stored as sorted, non-overlapping
+/// ranges. Serializes to the `sequence-set` syntax from RFC 3501 (`1:5,7,9:10`).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SequenceSet {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl SequenceSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, num: u32) {
+        self.insert_range(num..=num);
+    }
+
+    pub fn insert_range(&mut self, range: RangeInclusive<u32>) {
+        let (mut start, mut end) = (*range.start(), *range.end());
+        if start > end {
+            return;
+        }
+
+        // Find all existing ranges that overlap or touch the new one and merge them
+        let first = self
+            .ranges
+            .iter()
+            .position(|&(_, e)| e.saturating_add(1) >= start)
+            .unwrap_or(self.ranges.len());
+        let mut last = first;
+        while last < self.ranges.len() && self.ranges[last].0 <= end.saturating_add(1) {
+            start = start.min(self.ranges[last].0);
+            end = end.max(self.ranges[last].1);
+            last += 1;
+        }
+        self.ranges.splice(first..last, Some((start, end)));
+    }
+
+    pub fn contains(&self, num: u32) -> bool {
+        self.ranges.iter().any(|&(s, e)| s <= num && num <= e)
+    }
+
+    /// Number of messages in the set.
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|&(s, e)| (e - s) as usize + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = RangeInclusive<u32>> + '_ {
+        self.ranges.iter().map(|&(s, e)| s..=e)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.ranges().flatten()
+    }
+
+    /// Split the set into consecutive subsets holding at most `max_items` messages,
+    /// each of which serializes to at most `max_len` bytes.
+    ///
+    /// Servers commonly limit the length of command lines they accept (RFC 7162
+    /// recommends clients stay below 8192 octets), so large sets must be sent as
+    /// several commands.
+    pub fn chunks(&self, max_items: usize, max_len: usize) -> Vec<SequenceSet> {
+        let max_items = max_items.max(1);
+        let mut chunks = Vec::new();
+        let (mut cur, mut items, mut len) = (SequenceSet::new(), 0, 0);
+        for &(mut start, end) in &self.ranges {
+            loop {
+                let take = ((end - start) as usize).min(max_items - items - 1);
+                let stop = start + take as u32;
+                let added = range_len(start, stop) + if cur.is_empty() { 0 } else { 1 };
+                if !cur.is_empty() && len + added > max_len {
+                    chunks.push(std::mem::take(&mut cur));
+                    items = 0;
+                    len = 0;
+                    continue;
+                }
+
+                cur.ranges.push((start, stop));
+                items += take + 1;
+                len += added;
+                if items == max_items {
+                    chunks.push(std::mem::take(&mut cur));
+                    items = 0;
+                    len = 0;
+                }
+                if stop == end {
+                    break;
+                }
+                start = stop + 1;
+            }
+        }
+        if !cur.is_empty() {
+            chunks.push(cur);
+        }
+        chunks
+    }
+}
+
+fn range_len(start: u32, end: u32) -> usize {
+    let digits = |n: u32| n.to_string().len();
+    if start == end {
+        digits(start)
+    } else {
+        digits(start) + 1 + digits(end)
+    }
+}
+
+impl FromIterator<u32> for SequenceSet {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        let mut set = SequenceSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<u32> for SequenceSet {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, iter: I) {
+        for num in iter {
+            self.insert(num);
+        }
+    }
+}
+
+impl From<RangeInclusive<u32>> for SequenceSet {
+    fn from(range: RangeInclusive<u32>) -> Self {
+        let mut set = SequenceSet::new();
+        set.insert_range(range);
+        set
+    }
+}
+
+// Derived from the numbers it contains, to keep the ranges sorted and merged
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for SequenceSet {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut set = SequenceSet::new();
+        for range in u.arbitrary_iter::<(u32, u32)>()? {
+            let (start, end) = range?;
+            let (start, end) = (start.min(end), start.max(end));
+            set.insert_range(start..=end);
+        }
+        Ok(set)
+    }
+}
+
+// Serialized in the `sequence-set` syntax
+#[cfg(feature = "serde")]
+impl serde::Serialize for SequenceSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Display for SequenceSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, &(start, end)) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            if start == end {
+                write!(f, "{}", start)?;
+            } else {
+                write!(f, "{}:{}", start, end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A number in the sequence set of a command.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SeqNumber {
+    Value(u32),
+    /// `*`, the largest number in use: the number of messages in the mailbox,
+    /// or the largest UID.
+    Largest,
+}
+
+impl SeqNumber {
+    fn resolve(self, largest: u32) -> u32 {
+        match self {
+            SeqNumber::Value(num) => num,
+            SeqNumber::Largest => largest,
+        }
+    }
+}
+
+impl fmt::Display for SeqNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeqNumber::Value(num) => write!(f, "{}", num),
+            SeqNumber::Largest => f.write_str("*"),
+        }
+    }
+}
+
+/// A sequence set as sent in commands, which may refer to the largest number in
+/// use with `*` (as in `1:*`). The ranges are kept as sent, since what they
+/// cover depends on the mailbox: see `resolve()`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SequenceSpec {
+    ranges: Vec<(SeqNumber, SeqNumber)>,
+}
+
+impl SequenceSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the range from `start` to `end`, in either order.
+    pub fn push(&mut self, start: SeqNumber, end: SeqNumber) {
+        self.ranges.push((start, end));
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = (SeqNumber, SeqNumber)> + '_ {
+        self.ranges.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The numbers in the set, with `*` standing for `largest`.
+    ///
+    /// A mailbox without messages has no number in use, so that any set
+    /// resolves to an empty one when `largest` is zero.
+    pub fn resolve(&self, largest: u32) -> SequenceSet {
+        let mut set = SequenceSet::new();
+        if largest == 0 {
+            return set;
+        }
+        for &(start, end) in &self.ranges {
+            let (start, end) = (start.resolve(largest), end.resolve(largest));
+            set.insert_range(start.min(end)..=start.max(end));
+        }
+        set
+    }
+}
+
+impl From<&SequenceSet> for SequenceSpec {
+    fn from(set: &SequenceSet) -> Self {
+        let ranges = set
+            .ranges
+            .iter()
+            .map(|&(start, end)| (SeqNumber::Value(start), SeqNumber::Value(end)))
+            .collect();
+        Self { ranges }
+    }
+}
+
+// Serialized in the `sequence-set` syntax
+#[cfg(feature = "serde")]
+impl serde::Serialize for SequenceSpec {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Display for SequenceSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, &(start, end)) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            if start == end {
+                write!(f, "{}", start)?;
+            } else {
+                write!(f, "{}:{}", start, end)?;
+            }
+        }
+        Ok(())
     }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum State {
     NotAuthenticated,
     Authenticated,
@@ -277,24 +1070,89 @@ pub enum State {
 
 pub struct BodyFields<'a> {
     pub param: BodyParams<'a>,
-    pub id: Option<&'a str>,
-    pub description: Option<&'a str>,
+    pub id: Option<Cow<'a, str>>,
+    pub description: Option<Cow<'a, str>>,
     pub transfer_encoding: ContentEncoding<'a>,
     pub octets: u32,
 }
 
 pub struct BodyExt1Part<'a> {
-    pub md5: Option<&'a str>,
+    pub md5: Option<Cow<'a, str>>,
     pub disposition: Option<ContentDisposition<'a>>,
-    pub language: Option<Vec<&'a str>>,
-    pub location: Option<&'a str>,
+    pub language: Option<Vec<Cow<'a, str>>>,
+    pub location: Option<Cow<'a, str>>,
     pub extension: Option<BodyExtension<'a>>,
 }
 
 pub struct BodyExtMPart<'a> {
     pub param: BodyParams<'a>,
     pub disposition: Option<ContentDisposition<'a>>,
-    pub language: Option<Vec<&'a str>>,
-    pub location: Option<&'a str>,
+    pub language: Option<Vec<Cow<'a, str>>>,
+    pub location: Option<Cow<'a, str>>,
     pub extension: Option<BodyExtension<'a>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{RequestId, SequenceSet};
+
+    #[test]
+    fn request_id() {
+        let short = RequestId::new("A0001");
+        assert_eq!(short.as_str(), "A0001");
+        assert_eq!(format!("{:?}", short), "RequestId(\"A0001\")");
+        let long = RequestId::from("a-tag-longer-than-22-bytes".to_string());
+        assert_eq!(long.as_bytes(), b"a-tag-longer-than-22-bytes");
+        assert_ne!(short, long);
+        assert_eq!(short, RequestId::from("A0001"));
+    }
+
+    #[test]
+    fn sequence_set_merges_ranges() {
+        let mut set: SequenceSet = vec![5, 1, 2, 3, 9, 10].into_iter().collect();
+        assert_eq!(set.to_string(), "1:3,5,9:10");
+        set.insert(4);
+        assert_eq!(set.to_string(), "1:5,9:10");
+        set.insert_range(6..=8);
+        assert_eq!(set.to_string(), "1:10");
+        assert_eq!(set.len(), 10);
+        assert!(set.contains(7));
+        assert!(!set.contains(11));
+    }
+
+    #[test]
+    fn sequence_set_chunks() {
+        let set: SequenceSet = (1..=10).chain(20..=25).chain(Some(30)).collect();
+        let chunks = set.chunks(4, 100);
+        let rendered = chunks.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert_eq!(rendered, vec!["1:4", "5:8", "9:10,20:21", "22:25", "30"]);
+
+        let set: SequenceSet = (1..=20).step_by(2).collect();
+        let chunks = set.chunks(100, 8);
+        let rendered = chunks.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert_eq!(rendered, vec!["1,3,5,7", "9,11,13", "15,17,19"]);
+        assert_eq!(chunks.iter().map(|c| c.len()).sum::<usize>(), set.len());
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary() {
+        use super::Response;
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data = (0..8192u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect::<Vec<_>>();
+        let mut u = Unstructured::new(&data);
+        let set = SequenceSet::arbitrary(&mut u).unwrap();
+        let ranges = set.ranges().collect::<Vec<_>>();
+        for pair in ranges.windows(2) {
+            assert!(pair[0].end().saturating_add(1) < *pair[1].start());
+        }
+
+        let mut u = Unstructured::new(&data);
+        for _ in 0..10 {
+            Response::arbitrary(&mut u).unwrap();
+        }
+    }
+}
@@ -0,0 +1,204 @@
+/// The namespaces of the mailboxes a user can access, as sent in a `NAMESPACE`
+/// response (RFC 2342).
+///
+/// Mailbox names differ between servers in their prefix and hierarchy
+/// delimiter, so paths should be built and split with the namespaces rather
+/// than by assuming `/`:
+///
+/// ```
+/// use imap_proto::types::{Namespace, Namespaces};
+///
+/// let namespaces = Namespaces {
+///     personal: vec![Namespace::new("INBOX.", Some('.'))],
+///     ..Namespaces::default()
+/// };
+/// let name = namespaces.personal_path("Archive/2024".split('/')).unwrap();
+/// assert_eq!(name, "INBOX.Archive.2024");
+/// assert_eq!(namespaces.split(&name), Some(vec!["Archive", "2024"]));
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Namespaces {
+    /// The user's own mailboxes, typically a single namespace.
+    pub personal: Vec<Namespace>,
+    /// The mailboxes of other users.
+    pub other: Vec<Namespace>,
+    /// Mailboxes shared between users.
+    pub shared: Vec<Namespace>,
+}
+
+impl Namespaces {
+    /// The namespace of a mailbox: the one with the longest matching prefix.
+    pub fn find(&self, name: &str) -> Option<&Namespace> {
+        self.personal
+            .iter()
+            .chain(&self.other)
+            .chain(&self.shared)
+            .filter(|namespace| namespace.contains(name))
+            .max_by_key(|namespace| namespace.prefix.len())
+    }
+
+    /// The name of the mailbox with the given path in the first personal
+    /// namespace.
+    pub fn personal_path<I, S>(&self, path: I) -> Result<String, &'static str>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        match self.personal.first() {
+            Some(namespace) => namespace.join(path),
+            None => Err("no personal namespace"),
+        }
+    }
+
+    /// The path of a mailbox in its namespace, see `Namespace::split()`.
+    pub fn split<'n>(&self, name: &'n str) -> Option<Vec<&'n str>> {
+        self.find(name)?.split(name)
+    }
+}
+
+/// A namespace: the prefix of the names of the mailboxes in it, and the
+/// delimiter between the levels of their hierarchy (`None` if flat).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Namespace {
+    pub prefix: String,
+    pub delimiter: Option<char>,
+    /// Extension data, as names and values.
+    pub extensions: Vec<(String, Vec<String>)>,
+}
+
+impl Namespace {
+    pub fn new(prefix: &str, delimiter: Option<char>) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            delimiter,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Whether the mailbox is in the namespace, or is the namespace itself
+    /// (as `INBOX` for the `INBOX.` prefix). `INBOX` is compared
+    /// case-insensitively.
+    pub fn contains(&self, name: &str) -> bool {
+        self.strip_prefix(name).is_some()
+    }
+
+    /// The name of the mailbox with the given path in the namespace.
+    ///
+    /// Fails if a level of the path is empty or contains the delimiter, or if
+    /// the namespace is flat and the path has more than one level.
+    pub fn join<I, S>(&self, path: I) -> Result<String, &'static str>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut name = self.prefix.clone();
+        let mut levels = 0;
+        for level in path {
+            let level = level.as_ref();
+            if level.is_empty() {
+                return Err("empty mailbox name");
+            }
+            match self.delimiter {
+                Some(delimiter) if level.contains(delimiter) => {
+                    return Err("delimiter in mailbox name");
+                }
+                Some(delimiter) if levels > 0 => name.push(delimiter),
+                None if levels > 0 => return Err("namespace is flat"),
+                _ => {}
+            }
+            name.push_str(level);
+            levels += 1;
+        }
+        match levels {
+            0 => Err("empty mailbox name"),
+            _ => Ok(name),
+        }
+    }
+
+    /// The path of a mailbox in the namespace, or `None` if it is not in it.
+    /// The path of the namespace itself is empty.
+    pub fn split<'n>(&self, name: &'n str) -> Option<Vec<&'n str>> {
+        let rest = self.strip_prefix(name)?;
+        match (rest, self.delimiter) {
+            ("", _) => Some(Vec::new()),
+            (rest, Some(delimiter)) => Some(rest.split(delimiter).collect()),
+            (rest, None) => Some(vec![rest]),
+        }
+    }
+
+    /// The rest of the name after the prefix, which is empty for the
+    /// namespace itself.
+    fn strip_prefix<'n>(&self, name: &'n str) -> Option<&'n str> {
+        // The namespace itself is named without the trailing delimiter
+        let root = match self.delimiter {
+            Some(delimiter) => self.prefix.strip_suffix(delimiter),
+            None => None,
+        };
+        let prefix = match root {
+            Some(root) if root.len() == name.len() => root,
+            _ => self.prefix.as_str(),
+        };
+        let rest = match name.get(..prefix.len()) {
+            Some(start) if start == prefix => &name[prefix.len()..],
+            Some(start) if is_inbox_prefix(prefix) && start.eq_ignore_ascii_case(prefix) => {
+                &name[prefix.len()..]
+            }
+            _ => return None,
+        };
+        Some(rest)
+    }
+}
+
+/// Whether the prefix starts with the `INBOX` level, which is case-insensitive.
+fn is_inbox_prefix(prefix: &str) -> bool {
+    matches!(prefix.get(..5), Some(start) if start.eq_ignore_ascii_case("INBOX"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Namespace, Namespaces};
+
+    #[test]
+    fn paths() {
+        let inbox = Namespace::new("INBOX.", Some('.'));
+        assert_eq!(
+            inbox.join(["Archive", "2024"]).unwrap(),
+            "INBOX.Archive.2024"
+        );
+        assert!(inbox.join(["a.b"]).is_err());
+        assert!(inbox.join([""]).is_err());
+        assert!(inbox.join(Vec::<&str>::new()).is_err());
+        assert_eq!(inbox.split("inbox.Sent"), Some(vec!["Sent"]));
+        assert_eq!(inbox.split("INBOX"), Some(vec![]));
+        assert_eq!(inbox.split("INBOXES"), None);
+
+        let shared = Namespace::new("#shared/", Some('/'));
+        assert_eq!(shared.join(["team"]).unwrap(), "#shared/team");
+        assert!(shared.contains("#shared"));
+        assert!(!shared.contains("#shared2"));
+
+        let flat = Namespace::new("", None);
+        assert_eq!(flat.join(["Sent"]).unwrap(), "Sent");
+        assert!(flat.join(["a", "b"]).is_err());
+
+        let namespaces = Namespaces {
+            personal: vec![Namespace::new("", Some('/'))],
+            other: vec![Namespace::new("~", Some('/'))],
+            shared: vec![shared],
+        };
+        assert_eq!(namespaces.find("~bob/Sent").unwrap().prefix, "~");
+        assert_eq!(
+            namespaces.other[0].join(["bob", "Sent"]).unwrap(),
+            "~bob/Sent"
+        );
+        assert_eq!(namespaces.split("#shared/team/x"), Some(vec!["team", "x"]));
+        assert_eq!(
+            namespaces.split("Archive/2024"),
+            Some(vec!["Archive", "2024"])
+        );
+    }
+}
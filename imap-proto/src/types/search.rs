@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use super::SequenceSpec;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A date in search criteria, such as `1-Feb-1994`. Dates are compared without
+/// time and timezone.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Date {
+    pub year: u16,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Returns `None` if the month or the day is out of range.
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        match (1..=12).contains(&month) && (1..=31).contains(&day) {
+            true => Some(Self { year, month, day }),
+            false => None,
+        }
+    }
+
+    /// The number of a month from its three-letter name, ignoring case.
+    pub(crate) fn month(name: &str) -> Option<u8> {
+        MONTHS
+            .iter()
+            .position(|month| month.eq_ignore_ascii_case(name))
+            .map(|i| i as u8 + 1)
+    }
+}
+
+// date-text = date-day "-" date-month "-" date-year
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let month = MONTHS.get(usize::from(self.month).wrapping_sub(1));
+        write!(
+            f,
+            "{}-{}-{:04}",
+            self.day,
+            month.unwrap_or(&"???"),
+            self.year
+        )
+    }
+}
+
+/// The criteria of a `SEARCH` command (RFC 3501, section 6.4.4), as built by
+/// clients with `CommandBuilder::search_criteria()` and parsed by servers with
+/// `server::parse_search()`.
+///
+/// Strings are kept as bytes, in the charset given with the command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SearchCriteria<'a> {
+    /// Messages matching all the criteria: a parenthesized list, or the keys
+    /// of the command.
+    And(Vec<SearchCriteria<'a>>),
+    Or(Box<SearchCriteria<'a>>, Box<SearchCriteria<'a>>),
+    Not(Box<SearchCriteria<'a>>),
+    All,
+    Answered,
+    Deleted,
+    Draft,
+    Flagged,
+    New,
+    Old,
+    Recent,
+    Seen,
+    Unanswered,
+    Undeleted,
+    Undraft,
+    Unflagged,
+    Unseen,
+    Keyword(&'a str),
+    Unkeyword(&'a str),
+    Bcc(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+    ),
+    Body(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+    ),
+    Cc(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+    ),
+    From(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+    ),
+    Subject(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+    ),
+    Text(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+    ),
+    To(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+    ),
+    /// A header field name and the string its value must contain; an empty
+    /// string matches all messages with the field.
+    Header(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "super::json::cow_bytes"))]
+        Cow<'a, [u8]>,
+    ),
+    /// Compared with the internal date of messages.
+    Before(Date),
+    On(Date),
+    Since(Date),
+    /// Compared with the `Date` header field of messages.
+    SentBefore(Date),
+    SentOn(Date),
+    SentSince(Date),
+    Larger(u32),
+    Smaller(u32),
+    Uid(SequenceSpec),
+    /// Messages by sequence number.
+    SequenceSet(SequenceSpec),
+}
+
+/// A key to sort messages by with the `SORT` command (RFC 5256).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SortKey {
+    /// The internal date of messages.
+    Arrival,
+    /// The first `Cc` address.
+    Cc,
+    /// The `Date` header field, or the internal date without it.
+    Date,
+    /// The first `From` address.
+    From,
+    Size,
+    /// The base subject, without `Re:` prefixes and the like.
+    Subject,
+    /// The first `To` address.
+    To,
+}
+
+/// A sort criterion: a key, in ascending order unless `reverse`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SortCriterion {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl SortCriterion {
+    pub fn new(key: SortKey) -> Self {
+        Self {
+            key,
+            reverse: false,
+        }
+    }
+
+    pub fn reverse(key: SortKey) -> Self {
+        Self { key, reverse: true }
+    }
+}
+
+// sort-criterion = ["REVERSE" SP] sort-key
+impl fmt::Display for SortCriterion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.reverse {
+            f.write_str("REVERSE ")?;
+        }
+        f.write_str(match self.key {
+            SortKey::Arrival => "ARRIVAL",
+            SortKey::Cc => "CC",
+            SortKey::Date => "DATE",
+            SortKey::From => "FROM",
+            SortKey::Size => "SIZE",
+            SortKey::Subject => "SUBJECT",
+            SortKey::To => "TO",
+        })
+    }
+}
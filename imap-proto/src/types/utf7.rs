@@ -0,0 +1,100 @@
+use super::{decode_base64, encode_base64};
+
+/// Encode a mailbox name in modified UTF-7 (RFC 3501, section 5.1.3), as
+/// required for international mailbox names unless `UTF8=ACCEPT` is enabled.
+///
+/// ```
+/// use imap_proto::types::{decode_utf7, encode_utf7};
+///
+/// assert_eq!(encode_utf7("Entw\u{fc}rfe"), "Entw&APw-rfe");
+/// assert_eq!(encode_utf7("R&D"), "R&-D");
+/// assert_eq!(decode_utf7("Entw&APw-rfe").as_deref(), Some("Entw\u{fc}rfe"));
+/// ```
+pub fn encode_utf7(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut pending = Vec::new();
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            flush_utf16(&mut out, &mut pending);
+            match c {
+                '&' => out.push_str("&-"),
+                c => out.push(c),
+            }
+        } else {
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                pending.extend(&unit.to_be_bytes());
+            }
+        }
+    }
+    flush_utf16(&mut out, &mut pending);
+    out
+}
+
+fn flush_utf16(out: &mut String, pending: &mut Vec<u8>) {
+    if pending.is_empty() {
+        return;
+    }
+    out.push('&');
+    let encoded = encode_base64(pending);
+    out.extend(
+        encoded
+            .trim_end_matches('=')
+            .chars()
+            .map(|c| if c == '/' { ',' } else { c }),
+    );
+    out.push('-');
+    pending.clear();
+}
+
+/// Decode a mailbox name in modified UTF-7, returning `None` if it is not
+/// validly encoded.
+pub fn decode_utf7(name: &str) -> Option<String> {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let end = start + rest[start..].find('-')?;
+        let encoded = &rest[start + 1..end];
+        if encoded.is_empty() {
+            out.push('&');
+        } else {
+            let bytes = decode_base64(&encoded.replace(',', "/"))?;
+            if bytes.len() % 2 != 0 {
+                return None;
+            }
+            let units = bytes
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+            for c in std::char::decode_utf16(units) {
+                out.push(c.ok()?);
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_utf7, encode_utf7};
+
+    #[test]
+    fn round_trip() {
+        for (decoded, encoded) in &[
+            ("INBOX", "INBOX"),
+            (
+                "~peter/mail/\u{65e5}\u{672c}\u{8a9e}/\u{53f0}\u{5317}",
+                "~peter/mail/&ZeVnLIqe-/&U,BTFw-",
+            ),
+            ("Tom & Jerry", "Tom &- Jerry"),
+            ("\u{1f4e7} Mail", "&2D3c5w- Mail"),
+        ] {
+            assert_eq!(encode_utf7(decoded), *encoded);
+            assert_eq!(decode_utf7(encoded).as_deref(), Some(*decoded));
+        }
+        assert_eq!(decode_utf7("&ZeVnLIqe"), None);
+        assert_eq!(decode_utf7("&AG-"), None);
+    }
+}
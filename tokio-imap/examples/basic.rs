@@ -5,8 +5,8 @@ use std::io;
 use futures::stream::TryStreamExt;
 use tokio_imap::builders::CommandBuilder;
 use tokio_imap::types::{Attribute, AttributeValue, Response};
+use tokio_imap::Client;
 use tokio_imap::ResponseData;
-use tokio_imap::TlsClient;
 
 #[tokio::main]
 async fn main() {
@@ -29,9 +29,10 @@ async fn imap_fetch(
     mailbox: String,
 ) -> Result<(), ImapError> {
     eprintln!("Will connect to {}", server);
-    let (_, mut tls_client) = TlsClient::connect(server)
+    let (_, tls_client, connection) = Client::connect(server)
         .await
         .map_err(|e| ImapError::Connect { cause: e })?;
+    tokio::spawn(connection);
 
     let responses = tls_client
         .call(CommandBuilder::login(&login, &password))
@@ -46,7 +47,7 @@ async fn imap_fetch(
                 eprintln!("Login failed: {:?}", info);
             }
             return Err(ImapError::Login {
-                cause: io::Error::new(io::ErrorKind::Other, "login failed"),
+                cause: io::Error::other("login failed").into(),
             });
         }
         _ => unimplemented!(),
@@ -74,18 +75,23 @@ async fn imap_fetch(
         .await
         .map_err(|e| ImapError::Close { cause: e })?;
 
+    tls_client
+        .logout()
+        .await
+        .map_err(|e| ImapError::Logout { cause: e })?;
+
     eprintln!("Finished fetching messages");
     Ok(())
 }
 
-async fn process_email(response_data: ResponseData) -> Result<(), io::Error> {
+async fn process_email(response_data: ResponseData) -> Result<(), tokio_imap::Error> {
     if let Response::Fetch(_, ref attr_vals) = *response_data.parsed() {
         for val in attr_vals.iter() {
             match *val {
                 AttributeValue::Uid(u) => {
                     eprintln!("Message UID: {}", u);
                 }
-                AttributeValue::Rfc822(Some(src)) => {
+                AttributeValue::Rfc822(Some(ref src)) => {
                     eprintln!("Message length: {}", src.to_vec().len());
                 }
                 _ => (),
@@ -97,11 +103,12 @@ async fn process_email(response_data: ResponseData) -> Result<(), io::Error> {
 
 #[derive(Debug)]
 pub enum ImapError {
-    Connect { cause: io::Error },
-    Login { cause: io::Error },
-    Select { cause: io::Error },
-    UidFetch { cause: io::Error },
-    Close { cause: io::Error },
+    Connect { cause: tokio_imap::Error },
+    Login { cause: tokio_imap::Error },
+    Select { cause: tokio_imap::Error },
+    UidFetch { cause: tokio_imap::Error },
+    Close { cause: tokio_imap::Error },
+    Logout { cause: tokio_imap::Error },
 }
 
 impl Error for ImapError {
@@ -115,7 +122,8 @@ impl Error for ImapError {
             | ImapError::Login { ref cause }
             | ImapError::Select { ref cause }
             | ImapError::UidFetch { ref cause }
-            | ImapError::Close { ref cause } => Some(cause),
+            | ImapError::Close { ref cause }
+            | ImapError::Logout { ref cause } => Some(cause),
         }
     }
 }
@@ -128,6 +136,7 @@ impl Display for ImapError {
             ImapError::Select { ref cause } => write!(f, "Mailbox selection failed: {}", cause),
             ImapError::UidFetch { ref cause } => write!(f, "Fetching messages failed: {}", cause),
             ImapError::Close { ref cause } => write!(f, "Closing failed: {}", cause),
+            ImapError::Logout { ref cause } => write!(f, "Logging out failed: {}", cause),
         }
     }
 }
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, mailbox_error, Error};
+use imap_proto::builders::command::CommandBuilder;
+use imap_proto::{MailboxDatum, Response, Rights, StoreMode};
+
+impl Client {
+    /// Get the access control list of `mailbox` (RFC 4314): the rights of each
+    /// identifier, by identifier.
+    pub async fn acl(&self, mailbox: &str) -> Result<BTreeMap<String, Rights>, Error> {
+        let mailbox = self.encode_mailbox_name(mailbox);
+        let mut acl = BTreeMap::new();
+        let mut responses = self.call(CommandBuilder::get_acl(&mailbox));
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp).map_err(mailbox_error)?;
+            if let Response::MailboxData(MailboxDatum::Acl { entries, .. }) = rsp.parsed() {
+                for entry in entries {
+                    acl.insert(entry.identifier.clone(), entry.rights.clone());
+                }
+            }
+        }
+        Ok(acl)
+    }
+
+    /// Get the rights of the logged in user on `mailbox`.
+    pub async fn my_rights(&self, mailbox: &str) -> Result<Rights, Error> {
+        let mailbox = self.encode_mailbox_name(mailbox);
+        let mut rights = None;
+        let mut responses = self.call(CommandBuilder::my_rights(&mailbox));
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp).map_err(mailbox_error)?;
+            if let Response::MailboxData(MailboxDatum::MyRights { rights: r, .. }) = rsp.parsed() {
+                rights = Some(r.clone());
+            }
+        }
+        rights.ok_or_else(|| Error::Protocol("no MYRIGHTS response".to_string()))
+    }
+
+    /// Grant `rights` on `mailbox` to `identifier`, in addition to those it
+    /// already has.
+    pub async fn grant(
+        &self,
+        mailbox: &str,
+        identifier: &str,
+        rights: &Rights,
+    ) -> Result<(), Error> {
+        self.set_acl(mailbox, identifier, StoreMode::Add, rights)
+            .await
+    }
+
+    /// Revoke `rights` on `mailbox` from `identifier`, keeping its other rights.
+    pub async fn revoke(
+        &self,
+        mailbox: &str,
+        identifier: &str,
+        rights: &Rights,
+    ) -> Result<(), Error> {
+        self.set_acl(mailbox, identifier, StoreMode::Remove, rights)
+            .await
+    }
+
+    /// Remove `identifier` from the access control list of `mailbox`.
+    pub async fn delete_acl(&self, mailbox: &str, identifier: &str) -> Result<(), Error> {
+        let mailbox = self.encode_mailbox_name(mailbox);
+        self.run_on_mailbox(CommandBuilder::delete_acl(&mailbox, identifier))
+            .await
+    }
+
+    async fn set_acl(
+        &self,
+        mailbox: &str,
+        identifier: &str,
+        mode: StoreMode,
+        rights: &Rights,
+    ) -> Result<(), Error> {
+        let mailbox = self.encode_mailbox_name(mailbox);
+        self.run_on_mailbox(CommandBuilder::set_acl(&mailbox, identifier, mode, rights))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, Error};
+    use imap_proto::Right;
+
+    #[tokio::test]
+    async fn acl() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 ACL] ready\r\n")
+            .expect("A0001 GETACL \"Shared\"")
+            .send(
+                "* ACL Shared alice lrswipkxtea anyone lr\r\n\
+                 A0001 OK GETACL completed\r\n",
+            )
+            .expect("A0002 SETACL \"Shared\" \"bob\" \"+ilr\"")
+            .send("A0002 OK SETACL completed\r\n")
+            .expect("A0003 SETACL \"Shared\" \"anyone\" \"-r\"")
+            .send("A0003 OK SETACL completed\r\n")
+            .expect("A0004 DELETEACL \"Gone\" \"bob\"")
+            .send("A0004 NO [NONEXISTENT] No such mailbox\r\n")
+            .expect("A0005 MYRIGHTS \"Shared\"")
+            .send(
+                "* MYRIGHTS Shared lrs\r\n\
+                 A0005 OK MYRIGHTS completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let acl = client.acl("Shared").await.unwrap();
+        assert_eq!(acl.len(), 2);
+        assert!(acl["alice"].contains(Right::Administer));
+        assert_eq!(acl["anyone"].to_string(), "lr");
+
+        let rights = vec![Right::Lookup, Right::Read, Right::Insert]
+            .into_iter()
+            .collect();
+        client.grant("Shared", "bob", &rights).await.unwrap();
+        let read = "r".parse().unwrap();
+        client.revoke("Shared", "anyone", &read).await.unwrap();
+        match client.delete_acl("Gone", "bob").await {
+            Err(Error::NonExistent(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        let mine = client.my_rights("Shared").await.unwrap();
+        server.finish().await.unwrap();
+        assert!(mine.contains(Right::Seen));
+        assert!(!mine.contains(Right::Write));
+    }
+}
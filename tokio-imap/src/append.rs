@@ -0,0 +1,139 @@
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, mailbox_error, Error};
+use imap_proto::builders::command::{Command, CommandBuilder};
+use imap_proto::{Response, ResponseCode, UidSetMember};
+
+/// The UIDs assigned to appended messages, as reported by servers supporting
+/// UIDPLUS (RFC 4315).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AppendUids {
+    /// The `UIDVALIDITY` of the mailbox.
+    pub uid_validity: u32,
+    /// The UIDs of the messages, in the order they were given.
+    pub uids: Vec<u32>,
+}
+
+impl Client {
+    /// Append `messages` to `mailbox`.
+    ///
+    /// If the server supports MULTIAPPEND (RFC 3502), the messages are sent in a
+    /// single command, so that either all of them or none are appended.
+    /// Otherwise they are appended one command at a time, stopping at the first
+    /// failure.
+    ///
+    /// Returns the UIDs of the messages, or `None` if the server did not report
+    /// them all.
+    pub async fn append_messages(
+        &self,
+        mailbox: &str,
+        messages: &[&[u8]],
+    ) -> Result<Option<AppendUids>, Error> {
+        if messages.is_empty() {
+            return Ok(None);
+        } else if self.session().has_capability("MULTIAPPEND") {
+            return self
+                .append_uids(CommandBuilder::multi_append(mailbox, messages))
+                .await;
+        }
+
+        let mut appended: Option<AppendUids> = None;
+        let mut reported = true;
+        for message in messages {
+            match self
+                .append_uids(CommandBuilder::append(mailbox, message))
+                .await?
+            {
+                Some(uids) => match &mut appended {
+                    Some(appended) => {
+                        appended.uid_validity = uids.uid_validity;
+                        appended.uids.extend(uids.uids);
+                    }
+                    None => appended = Some(uids),
+                },
+                None => reported = false,
+            }
+        }
+        Ok(appended.filter(|_| reported))
+    }
+
+    async fn append_uids(&self, cmd: Command) -> Result<Option<AppendUids>, Error> {
+        let mut responses = self.call(cmd);
+        let mut appended = None;
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp).map_err(mailbox_error)?;
+            if let Response::Done {
+                code: Some(ResponseCode::AppendUid(uid_validity, uids)),
+                ..
+            } = rsp.parsed()
+            {
+                appended = Some(AppendUids {
+                    uid_validity: *uid_validity,
+                    uids: uids.iter().flat_map(UidSetMember::iter).collect(),
+                });
+            }
+        }
+        Ok(appended)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+
+    #[tokio::test]
+    async fn multi_append() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 UIDPLUS MULTIAPPEND] ready\r\n")
+            .expect("A0001 APPEND \"Archive\" {5}")
+            .send("+ ok\r\n")
+            .expect("hello {5}")
+            .send("+ ok\r\n")
+            .expect("world")
+            .send("A0001 OK [APPENDUID 38505 3955:3956] APPEND completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let appended = client
+            .append_messages("Archive", &[b"hello", b"world"])
+            .await
+            .unwrap()
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(appended.uid_validity, 38505);
+        assert_eq!(appended.uids, vec![3955, 3956]);
+    }
+
+    #[tokio::test]
+    async fn append_one_by_one() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 UIDPLUS] ready\r\n")
+            .expect("A0001 APPEND \"Archive\" {5}")
+            .send("+ ok\r\n")
+            .expect("hello")
+            .send("A0001 OK [APPENDUID 38505 3955] APPEND completed\r\n")
+            .expect("A0002 APPEND \"Archive\" {5}")
+            .send("+ ok\r\n")
+            .expect("world")
+            .send("A0002 OK [APPENDUID 38505 3956] APPEND completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let appended = client
+            .append_messages("Archive", &[b"hello", b"world"])
+            .await
+            .unwrap()
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(appended.uids, vec![3955, 3956]);
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::task::AtomicWaker;
+
+use crate::codec::ResponseData;
+
+/// The responses delivered to response streams by a `Connection` that have not
+/// been consumed yet, see `Connection::limit_unconsumed()`.
+pub(crate) struct Backlog {
+    max_responses: usize,
+    max_bytes: usize,
+    responses: AtomicUsize,
+    bytes: AtomicUsize,
+    /// The connection task, woken up when responses are consumed.
+    waker: AtomicWaker,
+}
+
+impl Backlog {
+    pub(crate) fn new(max_responses: usize, max_bytes: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max_responses: max_responses.max(1),
+            max_bytes,
+            responses: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            waker: AtomicWaker::new(),
+        })
+    }
+
+    /// Account for `rsp` until the returned ticket is dropped.
+    pub(crate) fn ticket(self: &Arc<Self>, rsp: &ResponseData) -> Ticket {
+        Ticket::new(self.clone(), rsp.raw().len())
+    }
+
+    /// Whether more responses may be received, or arrange for the task to be
+    /// woken up once they may.
+    pub(crate) fn poll_capacity(&self, cx: &mut Context) -> Poll<()> {
+        if !self.is_full() {
+            return Poll::Ready(());
+        }
+        self.waker.register(cx.waker());
+        // Responses may have been consumed before the waker was registered
+        match self.is_full() {
+            true => Poll::Pending,
+            false => Poll::Ready(()),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.responses.load(Ordering::Acquire) >= self.max_responses
+            || self.bytes.load(Ordering::Acquire) >= self.max_bytes
+    }
+}
+
+/// Held by a response that has not been consumed yet.
+pub(crate) struct Ticket {
+    backlog: Arc<Backlog>,
+    bytes: usize,
+}
+
+impl Ticket {
+    fn new(backlog: Arc<Backlog>, bytes: usize) -> Self {
+        backlog.responses.fetch_add(1, Ordering::AcqRel);
+        backlog.bytes.fetch_add(bytes, Ordering::AcqRel);
+        Self { backlog, bytes }
+    }
+}
+
+impl Clone for Ticket {
+    fn clone(&self) -> Self {
+        Self::new(self.backlog.clone(), self.bytes)
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        self.backlog.responses.fetch_sub(1, Ordering::AcqRel);
+        self.backlog.bytes.fetch_sub(self.bytes, Ordering::AcqRel);
+        self.backlog.waker.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use bytes::BytesMut;
+    use futures::task::noop_waker_ref;
+    use futures::StreamExt;
+    use tokio_util::codec::Decoder;
+
+    use super::Backlog;
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, ImapCodec, Observer, ResponseData};
+    use imap_proto::builders::command::CommandBuilder;
+    use imap_proto::{Attribute, Response};
+
+    fn response(data: &[u8]) -> ResponseData {
+        let mut buf = BytesMut::from(data);
+        ImapCodec::new().decode(&mut buf).unwrap().unwrap()
+    }
+
+    #[test]
+    fn capacity() {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let exists = response(b"* 1 EXISTS\r\n");
+
+        let backlog = Backlog::new(2, usize::MAX);
+        let first = backlog.ticket(&exists);
+        assert_eq!(backlog.poll_capacity(&mut cx), Poll::Ready(()));
+        let second = first.clone();
+        assert_eq!(backlog.poll_capacity(&mut cx), Poll::Pending);
+        drop(first);
+        assert_eq!(backlog.poll_capacity(&mut cx), Poll::Ready(()));
+        drop(second);
+
+        let backlog = Backlog::new(10, 20);
+        let _small = backlog.ticket(&exists);
+        assert_eq!(backlog.poll_capacity(&mut cx), Poll::Ready(()));
+        let large = backlog.ticket(&response(b"* OK [ALERT] maintenance\r\n"));
+        assert_eq!(backlog.poll_capacity(&mut cx), Poll::Pending);
+        drop(large);
+        assert_eq!(backlog.poll_capacity(&mut cx), Poll::Ready(()));
+    }
+
+    #[derive(Default)]
+    struct Received(AtomicUsize);
+
+    impl Observer for Received {
+        fn bytes_received(&self, len: usize) {
+            self.0.fetch_add(len, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_consumer() {
+        // Several times the 8 KiB read size, so that it takes several reads
+        let mut data = String::new();
+        for seq in 1..=1000 {
+            data.push_str(&format!("* {} FETCH (UID {})\r\n", seq, seq + 10));
+        }
+        data.push_str("A0001 OK FETCH completed\r\n");
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 FETCH 1:1000 (UID)")
+            .send(&data);
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, mut connection) = Client::from_stream(stream).await.unwrap();
+        let received = Arc::new(Received::default());
+        connection.observe(received.clone());
+        connection.limit_unconsumed(1, usize::MAX);
+        tokio::spawn(connection);
+
+        let mut responses =
+            client.call(CommandBuilder::fetch().range(1..=1000).attr(Attribute::Uid));
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+        // The connection stops reading while the first responses are unconsumed
+        let before = received.0.load(Ordering::SeqCst);
+        assert!(before > 0 && before < data.len(), "read {} bytes", before);
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+        assert_eq!(received.0.load(Ordering::SeqCst), before);
+
+        let mut fetched = Vec::new();
+        while let Some(rsp) = responses.next().await {
+            if let Response::Fetch(seq, _) = rsp.unwrap().parsed() {
+                fetched.push(*seq);
+            }
+        }
+        server.finish().await.unwrap();
+        assert_eq!(fetched, (1..=1000).collect::<Vec<_>>());
+        assert_eq!(received.0.load(Ordering::SeqCst), data.len());
+    }
+}
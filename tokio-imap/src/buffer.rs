@@ -0,0 +1,161 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use bytes::BytesMut;
+
+/// How the buffer holding the data received by a connection is sized, and
+/// optionally reused between connections.
+///
+/// The buffer starts out with `initial_capacity()` and grows as needed to hold
+/// complete responses, which can be very large (for example, when fetching a
+/// message with big attachments). Once all responses in it have been decoded, a
+/// buffer that grew beyond `max_capacity()` is released and replaced by a new
+/// one, so that a single large response does not inflate the memory used by the
+/// connection for the rest of its lifetime.
+///
+/// With `pooled()`, the buffers of closed connections are kept for reuse by
+/// connections opened later with a clone of the same policy.
+#[derive(Clone)]
+pub struct BufferPolicy {
+    initial: usize,
+    max: usize,
+    pool: Option<Arc<Pool>>,
+}
+
+struct Pool {
+    buffers: Mutex<Vec<BytesMut>>,
+    max_buffers: usize,
+}
+
+impl BufferPolicy {
+    /// An initial capacity of 8 KiB and a maximum capacity of 1 MiB, without
+    /// pooling.
+    pub fn new() -> Self {
+        Self {
+            initial: 8 * 1024,
+            max: 1024 * 1024,
+            pool: None,
+        }
+    }
+
+    /// The capacity of new buffers, which is also the minimum space made
+    /// available for each read.
+    pub fn initial_capacity(mut self, capacity: usize) -> Self {
+        self.initial = capacity;
+        self
+    }
+
+    /// The capacity beyond which buffers are not kept once empty.
+    pub fn max_capacity(mut self, capacity: usize) -> Self {
+        self.max = capacity;
+        self
+    }
+
+    /// Keep up to `max_buffers` buffers of closed connections for reuse.
+    pub fn pooled(mut self, max_buffers: usize) -> Self {
+        self.pool = Some(Arc::new(Pool {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+        }));
+        self
+    }
+
+    /// The number of buffers currently kept for reuse.
+    pub fn pooled_buffers(&self) -> usize {
+        match &self.pool {
+            Some(pool) => pool.buffers.lock().unwrap().len(),
+            None => 0,
+        }
+    }
+
+    pub(crate) fn read_size(&self) -> usize {
+        self.initial
+    }
+
+    /// Whether `buf` grew too large to be kept.
+    pub(crate) fn oversized(&self, buf: &BytesMut) -> bool {
+        buf.capacity() > self.max
+    }
+
+    /// A buffer from the pool, or a new one.
+    pub(crate) fn take(&self) -> BytesMut {
+        let pooled = self
+            .pool
+            .as_ref()
+            .and_then(|pool| pool.buffers.lock().unwrap().pop());
+        pooled.unwrap_or_else(|| BytesMut::with_capacity(self.initial))
+    }
+
+    /// Return a buffer that is no longer used to the pool, if it may be kept.
+    pub(crate) fn give_back(&self, mut buf: BytesMut) {
+        let pool = match &self.pool {
+            Some(pool) => pool,
+            None => return,
+        };
+        if self.oversized(&buf) || buf.capacity() < self.initial {
+            return;
+        }
+
+        buf.clear();
+        let mut buffers = pool.buffers.lock().unwrap();
+        if buffers.len() < pool.max_buffers {
+            buffers.push(buf);
+        }
+    }
+}
+
+impl Default for BufferPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for BufferPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufferPolicy")
+            .field("initial_capacity", &self.initial)
+            .field("max_capacity", &self.max)
+            .field("pooled_buffers", &self.pooled_buffers())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferPolicy;
+    use crate::protocol::{Event, Protocol};
+
+    #[test]
+    fn shrink_after_large_response() {
+        let mut protocol = Protocol::new();
+        protocol.buffer_policy(BufferPolicy::new().initial_capacity(64).max_capacity(1024));
+
+        let mut data = b"* 1 FETCH (BODY[] {4096}\r\n".to_vec();
+        data.extend(vec![b'x'; 4096]);
+        data.extend(b")\r\n* 2 EXISTS");
+        protocol.receive(&data).unwrap();
+        assert!(matches!(protocol.poll_event(), Some(Event::Unsolicited(_))));
+
+        protocol.receive(b"\r\n").unwrap();
+        assert!(matches!(protocol.poll_event(), Some(Event::Unsolicited(_))));
+        assert!(protocol.read_buf_mut().capacity() <= 1024);
+    }
+
+    #[test]
+    fn pool() {
+        let policy = BufferPolicy::new().initial_capacity(64).pooled(1);
+        let mut first = Protocol::new();
+        first.buffer_policy(policy.clone());
+        let mut second = Protocol::new();
+        second.buffer_policy(policy.clone());
+        assert_eq!(policy.pooled_buffers(), 0);
+
+        drop(first);
+        drop(second);
+        assert_eq!(policy.pooled_buffers(), 1);
+
+        let mut third = Protocol::new();
+        third.buffer_policy(policy.clone());
+        assert_eq!(policy.pooled_buffers(), 0);
+    }
+}
@@ -1,163 +1,1418 @@
+use std::collections::VecDeque;
+use std::future::Future;
 use std::io;
 use std::net::ToSocketAddrs;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::Duration;
 
-use futures::{ready, Sink, Stream, StreamExt};
-use pin_project::pin_project;
+use futures::channel::mpsc;
+use futures::{ready, Stream, StreamExt};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
-use tokio_rustls::rustls::ClientConfig;
-use tokio_rustls::webpki::DNSNameRef;
-use tokio_rustls::{client::TlsStream, TlsConnector};
-use tokio_util::codec::{Decoder, Framed};
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::time::{self, Delay, Instant};
+use tokio_rustls::client::TlsStream;
+#[cfg(feature = "futures-io")]
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt};
 
-use crate::codec::{ImapCodec, ResponseData};
-use imap_proto::builders::command::Command;
-use imap_proto::{Request, RequestId, State};
+use crate::backlog::Backlog;
+use crate::buffer::BufferPolicy;
+use crate::codec::ResponseData;
+use crate::error::Error;
+#[cfg(feature = "tracing")]
+use crate::instrument::CommandSpan;
+use crate::observer::Observer;
+use crate::protocol::{command_name, Event};
+use crate::session::Session;
+use crate::spill::Spill;
+use crate::stream::LiteralChunk;
+use crate::throttle::Throttle;
+use crate::tls::TlsConfig;
+use crate::trace::Trace;
+use crate::transport::Transport;
+use imap_proto::builders::command::{fetch, Command, CommandBuilder, FetchCommand};
+use imap_proto::parser::Quirks;
+use imap_proto::{RequestId, Response, SequenceSet, State, Status};
 
-pub type TlsClient = Client<TlsStream<TcpStream>>;
+pub type TlsConnection = Connection<TlsStream<TcpStream>>;
 
-pub struct Client<T> {
-    transport: Framed<T, ImapCodec>,
-    state: State,
-    request_ids: IdGenerator,
+/// The client type before commands could be pipelined; `Client` is now a handle
+/// to a `Connection`, which `Client::connect` returns alongside it and which must
+/// be spawned onto the runtime.
+#[deprecated(note = "use `Client`, and spawn the `Connection` returned by `Client::connect`")]
+pub type TlsClient = Client;
+
+/// A `Connection` over a stream implementing the `futures` I/O traits.
+#[cfg(feature = "futures-io")]
+pub type CompatConnection<T> = Connection<Compat<T>>;
+
+/// A handle used to send commands over a `Connection`.
+///
+/// The handle is cheap to clone; commands issued from several clones (or several
+/// commands issued from the same handle) can be awaiting completion at the same
+/// time. The `Connection` takes care of pipelining them where IMAP allows it, and
+/// of routing each response to the command it belongs to.
+#[derive(Clone)]
+pub struct Client {
+    sender: mpsc::UnboundedSender<Message>,
+    session: Arc<Mutex<Session>>,
 }
 
-impl TlsClient {
-    pub async fn connect(server: &str) -> io::Result<(ResponseData, Self)> {
-        let addr = (server, 993).to_socket_addrs()?.next().ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("no IP addresses found for {}", server),
-            )
-        })?;
+impl Client {
+    /// Connect to `server` on port 993 using TLS with the default settings.
+    ///
+    /// Returns the server greeting, the `Client` handle and the `Connection`. The
+    /// latter performs the actual I/O and must be polled (typically by spawning it
+    /// onto the runtime) for commands to make progress.
+    pub async fn connect(server: &str) -> Result<(ResponseData, Client, TlsConnection), Error> {
+        Self::connect_with(server, &TlsConfig::default()).await
+    }
 
-        let mut tls_config = ClientConfig::new();
-        tls_config
-            .root_store
-            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
-        let connector: TlsConnector = Arc::new(tls_config).into();
+    /// Connect to `server` on port 993 using the given TLS settings.
+    pub async fn connect_with(
+        server: &str,
+        tls: &TlsConfig,
+    ) -> Result<(ResponseData, Client, TlsConnection), Error> {
+        Self::connect_port(server, 993, tls).await
+    }
+
+    pub(crate) async fn connect_port(
+        server: &str,
+        port: u16,
+        tls: &TlsConfig,
+    ) -> Result<(ResponseData, Client, TlsConnection), Error> {
+        let addr = (server, port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::other(format!("no IP addresses found for {}", server)))?;
 
         let stream = TcpStream::connect(&addr).await?;
-        let stream = connector
-            .connect(DNSNameRef::try_from_ascii_str(server).unwrap(), stream)
-            .await?;
-        let mut transport = ImapCodec::default().framed(stream);
+        let stream = tls.connect(server, stream).await?;
+        Self::from_stream(stream).await
+    }
 
+    /// Start a session over an already established connection, such as a plain
+    /// TCP stream or a stream tunneled through a proxy.
+    ///
+    /// Waits for the server greeting, then returns like `connect()`. If the server
+    /// greets with `PREAUTH`, the session is already authenticated (see
+    /// `Session::greeting()`), so no login is needed.
+    pub async fn from_stream<T>(stream: T) -> Result<(ResponseData, Client, Connection<T>), Error>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut transport = Transport::new(stream);
         let greeting = match transport.next().await {
-            Some(greeting) => Ok(greeting),
-            None => Err(io::Error::new(io::ErrorKind::Other, "no greeting found")),
-        }?;
+            Some(greeting) => greeting?,
+            None => return Err(Error::Protocol("no greeting found".into())),
+        };
+
+        let session = Arc::new(Mutex::new(transport.session().clone()));
+        let (sender, receiver) = mpsc::unbounded();
         let client = Client {
+            sender,
+            session: session.clone(),
+        };
+        let connection = Connection {
             transport,
-            state: State::NotAuthenticated,
-            request_ids: IdGenerator::new(),
+            session,
+            receiver: Some(receiver),
+            held: VecDeque::new(),
+            pending: VecDeque::new(),
+            subscribers: Vec::new(),
+            bye: None,
+            closing: false,
+            throttle: None,
+            backlog: None,
+            observer: None,
+            identify: None,
+            id_responses: None,
+            permit: None,
         };
+        Ok((greeting, client, connection))
+    }
 
-        greeting.map(|greeting| (greeting, client))
+    /// Start a session over a stream implementing the `futures` I/O traits rather
+    /// than Tokio's, such as the streams provided by async-std or smol.
+    ///
+    /// The `Connection` does not depend on the Tokio runtime, so it can be spawned
+    /// onto any executor.
+    #[cfg(feature = "futures-io")]
+    pub async fn from_futures_stream<T>(
+        stream: T,
+    ) -> Result<(ResponseData, Client, CompatConnection<T>), Error>
+    where
+        T: futures::io::AsyncRead + futures::io::AsyncWrite + Unpin,
+    {
+        Self::from_stream(stream.compat()).await
+    }
+
+    /// A snapshot of the current session state: capabilities, enabled extensions
+    /// and the selected mailbox.
+    pub fn session(&self) -> Session {
+        self.session.lock().unwrap().clone()
+    }
+
+    /// Whether the connection was closed, so that no more commands can be sent.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Send a command to the server.
+    ///
+    /// The returned stream yields all responses attributed to this command, ending
+    /// with the tagged completion response.
+    ///
+    /// Dropping the stream cancels the command: if it has not been sent yet, it is
+    /// not sent at all. Otherwise, the `Connection` keeps receiving the responses
+    /// to the command in the background, so that other commands are not affected.
+    ///
+    /// Commands that are part of an extension (such as `MOVE` or `IDLE`) are only
+    /// sent if the server advertised the corresponding capability; otherwise, the
+    /// stream yields `Error::MissingCapability`. Use `call_unchecked()` to send
+    /// such commands regardless. The check requires the capabilities to be known
+    /// (see `Session::capabilities()`): while the set is empty, because the server
+    /// did not advertise them in its greeting or they are being asked for again
+    /// after logging in, commands are sent unchecked. Send a `CAPABILITY` command
+    /// first to have them checked.
+    ///
+    /// With the `tracing` feature, each command is covered by an `imap_command`
+    /// span, created as a child of the current span. It records the command name,
+    /// tag, mailbox, completion status (`OK`, `NO`, `BAD` or `error`) and duration.
+    pub fn call<C: Into<Command>>(&self, cmd: C) -> ResponseStream {
+        let cmd = cmd.into();
+        if let Some(required) = required_capability(&cmd) {
+            let session = self.session.lock().unwrap();
+            let caps = session.capabilities();
+            if !caps.is_empty() && !required.iter().any(|name| caps.contains(*name)) {
+                return ResponseStream::error(Error::MissingCapability(required[0]));
+            }
+        }
+        self.call_unchecked(cmd)
     }
 
-    pub fn call<C: Into<Command>>(&mut self, cmd: C) -> ResponseStream<TlsStream<TcpStream>> {
-        let request_id = self.request_ids.next().unwrap(); // safe: never returns Err,
+    /// Send a command to the server without checking whether the server advertised
+    /// the capabilities it requires.
+    pub fn call_unchecked<C: Into<Command>>(&self, cmd: C) -> ResponseStream {
+        let (sender, receiver) = mpsc::unbounded();
+        let (literals, literal_receiver) = mpsc::unbounded();
+        let cmd = cmd.into();
+        let pending = PendingCommand {
+            #[cfg(feature = "tracing")]
+            span: CommandSpan::new(&cmd),
+            cmd,
+            responses: sender,
+            literals,
+        };
+
+        if let Err(e) = self.sender.unbounded_send(Message::Command(pending)) {
+            if let Message::Command(pending) = e.into_inner() {
+                let _ = pending
+                    .responses
+                    .unbounded_send(Err(Error::Io(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "connection closed",
+                    ))));
+            }
+        }
         ResponseStream {
-            client: self,
-            request_id,
-            cmd: cmd.into(),
-            state: ResponseStreamState::Start,
+            receiver,
+            literals: Some(literal_receiver),
+        }
+    }
+
+    /// Log out, ending the session.
+    ///
+    /// Sends `LOGOUT` and waits for the server to confirm it. The `Connection` then
+    /// closes the stream (sending a TLS `close_notify` alert for TLS connections) and
+    /// resolves; commands submitted afterwards fail.
+    pub async fn logout(&self) -> Result<(), Error> {
+        let mut responses = self.call(CommandBuilder::logout());
+        let mut bye = false;
+        while let Some(rsp) = responses.next().await {
+            match rsp {
+                Ok(rsp) => {
+                    if let Response::Data {
+                        status: Status::Bye,
+                        ..
+                    } = rsp.parsed()
+                    {
+                        bye = true;
+                    }
+                }
+                // Some servers close the connection right after sending BYE
+                Err(Error::Io(_)) if bye => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Start idling (RFC 2177), to be notified of changes to the selected mailbox
+    /// as they happen.
+    ///
+    /// The returned stream yields the updates sent by the server until `done()` is
+    /// called or the stream is dropped. The command is renewed periodically (see
+    /// `Idle::renew_after()`), so that the server does not end the connection.
+    pub fn idle(&self) -> Idle {
+        Idle {
+            client: self.clone(),
+            responses: self.call(CommandBuilder::idle()),
+            renewal: None,
+            renew_after: IDLE_RENEWAL,
+            timer: time::delay_for(IDLE_RENEWAL),
+            done: false,
+        }
+    }
+
+    /// Subscribe to responses that are not attributed to any command.
+    ///
+    /// These are the server updates sent outside of command contexts, such as
+    /// `EXISTS`, `EXPUNGE` and `FETCH` responses announcing changes to the selected
+    /// mailbox, `[ALERT]`s and `BYE`. Each subscriber receives every unsolicited
+    /// response that arrives after it subscribed. If the connection is closed,
+    /// the stream ends immediately.
+    pub fn unsolicited(&self) -> UnsolicitedResponses {
+        let (sender, receiver) = mpsc::unbounded();
+        let _ = self.sender.unbounded_send(Message::Subscribe(sender));
+        UnsolicitedResponses { receiver }
+    }
+
+    /// Fetch a potentially huge set of messages by UID, split into several
+    /// `UID FETCH` commands of at most `chunk_size` messages each.
+    ///
+    /// `attrs` is called for each chunk to complete the command with the desired
+    /// attributes. Up to `max_in_flight` commands are awaiting completion at any
+    /// time. The returned stream yields the responses to all commands in the order
+    /// they arrive, including the tagged completion response for each chunk.
+    pub fn uid_fetch_chunked<F, C>(
+        &self,
+        uids: &SequenceSet,
+        chunk_size: usize,
+        max_in_flight: usize,
+        attrs: F,
+    ) -> ChunkedFetch
+    where
+        F: Fn(FetchCommand<fetch::Messages>) -> C,
+        C: Into<Command>,
+    {
+        let pending = uids
+            .chunks(chunk_size, MAX_SEQUENCE_SET_LEN)
+            .iter()
+            .map(|chunk| attrs(CommandBuilder::uid_fetch().set(chunk)).into())
+            .collect();
+        ChunkedFetch {
+            client: self.clone(),
+            pending,
+            in_flight: Vec::new(),
+            max_in_flight: max_in_flight.max(1),
         }
     }
 }
 
-#[pin_project]
-pub struct ResponseStream<'a, T> {
-    #[pin]
-    client: &'a mut Client<T>,
-    request_id: RequestId,
+// RFC 7162, section 4 recommends that clients limit command lines to about 8192
+// octets; leave some room for the command name and attributes.
+const MAX_SEQUENCE_SET_LEN: usize = 7000;
+
+pub struct ChunkedFetch {
+    client: Client,
+    pending: VecDeque<Command>,
+    in_flight: Vec<ResponseStream>,
+    max_in_flight: usize,
+}
+
+impl Stream for ChunkedFetch {
+    type Item = Result<ResponseData, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let me = self.get_mut();
+        while me.in_flight.len() < me.max_in_flight {
+            match me.pending.pop_front() {
+                Some(cmd) => me.in_flight.push(me.client.call(cmd)),
+                None => break,
+            }
+        }
+
+        let mut i = 0;
+        while i < me.in_flight.len() {
+            match me.in_flight[i].poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => return Poll::Ready(Some(item)),
+                Poll::Ready(None) => {
+                    me.in_flight.swap_remove(i);
+                    if let Some(cmd) = me.pending.pop_front() {
+                        me.in_flight.push(me.client.call(cmd));
+                    }
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+
+        if me.in_flight.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// The responses to a single command, ending with its tagged completion response.
+pub struct ResponseStream {
+    receiver: mpsc::UnboundedReceiver<Result<ResponseData, Error>>,
+    literals: Option<mpsc::UnboundedReceiver<LiteralChunk>>,
+}
+
+impl ResponseStream {
+    /// A stream yielding only `e`, for commands that cannot be sent.
+    pub(crate) fn error(e: Error) -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        let _ = sender.unbounded_send(Err(e));
+        Self {
+            receiver,
+            literals: None,
+        }
+    }
+
+    /// The parts of the literals in the `FETCH` responses to the command, as
+    /// they are received, if enabled with `Connection::stream_literals()`.
+    ///
+    /// Each literal is streamed before the response holding it is yielded by
+    /// this stream, so the chunks can be consumed concurrently. They are kept
+    /// until consumed, and the stream ends once the command completes. Only the
+    /// first call returns the chunks; later calls return an empty stream.
+    pub fn literals(&mut self) -> LiteralChunks {
+        LiteralChunks {
+            receiver: self.literals.take(),
+        }
+    }
+}
+
+impl Stream for ResponseStream {
+    type Item = Result<ResponseData, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let item = ready!(self.receiver.poll_next_unpin(cx));
+        Poll::Ready(item.map(|rsp| {
+            rsp.map(|mut rsp| {
+                // The response no longer counts against the connection's limit
+                rsp.ticket = None;
+                rsp
+            })
+        }))
+    }
+}
+
+// RFC 2177 requires clients to renew IDLE at least every 29 minutes
+const IDLE_RENEWAL: Duration = Duration::from_secs(29 * 60);
+
+/// The responses to an `IDLE` command, created by `Client::idle()`.
+///
+/// After `done()` has been called, the stream ends with the tagged completion
+/// response. Dropping the stream ends the command in the background.
+pub struct Idle {
+    client: Client,
+    responses: ResponseStream,
+    /// The `IDLE` command queued to replace the current one, which is ending.
+    renewal: Option<ResponseStream>,
+    renew_after: Duration,
+    timer: Delay,
+    done: bool,
+}
+
+impl Idle {
+    /// End the command and start a new one after `interval` (29 minutes by
+    /// default). This is invisible to the consumer of the stream, except for the
+    /// few responses that the server may send in between, which are reported as
+    /// unsolicited responses.
+    ///
+    /// Many servers and middleboxes drop connections that have been idle for much
+    /// shorter than the 30 minutes allowed by RFC 2177, such as 5 or 10 minutes.
+    pub fn renew_after(mut self, interval: Duration) -> Self {
+        self.renew_after = interval;
+        self.timer.reset(Instant::now() + interval);
+        self
+    }
+
+    /// End the command by sending `DONE`.
+    pub fn done(&mut self) {
+        if !self.done {
+            self.done = true;
+            // If renewing, DONE was already sent for the current command and this
+            // one ends the renewal
+            let _ = self.client.sender.unbounded_send(Message::Done);
+        }
+    }
+}
+
+impl Stream for Idle {
+    type Item = Result<ResponseData, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let me = &mut *self;
+        loop {
+            if !me.done && me.renewal.is_none() && Pin::new(&mut me.timer).poll(cx).is_ready() {
+                let _ = me.client.sender.unbounded_send(Message::Done);
+                me.renewal = Some(me.client.call(CommandBuilder::idle()));
+            }
+
+            match ready!(me.responses.poll_next_unpin(cx)) {
+                Some(Ok(rsp)) if rsp.request_id().is_some() => match me.renewal.take() {
+                    Some(renewal) => {
+                        me.responses = renewal;
+                        me.timer.reset(Instant::now() + me.renew_after);
+                    }
+                    None => return Poll::Ready(Some(Ok(rsp))),
+                },
+                item => return Poll::Ready(item),
+            }
+        }
+    }
+}
+
+impl Drop for Idle {
+    fn drop(&mut self) {
+        self.done();
+    }
+}
+
+/// The parts of literals received for a command, created by
+/// `ResponseStream::literals()`.
+pub struct LiteralChunks {
+    receiver: Option<mpsc::UnboundedReceiver<LiteralChunk>>,
+}
+
+impl Stream for LiteralChunks {
+    type Item = LiteralChunk;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        match &mut self.receiver {
+            Some(receiver) => receiver.poll_next_unpin(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// Responses sent by the server outside of the context of any command.
+///
+/// Created by `Client::unsolicited()` or `Connection::unsolicited()`.
+pub struct UnsolicitedResponses {
+    receiver: mpsc::UnboundedReceiver<ResponseData>,
+}
+
+impl Stream for UnsolicitedResponses {
+    type Item = ResponseData;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+enum Message {
+    Command(PendingCommand),
+    Subscribe(mpsc::UnboundedSender<ResponseData>),
+    /// End the `IDLE` command in progress.
+    Done,
+}
+
+struct PendingCommand {
     cmd: Command,
-    state: ResponseStreamState,
+    responses: mpsc::UnboundedSender<Result<ResponseData, Error>>,
+    literals: mpsc::UnboundedSender<LiteralChunk>,
+    #[cfg(feature = "tracing")]
+    span: CommandSpan,
+}
+
+/// A command handed to the protocol, awaiting completion.
+struct SentCommand {
+    request_id: RequestId,
+    responses: mpsc::UnboundedSender<Result<ResponseData, Error>>,
+    literals: mpsc::UnboundedSender<LiteralChunk>,
+    #[cfg(feature = "tracing")]
+    span: CommandSpan,
+}
+
+impl SentCommand {
+    fn complete(self, rsp: ResponseData) {
+        #[cfg(feature = "tracing")]
+        self.span.completed(&rsp);
+        let _ = self.responses.unbounded_send(Ok(rsp));
+    }
+
+    fn fail(self, err: Error) {
+        #[cfg(feature = "tracing")]
+        self.span.failed(&err);
+        let _ = self.responses.unbounded_send(Err(err));
+    }
+}
+
+/// Drives the I/O for a connection to an IMAP server.
+///
+/// Commands submitted through the associated `Client` handles are written to the
+/// server, and responses are routed back to the commands they belong to (see
+/// `Protocol` for the routing rules). The connection resolves once all `Client`
+/// handles have been dropped and all outstanding commands have completed, or
+/// when the server closes the connection.
+///
+/// Responses that cannot be attributed to any command are broadcast to all
+/// subscribers registered through `unsolicited()`.
+pub struct Connection<T> {
+    transport: Transport<T>,
+    session: Arc<Mutex<Session>>,
+    receiver: Option<mpsc::UnboundedReceiver<Message>>,
+    /// Commands (and `DONE` continuations) not yet handed to the protocol, in order.
+    held: VecDeque<Message>,
+    pending: VecDeque<SentCommand>,
+    subscribers: Vec<mpsc::UnboundedSender<ResponseData>>,
+    /// The reason given by the server in an unsolicited `BYE` response.
+    bye: Option<String>,
+    /// Set once the session has ended, to close the stream.
+    closing: bool,
+    throttle: Option<Throttle>,
+    backlog: Option<Arc<Backlog>>,
+    observer: Option<Arc<dyn Observer>>,
+    /// The `ID` command to send once authenticated, see `identify()`.
+    identify: Option<Command>,
+    /// The responses to that command, drained as they arrive.
+    id_responses: Option<mpsc::UnboundedReceiver<Result<ResponseData, Error>>>,
+    /// Held while the connection is open, if opened through a `ConnectionLimiter`.
+    pub(crate) permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<T> Connection<T> {
+    /// Subscribe to responses that are not attributed to any command.
+    ///
+    /// Like `Client::unsolicited()`, but usable before the connection is spawned,
+    /// so that no unsolicited responses are missed.
+    pub fn unsolicited(&mut self) -> UnsolicitedResponses {
+        let (sender, receiver) = mpsc::unbounded();
+        self.subscribers.push(sender);
+        UnsolicitedResponses { receiver }
+    }
+
+    /// Record all commands sent and responses received from now on.
+    ///
+    /// The greeting has already been received at this point, so it is not included.
+    pub fn trace(&mut self, trace: Trace) {
+        self.transport.protocol.trace(trace);
+    }
+
+    /// Size (and optionally pool) the buffer for data received from the server
+    /// according to `policy`.
+    pub fn buffer_policy(&mut self, policy: BufferPolicy) {
+        self.transport.buffer_policy(policy);
+    }
+
+    /// Write large literals (such as message bodies) received from now on to
+    /// temporary files or a sink, as configured by `spill`, rather than keeping
+    /// them in memory. Use `ResponseData::spilled()` to find them.
+    ///
+    /// Note that `Message::body()` returns an empty message for spilled bodies.
+    pub fn spill_literals(&mut self, spill: Spill) {
+        self.transport.protocol.spill_literals(spill);
+    }
+
+    /// Deliver the content of literals of at least `min_len` bytes in `FETCH`
+    /// responses (such as message bodies) received from now on as it arrives,
+    /// before the response is complete, through `ResponseStream::literals()`.
+    ///
+    /// The complete responses are still yielded once received. Literals written
+    /// elsewhere with `spill_literals()` are not streamed.
+    pub fn stream_literals(&mut self, min_len: u64) {
+        self.transport.protocol.stream_literals(min_len);
+    }
+
+    /// Accept responses from servers that deviate from the protocol in the ways
+    /// selected by `quirks`, such as `Quirks::lenient()`, rather than failing
+    /// with a parse error.
+    pub fn quirks(&mut self, quirks: Quirks) {
+        self.transport.quirks(quirks);
+    }
+
+    /// Send an `ID` command with `fields` (RFC 2971) as soon as the session is
+    /// authenticated, if the server supports it, so that the server identity is
+    /// available from `Session::server_id()` without a round trip by the caller.
+    pub fn identify(&mut self, fields: &[(&str, Option<&str>)]) {
+        self.identify = Some(CommandBuilder::id(fields));
+    }
+
+    /// Enable the quirks known to be needed by the server, as identified by its
+    /// `ID` response, in addition to those set with `quirks()`. See
+    /// `Quirks::for_server()`.
+    pub fn auto_quirks(&mut self, enabled: bool) {
+        self.transport.protocol.auto_quirks(enabled);
+    }
+
+    /// Fail with `Error::Framing` if the server sends a line longer than `max`
+    /// bytes, not counting the literals following it, rather than buffering it
+    /// until it is complete. This protects long-running clients from servers
+    /// sending endless lines.
+    pub fn max_line_length(&mut self, max: usize) {
+        self.transport.max_line_length(max);
+    }
+
+    /// Fail with `Error::Framing` if the server terminates a line with a bare
+    /// LF rather than CRLF, even if the quirks accept it.
+    pub fn strict_crlf(&mut self, strict: bool) {
+        self.transport.strict_crlf(strict);
+    }
+
+    /// Report the activity of the connection to `observer` from now on.
+    pub fn observe(&mut self, observer: Arc<dyn Observer>) {
+        observer.connected();
+        self.transport.protocol.observe(observer.clone());
+        self.observer = Some(observer);
+    }
+
+    /// Send at most `commands` commands every `per`, on average.
+    ///
+    /// Commands are held back in the order they were issued until they can be
+    /// sent; up to `commands` commands can be sent at once after a quiet period.
+    /// This avoids exceeding the command rate limits imposed by some providers.
+    /// Requires the Tokio timer.
+    pub fn throttle(&mut self, commands: u32, per: Duration) {
+        self.throttle = Some(Throttle::new(commands, per));
+    }
+
+    /// Stop reading from the server while the responses routed to commands but
+    /// not yet consumed from their `ResponseStream` reach `responses` in number,
+    /// or `bytes` in total size (including literals).
+    ///
+    /// This bounds the memory used by responses when their consumer is slower
+    /// than the server, such as during a large `FETCH`. The limits are checked
+    /// before each read, so they can be exceeded by the responses received in a
+    /// single read. Unsolicited responses do not count; note that the responses
+    /// to a command must be consumed for the responses to later commands to be
+    /// received.
+    pub fn limit_unconsumed(&mut self, responses: usize, bytes: usize) {
+        self.backlog = Some(Backlog::new(responses, bytes));
+    }
+
+    /// Hand held commands to the protocol, as far as the throttle allows.
+    fn release(&mut self, cx: &mut Context) {
+        while let Some(msg) = self.held.front() {
+            if let Message::Command(pending) = msg {
+                if !pending.responses.is_closed() {
+                    if let Some(throttle) = &mut self.throttle {
+                        if throttle.poll_acquire(cx).is_pending() {
+                            return;
+                        }
+                    }
+                }
+            }
+
+            // Commands whose response stream was dropped are skipped by the protocol
+            match self.held.pop_front() {
+                Some(Message::Command(pending)) => {
+                    let request_id = self.transport.protocol.enqueue(pending.cmd);
+                    #[cfg(feature = "tracing")]
+                    pending.span.sent(&request_id, self.transport.session());
+                    self.pending.push_back(SentCommand {
+                        request_id,
+                        responses: pending.responses,
+                        literals: pending.literals,
+                        #[cfg(feature = "tracing")]
+                        span: pending.span,
+                    });
+                }
+                Some(Message::Done) => {
+                    self.transport.protocol.done();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn broadcast(&mut self, rsp: ResponseData) {
+        self.subscribers
+            .retain(|subscriber| subscriber.unbounded_send(rsp.clone()).is_ok());
+    }
+
+    /// Deliver the responses processed by the protocol to their recipients.
+    fn dispatch(&mut self) {
+        while let Some(event) = self.transport.protocol.poll_event() {
+            let (request_id, mut rsp) = match event {
+                Event::Response(request_id, rsp) => (request_id, rsp),
+                Event::Unsolicited(rsp) => {
+                    if let Response::Data {
+                        status: Status::Bye,
+                        information,
+                        ..
+                    } = rsp.parsed()
+                    {
+                        self.bye = Some(information.unwrap_or_default().to_string());
+                    }
+                    self.broadcast(rsp);
+                    continue;
+                }
+                Event::Literal(chunk) => {
+                    let command = chunk
+                        .request_id()
+                        .and_then(|id| self.pending.iter().find(|c| &c.request_id == id));
+                    if let Some(command) = command {
+                        let _ = command.literals.unbounded_send(chunk);
+                    }
+                    continue;
+                }
+            };
+
+            let idx = match self.pending.iter().position(|c| c.request_id == request_id) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            if let Some(backlog) = &self.backlog {
+                rsp.ticket = Some(backlog.ticket(&rsp));
+            }
+            if rsp.request_id().is_some() {
+                self.pending.remove(idx).unwrap().complete(rsp); // safe: index found above
+            } else {
+                let _ = self.pending[idx].responses.unbounded_send(Ok(rsp));
+            }
+        }
+        if let Some(responses) = &mut self.id_responses {
+            loop {
+                match responses.try_recv() {
+                    Ok(_) => continue,
+                    Err(mpsc::TryRecvError::Closed) => self.id_responses = None,
+                    Err(mpsc::TryRecvError::Empty) => {}
+                }
+                break;
+            }
+        }
+
+        let session = self.transport.session();
+        if session.state() == State::Logout && self.transport.is_idle() {
+            self.closing = true;
+        }
+        let authenticated = matches!(session.state(), State::Authenticated | State::Selected);
+        if authenticated && self.identify.is_some() && !session.capabilities_stale() {
+            if session.has_capability("ID") {
+                let cmd = self.identify.take().unwrap(); // safe: checked above
+                let (sender, receiver) = mpsc::unbounded();
+                #[cfg(feature = "tracing")]
+                let span = CommandSpan::new(&cmd);
+                self.held.push_front(Message::Command(PendingCommand {
+                    cmd,
+                    responses: sender,
+                    literals: mpsc::unbounded().0,
+                    #[cfg(feature = "tracing")]
+                    span,
+                }));
+                self.id_responses = Some(receiver);
+            } else {
+                self.identify = None;
+            }
+        }
+
+        *self.session.lock().unwrap() = session.clone();
+    }
+
+    /// Fail all outstanding commands with `err`, or with `Error::Bye` if the server
+    /// said goodbye.
+    fn fail_all(&mut self, err: &io::Error) {
+        let error = |bye: &Option<String>| match bye {
+            Some(reason) => Error::Bye(reason.clone()),
+            None => Error::from_io_ref(err),
+        };
+        for cmd in self.pending.drain(..) {
+            cmd.fail(error(&self.bye));
+        }
+        for msg in self.held.drain(..) {
+            if let Message::Command(pending) = msg {
+                let e = error(&self.bye);
+                #[cfg(feature = "tracing")]
+                pending.span.failed(&e);
+                let _ = pending.responses.unbounded_send(Err(e));
+            }
+        }
+    }
 }
 
-impl<'a, T> Stream for ResponseStream<'a, T>
+impl<T> Future for Connection<T>
 where
     T: AsyncRead + AsyncWrite + Unpin,
 {
-    type Item = Result<ResponseData, io::Error>;
+    type Output = Result<(), Error>;
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        let mut me = self.project();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let me = self.get_mut();
+        let result = ready!(me.poll_drive(cx)).map_err(Error::from);
+        if let Some(observer) = me.observer.take() {
+            observer.disconnected(result.as_ref().err());
+        }
+        Poll::Ready(result)
+    }
+}
+
+impl<T> Connection<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_drive(&mut self, cx: &mut Context) -> Poll<Result<(), io::Error>> {
         loop {
-            match me.state {
-                ResponseStreamState::Start => {
-                    ready!(Pin::new(&mut me.client.transport).poll_ready(cx))?;
-                    let pinned = Pin::new(&mut me.client.transport);
-                    pinned.start_send(&Request(me.request_id.as_bytes(), &me.cmd.args))?;
-                    *me.state = ResponseStreamState::Sending;
-                }
-                ResponseStreamState::Sending => {
-                    let pinned = Pin::new(&mut me.client.transport);
-                    ready!(pinned.poll_flush(cx))?;
-                    *me.state = ResponseStreamState::Receiving;
-                }
-                ResponseStreamState::Receiving => {
-                    match ready!(Pin::new(&mut me.client.transport).poll_next(cx)) {
-                        Some(Ok(rsp)) => {
-                            match rsp.request_id() {
-                                Some(req_id) if req_id == me.request_id => {}
-                                Some(_) | None => return Poll::Ready(Some(Ok(rsp))),
-                            }
-
-                            if let Some(next_state) = me.cmd.next_state.as_ref() {
-                                me.client.state = *next_state;
-                            }
-                            *me.state = ResponseStreamState::Done;
-                            return Poll::Ready(Some(Ok(rsp)));
+            if let Some(receiver) = &mut self.receiver {
+                loop {
+                    match receiver.poll_next_unpin(cx) {
+                        Poll::Ready(Some(Message::Subscribe(subscriber))) => {
+                            self.subscribers.push(subscriber)
                         }
-                        Some(Err(e)) => return Poll::Ready(Some(Err(e))),
-                        None => {
-                            return Poll::Ready(Some(Err(io::Error::new(
-                                io::ErrorKind::Other,
-                                "stream ended before command completion",
-                            ))))
+                        Poll::Ready(Some(msg)) => self.held.push_back(msg),
+                        Poll::Ready(None) => {
+                            self.receiver = None;
+                            break;
                         }
+                        Poll::Pending => break,
                     }
                 }
-                ResponseStreamState::Done => {
-                    return Poll::Ready(None);
+            }
+            self.release(cx);
+
+            // Skip commands whose response stream was dropped before they were sent
+            let protocol = &mut self.transport.protocol;
+            self.pending
+                .retain(|c| !(c.responses.is_closed() && protocol.cancel(&c.request_id)));
+
+            if self.closing {
+                self.fail_all(&io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "connection closed",
+                ));
+                return self.transport.poll_shutdown(cx);
+            }
+
+            if let Poll::Ready(Err(e)) = self.transport.poll_send(cx) {
+                self.fail_all(&e);
+                return Poll::Ready(Err(e));
+            }
+
+            if self.receiver.is_none() && self.held.is_empty() && self.transport.is_idle() {
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Some(backlog) = &self.backlog {
+                ready!(backlog.poll_capacity(cx));
+            }
+            match ready!(self.transport.poll_receive(cx)) {
+                Ok(true) => self.dispatch(),
+                Ok(false) => {
+                    self.dispatch();
+                    self.fail_all(&io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "stream ended before command completion",
+                    ));
+                    return Poll::Ready(Ok(()));
+                }
+                Err(e) => {
+                    self.dispatch();
+                    self.fail_all(&e);
+                    return Poll::Ready(Err(e));
                 }
             }
         }
     }
 }
 
-enum ResponseStreamState {
-    Start,
-    Sending,
-    Receiving,
-    Done,
+/// The capabilities of which at least one must be advertised for the server
+/// to support the command, if the command is part of an extension.
+fn required_capability(cmd: &Command) -> Option<&'static [&'static str]> {
+    Some(match command_name(cmd).as_slice() {
+        b"COMPRESS" => &["COMPRESS=DEFLATE"],
+        b"ENABLE" => &["ENABLE"],
+        b"DELETEACL" | b"GETACL" | b"LISTRIGHTS" | b"MYRIGHTS" | b"SETACL" => &["ACL"],
+        b"GETMETADATA" | b"SETMETADATA" => &["METADATA", "METADATA-SERVER"],
+        b"GETQUOTA" | b"GETQUOTAROOT" | b"SETQUOTA" => &["QUOTA"],
+        b"ID" => &["ID"],
+        b"IDLE" => &["IDLE"],
+        b"MOVE" | b"UID MOVE" => &["MOVE"],
+        b"NAMESPACE" => &["NAMESPACE"],
+        b"SORT" | b"UID SORT" => &["SORT"],
+        b"STARTTLS" => &["STARTTLS"],
+        b"UID EXPUNGE" => &["UIDPLUS"],
+        b"UNSELECT" => &["UNSELECT"],
+        _ => return None,
+    })
 }
 
-pub struct IdGenerator {
-    next: u64,
-}
+#[cfg(test)]
+mod tests {
+    use super::{Client, ResponseStream};
+    use crate::error::check_completion;
+    use crate::testing::{MockServer, Script};
+    use crate::{Error, ResponseData};
+    use futures::StreamExt;
+    use imap_proto::builders::command::{Command, CommandBuilder};
+    use imap_proto::{Attribute, MailboxDatum, Response, ResponseCode, SequenceSet, State};
+    use std::time::Duration;
 
-impl IdGenerator {
-    pub fn new() -> Self {
-        Self { next: 0 }
+    async fn collect_errors(responses: ResponseStream) -> Vec<Error> {
+        responses
+            .filter_map(|rsp| async { rsp.err() })
+            .collect()
+            .await
     }
-}
 
-impl Default for IdGenerator {
-    fn default() -> Self {
-        Self::new()
+    async fn collect(mut responses: ResponseStream) -> Vec<ResponseData> {
+        let mut collected = Vec::new();
+        while let Some(rsp) = responses.next().await {
+            collected.push(rsp.unwrap());
+        }
+        collected
     }
-}
 
-impl Iterator for IdGenerator {
-    type Item = RequestId;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next += 1;
-        Some(RequestId(format!("A{:04}", self.next % 10_000)))
+    #[tokio::test]
+    async fn pipelined_responses() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 FETCH 1 (UID)")
+            .expect("A0002 LIST \"\" \"*\"")
+            .send("* LIST () \"/\" INBOX\r\n")
+            .send("* 1 FETCH (UID 10)\r\n")
+            .send("* 4 EXISTS\r\n")
+            .send("A0002 OK LIST completed\r\n")
+            .send("A0001 OK FETCH completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, mut connection) = Client::from_stream(stream).await.unwrap();
+        let mut unsolicited = connection.unsolicited();
+        tokio::spawn(connection);
+
+        let fetch = client.call(
+            CommandBuilder::fetch()
+                .num(1)
+                .attr(imap_proto::Attribute::Uid),
+        );
+        let list = client.call(CommandBuilder::list("", "*"));
+        server.finish().await.unwrap();
+        let (fetch, list) = (collect(fetch).await, collect(list).await);
+
+        assert_eq!(fetch.len(), 2);
+        assert!(matches!(fetch[0].parsed(), Response::Fetch(1, _)));
+        assert_eq!(list.len(), 2);
+        assert!(matches!(
+            list[0].parsed(),
+            Response::MailboxData(MailboxDatum::List { .. })
+        ));
+        let rsp = unsolicited.next().await.unwrap();
+        assert!(matches!(
+            rsp.parsed(),
+            Response::MailboxData(MailboxDatum::Exists(4))
+        ));
+    }
+
+    #[tokio::test]
+    async fn synchronizing_literal() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 LOGIN \"djc\" {5}")
+            .send("+ ready for literal\r\n")
+            .expect(b"p\xc3\xa4ss")
+            .send("A0001 OK logged in\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, connection) = Client::from_stream(stream).await.unwrap();
+        tokio::spawn(connection);
+
+        let responses = client.call(CommandBuilder::login("djc", "päss"));
+        server.finish().await.unwrap();
+        assert_eq!(collect(responses).await.len(), 1);
+        assert_eq!(client.session().state(), State::Authenticated);
+    }
+
+    #[tokio::test]
+    async fn non_synchronizing_literal() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 LITERAL+] ready\r\n")
+            .expect("A0001 LOGIN \"djc\" {5+}")
+            .expect(b"p\xc3\xa4ss")
+            .send("A0001 OK logged in\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, connection) = Client::from_stream(stream).await.unwrap();
+        tokio::spawn(connection);
+
+        let responses = client.call(CommandBuilder::login("djc", "päss"));
+        server.finish().await.unwrap();
+        assert_eq!(collect(responses).await.len(), 1);
+    }
+
+    #[cfg(feature = "futures-io")]
+    #[tokio::test]
+    async fn futures_io_stream() {
+        use tokio_util::compat::Tokio02AsyncReadCompatExt;
+
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 CHECK")
+            .send("A0001 OK CHECK completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        // Turn the Tokio stream into one implementing the `futures` I/O traits
+        let stream = server.connect().await.unwrap().compat();
+        let (_, client, connection) = Client::from_futures_stream(stream).await.unwrap();
+        tokio::spawn(connection);
+
+        let responses = client.call(CommandBuilder::check());
+        server.finish().await.unwrap();
+        assert_eq!(collect(responses).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn logout() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 LOGOUT")
+            .send("* BYE see you\r\nA0001 OK LOGOUT completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, connection) = Client::from_stream(stream).await.unwrap();
+        let connection = tokio::spawn(connection);
+
+        client.logout().await.unwrap();
+        connection.await.unwrap().unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(client.session().state(), State::Logout);
+        assert!(matches!(
+            collect_errors(client.call(CommandBuilder::check())).await[..],
+            [Error::Io(_)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn typed_errors() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 SELECT \"Archive\"")
+            .send("A0001 NO [TRYCREATE] No such mailbox\r\n")
+            .expect("A0002 CHECK")
+            .send("* FOO\x01\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, connection) = Client::from_stream(stream).await.unwrap();
+        let connection = tokio::spawn(connection);
+
+        let mut select = client.call(CommandBuilder::select("Archive"));
+        let rsp = select.next().await.unwrap().unwrap();
+        match check_completion(&rsp) {
+            Err(Error::No(e)) => {
+                assert_eq!(e.text(), "No such mailbox");
+                assert_eq!(e.code(), Some(&ResponseCode::TryCreate));
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let errors = collect_errors(client.call(CommandBuilder::check())).await;
+        server.finish().await.unwrap();
+        match &errors[..] {
+            [Error::Parse(e)] => assert_eq!(e.raw(), b"* FOO\x01\r\n"),
+            errors => panic!("unexpected errors {:?}", errors),
+        }
+        assert!(matches!(connection.await.unwrap(), Err(Error::Parse(_))));
+    }
+
+    #[tokio::test]
+    async fn unsolicited_bye() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 CHECK")
+            .send("* BYE server shutting down\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, connection) = Client::from_stream(stream).await.unwrap();
+        tokio::spawn(connection);
+
+        let responses = client.call(CommandBuilder::check());
+        server.finish().await.unwrap();
+        match &collect_errors(responses).await[..] {
+            [Error::Bye(reason)] => assert_eq!(reason, "server shutting down"),
+            errors => panic!("unexpected errors {:?}", errors),
+        }
+    }
+
+    #[tokio::test]
+    async fn broadcast_unsolicited() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 NOOP")
+            .send("* 3 EXISTS\r\n* 1 RECENT\r\nA0001 OK NOOP completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, mut connection) = Client::from_stream(stream).await.unwrap();
+        let first = connection.unsolicited();
+        drop(connection.unsolicited());
+        tokio::spawn(connection);
+
+        // Subscribed before the command is sent, so no responses are missed
+        let second = client.unsolicited();
+        let noop = client.call(CommandBuilder::noop());
+        assert_eq!(collect(noop).await.len(), 1);
+        server.finish().await.unwrap();
+
+        // Both streams end once the server closed the connection
+        for subscriber in [first, second] {
+            let responses = subscriber.collect::<Vec<_>>().await;
+            assert_eq!(responses.len(), 2);
+            assert!(matches!(
+                responses[0].parsed(),
+                Response::MailboxData(MailboxDatum::Exists(3))
+            ));
+            assert!(matches!(
+                responses[1].parsed(),
+                Response::MailboxData(MailboxDatum::Recent(1))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_in_flight() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 FETCH 1:2 (UID)")
+            .send("* 1 FETCH (UID 10)\r\n")
+            .expect("A0002 CHECK")
+            .send("* 2 FETCH (UID 20)\r\n")
+            .send("A0001 OK FETCH completed\r\n")
+            .send("A0002 OK CHECK completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, connection) = Client::from_stream(stream).await.unwrap();
+        tokio::spawn(connection);
+
+        let mut fetch = client.call(
+            CommandBuilder::fetch()
+                .range(1..=2)
+                .attr(imap_proto::Attribute::Uid),
+        );
+        fetch.next().await.unwrap().unwrap();
+        drop(fetch);
+
+        let check = client.call(CommandBuilder::check());
+        server.finish().await.unwrap();
+        let responses = collect(check).await;
+        assert_eq!(responses.len(), 1);
+        assert!(matches!(responses[0].parsed(), Response::Done { .. }));
+    }
+
+    #[tokio::test]
+    async fn idle_renewal() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 IDLE] ready\r\n")
+            .expect("A0001 IDLE")
+            .send("+ idling\r\n")
+            .expect("DONE")
+            .send("A0001 OK IDLE terminated\r\n")
+            .expect("A0002 IDLE")
+            .send("+ idling\r\n* 4 EXISTS\r\n")
+            .expect("DONE")
+            .send("A0002 OK IDLE terminated\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let mut idle = client.idle().renew_after(Duration::from_millis(20));
+        let rsp = idle.next().await.unwrap().unwrap();
+        assert!(matches!(
+            rsp.parsed(),
+            Response::MailboxData(MailboxDatum::Exists(4))
+        ));
+        idle.done();
+        let rsp = idle.next().await.unwrap().unwrap();
+        assert!(rsp.request_id().is_some());
+        assert!(idle.next().await.is_none());
+        server.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn capabilities_after_login() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 AUTH=PLAIN] ready\r\n")
+            .expect("A0001 LOGIN \"djc\" \"pass\"")
+            .send("A0001 OK logged in\r\n")
+            .expect("A0002 CAPABILITY")
+            .send(
+                "* CAPABILITY IMAP4rev1 NAMESPACE\r\n\
+                 A0002 OK CAPABILITY completed\r\n",
+            )
+            .expect("A0003 NAMESPACE")
+            .send(
+                "* NAMESPACE ((\"\" \"/\")) NIL NIL\r\n\
+                 A0003 OK NAMESPACE completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        collect(client.call(CommandBuilder::login("djc", "pass"))).await;
+        // Not rejected based on the capabilities advertised before login
+        let namespace = collect(client.call(CommandBuilder::namespace())).await;
+        server.finish().await.unwrap();
+        assert_eq!(namespace.len(), 2);
+        let session = client.session();
+        assert!(session.has_capability("NAMESPACE"));
+        assert!(!session.has_capability("AUTH=PLAIN"));
+    }
+
+    #[tokio::test]
+    async fn drop_before_sending() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 LOGIN \"djc\" \"pass\"")
+            .send("A0001 OK [CAPABILITY IMAP4rev1] logged in\r\n")
+            .expect("A0003 CHECK")
+            .send("A0003 OK CHECK completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, connection) = Client::from_stream(stream).await.unwrap();
+        tokio::spawn(connection);
+
+        // LOGIN is never pipelined, so the first CHECK is queued until it completes
+        let login = client.call(CommandBuilder::login("djc", "pass"));
+        drop(client.call(CommandBuilder::check()));
+        collect(login).await;
+
+        let check = client.call(CommandBuilder::check());
+        server.finish().await.unwrap();
+        assert_eq!(collect(check).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn streamed_literals() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 FETCH 1 (RFC822)")
+            .send("* 1 FETCH (BODY[] {11}\r\nHello")
+            .expect("A0002 NOOP")
+            .send(" world)\r\nA0001 OK FETCH completed\r\nA0002 OK NOOP completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, mut connection) = Client::from_stream(stream).await.unwrap();
+        connection.stream_literals(4);
+        tokio::spawn(connection);
+
+        // The start of the body is delivered while the server waits for NOOP
+        let mut responses = client.call(CommandBuilder::fetch().num(1).attr(Attribute::Rfc822));
+        let mut literals = responses.literals();
+        let chunk = literals.next().await.unwrap();
+        assert_eq!((chunk.message(), chunk.attribute()), (1, "BODY[]"));
+        assert_eq!(&chunk.data()[..], b"Hello");
+        let noop = client.call(CommandBuilder::noop());
+
+        let chunk = literals.next().await.unwrap();
+        assert_eq!((chunk.offset(), &chunk.data()[..]), (5, &b" world"[..]));
+        assert!(chunk.is_last());
+        assert!(literals.next().await.is_none());
+        assert!(responses.literals().next().await.is_none());
+
+        let fetched = collect(responses).await;
+        assert!(matches!(fetched[0].parsed(), Response::Fetch(1, _)));
+        assert_eq!(fetched.len(), 2);
+        assert_eq!(collect(noop).await.len(), 1);
+        server.finish().await.unwrap();
+    }
+
+    async fn call_gated(greeting: &str, cmd: Command) -> Vec<Error> {
+        let script = Script::new().send(greeting);
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let errors = collect_errors(client.call(cmd)).await;
+        server.finish().await.unwrap();
+        errors
+    }
+
+    #[tokio::test]
+    async fn missing_capability() {
+        let greeting = "* OK [CAPABILITY IMAP4rev1 UIDPLUS] ready\r\n";
+        let set = SequenceSet::from(4..=5);
+        match &call_gated(greeting, CommandBuilder::uid_move(&set, "Trash")).await[..] {
+            [Error::MissingCapability("MOVE")] => {}
+            errors => panic!("unexpected errors: {:?}", errors),
+        }
+        match &call_gated(greeting, CommandBuilder::idle()).await[..] {
+            [Error::MissingCapability("IDLE")] => {}
+            errors => panic!("unexpected errors: {:?}", errors),
+        }
+    }
+
+    #[tokio::test]
+    async fn advertised_capability() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 MOVE IDLE] ready\r\n")
+            .expect("A0001 UID MOVE 4:5 \"Trash\"")
+            .send("A0001 OK MOVE completed\r\n")
+            .expect("A0002 IDLE")
+            .send("+ idling\r\n")
+            .expect("DONE")
+            .send("A0002 OK IDLE terminated\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let set = SequenceSet::from(4..=5);
+        assert_eq!(
+            collect(client.call(CommandBuilder::uid_move(&set, "Trash")))
+                .await
+                .len(),
+            1
+        );
+        let mut idle = client.idle();
+        idle.done();
+        assert!(idle.next().await.unwrap().unwrap().request_id().is_some());
+        server.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unchecked_capability() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 UID MOVE 4:5 \"Trash\"")
+            .send("A0001 OK MOVE completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let set = SequenceSet::from(4..=5);
+        let cmd = CommandBuilder::uid_move(&set, "Trash");
+        assert_eq!(collect(client.call_unchecked(cmd)).await.len(), 1);
+        server.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_capabilities() {
+        // Without capabilities in the greeting, commands are sent unchecked
+        let script = Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 UID MOVE 4:5 \"Trash\"")
+            .send("A0001 NO [CANNOT] No MOVE here\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let set = SequenceSet::from(4..=5);
+        let responses = collect(client.call(CommandBuilder::uid_move(&set, "Trash"))).await;
+        assert!(check_completion(&responses[0]).is_err());
+        server.finish().await.unwrap();
     }
 }
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::io;
 use std::mem;
 
@@ -5,27 +6,138 @@ use bytes::{BufMut, Bytes, BytesMut};
 use nom::{self, Needed};
 use tokio_util::codec::{Decoder, Encoder};
 
-use imap_proto::types::{Request, RequestId, Response};
+use crate::backlog::Ticket;
+use crate::error::{FramingError, ParseError};
+use crate::spill::{self, SpilledLiteral};
+use imap_proto::parser::{parse_response_with, Quirks};
+use imap_proto::types::{Content, Request, RequestId, Response};
 
+/// Framing for the client side of an IMAP connection.
+///
+/// The decoder yields one `ResponseData` per complete server response, including
+/// any literals it contains; the encoder writes a tagged `Request` followed by
+/// CRLF. Together with `tokio_util::codec::Framed` this gives a low-level
+/// `Stream` of responses and `Sink` of requests, for building custom clients:
+///
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use std::net::SocketAddr;
+///
+/// use futures::{SinkExt, StreamExt};
+/// use tokio::net::TcpStream;
+/// use tokio_imap::types::Request;
+/// use tokio_imap::ImapCodec;
+/// use tokio_util::codec::Decoder;
+///
+/// let stream = TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], 143))).await?;
+/// let mut framed = ImapCodec::default().framed(stream);
+/// let greeting = framed.next().await.unwrap()?;
+/// println!("{:?}", greeting.parsed());
+///
+/// framed.send(&Request(b"A1", b"CAPABILITY")).await?;
+/// while let Some(rsp) = framed.next().await {
+///     let rsp = rsp?;
+///     if rsp.request_id().is_some() {
+///         break;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Commands that contain literals must be sent in several parts: everything up
+/// to and including the first literal header as a `Request`, then (after the
+/// server's continuation request, unless the literal is non-synchronizing) the
+/// literal data and the rest of the command up to the next literal as a `&[u8]`.
 #[derive(Default)]
 pub struct ImapCodec {
     decode_need_message_bytes: usize,
+    quirks: Quirks,
+    max_line_length: Option<usize>,
+    strict_crlf: bool,
+    /// How far the buffer was checked against the line limits, past the
+    /// literals announced in it.
+    checked: usize,
 }
 
-impl<'a> Decoder for ImapCodec {
+impl ImapCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept the deviations from the protocol selected by `quirks` in the
+    /// responses decoded from now on.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Fail with a `FramingError` on lines longer than `max` bytes, not
+    /// counting the literals following them, or accept lines of any length if
+    /// `None` (the default).
+    pub fn set_max_line_length(&mut self, max: Option<usize>) {
+        self.max_line_length = max;
+    }
+
+    /// Fail with a `FramingError` on lines terminated by a bare LF, even if
+    /// the quirks accept them, rather than with a parse error.
+    pub fn set_strict_crlf(&mut self, strict: bool) {
+        self.strict_crlf = strict;
+    }
+
+    /// Check the lines received since the last call against the limits.
+    fn check_lines(&mut self, buf: &[u8]) -> Result<(), FramingError> {
+        if self.max_line_length.is_none() && !self.strict_crlf {
+            return Ok(());
+        }
+        while self.checked < buf.len() {
+            let rest = &buf[self.checked..];
+            let (line, complete) = match rest.iter().position(|&b| b == b'\n') {
+                Some(lf) => (&rest[..=lf], true),
+                None => (rest, false),
+            };
+            let content = line.strip_suffix(b"\n").unwrap_or(line);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            if let Some(max) = self.max_line_length {
+                if content.len() > max {
+                    return Err(FramingError::LineTooLong { max });
+                }
+            }
+            if !complete {
+                break;
+            }
+            if self.strict_crlf && !line.ends_with(b"\r\n") {
+                return Err(FramingError::BareLf);
+            }
+            let literal = spill::literal_header(line).map_or(0, |(_, len)| len as usize);
+            self.checked += line.len() + literal;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a single server response.
+///
+/// Responses that cannot be parsed fail with an `io::Error` of kind `InvalidData`,
+/// wrapping a `ParseError` holding the bytes received, or a `FramingError` if
+/// they violate the line limits.
+impl Decoder for ImapCodec {
     type Item = ResponseData;
     type Error = io::Error;
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, io::Error> {
+        if let Err(err) = self.check_lines(buf) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+        }
         if self.decode_need_message_bytes > buf.len() {
             return Ok(None);
         }
-        let (response, rsp_len) = match imap_proto::Response::from_bytes(buf) {
+        let (response, rsp_len) = match parse_response_with(buf, &self.quirks) {
             Ok((remaining, response)) => {
                 // This SHOULD be acceptable/safe: BytesMut storage memory is
                 // allocated on the heap and should not move. It will not be
                 // freed as long as we keep a reference alive, which we do
                 // by retaining a reference to the split buffer, below.
-                let response = unsafe { mem::transmute(response) };
+                let response =
+                    unsafe { mem::transmute::<Response<'_>, Response<'static>>(response) };
                 (response, buf.len() - remaining.len())
             }
             Err(nom::Err::Incomplete(Needed::Size(min))) => {
@@ -37,18 +149,23 @@ impl<'a> Decoder for ImapCodec {
             }
             Err(nom::Err::Error((_input, err_kind)))
             | Err(nom::Err::Failure((_input, err_kind))) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("{:?} during parsing of {:?}", err_kind, buf),
-                ));
+                let err = ParseError::new(buf, format!("{:?}", err_kind));
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
             }
         };
         let raw = buf.split_to(rsp_len).freeze();
         self.decode_need_message_bytes = 0;
-        Ok(Some(ResponseData { raw, response }))
+        self.checked = self.checked.saturating_sub(rsp_len);
+        Ok(Some(ResponseData {
+            raw,
+            response,
+            spilled: Vec::new(),
+            ticket: None,
+        }))
     }
 }
 
+/// Encodes a command line: the tag, a space, the command and CRLF.
 impl<'a> Encoder<&'a Request<'a>> for ImapCodec {
     type Error = io::Error;
     fn encode(&mut self, msg: &Request, dst: &mut BytesMut) -> Result<(), io::Error> {
@@ -60,7 +177,22 @@ impl<'a> Encoder<&'a Request<'a>> for ImapCodec {
     }
 }
 
-#[derive(Debug)]
+/// Encodes the continuation of a command after a literal: the literal data,
+/// followed by the rest of the command up to the next literal (if any).
+impl Encoder<&[u8]> for ImapCodec {
+    type Error = io::Error;
+    fn encode(&mut self, msg: &[u8], dst: &mut BytesMut) -> Result<(), io::Error> {
+        dst.put_slice(msg);
+        dst.put_slice(b"\r\n");
+        Ok(())
+    }
+}
+
+/// A response parsed by `ImapCodec`, which owns the buffer it borrows from.
+///
+/// The `Debug` output summarizes the raw response and any message content in
+/// it, see `types::set_full_debug()`.
+#[derive(Clone)]
 pub struct ResponseData {
     raw: Bytes,
     // This reference is really scoped to the lifetime of the `raw`
@@ -71,10 +203,18 @@ pub struct ResponseData {
     //
     // `raw` is never mutated during the lifetime of `ResponseData`,
     // and `Response` does not not implement any specific drop glue.
+    // Clones share the underlying storage of `raw`, so the references
+    // held by a cloned `response` remain valid as well.
     response: Response<'static>,
+    /// The literals spilled from this response, with their offset in `raw`.
+    pub(crate) spilled: Vec<(usize, SpilledLiteral)>,
+    /// Held until the response is consumed, if the connection limits the
+    /// responses not consumed yet.
+    pub(crate) ticket: Option<Ticket>,
 }
 
 impl ResponseData {
+    /// The tag of the command this response completes, for tagged responses.
     pub fn request_id(&self) -> Option<&RequestId> {
         match self.response {
             Response::Done { ref tag, .. } => Some(tag),
@@ -82,8 +222,140 @@ impl ResponseData {
         }
     }
 
+    /// The parsed response.
     #[allow(clippy::needless_lifetimes)]
     pub fn parsed<'a>(&'a self) -> &'a Response<'a> {
         &self.response
     }
+
+    /// The response as received from the server.
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// A `Bytes` for `data` from `parsed()` (such as the message in a `BODY[]`
+    /// fetch response), sharing the buffer of this response instead of copying
+    /// it.
+    ///
+    /// Quoted strings containing escapes are not part of the buffer once
+    /// unescaped, so `data` is copied if it is not part of this response.
+    pub fn slice(&self, data: &[u8]) -> Bytes {
+        let start = self.raw.as_ptr() as usize;
+        let offset = (data.as_ptr() as usize).wrapping_sub(start);
+        match offset <= self.raw.len() && data.len() <= self.raw.len() - offset {
+            true => self.raw.slice_ref(data),
+            false => Bytes::copy_from_slice(data),
+        }
+    }
+
+    /// The spilled literal for the empty `data` taking its place in `parsed()`,
+    /// if literals are spilled (see `Connection::spill_literals()`).
+    pub fn spilled(&self, data: &[u8]) -> Option<&SpilledLiteral> {
+        let offset = (data.as_ptr() as usize).checked_sub(self.raw.as_ptr() as usize)?;
+        self.spilled
+            .iter()
+            .find(|(start, _)| *start == offset)
+            .map(|(_, literal)| literal)
+    }
+}
+
+impl fmt::Debug for ResponseData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResponseData")
+            .field("raw", &Content(&self.raw))
+            .field("response", &self.response)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImapCodec;
+    use crate::error::FramingError;
+    use bytes::BytesMut;
+    use imap_proto::parser::Quirks;
+    use imap_proto::{AttributeValue, Request, RequestId, Response};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    #[test]
+    fn decode_partial() {
+        let mut codec = ImapCodec::new();
+        let mut buf = BytesMut::from(&b"* 1 FETCH (BODY[] {5}\r\nhel"[..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        buf.extend_from_slice(b"lo)\r\nA1 OK done\r\n");
+        let rsp = codec.decode(&mut buf).unwrap().unwrap();
+        assert!(matches!(rsp.parsed(), Response::Fetch(1, _)));
+        assert_eq!(rsp.raw(), b"* 1 FETCH (BODY[] {5}\r\nhello)\r\n");
+        assert!(!format!("{:?}", rsp).contains("hello"));
+
+        let body = match rsp.parsed() {
+            Response::Fetch(_, attrs) => match &attrs[0] {
+                AttributeValue::BodySection {
+                    data: Some(data), ..
+                } => rsp.slice(data),
+                attr => panic!("unexpected attribute: {:?}", attr),
+            },
+            rsp => panic!("unexpected response: {:?}", rsp),
+        };
+        assert_eq!(body, &b"hello"[..]);
+        assert_eq!(body.as_ptr(), rsp.raw()[23..].as_ptr());
+        assert_eq!(rsp.slice(b"copied"), &b"copied"[..]);
+
+        let rsp = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(rsp.request_id(), Some(&RequestId::from("A1")));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn decode_bare_lf() {
+        let mut buf = BytesMut::from(&b"* 1 FETCH (BODY[] {5}\nhello)\nA1 OK done\n"[..]);
+        let mut codec = ImapCodec::new();
+        assert!(codec.decode(&mut buf).is_err());
+
+        codec.set_quirks(Quirks::lenient());
+        let rsp = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(rsp.raw(), b"* 1 FETCH (BODY[] {5}\nhello)\n");
+        let rsp = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(rsp.request_id(), Some(&RequestId::from("A1")));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn decode_line_limits() {
+        let mut codec = ImapCodec::new();
+        codec.set_max_line_length(Some(24));
+        let mut buf = BytesMut::from(&b"* 1 FETCH (BODY[] {40}\r\n"[..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(&[b'x'; 40]);
+        buf.extend_from_slice(b")\r\n* OK ");
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        buf.extend_from_slice(b"a rather long greeting");
+        let err = codec.decode(&mut buf).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<FramingError>();
+        assert_eq!(err, Some(&FramingError::LineTooLong { max: 24 }));
+
+        let mut codec = ImapCodec::new();
+        codec.set_quirks(Quirks::lenient());
+        codec.set_strict_crlf(true);
+        let mut buf = BytesMut::from(&b"* OK ready\r\n"[..]);
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+        buf.extend_from_slice(b"A1 OK done\n");
+        let err = codec.decode(&mut buf).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<FramingError>();
+        assert_eq!(err, Some(&FramingError::BareLf));
+    }
+
+    #[test]
+    fn encode() {
+        let mut buf = BytesMut::new();
+        let mut codec = ImapCodec::new();
+        codec
+            .encode(&Request(b"A1", b"APPEND Drafts {5}"), &mut buf)
+            .unwrap();
+        codec.encode(&b"hello"[..], &mut buf).unwrap();
+        assert_eq!(&buf[..], b"A1 APPEND Drafts {5}\r\nhello\r\n");
+    }
 }
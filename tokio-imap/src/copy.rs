@@ -0,0 +1,183 @@
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, mailbox_error, Error};
+use crate::list::run;
+use imap_proto::builders::command::{Command, CommandBuilder};
+use imap_proto::{Response, ResponseCode, SequenceSet, StoreMode, SystemFlags, UidSetMember};
+
+/// The UIDs assigned to copied or moved messages in the destination mailbox, as
+/// reported by servers supporting UIDPLUS (RFC 4315).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CopyUids {
+    /// The `UIDVALIDITY` of the destination mailbox.
+    pub uid_validity: u32,
+    /// Pairs of source and destination UIDs.
+    pub uids: Vec<(u32, u32)>,
+}
+
+impl CopyUids {
+    fn new(uid_validity: u32, source: &[UidSetMember], dest: &[UidSetMember]) -> Self {
+        let source = source.iter().flat_map(UidSetMember::iter);
+        let dest = dest.iter().flat_map(UidSetMember::iter);
+        Self {
+            uid_validity,
+            uids: source.zip(dest).collect(),
+        }
+    }
+
+    /// The destination UID of the message with the given source UID.
+    pub fn get(&self, source: u32) -> Option<u32> {
+        self.uids
+            .iter()
+            .find(|(src, _)| *src == source)
+            .map(|(_, dest)| *dest)
+    }
+}
+
+impl Client {
+    /// Copy the messages with the given UIDs in the selected mailbox to `mailbox`.
+    ///
+    /// Returns the UIDs of the copies, or `None` if the server did not report them.
+    pub async fn copy_messages(
+        &self,
+        uids: &SequenceSet,
+        mailbox: &str,
+    ) -> Result<Option<CopyUids>, Error> {
+        self.copy_uids(CommandBuilder::uid_copy(uids, mailbox))
+            .await
+    }
+
+    /// Move the messages with the given UIDs in the selected mailbox to `mailbox`.
+    ///
+    /// Uses the MOVE extension (RFC 6851) if the server supports it. Otherwise,
+    /// the messages are copied, marked `\Deleted` and removed with `UID EXPUNGE`,
+    /// which requires UIDPLUS (RFC 4315) so that other deleted messages are left
+    /// alone; unlike `MOVE`, this leaves the copies behind if it fails midway.
+    /// Fails with `Error::MissingCapability` if the server supports neither.
+    ///
+    /// Returns the UIDs of the messages in `mailbox`, or `None` if the server did
+    /// not report them.
+    pub async fn move_messages(
+        &self,
+        uids: &SequenceSet,
+        mailbox: &str,
+    ) -> Result<Option<CopyUids>, Error> {
+        let session = self.session();
+        if session.capabilities().is_empty() || session.has_capability("MOVE") {
+            return self
+                .copy_uids(CommandBuilder::uid_move(uids, mailbox))
+                .await;
+        }
+        if !session.has_capability("UIDPLUS") {
+            return Err(Error::MissingCapability("MOVE"));
+        }
+
+        let copied = self
+            .copy_uids(CommandBuilder::uid_copy(uids, mailbox))
+            .await?;
+        let deleted = SystemFlags::DELETED.into();
+        run(
+            self,
+            CommandBuilder::uid_store(uids, StoreMode::Add, &deleted)
+                .silent()
+                .into(),
+        )
+        .await?;
+        run(self, CommandBuilder::uid_expunge(uids)).await?;
+        Ok(copied)
+    }
+
+    async fn copy_uids(&self, cmd: Command) -> Result<Option<CopyUids>, Error> {
+        let mut responses = self.call(cmd);
+        let mut copied = None;
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp).map_err(mailbox_error)?;
+            // Sent in the tagged response for COPY, but in an untagged OK
+            // response for MOVE
+            match rsp.parsed() {
+                Response::Data {
+                    code: Some(ResponseCode::CopyUid(uid_validity, source, dest)),
+                    ..
+                }
+                | Response::Done {
+                    code: Some(ResponseCode::CopyUid(uid_validity, source, dest)),
+                    ..
+                } => copied = Some(CopyUids::new(*uid_validity, source, dest)),
+                _ => {}
+            }
+        }
+        Ok(copied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use imap_proto::SequenceSet;
+
+    #[tokio::test]
+    async fn copy_and_move() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 UIDPLUS MOVE] ready\r\n")
+            .expect("A0001 UID COPY 304,319:320 \"Archive\"")
+            .send("A0001 OK [COPYUID 38505 304,319:320 3956:3958] Done\r\n")
+            .expect("A0002 UID MOVE 42 \"Trash\"")
+            .send(
+                "* OK [COPYUID 432432 42 7]\r\n\
+                 * 3 EXPUNGE\r\n\
+                 A0002 OK Done\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let uids: SequenceSet = vec![304, 319, 320].into_iter().collect();
+        let copied = client
+            .copy_messages(&uids, "Archive")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(copied.uid_validity, 38505);
+        assert_eq!(copied.uids, vec![(304, 3956), (319, 3957), (320, 3958)]);
+        assert_eq!(copied.get(319), Some(3957));
+
+        let moved = client
+            .move_messages(&SequenceSet::from(42..=42), "Trash")
+            .await
+            .unwrap()
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(moved.uids, vec![(42, 7)]);
+    }
+
+    #[tokio::test]
+    async fn move_without_move() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 UIDPLUS] ready\r\n")
+            .expect("A0001 UID COPY 42,44 \"Trash\"")
+            .send("A0001 OK [COPYUID 432432 42,44 7:8] Done\r\n")
+            .expect("A0002 UID STORE 42,44 +FLAGS.SILENT (\\Deleted)")
+            .send("A0002 OK STORE completed\r\n")
+            .expect("A0003 UID EXPUNGE 42,44")
+            .send(
+                "* 3 EXPUNGE\r\n\
+                 * 3 EXPUNGE\r\n\
+                 A0003 OK EXPUNGE completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let uids: SequenceSet = vec![42, 44].into_iter().collect();
+        let moved = client.move_messages(&uids, "Trash").await.unwrap().unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(moved.uids, vec![(42, 7), (44, 8)]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashSet;
+
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::CommandBuilder;
+
+impl Client {
+    /// Enable the `extensions` advertised by the server (RFC 5161), such as
+    /// `CONDSTORE`, `QRESYNC` or `UTF8=ACCEPT`, and return those of them that
+    /// are enabled, including those enabled before.
+    ///
+    /// Extensions the server does not advertise are left out rather than sent.
+    /// `CONDSTORE` is sent before `QRESYNC`; enabling `QRESYNC` also enables
+    /// `CONDSTORE`. Once `UTF8=ACCEPT` is enabled, mailbox names are sent in
+    /// UTF-8 rather than modified UTF-7, and UTF-8 is accepted in quoted strings.
+    ///
+    /// Fails with `Error::MissingCapability` if the server does not support
+    /// `ENABLE` at all.
+    pub async fn enable_extensions(&self, extensions: &[&str]) -> Result<HashSet<String>, Error> {
+        let mut requested = extensions
+            .iter()
+            .map(|name| name.to_ascii_uppercase())
+            .collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        requested.retain(|name| seen.insert(name.clone()));
+        requested.sort_by_key(|name| name == "QRESYNC");
+
+        let session = self.session();
+        let known = !session.capabilities().is_empty();
+        let advertised = |name: &str| {
+            !known
+                || session.has_capability(name)
+                || (name == "UTF8=ACCEPT" && session.has_capability("UTF8=ONLY"))
+        };
+        let missing = requested
+            .iter()
+            .filter(|name| !session.is_enabled(name) && advertised(name))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            if known && !session.has_capability("ENABLE") {
+                return Err(Error::MissingCapability("ENABLE"));
+            }
+            let mut responses = self.call(CommandBuilder::enable(&missing));
+            while let Some(rsp) = responses.next().await {
+                check_completion(&rsp?)?;
+            }
+        }
+
+        let session = self.session();
+        Ok(requested
+            .into_iter()
+            .filter(|name| session.is_enabled(name))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, Error};
+    use futures::StreamExt;
+    use imap_proto::builders::command::CommandBuilder;
+    use imap_proto::{MailboxDatum, Response};
+
+    #[tokio::test]
+    async fn enable_extensions() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 ENABLE CONDSTORE QRESYNC UTF8=ACCEPT] ready\r\n")
+            .expect("A0001 ENABLE UTF8=ACCEPT CONDSTORE QRESYNC")
+            .send("* ENABLED QRESYNC UTF8=ACCEPT\r\nA0001 OK enabled\r\n")
+            .expect("A0002 LIST \"\" \"*\"")
+            .send(
+                "* LIST () \"/\" \"Entwürfe\"\r\n\
+                 A0002 OK LIST completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let enabled = client
+            .enable_extensions(&["QRESYNC", "utf8=accept", "CONDSTORE", "X-UNKNOWN"])
+            .await
+            .unwrap();
+        assert_eq!(enabled.len(), 3);
+        assert!(enabled.contains("CONDSTORE"));
+        assert!(client.session().is_enabled("CONDSTORE"));
+
+        // Already enabled, so not sent again
+        let enabled = client.enable_extensions(&["QRESYNC"]).await.unwrap();
+        assert!(enabled.contains("QRESYNC"));
+
+        let mut responses = client.call(CommandBuilder::list("", "*"));
+        let rsp = responses.next().await.unwrap().unwrap();
+        match rsp.parsed() {
+            Response::MailboxData(MailboxDatum::List { name, .. }) => assert_eq!(name, "Entwürfe"),
+            rsp => panic!("unexpected response: {:?}", rsp),
+        }
+        while let Some(rsp) = responses.next().await {
+            rsp.unwrap();
+        }
+        server.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn enable_unsupported() {
+        let script = Script::new().send("* OK [CAPABILITY IMAP4rev1 CONDSTORE] ready\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        match client.enable_extensions(&["CONDSTORE"]).await {
+            Err(Error::MissingCapability("ENABLE")) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(client
+            .enable_extensions(&["QRESYNC"])
+            .await
+            .unwrap()
+            .is_empty());
+        server.finish().await.unwrap();
+    }
+}
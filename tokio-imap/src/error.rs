@@ -0,0 +1,284 @@
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use imap_proto::{MetadataCode, Response, ResponseCode, Status};
+use tokio_rustls::rustls::TLSError;
+
+use crate::codec::ResponseData;
+use crate::referral::ImapUrl;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Connecting to the server, or reading from or writing to the connection,
+    /// failed.
+    Io(io::Error),
+    /// The TLS handshake failed, for example because the server certificate could
+    /// not be verified.
+    Tls(TLSError),
+    /// The server sent a response that could not be parsed.
+    Parse(ParseError),
+    /// The server sent a line exceeding the limits configured on the
+    /// connection.
+    Framing(FramingError),
+    /// The server closed the connection with a `BYE` response, giving the
+    /// included reason.
+    Bye(String),
+    /// The command requires a capability the server did not advertise.
+    MissingCapability(&'static str),
+    /// The server completed the command with `NO`.
+    No(ServerError),
+    /// The server rejected the command with `BAD`.
+    Bad(ServerError),
+    /// The target mailbox of an `APPEND`, `COPY` or `MOVE` command does not
+    /// exist, but could be created (`TRYCREATE`).
+    TryCreate(ServerError),
+    /// The mailbox to create, or the new name of a renamed mailbox, already
+    /// exists (`ALREADYEXISTS`, RFC 5530).
+    AlreadyExists(ServerError),
+    /// The mailbox does not exist (`NONEXISTENT`, RFC 5530).
+    NonExistent(ServerError),
+    /// The server refused to set metadata (RFC 5464), because a value is too
+    /// large, there would be too many entries, or private entries are not
+    /// supported.
+    Metadata(MetadataCode, ServerError),
+    /// The server refused the command, referring the client to another server
+    /// (RFC 2221).
+    Referral(ImapUrl),
+    /// The mailbox of a `Mailbox` handle is no longer selected, since another
+    /// mailbox was selected or it was closed.
+    NotSelected(String),
+    /// The operation did not complete in time.
+    Timeout,
+    /// The server did not follow the protocol, for example by not sending a
+    /// greeting or by omitting requested data.
+    Protocol(String),
+}
+
+impl Error {
+    /// Copy an I/O error reported for several commands at once.
+    pub(crate) fn from_io_ref(e: &io::Error) -> Self {
+        if let Some(inner) = e.get_ref() {
+            if let Some(e) = inner.downcast_ref::<ParseError>() {
+                return Error::Parse(e.clone());
+            } else if let Some(e) = inner.downcast_ref::<FramingError>() {
+                return Error::Framing(e.clone());
+            } else if let Some(e) = inner.downcast_ref::<TLSError>() {
+                return Error::Tls(e.clone());
+            }
+        }
+        match e.kind() {
+            io::ErrorKind::TimedOut => Error::Timeout,
+            kind => Error::Io(io::Error::new(kind, e.to_string())),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Tls(e) => write!(f, "TLS error: {}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Framing(e) => write!(f, "{}", e),
+            Error::Bye(reason) => write!(f, "server closed the connection: {}", reason),
+            Error::MissingCapability(name) => {
+                write!(f, "server does not support the {} capability", name)
+            }
+            Error::No(e) => write!(f, "command failed: {}", e),
+            Error::Bad(e) => write!(f, "command rejected: {}", e),
+            Error::TryCreate(e) => write!(f, "mailbox does not exist: {}", e),
+            Error::AlreadyExists(e) => write!(f, "mailbox already exists: {}", e),
+            Error::NonExistent(e) => write!(f, "mailbox does not exist: {}", e),
+            Error::Metadata(code, e) => match code {
+                MetadataCode::MaxSize(max) => {
+                    write!(f, "metadata value larger than {} bytes: {}", max, e)
+                }
+                MetadataCode::TooMany => write!(f, "too many metadata entries: {}", e),
+                MetadataCode::NoPrivate => write!(f, "private metadata not supported: {}", e),
+                MetadataCode::LongEntries(_) => write!(f, "metadata entries left out: {}", e),
+            },
+            Error::Referral(url) => write!(f, "referred to {}", url),
+            Error::NotSelected(name) => write!(f, "mailbox {} is no longer selected", name),
+            Error::Timeout => write!(f, "operation timed out"),
+            Error::Protocol(msg) => write!(f, "protocol error: {}", msg),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Tls(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Framing(e) => Some(e),
+            Error::Bye(_)
+            | Error::MissingCapability(_)
+            | Error::No(_)
+            | Error::Bad(_)
+            | Error::TryCreate(_)
+            | Error::AlreadyExists(_)
+            | Error::NonExistent(_)
+            | Error::Metadata(..)
+            | Error::Referral(_)
+            | Error::NotSelected(_)
+            | Error::Timeout
+            | Error::Protocol(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        let typed = matches!(
+            e.get_ref(),
+            Some(inner) if inner.is::<ParseError>()
+                || inner.is::<FramingError>()
+                || inner.is::<TLSError>()
+        );
+        if typed || e.kind() == io::ErrorKind::TimedOut {
+            Error::from_io_ref(&e)
+        } else {
+            Error::Io(e)
+        }
+    }
+}
+
+/// A response that could not be parsed.
+///
+/// The codec reports it as an `io::Error` of kind `InvalidData` wrapping this
+/// type; it is turned into `Error::Parse` by the client.
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    raw: Vec<u8>,
+    reason: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(raw: &[u8], reason: String) -> Self {
+        Self {
+            raw: raw.to_vec(),
+            reason,
+        }
+    }
+
+    /// The bytes received from the server starting with the response that could
+    /// not be parsed.
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} during parsing of {:?}",
+            self.reason,
+            String::from_utf8_lossy(&self.raw)
+        )
+    }
+}
+
+impl StdError for ParseError {}
+
+/// A line violating the limits set with `Connection::max_line_length()` or
+/// `Connection::strict_crlf()`.
+///
+/// The codec reports it as an `io::Error` of kind `InvalidData` wrapping this
+/// type; it is turned into `Error::Framing` by the client.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FramingError {
+    /// A line, not counting the literals following it, is longer than `max`
+    /// bytes.
+    LineTooLong { max: usize },
+    /// A line is terminated by a bare LF rather than CRLF.
+    BareLf,
+}
+
+impl Display for FramingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FramingError::LineTooLong { max } => {
+                write!(f, "server sent a line longer than {} bytes", max)
+            }
+            FramingError::BareLf => write!(f, "server sent a line terminated by a bare LF"),
+        }
+    }
+}
+
+impl StdError for FramingError {}
+
+/// A tagged `NO` or `BAD` response completing a command.
+#[derive(Clone, Debug)]
+pub struct ServerError {
+    rsp: Box<ResponseData>,
+}
+
+impl ServerError {
+    /// The response code, such as `TRYCREATE`, if any.
+    pub fn code(&self) -> Option<&ResponseCode<'_>> {
+        match self.rsp.parsed() {
+            Response::Done { code, .. } => code.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The human-readable text of the response, decoded lossily if the
+    /// server did not send UTF-8.
+    pub fn text(&self) -> Cow<'_, str> {
+        match self.rsp.parsed() {
+            Response::Done { information, .. } => information.unwrap_or_default().to_str_lossy(),
+            _ => Cow::Borrowed(""),
+        }
+    }
+
+    pub fn response(&self) -> &ResponseData {
+        &self.rsp
+    }
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// Turn a tagged `NO` or `BAD` completion response into an error.
+pub(crate) fn check_completion(rsp: &ResponseData) -> Result<(), Error> {
+    match rsp.parsed() {
+        Response::Done { status, code, .. } => {
+            if let (Status::No, Some(ResponseCode::Referral(url))) = (status, code) {
+                if let Some(url) = ImapUrl::parse(url) {
+                    return Err(Error::Referral(url));
+                }
+            }
+
+            let err = ServerError {
+                rsp: Box::new(rsp.clone()),
+            };
+            match status {
+                Status::No => Err(Error::No(err)),
+                Status::Bad => Err(Error::Bad(err)),
+                _ => Ok(()),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Turn a `NO` response with a response code about the target mailbox into the
+/// matching error, for commands operating on mailboxes.
+pub(crate) fn mailbox_error(err: Error) -> Error {
+    match err {
+        Error::No(e) => match e.code() {
+            Some(ResponseCode::TryCreate) => Error::TryCreate(e),
+            Some(ResponseCode::AlreadyExists) => Error::AlreadyExists(e),
+            Some(ResponseCode::NonExistent) => Error::NonExistent(e),
+            _ => Error::No(e),
+        },
+        err => err,
+    }
+}
@@ -0,0 +1,347 @@
+//! Export of fetched messages to Maildir directories and mbox files, for
+//! backup tools. Requires the `export` feature.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::CommandBuilder;
+use imap_proto::{Attribute, AttributeValue, Response, SequenceSet};
+
+/// A destination for exported messages.
+pub trait ExportSink {
+    /// Store `message`, as fetched with `BODY.PEEK[]`, with its flags and its
+    /// `INTERNALDATE` (such as `17-Jul-1996 02:44:25 -0700`), if known.
+    fn store(
+        &mut self,
+        message: &[u8],
+        flags: &[&str],
+        internal_date: Option<&str>,
+    ) -> io::Result<()>;
+}
+
+/// A Maildir directory, with `cur`, `new` and `tmp` subdirectories.
+///
+/// Messages are stored in `cur`, with the flags in the file name: `\Seen`,
+/// `\Answered`, `\Flagged`, `\Deleted`, `\Draft` and `$Forwarded` become `S`,
+/// `R`, `F`, `T`, `D` and `P`. Other flags are not kept.
+pub struct Maildir {
+    path: PathBuf,
+    host: String,
+    stored: u64,
+}
+
+impl Maildir {
+    /// Open the Maildir at `path`, creating it if needed.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        for dir in &["cur", "new", "tmp"] {
+            fs::create_dir_all(path.join(dir))?;
+        }
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+        Ok(Self {
+            path,
+            host: host.replace('/', "\\057").replace(':', "\\072"),
+            stored: 0,
+        })
+    }
+
+    /// The Maildir info of a message with `flags`, such as `2,FS`.
+    pub fn info(flags: &[&str]) -> String {
+        let mut letters = flags
+            .iter()
+            .filter_map(|flag| match flag.to_ascii_lowercase().as_str() {
+                "\\seen" => Some('S'),
+                "\\answered" => Some('R'),
+                "\\flagged" => Some('F'),
+                "\\deleted" => Some('T'),
+                "\\draft" => Some('D'),
+                "$forwarded" => Some('P'),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        letters.sort_unstable();
+        letters.dedup();
+        format!("2,{}", letters.into_iter().collect::<String>())
+    }
+}
+
+impl ExportSink for Maildir {
+    fn store(&mut self, message: &[u8], flags: &[&str], _: Option<&str>) -> io::Result<()> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let name = format!("{}.P{}Q{}.{}", time, process::id(), self.stored, self.host);
+        self.stored += 1;
+
+        // Written to tmp first, so that other readers never see partial messages
+        let tmp = self.path.join("tmp").join(&name);
+        let mut file = File::create(&tmp)?;
+        file.write_all(message)?;
+        file.sync_all()?;
+        let cur = format!("{}:{}", name, Self::info(flags));
+        fs::rename(&tmp, self.path.join("cur").join(cur))
+    }
+}
+
+/// An mbox file, in the mboxrd format.
+///
+/// Line endings are converted to LF, and lines starting with `From ` (after
+/// any number of `>`) are quoted with `>`. The flags are kept in the `Status`
+/// and `X-Status` header fields: `\Seen` becomes `R` in `Status`, and
+/// `\Answered`, `\Flagged`, `\Draft` and `\Deleted` become `A`, `F`, `T` and
+/// `D` in `X-Status`.
+pub struct Mbox {
+    file: BufWriter<File>,
+}
+
+impl Mbox {
+    /// Open the mbox file at `path` for appending, creating it if needed.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: BufWriter::new(file),
+        })
+    }
+
+    /// Write the buffered messages to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl ExportSink for Mbox {
+    fn store(
+        &mut self,
+        message: &[u8],
+        flags: &[&str],
+        internal_date: Option<&str>,
+    ) -> io::Result<()> {
+        let date = internal_date
+            .and_then(asctime)
+            .unwrap_or_else(|| "Thu Jan  1 00:00:00 1970".to_string());
+        writeln!(self.file, "From MAILER-DAEMON {}", date)?;
+
+        let has = |name: &str| flags.iter().any(|flag| flag.eq_ignore_ascii_case(name));
+        let mut status = String::from("O");
+        if has("\\Seen") {
+            status.insert(0, 'R');
+        }
+        let x_status = [
+            ("\\Answered", 'A'),
+            ("\\Flagged", 'F'),
+            ("\\Draft", 'T'),
+            ("\\Deleted", 'D'),
+        ]
+        .iter()
+        .filter(|(flag, _)| has(flag))
+        .map(|(_, letter)| *letter)
+        .collect::<String>();
+
+        let mut in_header = true;
+        let mut lines = message.split(|&b| b == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if lines.peek().is_none() && line.is_empty() {
+                break;
+            }
+            if in_header && line.is_empty() {
+                in_header = false;
+                writeln!(self.file, "Status: {}", status)?;
+                if !x_status.is_empty() {
+                    writeln!(self.file, "X-Status: {}", x_status)?;
+                }
+            } else if in_header && is_status_field(line) {
+                continue;
+            }
+            let quoted = line.iter().take_while(|&&b| b == b'>').count();
+            if line[quoted..].starts_with(b"From ") {
+                self.file.write_all(b">")?;
+            }
+            self.file.write_all(line)?;
+            self.file.write_all(b"\n")?;
+        }
+        self.file.write_all(b"\n")
+    }
+}
+
+/// Whether `line` is a `Status` or `X-Status` header field, which are replaced.
+fn is_status_field(line: &[u8]) -> bool {
+    let name = line.split(|&b| b == b':').next().unwrap_or_default();
+    name.eq_ignore_ascii_case(b"Status") || name.eq_ignore_ascii_case(b"X-Status")
+}
+
+/// Convert an `INTERNALDATE` to the `asctime()` format of mbox `From` lines,
+/// keeping the time in its own time zone.
+fn asctime(date: &str) -> Option<String> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+    let mut parts = date.trim().splitn(2, ' ');
+    let (day_month_year, time) = (parts.next()?, parts.next()?);
+    let mut fields = day_month_year.splitn(3, '-');
+    let day = fields.next()?.trim().parse::<u32>().ok()?;
+    let month = fields.next()?;
+    let year = fields.next()?.parse::<i32>().ok()?;
+    let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))?;
+    let time = time.split(' ').next()?;
+
+    // Day of the week, by Sakamoto's method
+    const OFFSETS: [i32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let y = if month < 2 { year - 1 } else { year };
+    let weekday = (y + y / 4 - y / 100 + y / 400 + OFFSETS[month] + day as i32).rem_euclid(7);
+    Some(format!(
+        "{} {} {:2} {} {}",
+        DAYS[weekday as usize], MONTHS[month], day, time, year
+    ))
+}
+
+impl Client {
+    /// Fetch the messages with the given UIDs in the selected mailbox, without
+    /// marking them as seen, and store them in `sink` as they are received.
+    ///
+    /// Returns the number of messages stored. Requires the `export` feature.
+    pub async fn export<S: ExportSink>(
+        &self,
+        uids: &SequenceSet,
+        sink: &mut S,
+    ) -> Result<usize, Error> {
+        let cmd = CommandBuilder::uid_fetch()
+            .set(uids)
+            .attr(Attribute::Uid)
+            .attr(Attribute::Flags)
+            .attr(Attribute::InternalDate)
+            .attr(Attribute::BodyPeek);
+        let mut responses = self.call(cmd);
+        let mut stored = 0;
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            let attrs = match rsp.parsed() {
+                Response::Fetch(_, attrs) => attrs,
+                _ => continue,
+            };
+            let mut flags = &[][..];
+            let mut internal_date = None;
+            let mut body = None;
+            for attr in attrs {
+                match attr {
+                    AttributeValue::Flags(list) => flags = &list[..],
+                    AttributeValue::InternalDate(date) => internal_date = Some(&**date),
+                    AttributeValue::BodySection {
+                        section: None,
+                        data: Some(data),
+                        ..
+                    } => body = Some(data),
+                    _ => {}
+                }
+            }
+            if let Some(body) = body {
+                sink.store(body, flags, internal_date)?;
+                stored += 1;
+            }
+        }
+        Ok(stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{asctime, ExportSink, Maildir, Mbox};
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use imap_proto::SequenceSet;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("tokio-imap-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            asctime("17-Jul-1996 02:44:25 -0700").as_deref(),
+            Some("Wed Jul 17 02:44:25 1996")
+        );
+        assert_eq!(
+            asctime(" 1-Mar-2024 10:00:00 +0000").as_deref(),
+            Some("Fri Mar  1 10:00:00 2024")
+        );
+        assert_eq!(asctime("yesterday"), None);
+    }
+
+    #[test]
+    fn mbox() {
+        let dir = temp_dir("mbox");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("INBOX.mbox");
+        let mut mbox = Mbox::create(&path).unwrap();
+        mbox.store(
+            b"Subject: Hi\r\nStatus: RO\r\n\r\nFrom here\r\n>From there\r\n",
+            &["\\Seen", "\\Flagged"],
+            Some("17-Jul-1996 02:44:25 -0700"),
+        )
+        .unwrap();
+        mbox.flush().unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            contents,
+            "From MAILER-DAEMON Wed Jul 17 02:44:25 1996\n\
+             Subject: Hi\n\
+             Status: RO\n\
+             X-Status: F\n\
+             \n\
+             >From here\n\
+             >>From there\n\
+             \n"
+        );
+    }
+
+    #[tokio::test]
+    async fn export_maildir() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 UID FETCH 4:5 (UID FLAGS INTERNALDATE BODY.PEEK[])")
+            .send(
+                "* 1 FETCH (UID 4 FLAGS (\\Seen \\Answered $Junk) INTERNALDATE \"17-Jul-1996 02:44:25 -0700\" BODY[] {8}\r\nSubject:)\r\n\
+                 * 2 FETCH (UID 5 FLAGS () INTERNALDATE \"17-Jul-1996 02:44:25 -0700\" BODY[] {8}\r\nSubject:)\r\n\
+                 A0001 OK FETCH completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let dir = temp_dir("maildir");
+        let mut maildir = Maildir::create(&dir).unwrap();
+        let stored = client
+            .export(&SequenceSet::from(4..=5), &mut maildir)
+            .await
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(stored, 2);
+
+        let mut names = fs::read_dir(dir.join("cur"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert!(names[0].ends_with(":2,RS"));
+        assert!(names[1].ends_with(":2,"));
+        assert_eq!(fs::read_dir(dir.join("tmp")).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::CommandBuilder;
+use imap_proto::{Identity, Response};
+
+impl Client {
+    /// Identify the client to the server with `fields`, such as `name` and
+    /// `version`, and get the server's identity (RFC 2971).
+    ///
+    /// Returns `None` if the server does not identify itself. The identity is
+    /// also available from `Session::server_id()` afterwards.
+    pub async fn identify(
+        &self,
+        fields: &[(&str, Option<&str>)],
+    ) -> Result<Option<Identity>, Error> {
+        let mut responses = self.call(CommandBuilder::id(fields));
+        let mut identity = None;
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            if let Response::Id(id) = rsp.parsed() {
+                identity = Some(id.clone()).filter(|id| !id.is_empty());
+            }
+        }
+        Ok(identity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use futures::StreamExt;
+    use imap_proto::Response;
+
+    #[tokio::test]
+    async fn identify() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 ID] ready\r\n")
+            .expect("A0001 ID (\"name\" \"test\")")
+            .send(
+                "* ID (\"name\" \"Cyrus\" \"version\" \"3.4\" \"vendor\" NIL)\r\n\
+                 A0001 OK ID completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let identity = client.identify(&[("name", Some("test"))]).await.unwrap();
+        server.finish().await.unwrap();
+        let identity = identity.unwrap();
+        assert_eq!(identity.name(), Some("Cyrus"));
+        assert_eq!(identity.version(), Some("3.4"));
+        assert_eq!(identity.vendor(), None);
+        assert_eq!(client.session().server_id(), Some(&identity));
+    }
+
+    #[tokio::test]
+    async fn identify_after_login() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 ID] ready\r\n")
+            .expect("A0001 LOGIN \"user\" \"pass\"")
+            .send("A0001 OK LOGIN completed\r\n")
+            .expect("A0002 CAPABILITY")
+            .send(
+                "* CAPABILITY IMAP4rev1 ID\r\n\
+                 A0002 OK CAPABILITY completed\r\n",
+            )
+            .expect("A0003 ID (\"name\" \"test\")")
+            .send(
+                "* ID (\"name\" \"IBM Domino IMAP Server\")\r\n\
+                 A0003 OK ID completed\r\n",
+            )
+            .send("* 1 FETCH (UID 4FLAGS (\\Seen))\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, mut connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        connection.identify(&[("name", Some("test"))]);
+        connection.auto_quirks(true);
+        let mut unsolicited = connection.unsolicited();
+        tokio::spawn(connection);
+
+        client.login("user", "pass").await.unwrap();
+        // The FETCH response fails to parse unless the Domino quirks are enabled
+        let rsp = unsolicited.next().await.unwrap();
+        assert!(matches!(rsp.parsed(), Response::Fetch(1, _)));
+        server.finish().await.unwrap();
+        let session = client.session();
+        let name = session.server_id().and_then(|id| id.name());
+        assert_eq!(name, Some("IBM Domino IMAP Server"));
+    }
+}
@@ -0,0 +1,202 @@
+use std::time::Instant;
+
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::codec::ResponseData;
+use crate::error::Error;
+use crate::protocol::{command_name, mailbox_argument};
+use crate::session::Session;
+use imap_proto::builders::command::Command;
+use imap_proto::{RequestId, Response, Status};
+
+/// The `tracing` span covering a command, from the call to its completion (see
+/// `Client::call()`). The span is created in the context of the caller, so that
+/// it becomes part of the caller's trace.
+pub(crate) struct CommandSpan {
+    span: Span,
+    start: Instant,
+    /// Whether the command has a mailbox argument.
+    has_mailbox: bool,
+}
+
+impl CommandSpan {
+    pub(crate) fn new(cmd: &Command) -> Self {
+        let name = command_name(cmd);
+        let name = String::from_utf8_lossy(&name);
+        let span = tracing::info_span!(
+            "imap_command",
+            command = %name,
+            tag = Empty,
+            mailbox = Empty,
+            status = Empty,
+            duration_ms = Empty,
+        );
+        let has_mailbox = matches!(
+            &*name,
+            "APPEND"
+                | "CREATE"
+                | "DELETE"
+                | "EXAMINE"
+                | "RENAME"
+                | "SELECT"
+                | "STATUS"
+                | "SUBSCRIBE"
+                | "UNSUBSCRIBE"
+        );
+        if has_mailbox {
+            span.record("mailbox", mailbox_argument(cmd).as_str());
+        }
+        Self {
+            span,
+            start: Instant::now(),
+            has_mailbox,
+        }
+    }
+
+    /// The command was handed to the protocol, which assigned it `request_id`.
+    pub(crate) fn sent(&self, request_id: &RequestId, session: &Session) {
+        self.span.record("tag", request_id.as_str());
+        match session.selected() {
+            Some(mailbox) if !self.has_mailbox => {
+                self.span.record("mailbox", mailbox.name.as_str());
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn completed(self, rsp: &ResponseData) {
+        let status = match rsp.parsed() {
+            Response::Done { status, .. } => match status {
+                Status::Ok => "OK",
+                Status::No => "NO",
+                Status::Bad => "BAD",
+                _ => "error",
+            },
+            _ => "error",
+        };
+        self.close(status);
+    }
+
+    pub(crate) fn failed(self, err: &Error) {
+        tracing::debug!(parent: &self.span, error = %err, "command failed");
+        self.close("error");
+    }
+
+    fn close(self, status: &str) {
+        self.span.record("status", status);
+        self.span
+            .record("duration_ms", self.start.elapsed().as_millis() as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+
+    use futures::StreamExt;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use imap_proto::builders::command::CommandBuilder;
+
+    type Fields = HashMap<&'static str, String>;
+
+    /// Records the fields of all spans created.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Fields>>>);
+
+    struct Visitor<'a>(&'a mut Fields);
+
+    impl<'a> Visit for Visitor<'a> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields::new();
+            fields.insert("name", span.metadata().name().to_string());
+            span.record(&mut Visitor(&mut fields));
+            let mut spans = self.0.lock().unwrap();
+            spans.push(fields);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0.lock().unwrap();
+            values.record(&mut Visitor(&mut spans[span.into_u64() as usize - 1]));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[tokio::test]
+    async fn command_spans() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send("* 3 EXISTS\r\nA0001 OK [READ-WRITE] SELECT completed\r\n")
+            .expect("A0002 CHECK")
+            .send("A0002 NO CHECK failed\r\n")
+            .expect("A0003 NOOP");
+        let server = MockServer::start(script).await.unwrap();
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        let stream = server.connect().await.unwrap();
+        let (_, client, connection) = Client::from_stream(stream).await.unwrap();
+        tokio::spawn(connection);
+        for cmd in [
+            CommandBuilder::select("INBOX").into(),
+            CommandBuilder::check(),
+            CommandBuilder::noop(),
+        ] {
+            let mut responses = client.call(cmd);
+            while responses.next().await.is_some() {}
+        }
+        server.finish().await.unwrap();
+
+        let spans = recorder.0.lock().unwrap();
+        let commands = spans
+            .iter()
+            .filter(|span| span["name"] == "imap_command")
+            .collect::<Vec<_>>();
+        let field = |idx: usize, name| commands[idx].get(name).map(String::as_str);
+        assert_eq!(commands.len(), 3);
+        assert_eq!(field(0, "command"), Some("SELECT"));
+        assert_eq!(field(0, "tag"), Some("A0001"));
+        assert_eq!(field(0, "mailbox"), Some("INBOX"));
+        assert_eq!(field(0, "status"), Some("OK"));
+        assert!(field(0, "duration_ms").is_some());
+
+        // The selected mailbox is recorded for commands operating on it
+        assert_eq!(field(1, "command"), Some("CHECK"));
+        assert_eq!(field(1, "mailbox"), Some("INBOX"));
+        assert_eq!(field(1, "status"), Some("NO"));
+
+        // The connection was closed before the command completed
+        assert_eq!(field(2, "tag"), Some("A0003"));
+        assert_eq!(field(2, "status"), Some("error"));
+    }
+}
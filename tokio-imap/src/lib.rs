@@ -1,11 +1,79 @@
+mod acl;
+mod append;
+mod backlog;
+mod buffer;
 mod client;
 mod codec;
+mod copy;
+mod enable;
+mod error;
+#[cfg(feature = "export")]
+mod export;
+mod id;
+#[cfg(feature = "tracing")]
+mod instrument;
+mod list;
+mod mailbox;
+mod manage;
+mod messages;
+mod metadata;
+mod namespace;
+mod observer;
+mod protocol;
+mod quota;
+mod referral;
+mod retry;
+mod search;
+mod session;
+mod spill;
+mod status;
+mod stream;
+mod sync;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod throttle;
+mod tls;
+mod trace;
+mod transport;
+mod watch;
 
-pub use crate::client::{Client, TlsClient};
-pub use crate::codec::ResponseData;
+pub use crate::append::AppendUids;
+pub use crate::buffer::BufferPolicy;
+#[cfg(feature = "futures-io")]
+pub use crate::client::CompatConnection;
+#[allow(deprecated)]
+pub use crate::client::TlsClient;
+pub use crate::client::{
+    Client, Connection, Idle, LiteralChunks, ResponseStream, TlsConnection, UnsolicitedResponses,
+};
+pub use crate::codec::{ImapCodec, ResponseData};
+pub use crate::copy::CopyUids;
+pub use crate::error::{Error, FramingError, ParseError, ServerError};
+#[cfg(feature = "export")]
+pub use crate::export::{ExportSink, Maildir, Mbox};
+pub use crate::list::ListedMailbox;
+pub use crate::mailbox::{Expunged, Mailbox, Page};
+pub use crate::messages::{Message, Messages, Part};
+pub use crate::metadata::MetadataValues;
+pub use crate::observer::Observer;
+pub use crate::protocol::{Event, Protocol};
+pub use crate::quota::{MailboxQuota, Quota};
+pub use crate::referral::ImapUrl;
+pub use crate::retry::{is_retryable, RetryPolicy};
+pub use crate::session::{SelectedMailbox, Session};
+pub use crate::spill::{LiteralSink, Spill, SpilledLiteral};
+pub use crate::status::MailboxStatus;
+pub use crate::stream::LiteralChunk;
+pub use crate::sync::{FlagChanges, FlagDiff, MailboxChanges, MessageChange};
+pub use crate::throttle::ConnectionLimiter;
+pub use crate::tls::TlsConfig;
+pub use crate::trace::Trace;
+pub use crate::transport::Transport;
+pub use crate::watch::{MailboxEvent, Watch};
+pub use imap_proto::parser::Quirks;
 
 pub mod builders {
-    pub use imap_proto::builders::command::{fetch, CommandBuilder, FetchCommand};
+    pub use imap_proto::builders::command::{fetch, CommandBuilder, FetchCommand, StoreCommand};
 }
 
 pub mod types {
@@ -0,0 +1,147 @@
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::{Command, CommandBuilder};
+use imap_proto::{MailboxDatum, Response};
+
+/// A mailbox returned by `Client::list()` or `Client::subscribed()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ListedMailbox {
+    pub name: String,
+    /// The hierarchy delimiter, or `None` if the hierarchy is flat.
+    pub delimiter: Option<String>,
+    /// The mailbox attributes, such as `\HasChildren` or `\Noselect`.
+    pub attributes: Vec<String>,
+}
+
+impl ListedMailbox {
+    /// Whether the mailbox has the attribute, compared case-insensitively.
+    pub fn has_attribute(&self, attribute: &str) -> bool {
+        self.attributes
+            .iter()
+            .any(|attr| attr.eq_ignore_ascii_case(attribute))
+    }
+
+    /// Whether the mailbox can be selected: it exists and is not only a level
+    /// of the hierarchy.
+    pub fn is_selectable(&self) -> bool {
+        !self.has_attribute("\\Noselect") && !self.has_attribute("\\NonExistent")
+    }
+}
+
+impl Client {
+    /// List the mailboxes matching `pattern` (where `*` matches any part of
+    /// a name and `%` does not match the hierarchy delimiter) under `reference`.
+    pub async fn list(&self, reference: &str, pattern: &str) -> Result<Vec<ListedMailbox>, Error> {
+        collect_mailboxes(self, CommandBuilder::list(reference, pattern)).await
+    }
+
+    /// List the subscribed mailboxes matching `pattern`, see `list()`.
+    ///
+    /// Uses `LIST (SUBSCRIBED)` if the server supports LIST-EXTENDED (RFC 5258),
+    /// in which case subscribed mailboxes that no longer exist have the
+    /// `\NonExistent` attribute; otherwise `LSUB`.
+    pub async fn subscribed(
+        &self,
+        reference: &str,
+        pattern: &str,
+    ) -> Result<Vec<ListedMailbox>, Error> {
+        let cmd = match self.session().has_capability("LIST-EXTENDED") {
+            true => CommandBuilder::list_subscribed(reference, pattern),
+            false => CommandBuilder::lsub(reference, pattern),
+        };
+        collect_mailboxes(self, cmd).await
+    }
+
+    pub async fn subscribe(&self, mailbox: &str) -> Result<(), Error> {
+        run(self, CommandBuilder::subscribe(mailbox)).await
+    }
+
+    pub async fn unsubscribe(&self, mailbox: &str) -> Result<(), Error> {
+        run(self, CommandBuilder::unsubscribe(mailbox)).await
+    }
+}
+
+async fn collect_mailboxes(client: &Client, cmd: Command) -> Result<Vec<ListedMailbox>, Error> {
+    let mut responses = client.call(cmd);
+    let mut mailboxes = Vec::new();
+    while let Some(rsp) = responses.next().await {
+        let rsp = rsp?;
+        check_completion(&rsp)?;
+        if let Response::MailboxData(MailboxDatum::List {
+            flags,
+            delimiter,
+            name,
+            ..
+        }) = rsp.parsed()
+        {
+            mailboxes.push(ListedMailbox {
+                name: name.to_string(),
+                delimiter: delimiter.as_deref().map(str::to_string),
+                attributes: flags.iter().map(|flag| flag.to_string()).collect(),
+            });
+        }
+    }
+    Ok(mailboxes)
+}
+
+pub(crate) async fn run(client: &Client, cmd: Command) -> Result<(), Error> {
+    let mut responses = client.call(cmd);
+    while let Some(rsp) = responses.next().await {
+        check_completion(&rsp?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+
+    #[tokio::test]
+    async fn subscriptions() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 LIST-EXTENDED] ready\r\n")
+            .expect("A0001 SUBSCRIBE \"Lists/rust\"")
+            .send("A0001 OK SUBSCRIBE completed\r\n")
+            .expect("A0002 LIST (SUBSCRIBED) \"\" \"*\"")
+            .send(
+                "* LIST (\\Subscribed) \"/\" INBOX\r\n\
+                 * LIST (\\Subscribed \\NonExistent) \"/\" Lists/rust\r\n\
+                 A0002 OK LIST completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        client.subscribe("Lists/rust").await.unwrap();
+        let subscribed = client.subscribed("", "*").await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(subscribed.len(), 2);
+        assert_eq!(subscribed[1].name, "Lists/rust");
+        assert_eq!(subscribed[1].delimiter.as_deref(), Some("/"));
+        assert!(subscribed[0].is_selectable());
+        assert!(!subscribed[1].is_selectable());
+    }
+
+    #[tokio::test]
+    async fn lsub() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 LSUB \"\" \"%\"")
+            .send("* LSUB () \".\" Sent\r\nA0001 OK LSUB completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let subscribed = client.subscribed("", "%").await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(subscribed[0].name, "Sent");
+        assert!(subscribed[0].attributes.is_empty());
+    }
+}
@@ -0,0 +1,615 @@
+use std::collections::BTreeMap;
+
+use futures::StreamExt;
+
+use crate::client::{Client, ResponseStream};
+use crate::error::{check_completion, Error};
+use crate::messages::{fetch_messages, Message};
+use crate::session::SelectedMailbox;
+use imap_proto::builders::command::{fetch, Command, CommandBuilder, FetchCommand};
+use imap_proto::{
+    Attribute, AttributeValue, FlagSet, Headers, MailboxDatum, Response, SearchCriteria,
+    SequenceSet, SortCriterion, SortKey, StoreMode, SystemFlags,
+};
+
+/// A mailbox selected with `Client::select()` or `Client::examine()`.
+///
+/// Commands sent through the handle only apply to this mailbox: once another
+/// mailbox is selected (even the same one again) or the mailbox is closed, they
+/// fail with `Error::NotSelected` rather than operating on whichever mailbox is
+/// selected then. Messages are identified by UID, which do not change when
+/// other messages are expunged.
+#[derive(Clone)]
+pub struct Mailbox {
+    client: Client,
+    name: String,
+    selection: u64,
+}
+
+impl Mailbox {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.state().is_some()
+    }
+
+    /// The state of the mailbox, kept up to date from the responses of the
+    /// server (including unsolicited `EXISTS` and `EXPUNGE` responses), or
+    /// `None` once it is no longer selected.
+    pub fn state(&self) -> Option<SelectedMailbox> {
+        let session = self.client.session();
+        match session.selected() {
+            Some(mailbox) if session.selection() == self.selection => Some(mailbox.clone()),
+            _ => None,
+        }
+    }
+
+    /// The `UIDVALIDITY` of the mailbox: UIDs are only valid along with it.
+    pub fn uid_validity(&self) -> Option<u32> {
+        self.state()?.uid_validity
+    }
+
+    /// The number of messages in the mailbox, or 0 once it is no longer
+    /// selected.
+    pub fn exists(&self) -> u32 {
+        self.state().map_or(0, |mailbox| mailbox.exists)
+    }
+
+    /// Send a command operating on the mailbox, as with `Client::call()`.
+    pub fn call<C: Into<Command>>(&self, cmd: C) -> ResponseStream {
+        match self.check() {
+            Ok(()) => self.client.call(cmd),
+            Err(e) => ResponseStream::error(e),
+        }
+    }
+
+    /// Fetch `attrs` (and the UID) of the messages with the given UIDs.
+    pub fn fetch(&self, uids: &SequenceSet, attrs: &[Attribute]) -> ResponseStream {
+        let mut cmd = CommandBuilder::uid_fetch().set(uids).attr(Attribute::Uid);
+        for attr in attrs.iter().filter(|attr| **attr != Attribute::Uid) {
+            cmd = cmd.attr(attr.clone());
+        }
+        self.call(cmd)
+    }
+
+    /// Fetch the header fields with the given names (`BODY.PEEK[HEADER.FIELDS
+    /// (...)]`) of the messages with the given UIDs, by UID.
+    pub async fn fetch_headers(
+        &self,
+        uids: &SequenceSet,
+        names: &[&str],
+    ) -> Result<BTreeMap<u32, Headers>, Error> {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        let mut responses = self.fetch(uids, &[Attribute::BodyPeekHeaderFields(names)]);
+        let mut headers = BTreeMap::new();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            let attrs = match rsp.parsed() {
+                Response::Fetch(_, attrs) => attrs,
+                _ => continue,
+            };
+            let mut uid = None;
+            let mut fields = Headers::default();
+            for attr in attrs {
+                match attr {
+                    AttributeValue::Uid(n) => uid = Some(*n),
+                    AttributeValue::BodySection {
+                        data: Some(data), ..
+                    } => fields = Headers::parse(data),
+                    _ => {}
+                }
+            }
+            if let Some(uid) = uid {
+                headers.insert(uid, fields);
+            }
+        }
+        Ok(headers)
+    }
+
+    /// Search the mailbox, returning the UIDs of the matching messages; see
+    /// `Client::search()`.
+    pub async fn search(&self, criteria: &str) -> Result<SequenceSet, Error> {
+        self.check()?;
+        self.client.uid_search(criteria).await
+    }
+
+    /// Fetch the UID, flags and envelope of the messages on one page of the
+    /// mailbox listed in the given order, as for a list view. Pages hold
+    /// `page_size` messages and are numbered from 0.
+    ///
+    /// The order is computed by the server with `SORT` (RFC 5256). Servers
+    /// without `SORT` list messages in mailbox order, by arrival, reversed if
+    /// the first criterion is.
+    pub async fn page(
+        &self,
+        order: &[SortCriterion],
+        page_size: u32,
+        page_index: u32,
+    ) -> Result<Page, Error> {
+        self.check()?;
+        let page_size = page_size.max(1);
+        let start = page_index.saturating_mul(page_size);
+        if self.client.session().has_capability("SORT") {
+            let uids = self.sort(order).await?;
+            let page = uids
+                .iter()
+                .skip(start as usize)
+                .take(page_size as usize)
+                .copied()
+                .collect::<Vec<_>>();
+            let mut messages = match page.is_empty() {
+                true => Vec::new(),
+                false => {
+                    let set = page.iter().copied().collect::<SequenceSet>();
+                    fetch_messages(
+                        &self.client,
+                        Self::list_fetch(CommandBuilder::uid_fetch().set(&set)),
+                    )
+                    .await?
+                }
+            };
+            messages.sort_by_key(|msg| page.iter().position(|uid| *uid == msg.uid()));
+            return Ok(Page {
+                messages,
+                total: uids.len() as u32,
+            });
+        }
+
+        let total = self.exists();
+        if start >= total {
+            return Ok(Page {
+                messages: Vec::new(),
+                total,
+            });
+        }
+        let reverse = matches!(order.first(), Some(criterion) if criterion.reverse);
+        let range = match reverse {
+            true => (total - start).saturating_sub(page_size - 1).max(1)..=total - start,
+            false => start + 1..=total.min(start.saturating_add(page_size)),
+        };
+        let mut messages = fetch_messages(
+            &self.client,
+            Self::list_fetch(CommandBuilder::fetch().range(range)),
+        )
+        .await?;
+        messages.sort_by_key(|msg| msg.seq());
+        if reverse {
+            messages.reverse();
+        }
+        Ok(Page { messages, total })
+    }
+
+    /// Change the flags of the messages with the given UIDs.
+    pub async fn store(
+        &self,
+        uids: &SequenceSet,
+        mode: StoreMode,
+        flags: &FlagSet,
+    ) -> Result<(), Error> {
+        self.run(CommandBuilder::uid_store(uids, mode, flags).silent())
+            .await
+    }
+
+    /// Change the flags of the messages with the given UIDs, returning their
+    /// flags after the change as sent back by the server, by UID.
+    pub async fn update_flags(
+        &self,
+        uids: &SequenceSet,
+        mode: StoreMode,
+        flags: &FlagSet,
+    ) -> Result<BTreeMap<u32, FlagSet>, Error> {
+        let mut responses = self.call(CommandBuilder::uid_store(uids, mode, flags));
+        let mut updated = BTreeMap::new();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            let attrs = match rsp.parsed() {
+                Response::Fetch(_, attrs) => attrs,
+                _ => continue,
+            };
+            let mut uid = None;
+            let mut flags = None;
+            for attr in attrs {
+                match attr {
+                    AttributeValue::Uid(n) => uid = Some(*n),
+                    AttributeValue::Flags(list) => flags = Some(list.iter().collect()),
+                    _ => {}
+                }
+            }
+            if let (Some(uid), Some(flags)) = (uid, flags) {
+                updated.insert(uid, flags);
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Replace the flags of the messages with the given UIDs.
+    pub async fn set_flags(
+        &self,
+        uids: &SequenceSet,
+        flags: &FlagSet,
+    ) -> Result<BTreeMap<u32, FlagSet>, Error> {
+        self.update_flags(uids, StoreMode::Replace, flags).await
+    }
+
+    pub async fn mark_seen(&self, uids: &SequenceSet) -> Result<BTreeMap<u32, FlagSet>, Error> {
+        self.update_flags(uids, StoreMode::Add, &SystemFlags::SEEN.into())
+            .await
+    }
+
+    pub async fn mark_unseen(&self, uids: &SequenceSet) -> Result<BTreeMap<u32, FlagSet>, Error> {
+        self.update_flags(uids, StoreMode::Remove, &SystemFlags::SEEN.into())
+            .await
+    }
+
+    /// Set the `\Deleted` flag, so that the messages are removed by the next
+    /// `expunge()`.
+    pub async fn mark_deleted(&self, uids: &SequenceSet) -> Result<BTreeMap<u32, FlagSet>, Error> {
+        self.update_flags(uids, StoreMode::Add, &SystemFlags::DELETED.into())
+            .await
+    }
+
+    /// Add a keyword, such as `$Label1` or `$Junk`.
+    pub async fn add_keyword(
+        &self,
+        uids: &SequenceSet,
+        keyword: &str,
+    ) -> Result<BTreeMap<u32, FlagSet>, Error> {
+        let flags = std::iter::once(keyword).collect();
+        self.update_flags(uids, StoreMode::Add, &flags).await
+    }
+
+    pub async fn remove_keyword(
+        &self,
+        uids: &SequenceSet,
+        keyword: &str,
+    ) -> Result<BTreeMap<u32, FlagSet>, Error> {
+        let flags = std::iter::once(keyword).collect();
+        self.update_flags(uids, StoreMode::Remove, &flags).await
+    }
+
+    /// Permanently remove the messages with the `\Deleted` flag.
+    pub async fn expunge(&self) -> Result<Expunged, Error> {
+        self.expunge_with(CommandBuilder::expunge()).await
+    }
+
+    /// Permanently remove the messages with the given UIDs that have the
+    /// `\Deleted` flag, leaving other deleted messages alone (as they may have
+    /// been deleted by another client). Requires UIDPLUS (RFC 4315).
+    pub async fn uid_expunge(&self, uids: &SequenceSet) -> Result<Expunged, Error> {
+        self.expunge_with(CommandBuilder::uid_expunge(uids)).await
+    }
+
+    async fn expunge_with(&self, cmd: Command) -> Result<Expunged, Error> {
+        let mut responses = self.call(cmd);
+        let mut expunged = Expunged::default();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            match rsp.parsed() {
+                Response::Expunge(seq) => expunged.seqs.push(*seq),
+                Response::Vanished {
+                    earlier: false,
+                    uids,
+                } => {
+                    for range in uids.ranges() {
+                        expunged.uids.insert_range(range);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(expunged)
+    }
+
+    /// Close the mailbox, which also expunges it unless it was opened with
+    /// `Client::examine()`.
+    pub async fn close(self) -> Result<(), Error> {
+        self.run(CommandBuilder::close()).await
+    }
+
+    async fn run<C: Into<Command>>(&self, cmd: C) -> Result<(), Error> {
+        let mut responses = self.call(cmd);
+        while let Some(rsp) = responses.next().await {
+            check_completion(&rsp?)?;
+        }
+        Ok(())
+    }
+
+    /// The UIDs of all the messages, in the given order.
+    async fn sort(&self, order: &[SortCriterion]) -> Result<Vec<u32>, Error> {
+        let order = match order.is_empty() {
+            true => &[SortCriterion::new(SortKey::Arrival)][..],
+            false => order,
+        };
+        let mut responses = self.call(CommandBuilder::uid_sort(order, &SearchCriteria::All));
+        let mut uids = Vec::new();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            if let Response::MailboxData(MailboxDatum::Sort(ids)) = rsp.parsed() {
+                uids.extend(ids);
+            }
+        }
+        Ok(uids)
+    }
+
+    fn list_fetch(cmd: FetchCommand<fetch::Messages>) -> FetchCommand<fetch::Attributes> {
+        cmd.attr(Attribute::Uid)
+            .attr(Attribute::Flags)
+            .attr(Attribute::Envelope)
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        match self.is_selected() {
+            true => Ok(()),
+            false => Err(Error::NotSelected(self.name.clone())),
+        }
+    }
+}
+
+/// The messages removed by `Mailbox::expunge()` or `Mailbox::uid_expunge()`.
+///
+/// Servers report removed messages by sequence number, or by UID once QRESYNC
+/// (RFC 7162) is enabled.
+#[derive(Clone, Debug, Default)]
+pub struct Expunged {
+    /// The sequence numbers from the `EXPUNGE` responses, in order. Each
+    /// applies to the mailbox as left by the previous ones, so removing
+    /// messages 3 and 4 is reported as 3 twice.
+    pub seqs: Vec<u32>,
+    /// The UIDs from the `VANISHED` responses.
+    pub uids: SequenceSet,
+}
+
+impl Expunged {
+    /// The number of messages removed.
+    pub fn len(&self) -> usize {
+        self.seqs.len() + self.uids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A page of messages, returned by `Mailbox::page()`.
+#[derive(Clone)]
+pub struct Page {
+    /// The messages on the page, in order.
+    pub messages: Vec<Message>,
+    /// The number of messages in the mailbox, to compute the number of pages.
+    pub total: u32,
+}
+
+impl Client {
+    /// Select `mailbox`, returning a handle to operate on it.
+    pub async fn select(&self, mailbox: &str) -> Result<Mailbox, Error> {
+        self.open(CommandBuilder::select(mailbox).into(), mailbox)
+            .await
+    }
+
+    /// Select `mailbox` in read-only mode.
+    pub async fn examine(&self, mailbox: &str) -> Result<Mailbox, Error> {
+        self.open(CommandBuilder::examine(mailbox).into(), mailbox)
+            .await
+    }
+
+    async fn open(&self, cmd: Command, mailbox: &str) -> Result<Mailbox, Error> {
+        let mut responses = self.call(cmd);
+        while let Some(rsp) = responses.next().await {
+            check_completion(&rsp?)?;
+        }
+        Ok(Mailbox {
+            client: self.clone(),
+            name: mailbox.to_string(),
+            selection: self.session().selection(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, Error};
+    use imap_proto::{FlagSet, SequenceSet, SortCriterion, SortKey, StoreMode};
+
+    #[tokio::test]
+    async fn uid_expunge() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 UIDPLUS] ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send("* 5 EXISTS\r\nA0001 OK [READ-WRITE] SELECT completed\r\n")
+            .expect("A0002 UID EXPUNGE 7:8")
+            .send("* 3 EXPUNGE\r\n* 3 EXPUNGE\r\nA0002 OK EXPUNGE completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let inbox = client.select("INBOX").await.unwrap();
+        let expunged = inbox.uid_expunge(&SequenceSet::from(7..=8)).await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(expunged.seqs, [3, 3]);
+        assert_eq!(expunged.len(), 2);
+        assert_eq!(inbox.exists(), 3);
+    }
+
+    #[tokio::test]
+    async fn update_flags() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send("* 2 EXISTS\r\nA0001 OK [READ-WRITE] SELECT completed\r\n")
+            .expect("A0002 UID STORE 4:5 +FLAGS (\\Seen)")
+            .send(
+                "* 1 FETCH (UID 4 FLAGS (\\Seen))\r\n\
+                 * 2 FETCH (FLAGS (\\Seen $Label1) UID 5)\r\n\
+                 A0002 OK STORE completed\r\n",
+            )
+            .expect("A0003 UID STORE 5 -FLAGS ($Label1)")
+            .send("* 2 FETCH (UID 5 FLAGS (\\Seen))\r\nA0003 OK STORE completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let inbox = client.select("INBOX").await.unwrap();
+        let flags = inbox.mark_seen(&SequenceSet::from(4..=5)).await.unwrap();
+        assert_eq!(flags[&4].to_string(), "(\\Seen)");
+        assert!(flags[&5].contains("$label1"));
+        let flags = inbox
+            .remove_keyword(&SequenceSet::from(5..=5), "$Label1")
+            .await
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(flags.keys().collect::<Vec<_>>(), [&5]);
+        assert!(!flags[&5].contains("$Label1"));
+    }
+
+    #[tokio::test]
+    async fn fetch_headers() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 EXAMINE \"INBOX\"")
+            .send("* 2 EXISTS\r\nA0001 OK [READ-ONLY] EXAMINE completed\r\n")
+            .expect("A0002 UID FETCH 4:5 (UID BODY.PEEK[HEADER.FIELDS (From Subject)])")
+            .send(
+                "* 1 FETCH (UID 4 BODY[HEADER.FIELDS (From Subject)] {50}\r\n\
+                 From: a@example.com\r\nSubject: =?UTF-8?B?SGk=?=\r\n\r\n)\r\n\
+                 * 2 FETCH (UID 5 BODY[HEADER.FIELDS (From Subject)] {28}\r\n\
+                 Subject: long\r\n  subject\r\n\r\n)\r\n\
+                 A0002 OK FETCH completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let inbox = client.examine("INBOX").await.unwrap();
+        let uids = SequenceSet::from(4..=5);
+        let headers = inbox
+            .fetch_headers(&uids, &["From", "Subject"])
+            .await
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(headers[&4].get("from"), Some("a@example.com"));
+        assert_eq!(headers[&4].get("subject"), Some("Hi"));
+        assert_eq!(headers[&5].get("from"), None);
+        assert_eq!(headers[&5].get("subject"), Some("long  subject"));
+    }
+
+    #[tokio::test]
+    async fn mailbox() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send(
+                "* 3 EXISTS\r\n\
+                 * OK [UIDVALIDITY 42] UIDs valid\r\n\
+                 A0001 OK [READ-WRITE] SELECT completed\r\n",
+            )
+            .expect("A0002 UID STORE 2 +FLAGS.SILENT (\\Deleted)")
+            .send("A0002 OK STORE completed\r\n")
+            .expect("A0003 EXPUNGE")
+            .send("* 2 EXPUNGE\r\nA0003 OK EXPUNGE completed\r\n")
+            .expect("A0004 EXAMINE \"Archive\"")
+            .send("* 7 EXISTS\r\nA0004 OK [READ-ONLY] EXAMINE completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let inbox = client.select("INBOX").await.unwrap();
+        assert_eq!(inbox.uid_validity(), Some(42));
+        assert_eq!(inbox.exists(), 3);
+
+        let deleted: FlagSet = vec!["\\Deleted"].into_iter().collect();
+        let uids = SequenceSet::from(2..=2);
+        inbox.store(&uids, StoreMode::Add, &deleted).await.unwrap();
+        let expunged = inbox.expunge().await.unwrap();
+        assert_eq!(expunged.seqs, [2]);
+        assert_eq!(inbox.exists(), 2);
+
+        let archive = client.examine("Archive").await.unwrap();
+        assert!(!inbox.is_selected());
+        assert!(archive.state().unwrap().read_only);
+        match inbox.expunge().await {
+            Err(Error::NotSelected(name)) => assert_eq!(name, "INBOX"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        server.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn page() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 SORT] ready\r\n")
+            .expect("A0001 EXAMINE \"INBOX\"")
+            .send("* 5 EXISTS\r\nA0001 OK [READ-ONLY] EXAMINE completed\r\n")
+            .expect("A0002 UID SORT (REVERSE DATE) UTF-8 ALL")
+            .send("* SORT 9 4 7 3 8\r\nA0002 OK SORT completed\r\n")
+            .expect("A0003 UID FETCH 3,7 (UID FLAGS ENVELOPE)")
+            .send(
+                "* 2 FETCH (UID 3 FLAGS () ENVELOPE (NIL \"b\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+                 * 4 FETCH (UID 7 FLAGS () ENVELOPE (NIL \"a\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+                 A0003 OK FETCH completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let inbox = client.examine("INBOX").await.unwrap();
+        let order = [SortCriterion::reverse(SortKey::Date)];
+        let page = inbox.page(&order, 2, 1).await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(page.total, 5);
+        let uids = page
+            .messages
+            .iter()
+            .map(|msg| msg.uid())
+            .collect::<Vec<_>>();
+        assert_eq!(uids, [7, 3]);
+    }
+
+    #[tokio::test]
+    async fn page_without_sort() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 EXAMINE \"INBOX\"")
+            .send("* 5 EXISTS\r\nA0001 OK [READ-ONLY] EXAMINE completed\r\n")
+            .expect("A0002 FETCH 2:3 (UID FLAGS ENVELOPE)")
+            .send(
+                "* 2 FETCH (UID 4 FLAGS () ENVELOPE (NIL NIL NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+                 * 3 FETCH (UID 7 FLAGS () ENVELOPE (NIL NIL NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+                 A0002 OK FETCH completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let inbox = client.examine("INBOX").await.unwrap();
+        let order = [SortCriterion::reverse(SortKey::Arrival)];
+        let page = inbox.page(&order, 2, 3).await.unwrap();
+        assert!(page.messages.is_empty());
+        let page = inbox.page(&order, 2, 1).await.unwrap();
+        server.finish().await.unwrap();
+        let uids = page
+            .messages
+            .iter()
+            .map(|msg| msg.uid())
+            .collect::<Vec<_>>();
+        assert_eq!(uids, [7, 4]);
+    }
+}
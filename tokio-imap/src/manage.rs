@@ -0,0 +1,144 @@
+use std::borrow::Cow;
+use std::future::Future;
+
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, mailbox_error, Error};
+use imap_proto::builders::command::{Command, CommandBuilder};
+use imap_proto::types::encode_utf7;
+use imap_proto::ResponseCode;
+
+impl Client {
+    /// Encode a mailbox name for use in a command: as-is if `UTF8=ACCEPT`
+    /// (RFC 6855) is enabled, in modified UTF-7 otherwise.
+    pub fn encode_mailbox_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        if (name.is_ascii() && !name.contains('&')) || self.session().is_enabled("UTF8=ACCEPT") {
+            Cow::Borrowed(name)
+        } else {
+            Cow::Owned(encode_utf7(name))
+        }
+    }
+
+    /// Create the mailbox `name`, failing with `Error::AlreadyExists` if the
+    /// server reports that it exists.
+    pub async fn create_mailbox(&self, name: &str) -> Result<(), Error> {
+        let name = self.encode_mailbox_name(name);
+        self.run_on_mailbox(CommandBuilder::create(&name)).await
+    }
+
+    /// Delete the mailbox `name`, failing with `Error::NonExistent` if the
+    /// server reports that it does not exist.
+    pub async fn delete_mailbox(&self, name: &str) -> Result<(), Error> {
+        let name = self.encode_mailbox_name(name);
+        self.run_on_mailbox(CommandBuilder::delete(&name)).await
+    }
+
+    /// Rename the mailbox `from` to `to`.
+    pub async fn rename_mailbox(&self, from: &str, to: &str) -> Result<(), Error> {
+        let from = self.encode_mailbox_name(from);
+        let to = self.encode_mailbox_name(to);
+        self.run_on_mailbox(CommandBuilder::rename(&from, &to))
+            .await
+    }
+
+    /// Run `op`, and if it fails with `Error::TryCreate`, create `mailbox` and
+    /// run `op` again.
+    ///
+    /// ```no_run
+    /// # async fn example(client: tokio_imap::Client, uids: imap_proto::SequenceSet) -> Result<(), tokio_imap::Error> {
+    /// client
+    ///     .create_and_retry("Archive", || client.copy_messages(&uids, "Archive"))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_and_retry<F, Fut, T>(&self, mailbox: &str, op: F) -> Result<T, Error>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        match op().await {
+            Err(Error::TryCreate(_)) => {}
+            Err(Error::No(e)) if e.code() == Some(&ResponseCode::TryCreate) => {}
+            res => return res,
+        }
+        match self.create_mailbox(mailbox).await {
+            // Created concurrently by another client
+            Ok(()) | Err(Error::AlreadyExists(_)) => {}
+            Err(e) => return Err(e),
+        }
+        op().await
+    }
+
+    pub(crate) async fn run_on_mailbox(&self, cmd: Command) -> Result<(), Error> {
+        let mut responses = self.call(cmd);
+        while let Some(rsp) = responses.next().await {
+            check_completion(&rsp?).map_err(mailbox_error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, Error};
+    use imap_proto::SequenceSet;
+
+    #[tokio::test]
+    async fn manage_mailboxes() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 CREATE \"Entw&APw-rfe\"")
+            .send("A0001 NO [ALREADYEXISTS] Mailbox exists\r\n")
+            .expect("A0002 RENAME \"Drafts\" \"Entw&APw-rfe\"")
+            .send("A0002 OK RENAME completed\r\n")
+            .expect("A0003 DELETE \"Old\"")
+            .send("A0003 NO [NONEXISTENT] No such mailbox\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        match client.create_mailbox("Entw\u{fc}rfe").await {
+            Err(Error::AlreadyExists(e)) => assert_eq!(e.text(), "Mailbox exists"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        client
+            .rename_mailbox("Drafts", "Entw\u{fc}rfe")
+            .await
+            .unwrap();
+        match client.delete_mailbox("Old").await {
+            Err(Error::NonExistent(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        server.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_and_retry() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 UID COPY 7 \"Archive\"")
+            .send("A0001 NO [TRYCREATE] No such mailbox\r\n")
+            .expect("A0002 CREATE \"Archive\"")
+            .send("A0002 OK CREATE completed\r\n")
+            .expect("A0003 UID COPY 7 \"Archive\"")
+            .send("A0003 OK COPY completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let uids = SequenceSet::from(7..=7);
+        let copied = client
+            .create_and_retry("Archive", || client.copy_messages(&uids, "Archive"))
+            .await
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(copied, None);
+    }
+}
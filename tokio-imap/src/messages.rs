@@ -0,0 +1,406 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt};
+
+use crate::client::Client;
+use crate::codec::ResponseData;
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::{Command, CommandBuilder};
+use imap_proto::{Attribute, AttributeValue, ContentEncoding, Envelope, Response, SectionPath};
+
+/// A message in the selected mailbox, yielded by `Messages`.
+///
+/// Holds the UID, flags and envelope of the message; the message itself is only
+/// fetched when calling `body()`.
+#[derive(Clone)]
+pub struct Message {
+    client: Client,
+    seq: u32,
+    uid: u32,
+    data: ResponseData,
+}
+
+impl Message {
+    /// The sequence number of the message when it was fetched.
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn flags(&self) -> &[&str] {
+        self.attributes()
+            .iter()
+            .find_map(|attr| match attr {
+                AttributeValue::Flags(flags) => Some(&flags[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn envelope(&self) -> Option<&Envelope<'_>> {
+        self.attributes().iter().find_map(|attr| match attr {
+            AttributeValue::Envelope(envelope) => Some(&**envelope),
+            _ => None,
+        })
+    }
+
+    /// Fetch the whole message (`BODY.PEEK[]`), without marking it as seen.
+    ///
+    /// The message shares the buffer it was received in, so it is not copied.
+    pub async fn body(&self) -> Result<Bytes, Error> {
+        let cmd = CommandBuilder::uid_fetch()
+            .num(self.uid)
+            .attr(Attribute::BodyPeek);
+        let mut responses = self.client.call(cmd);
+        let mut body = None;
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            if let Response::Fetch(_, attrs) = rsp.parsed() {
+                let mut uid = None;
+                let mut data = None;
+                for attr in attrs {
+                    match attr {
+                        AttributeValue::Uid(n) => uid = Some(*n),
+                        AttributeValue::BodySection {
+                            section: None,
+                            data: Some(bytes),
+                            ..
+                        } => data = Some(rsp.slice(bytes)),
+                        _ => {}
+                    }
+                }
+                if uid == Some(self.uid) && data.is_some() {
+                    body = data;
+                }
+            }
+        }
+        body.ok_or_else(|| Error::Protocol(format!("message with UID {} not found", self.uid)))
+    }
+
+    /// Fetch a part of the message (`BODY.PEEK[<section>]`), such as an
+    /// attachment found with `BodyStructure::attachments()`, without marking
+    /// the message as seen.
+    ///
+    /// The part is decoded from its transfer encoding (base64 or
+    /// quoted-printable), as given by the body structure fetched along with it.
+    /// Fails with `Error::Protocol` if the encoding is unknown or invalid.
+    pub async fn fetch_part(&self, section: &SectionPath) -> Result<Part, Error> {
+        let cmd = CommandBuilder::uid_fetch()
+            .num(self.uid)
+            .attr(Attribute::BodyStructure)
+            .attr(Attribute::BodyPeekSection(section.clone()));
+        let mut responses = self.client.call(cmd);
+        let mut part = None;
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            let attrs = match rsp.parsed() {
+                Response::Fetch(_, attrs) => attrs,
+                _ => continue,
+            };
+            let mut uid = None;
+            let mut structure = None;
+            let mut data = None;
+            for attr in attrs {
+                match attr {
+                    AttributeValue::Uid(n) => uid = Some(*n),
+                    AttributeValue::BodyStructure(body) => structure = Some(body),
+                    AttributeValue::BodySection {
+                        section: Some(found),
+                        data: Some(bytes),
+                        ..
+                    } if found == section => data = Some(&**bytes),
+                    _ => {}
+                }
+            }
+            let (body, data) = match (uid == Some(self.uid), structure, data) {
+                (true, Some(structure), Some(data)) => match structure.part(section) {
+                    Some(body) => (body, data),
+                    None => return Err(Error::Protocol(format!("no part {}", section))),
+                },
+                _ => continue,
+            };
+
+            let encoding = match body.single_part() {
+                Some(single) => &single.transfer_encoding,
+                None => &ContentEncoding::SevenBit,
+            };
+            let data = match encoding.decode(data) {
+                Some(Cow::Borrowed(data)) => rsp.slice(data),
+                Some(Cow::Owned(data)) => Bytes::from(data),
+                None => {
+                    return Err(Error::Protocol(format!(
+                        "cannot decode part {} from {:?}",
+                        section, encoding
+                    )))
+                }
+            };
+            let ty = &body.common().ty;
+            part = Some(Part {
+                data,
+                content_type: format!("{}/{}", ty.ty, ty.subtype).to_ascii_lowercase(),
+                filename: body.filename(),
+            });
+        }
+        part.ok_or_else(|| Error::Protocol(format!("message with UID {} not found", self.uid)))
+    }
+
+    fn attributes(&self) -> &[AttributeValue<'_>] {
+        match self.data.parsed() {
+            Response::Fetch(_, attrs) => attrs,
+            _ => &[],
+        }
+    }
+}
+
+/// A part of a message fetched with `Message::fetch_part()`, such as an
+/// attachment.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Part {
+    /// The content, decoded from its transfer encoding.
+    pub data: Bytes,
+    /// The content type, such as `image/png`, in lower case.
+    pub content_type: String,
+    /// The decoded file name, if any.
+    pub filename: Option<String>,
+}
+
+/// A stream of the messages in the selected mailbox, created by
+/// `Client::messages()`.
+///
+/// Messages are fetched by sequence number, in batches. The stream ends after
+/// yielding an error.
+pub struct Messages {
+    client: Client,
+    batch_size: u32,
+    messages: Option<BoxStream<'static, Result<Message, Error>>>,
+}
+
+impl Messages {
+    /// The number of messages to fetch per command. Defaults to 100.
+    pub fn batch_size(mut self, size: u32) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+}
+
+impl Stream for Messages {
+    type Item = Result<Message, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if self.messages.is_none() {
+            let exists = self.client.session().selected().map_or(0, |m| m.exists);
+            let batches = Batches {
+                client: self.client.clone(),
+                next: 1,
+                exists,
+                batch_size: self.batch_size,
+                buffered: VecDeque::new(),
+                failed: false,
+            };
+            let messages = stream::unfold(batches, |mut batches| async move {
+                let message = batches.next().await?;
+                Some((message, batches))
+            });
+            self.messages = Some(messages.boxed());
+        }
+        self.messages.as_mut().unwrap().poll_next_unpin(cx) // safe: set above
+    }
+}
+
+impl Client {
+    /// Iterate over the messages in the selected mailbox, fetching their UID, flags
+    /// and envelope.
+    ///
+    /// The number of messages is taken from the session when the stream is first
+    /// polled; messages added afterwards are not included.
+    pub fn messages(&self) -> Messages {
+        Messages {
+            client: self.clone(),
+            batch_size: 100,
+            messages: None,
+        }
+    }
+}
+
+struct Batches {
+    client: Client,
+    next: u32,
+    exists: u32,
+    batch_size: u32,
+    buffered: VecDeque<Message>,
+    failed: bool,
+}
+
+impl Batches {
+    async fn next(&mut self) -> Option<Result<Message, Error>> {
+        loop {
+            if let Some(message) = self.buffered.pop_front() {
+                return Some(Ok(message));
+            } else if self.failed || self.next > self.exists {
+                return None;
+            }
+
+            if let Err(e) = self.fetch().await {
+                self.failed = true;
+                return Some(Err(e));
+            }
+        }
+    }
+
+    async fn fetch(&mut self) -> Result<(), Error> {
+        let end = self
+            .exists
+            .min(self.next.saturating_add(self.batch_size - 1));
+        let cmd = CommandBuilder::fetch()
+            .range(self.next..=end)
+            .attr(Attribute::Uid)
+            .attr(Attribute::Flags)
+            .attr(Attribute::Envelope);
+        self.next = end.saturating_add(1);
+        self.buffered
+            .extend(fetch_messages(&self.client, cmd).await?);
+        Ok(())
+    }
+}
+
+/// Send a `FETCH` command, collecting the messages with a UID.
+pub(crate) async fn fetch_messages<C: Into<Command>>(
+    client: &Client,
+    cmd: C,
+) -> Result<Vec<Message>, Error> {
+    let mut messages = Vec::new();
+    let mut responses = client.call(cmd);
+    while let Some(rsp) = responses.next().await {
+        let rsp = rsp?;
+        check_completion(&rsp)?;
+        let (seq, uid) = match rsp.parsed() {
+            Response::Fetch(seq, attrs) => {
+                let uid = attrs.iter().find_map(|attr| match attr {
+                    AttributeValue::Uid(uid) => Some(*uid),
+                    _ => None,
+                });
+                match uid {
+                    Some(uid) => (*seq, uid),
+                    None => continue,
+                }
+            }
+            _ => continue,
+        };
+        messages.push(Message {
+            client: client.clone(),
+            seq,
+            uid,
+            data: rsp,
+        });
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use futures::StreamExt;
+    use imap_proto::builders::command::CommandBuilder;
+    use imap_proto::SectionPath;
+
+    #[tokio::test]
+    async fn messages() {
+        let script = Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send("* 3 EXISTS\r\nA0001 OK [READ-WRITE] selected\r\n")
+            .expect("A0002 FETCH 1:2 (UID FLAGS ENVELOPE)")
+            .send(
+                "* 1 FETCH (UID 10 FLAGS (\\Seen) ENVELOPE (NIL \"Hi\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+                 * 2 FETCH (UID 11 FLAGS () ENVELOPE (NIL \"Re: Hi\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+                 A0002 OK FETCH completed\r\n",
+            )
+            .expect("A0003 FETCH 3:3 (UID FLAGS ENVELOPE)")
+            .send(
+                "* 3 FETCH (UID 14 FLAGS () ENVELOPE (NIL NIL NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+                 A0003 OK FETCH completed\r\n",
+            )
+            .expect("A0004 UID FETCH 11 (BODY.PEEK[])")
+            .send("* 2 FETCH (UID 11 BODY[] {5}\r\nhello)\r\nA0004 OK FETCH completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let mut select = client.call(CommandBuilder::select("INBOX"));
+        while let Some(rsp) = select.next().await {
+            rsp.unwrap();
+        }
+
+        let messages = client
+            .messages()
+            .batch_size(2)
+            .map(|msg| msg.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            messages.iter().map(|msg| msg.uid()).collect::<Vec<_>>(),
+            vec![10, 11, 14]
+        );
+        assert_eq!(messages[0].flags(), &["\\Seen"]);
+        assert_eq!(
+            messages[1].envelope().unwrap().subject.as_deref(),
+            Some(&b"Re: Hi"[..])
+        );
+
+        let body = messages[1].body().await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(body, &b"hello"[..]);
+    }
+
+    #[tokio::test]
+    async fn fetch_part() {
+        let script = Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send("* 1 EXISTS\r\nA0001 OK [READ-WRITE] selected\r\n")
+            .expect("A0002 FETCH 1:1 (UID FLAGS ENVELOPE)")
+            .send(
+                "* 1 FETCH (UID 10 FLAGS () ENVELOPE (NIL NIL NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+                 A0002 OK FETCH completed\r\n",
+            )
+            .expect("A0003 UID FETCH 10 (BODYSTRUCTURE BODY.PEEK[2])")
+            .send(
+                "* 1 FETCH (UID 10 BODYSTRUCTURE ((\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 5 1)\
+                 (\"APPLICATION\" \"PDF\" (\"NAME\" \"a.pdf\") NIL NIL \"BASE64\" 8 NIL (\"ATTACHMENT\" (\"FILENAME\" \"a.pdf\")) NIL) \
+                 \"MIXED\") BODY[2] {8}\r\naGVsbG8=)\r\n\
+                 A0003 OK FETCH completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let mut select = client.call(CommandBuilder::select("INBOX"));
+        while let Some(rsp) = select.next().await {
+            rsp.unwrap();
+        }
+        let message = client.messages().next().await.unwrap().unwrap();
+        let part = message
+            .fetch_part(&SectionPath::Part(vec![2], None))
+            .await
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(part.data, &b"hello"[..]);
+        assert_eq!(part.content_type, "application/pdf");
+        assert_eq!(part.filename.as_deref(), Some("a.pdf"));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, mailbox_error, Error};
+use imap_proto::builders::command::CommandBuilder;
+use imap_proto::{MailboxDatum, MetadataCode, MetadataOptions, Response, ResponseCode};
+
+/// The entries returned by `Client::get_metadata()`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MetadataValues {
+    /// The values by entry name. Entries without a value are left out.
+    pub values: BTreeMap<String, String>,
+    /// If entries were left out for being larger than `max_size`, the size of
+    /// the largest of them; asking again with at least this size returns them.
+    pub long_entries: Option<u32>,
+}
+
+impl MetadataValues {
+    pub fn get(&self, entry: &str) -> Option<&str> {
+        self.values.get(entry).map(String::as_str)
+    }
+}
+
+impl Client {
+    /// Get the values of metadata `entries` (RFC 5464), such as
+    /// `/shared/comment`, on `mailbox`, or on the server if `mailbox` is empty.
+    pub async fn get_metadata(
+        &self,
+        mailbox: &str,
+        entries: &[&str],
+        options: &MetadataOptions,
+    ) -> Result<MetadataValues, Error> {
+        let mailbox = self.encode_mailbox_name(mailbox);
+        let mut responses = self.call(CommandBuilder::get_metadata(&mailbox, entries, options));
+        let mut metadata = MetadataValues::default();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp).map_err(mailbox_error)?;
+            match rsp.parsed() {
+                Response::MailboxData(MailboxDatum::MetadataSolicited { values, .. }) => {
+                    for value in values {
+                        if let Some(v) = &value.value {
+                            metadata.values.insert(value.entry.clone(), v.clone());
+                        }
+                    }
+                }
+                Response::Done {
+                    code: Some(ResponseCode::Metadata(MetadataCode::LongEntries(size))),
+                    ..
+                } => metadata.long_entries = Some(*size),
+                _ => {}
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// Set metadata entries on `mailbox`, or on the server if `mailbox` is
+    /// empty. Entries with no value are removed.
+    ///
+    /// Fails with `Error::Metadata` if the server refuses the values because of
+    /// their size or number.
+    pub async fn set_metadata(
+        &self,
+        mailbox: &str,
+        values: &[(&str, Option<&[u8]>)],
+    ) -> Result<(), Error> {
+        let mailbox = self.encode_mailbox_name(mailbox);
+        let mut responses = self.call(CommandBuilder::set_metadata(&mailbox, values));
+        while let Some(rsp) = responses.next().await {
+            check_completion(&rsp?).map_err(|e| match e {
+                Error::No(e) => match e.code() {
+                    Some(ResponseCode::Metadata(code)) => Error::Metadata(*code, e),
+                    _ => mailbox_error(Error::No(e)),
+                },
+                e => e,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, Error};
+    use imap_proto::{MetadataCode, MetadataOptions};
+
+    #[tokio::test]
+    async fn metadata() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 METADATA] ready\r\n")
+            .expect("A0001 GETMETADATA (MAXSIZE 64) \"INBOX\" (\"/shared/comment\" \"/private/comment\")")
+            .send(
+                "* METADATA \"INBOX\" (/shared/comment \"Shared comment\" /private/comment NIL)\r\n\
+                 A0001 OK [METADATA LONGENTRIES 2048] GETMETADATA complete\r\n",
+            )
+            .expect("A0002 SETMETADATA \"INBOX\" (\"/private/comment\" \"Mine\")")
+            .send("A0002 OK SETMETADATA complete\r\n")
+            .expect("A0003 SETMETADATA \"INBOX\" (\"/shared/vendor/acme/blob\" \"xxxx\")")
+            .send("A0003 NO [METADATA MAXSIZE 2] Value too large\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let options = MetadataOptions {
+            max_size: Some(64),
+            ..MetadataOptions::default()
+        };
+        let metadata = client
+            .get_metadata("INBOX", &["/shared/comment", "/private/comment"], &options)
+            .await
+            .unwrap();
+        assert_eq!(metadata.get("/shared/comment"), Some("Shared comment"));
+        assert_eq!(metadata.get("/private/comment"), None);
+        assert_eq!(metadata.long_entries, Some(2048));
+
+        client
+            .set_metadata("INBOX", &[("/private/comment", Some(b"Mine"))])
+            .await
+            .unwrap();
+        match client
+            .set_metadata("INBOX", &[("/shared/vendor/acme/blob", Some(b"xxxx"))])
+            .await
+        {
+            Err(Error::Metadata(MetadataCode::MaxSize(2), _)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        server.finish().await.unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::CommandBuilder;
+use imap_proto::{Namespaces, Response};
+
+impl Client {
+    /// Get the namespaces of the mailboxes the user can access (RFC 2342), to
+    /// build mailbox names with the right prefix and hierarchy delimiter.
+    pub async fn namespaces(&self) -> Result<Namespaces, Error> {
+        let mut responses = self.call(CommandBuilder::namespace());
+        let mut namespaces = None;
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            if let Response::Namespace(ns) = rsp.parsed() {
+                namespaces = Some(ns.clone());
+            }
+        }
+        namespaces.ok_or_else(|| Error::Protocol("no NAMESPACE response".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+
+    #[tokio::test]
+    async fn namespaces() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 NAMESPACE] ready\r\n")
+            .expect("A0001 NAMESPACE")
+            .send(
+                "* NAMESPACE ((\"INBOX.\" \".\")) NIL NIL\r\n\
+                 A0001 OK NAMESPACE completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let namespaces = client.namespaces().await.unwrap();
+        server.finish().await.unwrap();
+        let archive = namespaces.personal_path(["Archive", "2024"]).unwrap();
+        assert_eq!(archive, "INBOX.Archive.2024");
+    }
+}
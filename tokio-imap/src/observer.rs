@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::protocol::literal_len;
+use imap_proto::{Response, Status};
+
+/// Receives notifications about the activity of a connection, to collect metrics.
+///
+/// Install an observer with `Connection::observe()` (or `Protocol::observe()`).
+/// All methods do nothing by default, so implementations only need to provide
+/// the ones they are interested in. Methods are called from the task driving the
+/// connection, so they should return quickly (for example, by updating counters
+/// in a metrics registry).
+///
+/// The same observer can be shared by several connections. This crate does not
+/// reconnect by itself; an application that does can count reconnections
+/// through `connected()`.
+pub trait Observer: Send + Sync {
+    /// The observer was installed on a new connection.
+    fn connected(&self) {}
+
+    /// The connection ended, either cleanly or with the given error.
+    fn disconnected(&self, _error: Option<&Error>) {}
+
+    /// A command completed with the given status, `latency` after it was sent.
+    ///
+    /// `command` is the upper-cased command name, such as `FETCH` or `UID STORE`.
+    fn command_completed(&self, _command: &str, _status: Status, _latency: Duration) {}
+
+    /// A response was received from the server.
+    fn response_received(&self, _rsp: &Response<'_>) {}
+
+    /// Bytes were written to the server.
+    fn bytes_sent(&self, _len: usize) {}
+
+    /// Bytes were read from the server.
+    fn bytes_received(&self, _len: usize) {}
+
+    /// A literal (such as a message for `APPEND`) of the given size was sent.
+    fn literal_sent(&self, _len: usize) {}
+
+    /// A literal (such as a message body) of the given size was received.
+    fn literal_received(&self, _len: usize) {}
+}
+
+/// Report the literals contained in the raw response `raw` to `observer`.
+pub(crate) fn literals_received(observer: &dyn Observer, mut raw: &[u8]) {
+    while let Some(pos) = raw.windows(2).position(|w| w == b"\r\n") {
+        let literal = literal_len(&raw[..pos]);
+        raw = &raw[pos + 2..];
+        if let Some(len) = literal {
+            observer.literal_received(len);
+            raw = &raw[len.min(raw.len())..];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::Observer;
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, Error};
+    use futures::StreamExt;
+    use imap_proto::builders::command::CommandBuilder;
+    use imap_proto::{Attribute, Response, Status};
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Recorder {
+        fn push(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    impl Observer for Recorder {
+        fn connected(&self) {
+            self.push("connected".into());
+        }
+
+        fn disconnected(&self, error: Option<&Error>) {
+            self.push(format!("disconnected {}", error.is_some()));
+        }
+
+        fn command_completed(&self, command: &str, status: Status, _: Duration) {
+            self.push(format!("{} {:?}", command, status));
+        }
+
+        fn response_received(&self, rsp: &Response<'_>) {
+            if let Response::Fetch(..) = rsp {
+                self.push("fetch".into());
+            }
+        }
+
+        fn bytes_sent(&self, len: usize) {
+            self.push(format!("sent {}", len));
+        }
+
+        fn literal_sent(&self, len: usize) {
+            self.push(format!("literal sent {}", len));
+        }
+
+        fn literal_received(&self, len: usize) {
+            self.push(format!("literal received {}", len));
+        }
+    }
+
+    #[tokio::test]
+    async fn observe() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 LITERAL+] ready\r\n")
+            .expect("A0001 APPEND \"Drafts\" {5+}")
+            .expect("hello")
+            .send("A0001 OK APPEND completed\r\n")
+            .expect("A0002 FETCH 1 (BODY.PEEK[])")
+            .send("* 1 FETCH (BODY[] {5}\r\nhello)\r\nA0002 NO too busy\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, mut connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        let recorder = Arc::new(Recorder::default());
+        connection.observe(recorder.clone());
+        let connection = tokio::spawn(connection);
+
+        for cmd in [
+            CommandBuilder::append("Drafts", b"hello"),
+            CommandBuilder::fetch()
+                .num(1)
+                .attr(Attribute::BodyPeek)
+                .into(),
+        ] {
+            let mut responses = client.call(cmd);
+            while let Some(rsp) = responses.next().await {
+                rsp.unwrap();
+            }
+        }
+        drop(client);
+        connection.await.unwrap().unwrap();
+        server.finish().await.unwrap();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                "connected",
+                "literal sent 5",
+                "sent 35",
+                "APPEND Ok",
+                "sent 29",
+                "literal received 5",
+                "fetch",
+                "FETCH No",
+                "disconnected false",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1071 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io;
+use std::str;
+use std::sync::Arc;
+use std::time::Instant;
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::Decoder;
+
+use crate::buffer::BufferPolicy;
+use crate::codec::{ImapCodec, ResponseData};
+use crate::observer::{self, Observer};
+use crate::session::Session;
+use crate::spill::{Spill, Spiller};
+use crate::stream::{LiteralChunk, Streamer};
+use crate::trace::Trace;
+use imap_proto::builders::command::{Command, CommandBuilder};
+use imap_proto::parser::{core::astring_utf8, Quirks};
+use imap_proto::{MailboxDatum, RequestId, Response, State, Status};
+
+/// Parts of commands up to this size are copied into a single buffer to be sent;
+/// larger ones (literals) are sent as they are.
+const COPY_LIMIT: usize = 4096;
+
+/// The state of the client side of an IMAP connection, without any I/O.
+///
+/// `Protocol` takes care of tag allocation, pipelining, literal continuations,
+/// response routing and session tracking; the caller moves bytes between it and
+/// the network, which makes it usable with any runtime (or none at all):
+///
+/// * commands are submitted with `enqueue()`, which returns the tag assigned to
+///   the command;
+/// * `transmit()` returns the bytes to write to the server next, if any;
+/// * bytes read from the server are passed to `receive()`;
+/// * `poll_event()` then returns the responses received, attributed to the
+///   commands they belong to.
+///
+/// `Connection` is a thin wrapper that does this on top of Tokio.
+///
+/// Tagged responses are matched to their command by tag. Untagged responses are
+/// attributed to the oldest in-flight command that may elicit that kind of
+/// response (for example, `* SEARCH` to a `SEARCH` command); commands that change
+/// the connection state, such as `SELECT` or `LOGIN`, are never pipelined with
+/// other commands and receive all untagged responses sent while they are in flight.
+/// `BYE` responses (other than the one sent in response to `LOGOUT`) and
+/// responses that cannot be attributed to any command (including the greeting)
+/// are reported as `Event::Unsolicited`.
+///
+/// Once `STARTTLS` completes, the server expects a TLS handshake, which
+/// `Protocol` cannot perform: nothing is sent from then on, and the
+/// capabilities are forgotten, as those advertised before cannot be trusted.
+pub struct Protocol {
+    codec: ImapCodec,
+    read_buf: BytesMut,
+    buffers: BufferPolicy,
+    /// Whether `read_buf` grew beyond the maximum capacity of the policy.
+    read_buf_grown: bool,
+    write_buf: BytesMut,
+    /// The data to send, in order: parts of `write_buf` and large literals.
+    outgoing: VecDeque<Bytes>,
+    session: Session,
+    request_ids: IdGenerator,
+    queued: VecDeque<Queued>,
+    in_flight: VecDeque<InFlight>,
+    events: VecDeque<Event>,
+    trace: Option<Trace>,
+    observer: Option<Arc<dyn Observer>>,
+    spiller: Option<Spiller>,
+    streamer: Option<Streamer>,
+    /// The quirks set with `quirks()`, to which those needed by the server
+    /// and by the enabled extensions are added.
+    quirks: Quirks,
+    auto_quirks: bool,
+    server_quirks: Quirks,
+    /// Set once `STARTTLS` completed, after which nothing can be sent in plain text.
+    starttls_completed: bool,
+}
+
+impl Drop for Protocol {
+    fn drop(&mut self) {
+        let buf = std::mem::replace(&mut self.read_buf, BytesMut::new());
+        if !self.read_buf_grown {
+            self.buffers.give_back(buf);
+        }
+    }
+}
+
+/// A response returned by `Protocol::poll_event()`.
+#[derive(Debug)]
+pub enum Event {
+    /// A response to the command with the given tag. The last response for each
+    /// command is the tagged completion response, for which
+    /// `ResponseData::request_id()` returns the same tag.
+    Response(RequestId, ResponseData),
+    /// A response that is not attributed to any command.
+    Unsolicited(ResponseData),
+    /// Part of a literal in a `FETCH` response that has not been received
+    /// completely yet, see `stream_literals()`.
+    Literal(LiteralChunk),
+}
+
+struct Queued {
+    request_id: RequestId,
+    cmd: Command,
+    /// Whether `done()` was called for this `IDLE` command.
+    done: bool,
+}
+
+struct InFlight {
+    request_id: RequestId,
+    verb: Verb,
+    next_state: Option<State>,
+    /// Parts of the command that follow a literal and have not been sent yet.
+    remaining: VecDeque<Bytes>,
+    awaiting_continuation: bool,
+    done: bool,
+    /// The command name and the time it was sent, if observed.
+    sent: Option<(String, Instant)>,
+    /// For commands after which the capabilities change, the number of
+    /// capability updates when the command was sent.
+    capability_updates: Option<u64>,
+    starttls: bool,
+}
+
+impl Protocol {
+    pub fn new() -> Self {
+        Self {
+            codec: ImapCodec::default(),
+            read_buf: BytesMut::new(),
+            buffers: BufferPolicy::new(),
+            read_buf_grown: false,
+            write_buf: BytesMut::new(),
+            outgoing: VecDeque::new(),
+            session: Session::new(),
+            request_ids: IdGenerator::new(),
+            queued: VecDeque::new(),
+            in_flight: VecDeque::new(),
+            events: VecDeque::new(),
+            trace: None,
+            observer: None,
+            spiller: None,
+            streamer: None,
+            quirks: Quirks::new(),
+            auto_quirks: false,
+            server_quirks: Quirks::new(),
+            starttls_completed: false,
+        }
+    }
+
+    /// The current session state: capabilities, enabled extensions and the
+    /// selected mailbox.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Record all commands sent and responses received from now on.
+    pub fn trace(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
+    /// Report activity to `observer` from now on.
+    pub fn observe(&mut self, observer: Arc<dyn Observer>) {
+        self.observer = Some(observer);
+    }
+
+    /// Size the buffer for received data according to `policy`, taking it from
+    /// the pool of the policy if it has one.
+    pub fn buffer_policy(&mut self, policy: BufferPolicy) {
+        let mut buf = policy.take();
+        buf.extend_from_slice(&self.read_buf);
+        self.read_buf = buf;
+        self.read_buf_grown = false;
+        self.buffers = policy;
+    }
+
+    /// Write literals received from now on elsewhere as configured by `spill`,
+    /// rather than keeping them in memory.
+    pub fn spill_literals(&mut self, spill: Spill) {
+        self.spiller = Some(Spiller::new(spill));
+    }
+
+    /// Return the content of literals of at least `min_len` bytes in `FETCH`
+    /// responses (such as message bodies) as `Event::Literal` as it is received,
+    /// before the response is complete.
+    ///
+    /// The chunks are copies of the data received; the complete response is
+    /// still returned once received, including the whole literal. Literals
+    /// written elsewhere with `spill_literals()` are not streamed.
+    pub fn stream_literals(&mut self, min_len: u64) {
+        self.streamer = Some(Streamer::new(min_len));
+    }
+
+    /// Accept the deviations from the protocol selected by `quirks` in the
+    /// responses received from now on.
+    pub fn quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.update_quirks();
+    }
+
+    /// Also accept the deviations known to be needed by the server once it
+    /// identifies itself in an `ID` response, see `Quirks::for_server()`.
+    pub fn auto_quirks(&mut self, enabled: bool) {
+        self.auto_quirks = enabled;
+    }
+
+    /// Fail with a `FramingError` if the server sends a line longer than `max`
+    /// bytes, not counting the literals following it.
+    pub fn max_line_length(&mut self, max: usize) {
+        self.codec.set_max_line_length(Some(max));
+    }
+
+    /// Fail with a `FramingError` if the server sends a line terminated by a
+    /// bare LF rather than CRLF, even if the quirks accept it.
+    pub fn strict_crlf(&mut self, strict: bool) {
+        self.codec.set_strict_crlf(strict);
+    }
+
+    /// Queue a command to be sent, returning the tag it will be sent with.
+    pub fn enqueue(&mut self, cmd: Command) -> RequestId {
+        let request_id = self.request_ids.next().unwrap(); // safe: never returns Err
+        self.queued.push_back(Queued {
+            request_id,
+            cmd,
+            done: false,
+        });
+        self.queued.back().unwrap().request_id.clone() // safe: just pushed
+    }
+
+    /// Remove a command that has not been sent yet from the queue. Returns `false`
+    /// if the command was already (partially) sent, in which case the server's
+    /// responses to it will still be reported.
+    pub fn cancel(&mut self, request_id: &RequestId) -> bool {
+        match self.queued.iter().position(|q| &q.request_id == request_id) {
+            Some(idx) => {
+                self.queued.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// End the oldest `IDLE` command that has not been ended yet, by sending `DONE`
+    /// once the server has confirmed the start of idling. Returns `false` if there
+    /// is no such command.
+    pub fn done(&mut self) -> bool {
+        let in_flight = self
+            .in_flight
+            .iter_mut()
+            .find(|cmd| cmd.verb == Verb::Idle && !cmd.done);
+        if let Some(cmd) = in_flight {
+            cmd.done = true;
+            cmd.remaining.push_back(Bytes::from_static(b"DONE"));
+            return true;
+        }
+
+        let queued = self
+            .queued
+            .iter_mut()
+            .find(|q| Verb::of(&q.cmd) == Verb::Idle && !q.done);
+        match queued {
+            Some(queued) => {
+                queued.done = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether all commands have been sent and completed.
+    pub fn is_idle(&self) -> bool {
+        self.queued.is_empty() && self.in_flight.is_empty()
+    }
+
+    /// The bytes to write to the server next, if any.
+    ///
+    /// Returns as much of the queued commands as the pipelining rules and
+    /// outstanding literal continuations allow. Large literals are returned on
+    /// their own rather than copied along with the rest of the command, so that
+    /// they can be written with vectored I/O; call `transmit()` until it returns
+    /// `None` to get all the data that can be sent.
+    pub fn transmit(&mut self) -> Option<Bytes> {
+        if self.starttls_completed {
+            return None;
+        }
+        loop {
+            let continuing = match self.in_flight.back() {
+                Some(cmd) if !cmd.remaining.is_empty() => {
+                    if cmd.awaiting_continuation {
+                        break;
+                    }
+                    true
+                }
+                _ => match self.queued.front() {
+                    Some(queued) if self.can_send(&queued.cmd) => false,
+                    _ => break,
+                },
+            };
+
+            let max_non_sync = self.max_non_synchronizing_literal();
+            if continuing {
+                let cmd = self.in_flight.back_mut().unwrap(); // safe: checked above
+                let line = cmd.remaining.pop_front().unwrap(); // safe: checked above
+                let plus = match cmd.remaining.is_empty() {
+                    true => false,
+                    false => match non_synchronizing(&line, max_non_sync) {
+                        Some(plus) => plus,
+                        None => {
+                            cmd.awaiting_continuation = true;
+                            false
+                        }
+                    },
+                };
+                if let Some(trace) = &mut self.trace {
+                    trace.continuation(&with_plus(&line, plus));
+                }
+                self.literal_sent(&line);
+                self.write_line(line, plus);
+                continue;
+            }
+
+            let Queued {
+                request_id,
+                cmd,
+                done,
+            } = self.queued.pop_front().unwrap(); // safe: checked above
+            let verb = Verb::of(&cmd);
+            let name = command_name(&cmd);
+            let capability_updates =
+                Some(self.session.capability_updates()).filter(|_| changes_capabilities(&name));
+            let starttls = name == b"STARTTLS";
+            let sent = self.observer.as_ref().map(|_| {
+                let name = String::from_utf8_lossy(&command_name(&cmd)).into_owned();
+                (name, Instant::now())
+            });
+            if verb == Verb::Select {
+                // Selecting a mailbox immediately deselects the current one
+                self.session.selecting(mailbox_argument(&cmd));
+            }
+
+            let Command { args, next_state } = cmd;
+            let mut remaining = split_literals(Bytes::from(args));
+            let line = remaining.pop_front().unwrap(); // safe: never empty
+            let (awaiting_continuation, plus) = if verb == Verb::Idle {
+                // The server confirms with a continuation request before idling
+                if done {
+                    remaining.push_back(Bytes::from_static(b"DONE"));
+                }
+                (true, false)
+            } else if remaining.is_empty() {
+                (false, false)
+            } else {
+                match non_synchronizing(&line, max_non_sync) {
+                    Some(plus) => (false, plus),
+                    None => (true, false),
+                }
+            };
+            if let Some(trace) = &mut self.trace {
+                trace.command(request_id.as_bytes(), &with_plus(&line, plus));
+            }
+            self.literal_sent(&line);
+            self.write_buf.extend_from_slice(request_id.as_bytes());
+            self.write_buf.extend_from_slice(b" ");
+            self.write_line(line, plus);
+
+            self.in_flight.push_back(InFlight {
+                request_id,
+                verb,
+                next_state,
+                remaining,
+                awaiting_continuation,
+                done,
+                sent,
+                capability_updates,
+                starttls,
+            });
+        }
+
+        if !self.write_buf.is_empty() {
+            self.outgoing.push_back(self.write_buf.split().freeze());
+        }
+        let data = self.outgoing.pop_front()?;
+        if let Some(observer) = &self.observer {
+            observer.bytes_sent(data.len());
+        }
+        Some(data)
+    }
+
+    /// Queue a part of a command followed by CRLF, turning the literal at its
+    /// end into a non-synchronizing one if `plus` is set.
+    fn write_line(&mut self, line: Bytes, plus: bool) {
+        let end: &'static [u8] = match plus {
+            true => b"+}\r\n",
+            false => b"\r\n",
+        };
+        let line = match plus {
+            true => line.slice(..line.len() - 1),
+            false => line,
+        };
+        if line.len() < COPY_LIMIT {
+            self.write_buf.extend_from_slice(&line);
+        } else {
+            if !self.write_buf.is_empty() {
+                self.outgoing.push_back(self.write_buf.split().freeze());
+            }
+            self.outgoing.push_back(line);
+        }
+        self.write_buf.extend_from_slice(end);
+    }
+
+    /// Process bytes received from the server.
+    ///
+    /// Fails if the server sent a response that cannot be parsed.
+    pub fn receive(&mut self, data: &[u8]) -> Result<(), io::Error> {
+        self.read_buf.extend_from_slice(data);
+        self.received(data.len())
+    }
+
+    /// The buffer holding the data received but not decoded yet, with space
+    /// for at least one read.
+    ///
+    /// Transports read into it directly, so that the literals in responses
+    /// share this buffer rather than being copied into it; call `received()`
+    /// afterwards.
+    pub(crate) fn read_buf_mut(&mut self) -> &mut BytesMut {
+        self.read_buf.reserve(self.buffers.read_size());
+        &mut self.read_buf
+    }
+
+    /// Process `len` bytes appended to `read_buf_mut()`.
+    pub(crate) fn received(&mut self, len: usize) -> Result<(), io::Error> {
+        if let Some(observer) = &self.observer {
+            observer.bytes_received(len);
+        }
+        let start = self.read_buf.len() - len;
+        if let Some(spiller) = &mut self.spiller {
+            spiller.scan(&mut self.read_buf, start)?;
+        }
+        // Spilled literals were removed from the data from `start` by now
+        if let Some(streamer) = &mut self.streamer {
+            streamer.scan(&self.read_buf, start);
+        }
+        self.read_buf_grown |= self.buffers.oversized(&self.read_buf);
+        while let Some(mut rsp) = self.codec.decode(&mut self.read_buf)? {
+            if let Some(spiller) = &mut self.spiller {
+                rsp.spilled = spiller.take(rsp.raw().len());
+            }
+            if let Some(streamer) = &mut self.streamer {
+                for chunk in streamer.take(rsp.raw().len()) {
+                    self.literal_received(chunk);
+                }
+            }
+            if let Some(trace) = &mut self.trace {
+                trace.response(rsp.raw());
+            }
+            if let Some(observer) = &self.observer {
+                observer::literals_received(&**observer, rsp.raw());
+                observer.response_received(rsp.parsed());
+            }
+            self.route(rsp);
+        }
+        if let Some(streamer) = &mut self.streamer {
+            for chunk in streamer.take_incomplete() {
+                self.literal_received(chunk);
+            }
+        }
+        // Release the memory of large responses once they have been decoded
+        if self.read_buf_grown && self.read_buf.is_empty() {
+            self.read_buf = self.buffers.take();
+            self.read_buf_grown = false;
+        }
+        Ok(())
+    }
+
+    /// The next response received, if any.
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    /// Report part of a literal, attributed like the `FETCH` response it is
+    /// part of.
+    fn literal_received(&mut self, mut chunk: LiteralChunk) {
+        chunk.request_id = self
+            .in_flight
+            .iter()
+            .find(|cmd| cmd.verb.expects_fetch())
+            .map(|cmd| cmd.request_id.clone());
+        self.events.push_back(Event::Literal(chunk));
+    }
+
+    fn route(&mut self, rsp: ResponseData) {
+        if let Response::Continue { .. } = rsp.parsed() {
+            match self.in_flight.back_mut() {
+                Some(cmd) if cmd.awaiting_continuation => {
+                    cmd.awaiting_continuation = false;
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let idx = match rsp.request_id() {
+            Some(req_id) => self.in_flight.iter().position(|c| &c.request_id == req_id),
+            None => self.in_flight.iter().position(|c| c.verb.expects(&rsp)),
+        };
+
+        if let (true, Response::Id(identity)) = (self.auto_quirks, rsp.parsed()) {
+            if let Some(name) = identity.name() {
+                self.server_quirks = Quirks::for_server(name);
+                self.update_quirks();
+            }
+        }
+        match (idx.map(|idx| self.in_flight[idx].verb), rsp.parsed()) {
+            // The ENABLED response is parsed like a capability listing
+            (Some(Verb::Enable), Response::Capabilities(caps)) => {
+                self.session.set_enabled(caps);
+                self.update_quirks();
+            }
+            (_, parsed) => self.session.update(parsed),
+        }
+
+        let idx = match idx {
+            Some(idx) => idx,
+            None => return self.events.push_back(Event::Unsolicited(rsp)),
+        };
+
+        if rsp.request_id().is_none() {
+            let request_id = self.in_flight[idx].request_id.clone();
+            return self.events.push_back(Event::Response(request_id, rsp));
+        }
+
+        let cmd = self.in_flight.remove(idx).unwrap(); // safe: index found above
+        if let (
+            Some(updates),
+            Response::Done {
+                status: Status::Ok, ..
+            },
+        ) = (cmd.capability_updates, rsp.parsed())
+        {
+            self.refresh_capabilities(updates);
+        }
+        if let (
+            true,
+            Response::Done {
+                status: Status::Ok, ..
+            },
+        ) = (cmd.starttls, rsp.parsed())
+        {
+            self.session.invalidate_capabilities();
+            self.starttls_completed = true;
+        }
+        if let (Some(observer), Some((name, sent)), Response::Done { status, .. }) =
+            (&self.observer, &cmd.sent, rsp.parsed())
+        {
+            observer.command_completed(name, *status, sent.elapsed());
+        }
+        match (rsp.parsed(), cmd.next_state) {
+            (
+                Response::Done {
+                    status: Status::Ok, ..
+                },
+                Some(next_state),
+            ) => self.session.set_state(next_state),
+            // A failed SELECT or EXAMINE leaves no mailbox selected
+            (Response::Done { .. }, Some(State::Selected)) => {
+                self.session.set_state(State::Authenticated)
+            }
+            _ => {}
+        }
+        self.events.push_back(Event::Response(cmd.request_id, rsp));
+    }
+
+    /// Parse responses with the quirks set, those needed by the server, and
+    /// UTF-8 quoted strings once `UTF8=ACCEPT` is enabled.
+    fn update_quirks(&mut self) {
+        let utf8 = Quirks::new().utf8_quoted(self.session.is_enabled("UTF8=ACCEPT"));
+        let quirks = self.quirks.union(self.server_quirks).union(utf8);
+        self.codec.set_quirks(quirks);
+    }
+
+    /// Ask for the capabilities again after a command changing them completed,
+    /// unless the server advertised them since `updates`.
+    fn refresh_capabilities(&mut self, updates: u64) {
+        if self.session.capability_updates() != updates {
+            return;
+        }
+        self.session.invalidate_capabilities();
+        let request_id = self.request_ids.next().unwrap(); // safe: never returns Err
+        self.queued.push_front(Queued {
+            request_id,
+            cmd: CommandBuilder::capability(),
+            done: false,
+        });
+    }
+
+    /// The size up to which literals can be sent without waiting for a continuation
+    /// request from the server (RFC 7888), if at all.
+    fn max_non_synchronizing_literal(&self) -> Option<usize> {
+        if self.session.has_capability("LITERAL+") {
+            Some(usize::MAX)
+        } else if self.session.has_capability("LITERAL-") {
+            Some(4096)
+        } else {
+            None
+        }
+    }
+
+    fn literal_sent(&self, line: &[u8]) {
+        if let (Some(observer), Some(len)) = (&self.observer, literal_len(line)) {
+            observer.literal_sent(len);
+        }
+    }
+
+    fn can_send(&self, cmd: &Command) -> bool {
+        match self.in_flight.back() {
+            None => true,
+            Some(last) => last.verb.pipelinable() && Verb::of(cmd).pipelinable(),
+        }
+    }
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The kind of command in flight, used to decide which untagged responses belong
+/// to it and whether it can be pipelined with other commands.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Verb {
+    Acl,
+    Capability,
+    Enable,
+    Fetch,
+    Idle,
+    List,
+    Logout,
+    Metadata,
+    Noop,
+    Quota,
+    Search,
+    Select,
+    Status,
+    Store,
+    /// Any command that is not known to be safe to pipeline with others.
+    Exclusive,
+}
+
+impl Verb {
+    fn of(cmd: &Command) -> Self {
+        let name = command_name(cmd);
+        match name.strip_prefix(b"UID ").unwrap_or(&name) {
+            b"SELECT" | b"EXAMINE" => Verb::Select,
+            b"ENABLE" => Verb::Enable,
+            b"IDLE" => Verb::Idle,
+            b"LOGOUT" => Verb::Logout,
+            _ if cmd.next_state.is_some() => Verb::Exclusive,
+            b"CAPABILITY" => Verb::Capability,
+            b"CHECK" | b"NOOP" => Verb::Noop,
+            b"FETCH" => Verb::Fetch,
+            b"GETACL" | b"LISTRIGHTS" | b"MYRIGHTS" => Verb::Acl,
+            b"GETMETADATA" => Verb::Metadata,
+            b"LIST" | b"LSUB" => Verb::List,
+            b"GETQUOTA" | b"GETQUOTAROOT" => Verb::Quota,
+            b"SEARCH" | b"SORT" => Verb::Search,
+            b"STATUS" => Verb::Status,
+            b"STORE" => Verb::Store,
+            _ => Verb::Exclusive,
+        }
+    }
+
+    /// Whether the command can be in flight with other pipelinable commands.
+    ///
+    /// `STORE` is not: the `FETCH` responses it elicits cannot be told apart
+    /// from those of a `FETCH` command (RFC 3501, section 5.5).
+    fn pipelinable(self) -> bool {
+        !matches!(
+            self,
+            Verb::Enable | Verb::Idle | Verb::Logout | Verb::Select | Verb::Store | Verb::Exclusive
+        )
+    }
+
+    /// Whether `FETCH` responses are attributed to this kind of command.
+    fn expects_fetch(self) -> bool {
+        matches!(
+            self,
+            Verb::Enable
+                | Verb::Idle
+                | Verb::Logout
+                | Verb::Select
+                | Verb::Exclusive
+                | Verb::Fetch
+                | Verb::Store
+        )
+    }
+
+    fn expects(self, rsp: &ResponseData) -> bool {
+        match (self, rsp.parsed()) {
+            (
+                verb,
+                Response::Data {
+                    status: Status::Bye,
+                    ..
+                },
+            ) => verb == Verb::Logout,
+            (Verb::Enable, _)
+            | (Verb::Idle, _)
+            | (Verb::Logout, _)
+            | (Verb::Select, _)
+            | (Verb::Exclusive, _) => true,
+            (_, Response::Data { .. }) => true,
+            (Verb::Acl, Response::MailboxData(MailboxDatum::Acl { .. }))
+            | (Verb::Acl, Response::MailboxData(MailboxDatum::ListRights { .. }))
+            | (Verb::Acl, Response::MailboxData(MailboxDatum::MyRights { .. })) => true,
+            (Verb::Capability, Response::Capabilities(_)) => true,
+            (Verb::Fetch, Response::Fetch(..)) | (Verb::Store, Response::Fetch(..)) => true,
+            // Sent for UID FETCH/STORE with the VANISHED modifier (RFC 7162)
+            (Verb::Fetch, Response::Vanished { earlier: true, .. }) => true,
+            (Verb::List, Response::MailboxData(MailboxDatum::List { .. })) => true,
+            // Sent for LIST with the STATUS return option (RFC 5819)
+            (Verb::List, Response::MailboxData(MailboxDatum::Status { .. })) => true,
+            (Verb::Metadata, Response::MailboxData(MailboxDatum::MetadataSolicited { .. })) => true,
+            (Verb::Quota, Response::MailboxData(MailboxDatum::Quota { .. }))
+            | (Verb::Quota, Response::MailboxData(MailboxDatum::QuotaRoot { .. })) => true,
+            (Verb::Search, Response::MailboxData(MailboxDatum::Search { .. }))
+            | (Verb::Search, Response::MailboxData(MailboxDatum::ESearch(_)))
+            | (Verb::Search, Response::MailboxData(MailboxDatum::Sort(_))) => true,
+            (Verb::Status, Response::MailboxData(MailboxDatum::Status { .. })) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The upper-cased name of the command, including the `UID` prefix if present.
+pub(crate) fn command_name(cmd: &Command) -> Vec<u8> {
+    let mut words = cmd.args.splitn(3, |&b| b == b' ');
+    let mut name = words.next().unwrap_or_default().to_ascii_uppercase();
+    if name == b"UID" {
+        name.push(b' ');
+        name.extend(words.next().unwrap_or_default().to_ascii_uppercase());
+    }
+    name
+}
+
+/// Whether the capabilities of the server change after the command completes
+/// successfully, so that they are asked for again. They do not change after
+/// `ENABLE` (RFC 5161, section 3.1). They do change after `STARTTLS`, but
+/// cannot be asked for before the TLS handshake, see `Protocol`.
+fn changes_capabilities(name: &[u8]) -> bool {
+    matches!(name, b"AUTHENTICATE" | b"LOGIN")
+}
+
+/// Split the arguments of a command into the parts separated by literals.
+///
+/// Every part but the last ends with a literal header (`{n}`), and every part but
+/// the first starts with literal data.
+fn split_literals(args: Bytes) -> VecDeque<Bytes> {
+    let mut parts = VecDeque::new();
+    let (mut start, mut search) = (0, 0);
+    while let Some(pos) = args[search..].windows(2).position(|w| w == b"\r\n") {
+        let end = search + pos;
+        match literal_len(&args[start..end]) {
+            Some(len) => {
+                parts.push_back(args.slice(start..end));
+                start = end + 2;
+                search = (start + len).min(args.len());
+            }
+            // CRLF can only legitimately appear after a literal header
+            None => search = end + 2,
+        }
+    }
+    parts.push_back(args.slice(start..));
+    parts
+}
+
+/// The size of the literal announced at the end of `line`, if any.
+pub(crate) fn literal_len(line: &[u8]) -> Option<usize> {
+    let line = line.strip_suffix(b"}")?;
+    let open = line.iter().rposition(|&b| b == b'{')?;
+    let digits = &line[open + 1..];
+    let digits = digits.strip_suffix(b"+").unwrap_or(digits);
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// Whether the literal at the end of `line` can be sent as a non-synchronizing
+/// one (`{n+}`), as its size allows. If so, returns whether a `+` needs to be
+/// added to the literal header for that.
+fn non_synchronizing(line: &[u8], max: Option<usize>) -> Option<bool> {
+    if line.ends_with(b"+}") {
+        return Some(false);
+    }
+    match (literal_len(line), max) {
+        (Some(len), Some(max)) if len <= max => Some(true),
+        _ => None,
+    }
+}
+
+/// `line` as sent, for traces.
+fn with_plus(line: &[u8], plus: bool) -> Cow<'_, [u8]> {
+    match plus {
+        true => {
+            let mut line = line.to_vec();
+            line.insert(line.len() - 1, b'+');
+            Cow::Owned(line)
+        }
+        false => Cow::Borrowed(line),
+    }
+}
+
+/// Extract the mailbox name from a command taking it as first argument, such as
+/// `SELECT` or `EXAMINE`.
+pub(crate) fn mailbox_argument(cmd: &Command) -> String {
+    let args = match cmd.args.iter().position(|&b| b == b' ') {
+        Some(pos) => &cmd.args[pos + 1..],
+        None => return String::new(),
+    };
+    match astring_utf8(args) {
+        Ok((_, name)) => name.to_string(),
+        // `astring` needs a delimiter to detect the end of an unquoted name
+        Err(_) => String::from_utf8_lossy(args).into_owned(),
+    }
+}
+
+pub struct IdGenerator {
+    next: u64,
+}
+
+impl IdGenerator {
+    pub fn new() -> Self {
+        Self { next: 0 }
+    }
+}
+
+impl Default for IdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for IdGenerator {
+    type Item = RequestId;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next += 1;
+        // Formatted without allocating: `A0001` to `A9999`, then `A0000`
+        let mut tag = *b"A0000";
+        let mut n = self.next % 10_000;
+        for digit in tag[1..].iter_mut().rev() {
+            *digit = b'0' + (n % 10) as u8;
+            n /= 10;
+        }
+        Some(RequestId::new(str::from_utf8(&tag).unwrap())) // safe: ASCII
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{non_synchronizing, split_literals, Event, Protocol};
+    use bytes::Bytes;
+    use imap_proto::builders::command::{Command, CommandBuilder};
+    use imap_proto::{Attribute, RequestId, Response, SequenceSet, State, StatusItem, StoreMode};
+
+    #[test]
+    fn literals() {
+        let parts = split_literals(Bytes::from_static(b"APPEND \"Drafts\" {4}\r\na\r\nb"));
+        assert_eq!(parts, vec![&b"APPEND \"Drafts\" {4}"[..], &b"a\r\nb"[..]]);
+
+        let parts = split_literals(Bytes::from_static(b"LOGIN {1}\r\nx {2+}\r\nyz"));
+        assert_eq!(parts, vec![&b"LOGIN {1}"[..], &b"x {2+}"[..], &b"yz"[..]]);
+
+        assert_eq!(non_synchronizing(b"LOGIN {5000}", None), None);
+        assert_eq!(non_synchronizing(b"LOGIN {5000}", Some(4096)), None);
+        assert_eq!(
+            non_synchronizing(b"LOGIN {5000}", Some(usize::MAX)),
+            Some(true)
+        );
+        assert_eq!(non_synchronizing(b"LOGIN {5000+}", None), Some(false));
+    }
+
+    #[test]
+    fn large_literals() {
+        let mut protocol = Protocol::new();
+        protocol
+            .receive(b"* OK [CAPABILITY IMAP4rev1 LITERAL+] ready\r\n")
+            .unwrap();
+        let message = vec![b'x'; 100_000];
+        protocol.enqueue(CommandBuilder::append("INBOX", &message));
+        protocol.enqueue(CommandBuilder::check());
+
+        // The message is returned on its own, without being copied
+        let data = protocol.transmit().unwrap();
+        assert_eq!(&data[..], b"A0001 APPEND \"INBOX\" {100000+}\r\n");
+        let data = protocol.transmit().unwrap();
+        assert_eq!(data.len(), message.len());
+        assert_eq!(&protocol.transmit().unwrap()[..], b"\r\n");
+        assert!(protocol.transmit().is_none());
+    }
+
+    #[test]
+    fn bytes_in_bytes_out() {
+        let mut protocol = Protocol::new();
+        protocol
+            .receive(b"* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .unwrap();
+        assert!(matches!(protocol.poll_event(), Some(Event::Unsolicited(_))));
+        assert!(protocol.transmit().is_none());
+
+        let login = protocol.enqueue(CommandBuilder::login("djc", "päss"));
+        let check = protocol.enqueue(CommandBuilder::check());
+        assert_eq!(login, RequestId::from("A0001"));
+        assert_eq!(
+            &protocol.transmit().unwrap()[..],
+            b"A0001 LOGIN \"djc\" {5}\r\n"
+        );
+        assert!(protocol.transmit().is_none());
+
+        protocol.receive(b"+ go ahead\r\n").unwrap();
+        assert!(protocol.poll_event().is_none());
+        assert_eq!(&protocol.transmit().unwrap()[..], b"p\xc3\xa4ss\r\n");
+        // LOGIN changes the state, so CHECK is not pipelined with it
+        assert!(protocol.transmit().is_none());
+
+        protocol
+            .receive(b"A0001 OK [CAPABILITY IMAP4rev1 IDLE] done\r\n")
+            .unwrap();
+        match protocol.poll_event() {
+            Some(Event::Response(id, rsp)) => {
+                assert_eq!(id, login);
+                assert!(matches!(rsp.parsed(), Response::Done { .. }));
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_eq!(protocol.session().state(), State::Authenticated);
+        assert!(protocol.session().has_capability("IDLE"));
+
+        assert_eq!(&protocol.transmit().unwrap()[..], b"A0002 CHECK\r\n");
+        protocol
+            .receive(b"* 3 EXISTS\r\nA0002 OK done\r\n")
+            .unwrap();
+        assert!(matches!(protocol.poll_event(), Some(Event::Unsolicited(_))));
+        assert!(matches!(protocol.poll_event(), Some(Event::Response(id, _)) if id == check));
+        assert!(protocol.is_idle());
+    }
+
+    #[test]
+    fn idle() {
+        let mut protocol = Protocol::new();
+        let idle = protocol.enqueue(CommandBuilder::idle());
+        assert_eq!(&protocol.transmit().unwrap()[..], b"A0001 IDLE\r\n");
+        // DONE is only sent once the server started idling
+        assert!(protocol.done());
+        assert!(!protocol.done());
+        assert!(protocol.transmit().is_none());
+
+        protocol.receive(b"+ idling\r\n* 4 EXISTS\r\n").unwrap();
+        assert!(matches!(protocol.poll_event(), Some(Event::Response(id, _)) if id == idle));
+        assert_eq!(&protocol.transmit().unwrap()[..], b"DONE\r\n");
+        protocol.receive(b"A0001 OK IDLE terminated\r\n").unwrap();
+        assert!(matches!(protocol.poll_event(), Some(Event::Response(id, _)) if id == idle));
+        assert!(protocol.is_idle());
+    }
+
+    #[test]
+    fn demultiplexing() {
+        let mut protocol = Protocol::new();
+        let fetch = protocol.enqueue(CommandBuilder::fetch().num(1).attr(Attribute::Uid).into());
+        let search = protocol.enqueue(CommandBuilder::search("ALL").into());
+        let status = protocol.enqueue(CommandBuilder::status("INBOX", &[StatusItem::Messages]));
+        let list = protocol.enqueue(CommandBuilder::list("", "*"));
+        assert_eq!(
+            &protocol.transmit().unwrap()[..],
+            &b"A0001 FETCH 1 (UID)\r\nA0002 SEARCH ALL\r\nA0003 STATUS \"INBOX\" (MESSAGES)\r\n\
+               A0004 LIST \"\" \"*\"\r\n"[..]
+        );
+
+        protocol
+            .receive(
+                b"* LIST () \"/\" INBOX\r\n\
+                  * STATUS INBOX (MESSAGES 2)\r\n\
+                  * 1 FETCH (UID 10)\r\n\
+                  * SEARCH 1 2\r\n\
+                  * 3 EXISTS\r\n\
+                  A0003 OK STATUS completed\r\n\
+                  A0004 OK LIST completed\r\n\
+                  A0001 OK FETCH completed\r\n\
+                  A0002 OK SEARCH completed\r\n",
+            )
+            .unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = protocol.poll_event() {
+            events.push(match event {
+                Event::Response(id, rsp) => (Some(id), rsp.request_id().is_some()),
+                Event::Unsolicited(_) => (None, false),
+                Event::Literal(_) => panic!("unexpected literal"),
+            });
+        }
+        assert_eq!(
+            events,
+            [
+                (Some(list.clone()), false),
+                (Some(status.clone()), false),
+                (Some(fetch.clone()), false),
+                (Some(search.clone()), false),
+                (None, false),
+                (Some(status), true),
+                (Some(list), true),
+                (Some(fetch), true),
+                (Some(search), true),
+            ]
+        );
+        assert!(protocol.is_idle());
+    }
+
+    #[test]
+    fn store_not_pipelined_with_fetch() {
+        let mut protocol = Protocol::new();
+        let fetch = protocol.enqueue(CommandBuilder::fetch().num(1).attr(Attribute::Flags).into());
+        let flags = ["\\Seen"].iter().collect();
+        let store = protocol.enqueue(
+            CommandBuilder::store(&SequenceSet::from(2..=2), StoreMode::Add, &flags).into(),
+        );
+        assert_eq!(
+            &protocol.transmit().unwrap()[..],
+            b"A0001 FETCH 1 (FLAGS)\r\n"
+        );
+        // The STORE waits, so that the FETCH responses can be attributed
+        assert!(protocol.transmit().is_none());
+
+        protocol
+            .receive(b"* 1 FETCH (FLAGS ())\r\n* 2 FETCH (FLAGS (\\Deleted))\r\nA0001 OK done\r\n")
+            .unwrap();
+        for _ in 0..3 {
+            assert!(matches!(protocol.poll_event(), Some(Event::Response(id, _)) if id == fetch));
+        }
+        assert_eq!(
+            &protocol.transmit().unwrap()[..],
+            b"A0002 STORE 2 +FLAGS (\\Seen)\r\n"
+        );
+        protocol
+            .receive(b"* 2 FETCH (FLAGS (\\Deleted \\Seen))\r\nA0002 OK done\r\n")
+            .unwrap();
+        for _ in 0..2 {
+            assert!(matches!(protocol.poll_event(), Some(Event::Response(id, _)) if id == store));
+        }
+        assert!(protocol.is_idle());
+    }
+
+    #[test]
+    fn nothing_sent_after_starttls() {
+        let mut protocol = Protocol::new();
+        protocol
+            .receive(b"* OK [CAPABILITY IMAP4rev1 STARTTLS LOGINDISABLED] ready\r\n")
+            .unwrap();
+        let starttls = protocol.enqueue(Command {
+            args: b"STARTTLS".to_vec(),
+            next_state: None,
+        });
+        protocol.enqueue(CommandBuilder::login("djc", "pass"));
+        assert_eq!(&protocol.transmit().unwrap()[..], b"A0001 STARTTLS\r\n");
+        assert!(protocol.transmit().is_none());
+
+        protocol
+            .receive(b"A0001 OK Begin TLS negotiation now\r\n")
+            .unwrap();
+        assert!(matches!(protocol.poll_event(), Some(Event::Unsolicited(_))));
+        assert!(matches!(protocol.poll_event(), Some(Event::Response(id, _)) if id == starttls));
+        // Neither the login nor a CAPABILITY command go out in plain text
+        assert!(protocol.transmit().is_none());
+        assert!(protocol.session().capabilities().is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, Error};
+use crate::ResponseData;
+use imap_proto::builders::command::{Command, CommandBuilder};
+use imap_proto::{MailboxDatum, QuotaResource, Response};
+
+/// The usage and limits of the resources in a quota root (RFC 9208).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Quota {
+    pub root: String,
+    pub resources: Vec<QuotaResource>,
+}
+
+impl Quota {
+    /// The resource `name`, such as `STORAGE` or `MESSAGE`, if it is limited.
+    pub fn resource(&self, name: &str) -> Option<&QuotaResource> {
+        self.resources
+            .iter()
+            .find(|resource| resource.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The quota roots a mailbox counts against, in the order listed by the
+/// server.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MailboxQuota {
+    pub roots: Vec<Quota>,
+}
+
+impl MailboxQuota {
+    /// The resource `name` in the quota root closest to its limit, which is the
+    /// one limiting the mailbox.
+    pub fn resource(&self, name: &str) -> Option<&QuotaResource> {
+        self.roots
+            .iter()
+            .filter_map(|quota| quota.resource(name))
+            .min_by_key(|resource| resource.remaining())
+    }
+}
+
+impl Client {
+    /// Get the quota roots of `mailbox` and their usage and limits, with
+    /// `GETQUOTAROOT`.
+    pub async fn quota_for(&self, mailbox: &str) -> Result<MailboxQuota, Error> {
+        let mailbox = self.encode_mailbox_name(mailbox);
+        let cmd = CommandBuilder::get_quota_root(&mailbox);
+        let mut names = Vec::new();
+        let mut quotas = Vec::new();
+        for rsp in self.run_quota(cmd).await? {
+            match rsp.parsed() {
+                Response::MailboxData(MailboxDatum::QuotaRoot { roots, .. }) => {
+                    names.extend(roots.iter().map(|root| root.to_string()));
+                }
+                Response::MailboxData(MailboxDatum::Quota { root, resources }) => {
+                    quotas.push(Quota {
+                        root: root.to_string(),
+                        resources: resources.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // Roots without a QUOTA response have no limits
+        let roots = names
+            .into_iter()
+            .map(|root| match quotas.iter().position(|q| q.root == root) {
+                Some(i) => quotas.remove(i),
+                None => Quota {
+                    root,
+                    resources: Vec::new(),
+                },
+            })
+            .collect();
+        Ok(MailboxQuota { roots })
+    }
+
+    /// Set the limits of the quota root `root`, as pairs of resource names and
+    /// limits, with `SETQUOTA`. This usually requires administrator rights.
+    ///
+    /// Returns the updated quota, or `None` if the server did not report it.
+    pub async fn set_quota(
+        &self,
+        root: &str,
+        limits: &[(&str, u64)],
+    ) -> Result<Option<Quota>, Error> {
+        let cmd = CommandBuilder::set_quota(root, limits);
+        let mut quota = None;
+        for rsp in self.run_quota(cmd).await? {
+            if let Response::MailboxData(MailboxDatum::Quota { root, resources }) = rsp.parsed() {
+                quota = Some(Quota {
+                    root: root.to_string(),
+                    resources: resources.clone(),
+                });
+            }
+        }
+        Ok(quota)
+    }
+
+    async fn run_quota(&self, cmd: Command) -> Result<Vec<ResponseData>, Error> {
+        let mut responses = self.call(cmd);
+        let mut collected = Vec::new();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            collected.push(rsp);
+        }
+        Ok(collected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+
+    #[tokio::test]
+    async fn quota() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 QUOTA] ready\r\n")
+            .expect("A0001 GETQUOTAROOT \"INBOX\"")
+            .send(
+                "* QUOTAROOT INBOX \"\" shared\r\n\
+                 * QUOTA \"\" (STORAGE 10 512 MESSAGE 3 100)\r\n\
+                 * QUOTA shared (STORAGE 500 600)\r\n\
+                 A0001 OK GETQUOTAROOT completed\r\n",
+            )
+            .expect("A0002 SETQUOTA \"\" (STORAGE 1024)")
+            .send(
+                "* QUOTA \"\" (STORAGE 10 1024)\r\n\
+                 A0002 OK SETQUOTA completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let quota = client.quota_for("INBOX").await.unwrap();
+        assert_eq!(quota.roots.len(), 2);
+        assert_eq!(quota.roots[0].resource("message").unwrap().limit, 100);
+        assert_eq!(quota.resource("STORAGE").unwrap().remaining(), 100);
+        assert_eq!(quota.resource("MESSAGE").unwrap().usage, 3);
+
+        let updated = client.set_quota("", &[("STORAGE", 1024)]).await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(updated.unwrap().resource("STORAGE").unwrap().limit, 1024);
+    }
+}
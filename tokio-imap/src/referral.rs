@@ -0,0 +1,359 @@
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+
+use futures::future::{self, Either};
+use futures::{FutureExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::client::{Client, Connection, TlsConnection};
+use crate::codec::ResponseData;
+use crate::error::{check_completion, Error};
+use crate::tls::TlsConfig;
+use imap_proto::builders::command::CommandBuilder;
+
+/// The maximum number of referrals followed by `Client::connect_login()`.
+const MAX_REFERRALS: usize = 3;
+
+/// An IMAP URL (RFC 5092), as sent by servers referring the client to another
+/// server in a `REFERRAL` response code (RFC 2221).
+///
+/// Only the server and mailbox parts are parsed; other parts (such as a message
+/// UID) are ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImapUrl {
+    pub user: Option<String>,
+    /// The authentication mechanism to use, or `*` for any mechanism.
+    pub auth: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    pub mailbox: Option<String>,
+}
+
+impl ImapUrl {
+    /// Parse an `imap://` URL, returning `None` if it is invalid.
+    pub fn parse(url: &str) -> Option<Self> {
+        let scheme = url.get(..7)?;
+        if !scheme.eq_ignore_ascii_case("imap://") {
+            return None;
+        }
+        let rest = &url[7..];
+        let (server, path) = match rest.find('/') {
+            Some(pos) => (&rest[..pos], &rest[pos + 1..]),
+            None => (rest, ""),
+        };
+
+        let (user_info, host_port) = match server.rfind('@') {
+            Some(pos) => (Some(&server[..pos]), &server[pos + 1..]),
+            None => (None, server),
+        };
+        let (user, auth) = match user_info {
+            Some(info) => match info.to_ascii_uppercase().find(";AUTH=") {
+                Some(pos) => (&info[..pos], Some(info[pos + 6..].to_string())),
+                None => (info, None),
+            },
+            None => ("", None),
+        };
+
+        let (host, port) = match host_port.rfind(':') {
+            // The colon may be part of an IPv6 address
+            Some(pos) if !host_port[pos..].contains(']') => {
+                (&host_port[..pos], Some(host_port[pos + 1..].parse().ok()?))
+            }
+            _ => (host_port, None),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return None;
+        }
+
+        // Strip parameters such as `;UIDVALIDITY=` and message parts
+        let mailbox = path.split(&[';', '?'][..]).next().unwrap_or_default();
+        let mailbox = mailbox.trim_end_matches('/');
+        Some(Self {
+            user: Some(percent_decode(user)?).filter(|user| !user.is_empty()),
+            auth,
+            host: host.to_string(),
+            port,
+            mailbox: Some(percent_decode(mailbox)?).filter(|mailbox| !mailbox.is_empty()),
+        })
+    }
+}
+
+impl Display for ImapUrl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "imap://")?;
+        if let Some(user) = &self.user {
+            write!(f, "{}", user)?;
+        }
+        if let Some(auth) = &self.auth {
+            write!(f, ";AUTH={}", auth)?;
+        }
+        if self.user.is_some() || self.auth.is_some() {
+            write!(f, "@")?;
+        }
+        if self.host.contains(':') {
+            write!(f, "[{}]", self.host)?;
+        } else {
+            write!(f, "{}", self.host)?;
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        write!(f, "/{}", self.mailbox.as_deref().unwrap_or_default())
+    }
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'%' {
+            out.push(b);
+            continue;
+        }
+        let hex = [bytes.next()?, bytes.next()?];
+        out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+    }
+    String::from_utf8(out).ok()
+}
+
+impl Client {
+    /// Log in with `LOGIN`.
+    ///
+    /// Fails with `Error::Referral` if the server refers the client to another
+    /// server; `connect_login()` can follow such referrals.
+    pub async fn login(&self, user: &str, password: &str) -> Result<(), Error> {
+        let mut responses = self.call(CommandBuilder::login(user, password));
+        while let Some(rsp) = responses.next().await {
+            check_completion(&rsp?)?;
+        }
+        Ok(())
+    }
+
+    /// Connect to `server` like `connect_with()` and log in, following login
+    /// referrals (RFC 2221) for which `follow` returns `true`.
+    ///
+    /// The referred server is logged in to with the user from the URL, if any,
+    /// and the same password, so `follow` should only accept trusted servers.
+    /// Since the connection cannot be upgraded with `STARTTLS`, only referrals
+    /// to port 993 (using TLS) are followed: the default port of an `imap://`
+    /// URL is 143. Referrals that are not followed are returned as
+    /// `Error::Referral`, as is the last one once three were followed.
+    pub async fn connect_login<F>(
+        server: &str,
+        tls: &TlsConfig,
+        user: &str,
+        password: &str,
+        follow: F,
+    ) -> Result<(ResponseData, Client, TlsConnection), Error>
+    where
+        F: FnMut(&ImapUrl) -> bool,
+    {
+        let connect = |host: &str, port| {
+            let host = host.to_string();
+            async move { Client::connect_port(&host, port, tls).await }
+        };
+        login_following(connect, (server, 993), user, password, tls_port, follow).await
+    }
+}
+
+/// The port to connect to with TLS to follow a referral to `url`, if any.
+fn tls_port(url: &ImapUrl) -> Option<u16> {
+    // RFC 5092, section 3: the default port is 143, on which credentials
+    // would be sent in plain text
+    match url.port.unwrap_or(143) {
+        993 => Some(993),
+        _ => None,
+    }
+}
+
+async fn login_following<T, C, Fut, P, F>(
+    mut connect: C,
+    (server, port): (&str, u16),
+    user: &str,
+    password: &str,
+    referred_port: P,
+    mut follow: F,
+) -> Result<(ResponseData, Client, Connection<T>), Error>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    C: FnMut(&str, u16) -> Fut,
+    Fut: Future<Output = Result<(ResponseData, Client, Connection<T>), Error>>,
+    P: Fn(&ImapUrl) -> Option<u16>,
+    F: FnMut(&ImapUrl) -> bool,
+{
+    let (mut host, mut port, mut user) = (server.to_string(), port, user.to_string());
+    let mut referrals = 0;
+    loop {
+        let (greeting, client, mut connection) = connect(&host, port).await?;
+        let login = client.login(&user, password).boxed();
+        let result = match future::select(login, &mut connection).await {
+            Either::Left((result, _)) => result,
+            // The server may close the connection right after responding
+            Either::Right((result, login)) => match login.now_or_never() {
+                Some(login) => login,
+                None => Err(result.err().unwrap_or_else(|| {
+                    Error::Protocol("connection closed before login completed".into())
+                })),
+            },
+        };
+
+        let url = match result {
+            Ok(()) => return Ok((greeting, client, connection)),
+            Err(Error::Referral(url)) => url,
+            Err(e) => return Err(e),
+        };
+        match referred_port(&url) {
+            Some(referred) if referrals < MAX_REFERRALS && follow(&url) => {
+                referrals += 1;
+                host = url.host;
+                port = referred;
+                if let Some(referred) = url.user {
+                    user = referred;
+                }
+            }
+            _ => return Err(Error::Referral(url)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::net::SocketAddr;
+
+    use super::{login_following, tls_port, ImapUrl};
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, Connection, Error, ResponseData};
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn parse_url() {
+        let url = ImapUrl::parse("IMAP://user;AUTH=*@SERVER2/").unwrap();
+        assert_eq!(
+            url,
+            ImapUrl {
+                user: Some("user".into()),
+                auth: Some("*".into()),
+                host: "SERVER2".into(),
+                port: None,
+                mailbox: None,
+            }
+        );
+        assert_eq!(url.to_string(), "imap://user;AUTH=*@SERVER2/");
+
+        let url = ImapUrl::parse(
+            "imap://fred%40example.com@[::1]:1143/Shared%20Folders;UIDVALIDITY=385759045",
+        )
+        .unwrap();
+        assert_eq!(url.user.as_deref(), Some("fred@example.com"));
+        assert_eq!(url.host, "::1");
+        assert_eq!(url.port, Some(1143));
+        assert_eq!(url.mailbox.as_deref(), Some("Shared Folders"));
+
+        assert!(ImapUrl::parse("imap:///INBOX").is_none());
+        assert!(ImapUrl::parse("http://example.com/").is_none());
+    }
+
+    #[tokio::test]
+    async fn login_referral() {
+        let script = Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 LOGIN \"djc\" \"pass\"")
+            .send("A0001 NO [REFERRAL IMAP://djc@imap2.example.com/] Try another server\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let err = client.login("djc", "pass").await.unwrap_err();
+        server.finish().await.unwrap();
+        match err {
+            Error::Referral(url) => assert_eq!(url.host, "imap2.example.com"),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn referral_port() {
+        let url = ImapUrl::parse("imap://imap2.example.com/").unwrap();
+        assert_eq!(tls_port(&url), None);
+        let url = ImapUrl::parse("imap://imap2.example.com:143/").unwrap();
+        assert_eq!(tls_port(&url), None);
+        let url = ImapUrl::parse("imap://imap2.example.com:993/").unwrap();
+        assert_eq!(tls_port(&url), Some(993));
+    }
+
+    fn connect_mock(
+        host: &str,
+        port: u16,
+    ) -> impl Future<Output = Result<(ResponseData, Client, Connection<TcpStream>), Error>> {
+        let addr = SocketAddr::new(host.parse().unwrap(), port);
+        async move { Client::from_stream(TcpStream::connect(addr).await?).await }
+    }
+
+    fn referring(url: &str) -> Script {
+        Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 LOGIN \"djc\" \"pass\"")
+            .send(format!(
+                "A0001 NO [REFERRAL {}] Try another server\r\n",
+                url
+            ))
+    }
+
+    #[tokio::test]
+    async fn referral_not_followed() {
+        let server = MockServer::start(referring("imap://imap2.example.com:993/"))
+            .await
+            .unwrap();
+        let addr = server.addr();
+
+        let result = login_following(
+            connect_mock,
+            (&addr.ip().to_string(), addr.port()),
+            "djc",
+            "pass",
+            tls_port,
+            |_| false,
+        )
+        .await;
+        server.finish().await.unwrap();
+        match result {
+            Err(Error::Referral(url)) => assert_eq!(url.host, "imap2.example.com"),
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(_) => panic!("referral followed"),
+        }
+    }
+
+    #[tokio::test]
+    async fn referral_followed() {
+        let referred = Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 LOGIN \"fred\" \"pass\"")
+            .send("A0001 OK logged in\r\n");
+        let referred = MockServer::start(referred).await.unwrap();
+        let url = format!("imap://fred@127.0.0.1:{}/", referred.addr().port());
+        let server = MockServer::start(referring(&url)).await.unwrap();
+        let addr = server.addr();
+
+        let mut followed = Vec::new();
+        let result = login_following(
+            connect_mock,
+            (&addr.ip().to_string(), addr.port()),
+            "djc",
+            "pass",
+            |url| url.port,
+            |url| {
+                followed.push(url.to_string());
+                url.host == "127.0.0.1"
+            },
+        )
+        .await;
+        server.finish().await.unwrap();
+        referred.finish().await.unwrap();
+        assert!(result.is_ok());
+        assert_eq!(followed, [url]);
+    }
+}
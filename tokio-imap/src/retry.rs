@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::codec::ResponseData;
+use crate::error::{check_completion, Error};
+use crate::protocol::command_name;
+use imap_proto::builders::command::Command;
+use imap_proto::ResponseCode;
+
+/// How often and how fast to retry commands failing with transient errors.
+///
+/// Delays grow exponentially from `initial_backoff`, doubling with every attempt
+/// up to `max_backoff`. See `is_retryable()` for the failures considered
+/// transient.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    /// The delay before the first retry (default 1 second).
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// The maximum delay between retries (default 30 seconds).
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// The delay before the given retry, counting from 0.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Whether `cmd` can be sent again after failing with `err`.
+///
+/// A `NO` response with the `UNAVAILABLE` or `INUSE` code (RFC 5530) means the
+/// server did not execute the command, so any command can be retried. When the
+/// connection fails or is closed by the server (`BYE`) while a command is in
+/// progress, the command may or may not have been executed; only commands that
+/// have no effect when executed twice (such as `FETCH` or `SEARCH`) can then be
+/// retried on a new connection. In particular, `APPEND` is never retried in that
+/// case, as it could store the message twice: check whether the message exists
+/// first (for example with the UID returned by a UIDPLUS server) instead.
+pub fn is_retryable(cmd: &Command, err: &Error) -> bool {
+    match err {
+        Error::No(e) => matches!(
+            e.code(),
+            Some(ResponseCode::Unavailable) | Some(ResponseCode::InUse)
+        ),
+        Error::Io(_) | Error::Bye(_) | Error::Timeout => is_idempotent(cmd),
+        _ => false,
+    }
+}
+
+/// Whether executing `cmd` twice has the same effect as executing it once.
+fn is_idempotent(cmd: &Command) -> bool {
+    let name = command_name(cmd);
+    let name = name.strip_prefix(b"UID ").unwrap_or(&name);
+    matches!(
+        name,
+        b"CAPABILITY"
+            | b"NOOP"
+            | b"CHECK"
+            | b"EXAMINE"
+            | b"SELECT"
+            | b"FETCH"
+            | b"SEARCH"
+            | b"SORT"
+            | b"THREAD"
+            | b"STATUS"
+            | b"LIST"
+            | b"LSUB"
+            | b"NAMESPACE"
+            | b"GETMETADATA"
+            | b"GETQUOTA"
+            | b"GETACL"
+            | b"LISTRIGHTS"
+            | b"MYRIGHTS"
+            | b"GETQUOTAROOT"
+            | b"ID"
+            | b"ENABLE"
+    )
+}
+
+impl Client {
+    /// Send a command and collect its responses, retrying it according to
+    /// `policy` while it fails with a transient error (see `is_retryable()`).
+    ///
+    /// Retries are sent on this connection, so only failures reported by the
+    /// server (such as `NO [UNAVAILABLE]`) are retried; once the connection is
+    /// closed, the error is returned. Applications that reconnect can use
+    /// `is_retryable()` to decide whether to send the command again.
+    pub async fn call_with_retry<C: Into<Command>>(
+        &self,
+        cmd: C,
+        policy: &RetryPolicy,
+    ) -> Result<Vec<ResponseData>, Error> {
+        let cmd = cmd.into();
+        let mut retry = 0;
+        loop {
+            let err = match self.collect(cmd.clone()).await {
+                Ok(responses) => return Ok(responses),
+                Err(e) => e,
+            };
+            if retry >= policy.max_retries || self.is_closed() || !is_retryable(&cmd, &err) {
+                return Err(err);
+            }
+
+            log::debug!("retrying after transient failure: {}", err);
+            tokio::time::delay_for(policy.delay(retry)).await;
+            retry += 1;
+        }
+    }
+
+    async fn collect(&self, cmd: Command) -> Result<Vec<ResponseData>, Error> {
+        let mut responses = self.call(cmd);
+        let mut collected = Vec::new();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            collected.push(rsp);
+        }
+        Ok(collected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use super::{is_retryable, RetryPolicy};
+    use crate::testing::{MockServer, Script};
+    use crate::{Client, Error};
+    use imap_proto::builders::command::CommandBuilder;
+    use imap_proto::{AttrMacro, SequenceSet};
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::new(5)
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500));
+        let delays = (0..5).map(|i| policy.delay(i)).collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 500, 500]
+                .iter()
+                .map(|&ms| Duration::from_millis(ms))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(policy.delay(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn idempotency() {
+        let disconnected = Error::Io(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+        let fetch = CommandBuilder::uid_fetch()
+            .num(1)
+            .attr_macro(AttrMacro::All)
+            .into();
+        assert!(is_retryable(&fetch, &disconnected));
+        assert!(is_retryable(&fetch, &Error::Bye("shutting down".into())));
+        let append = CommandBuilder::append("Drafts", b"hello");
+        assert!(!is_retryable(&append, &disconnected));
+        let copy = CommandBuilder::uid_copy(&SequenceSet::from(1..=1), "Archive");
+        assert!(!is_retryable(&copy, &Error::Timeout));
+        assert!(!is_retryable(&fetch, &Error::MissingCapability("MOVE")));
+    }
+
+    #[tokio::test]
+    async fn retry_unavailable() {
+        let script = Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 APPEND \"Drafts\" {5}")
+            .send("+ ok\r\n")
+            .expect("hello")
+            .send("A0001 NO [UNAVAILABLE] backend down\r\n")
+            .expect("A0002 APPEND \"Drafts\" {5}")
+            .send("+ ok\r\n")
+            .expect("hello")
+            .send("A0002 NO [INUSE] mailbox locked\r\n")
+            .expect("A0003 APPEND \"Drafts\" {5}")
+            .send("+ ok\r\n")
+            .expect("hello")
+            .send("A0003 OK APPEND completed\r\n")
+            .expect("A0004 SELECT \"INBOX\"")
+            .send("A0004 NO [NONEXISTENT] no such mailbox\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let policy = RetryPolicy::new(2).initial_backoff(Duration::from_millis(1));
+        let append = CommandBuilder::append("Drafts", b"hello");
+        let responses = client.call_with_retry(append, &policy).await.unwrap();
+        assert_eq!(responses.len(), 1);
+
+        let select = CommandBuilder::select("INBOX");
+        match client.call_with_retry(select, &policy).await {
+            Err(Error::No(_)) => {}
+            rsp => panic!("unexpected result: {:?}", rsp),
+        }
+        server.finish().await.unwrap();
+    }
+}
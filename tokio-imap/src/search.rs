@@ -0,0 +1,95 @@
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::{CommandBuilder, SearchCommand};
+use imap_proto::{MailboxDatum, Response, SearchReturn, SequenceSet};
+
+impl Client {
+    /// Search the selected mailbox, returning the sequence numbers of the matching
+    /// messages.
+    ///
+    /// `criteria` is sent as-is, so strings in it must be quoted. If the server
+    /// supports ESEARCH (RFC 4731), the results are transferred as ranges, which
+    /// is much more compact for large result sets.
+    pub async fn search(&self, criteria: &str) -> Result<SequenceSet, Error> {
+        self.search_set(CommandBuilder::search(criteria)).await
+    }
+
+    /// Like `search()`, but returning UIDs.
+    pub async fn uid_search(&self, criteria: &str) -> Result<SequenceSet, Error> {
+        self.search_set(CommandBuilder::uid_search(criteria)).await
+    }
+
+    async fn search_set(&self, cmd: SearchCommand) -> Result<SequenceSet, Error> {
+        let mut responses = if self.session().has_capability("ESEARCH") {
+            self.call(cmd.returning(&[SearchReturn::All]))
+        } else {
+            self.call(cmd)
+        };
+
+        let mut results = SequenceSet::new();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            match rsp.parsed() {
+                Response::MailboxData(MailboxDatum::Search { ids: nums, .. }) => {
+                    results.extend(nums.iter().copied())
+                }
+                Response::MailboxData(MailboxDatum::ESearch(esearch)) => {
+                    if let Some(all) = &esearch.all {
+                        for range in all.ranges() {
+                            results.insert_range(range);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+
+    #[tokio::test]
+    async fn search() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 SEARCH UNSEEN")
+            .send("* SEARCH 2 3 4 8\r\nA0001 OK SEARCH completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let results = client.search("UNSEEN").await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(results.to_string(), "2:4,8");
+    }
+
+    #[tokio::test]
+    async fn esearch() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 ESEARCH] ready\r\n")
+            .expect("A0001 UID SEARCH RETURN (ALL) UNSEEN")
+            .send(
+                "* ESEARCH (TAG \"A0001\") UID ALL 4:100000,100002\r\n\
+                 A0001 OK SEARCH completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let results = client.uid_search("UNSEEN").await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(results.len(), 99_998);
+        assert!(results.contains(100_002));
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashSet;
+
+use imap_proto::{
+    Capability, Identity, MailboxDatum, PermanentFlags, Response, ResponseCode, State, Status,
+};
+
+/// Connection state tracked across commands.
+///
+/// A snapshot can be obtained through `Client::session()`. The session is kept up
+/// to date by the `Connection` from the responses sent by the server, including
+/// unsolicited ones.
+#[derive(Clone, Debug)]
+pub struct Session {
+    greeting: Option<Status>,
+    state: State,
+    capabilities: HashSet<String>,
+    /// Incremented each time the capabilities are advertised.
+    capability_updates: u64,
+    /// Whether the capabilities may have changed since last advertised.
+    capabilities_stale: bool,
+    enabled: HashSet<String>,
+    selected: Option<SelectedMailbox>,
+    /// Incremented each time a mailbox is selected.
+    selection: u64,
+    server_id: Option<Identity>,
+}
+
+impl Session {
+    pub(crate) fn new() -> Self {
+        Self {
+            greeting: None,
+            state: State::NotAuthenticated,
+            capabilities: HashSet::new(),
+            capability_updates: 0,
+            capabilities_stale: false,
+            enabled: HashSet::new(),
+            selected: None,
+            selection: 0,
+            server_id: None,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// The status of the server greeting, once received: `Ok`, `PreAuth` if the
+    /// connection is already authenticated (as with servers started over an SSH
+    /// tunnel), in which case the session starts in the authenticated state, or
+    /// `Bye` if the server rejected the connection.
+    ///
+    /// Capabilities advertised in the greeting are available through
+    /// `capabilities()`.
+    pub fn greeting(&self) -> Option<Status> {
+        self.greeting
+    }
+
+    /// The capabilities most recently advertised by the server, in upper case.
+    ///
+    /// Capabilities change after `LOGIN` and `AUTHENTICATE`; if the server
+    /// does not advertise them again in its response, the `Connection` asks
+    /// for them with a `CAPABILITY` command, and the set is empty until they
+    /// are received. After `STARTTLS`, the set is empty, as nothing more is
+    /// sent in plain text. `ENABLE` does not change them (RFC 5161, section
+    /// 3.1); the extensions it enables are listed by `enabled()`.
+    pub fn capabilities(&self) -> &HashSet<String> {
+        &self.capabilities
+    }
+
+    /// Whether the server advertised the given capability (compared case-insensitively).
+    pub fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.contains(&name.to_ascii_uppercase())
+    }
+
+    pub(crate) fn capability_updates(&self) -> u64 {
+        self.capability_updates
+    }
+
+    /// Whether the capabilities are being asked for again, see `capabilities()`.
+    pub(crate) fn capabilities_stale(&self) -> bool {
+        self.capabilities_stale
+    }
+
+    /// Forget the capabilities, until they are advertised again.
+    pub(crate) fn invalidate_capabilities(&mut self) {
+        self.capabilities.clear();
+        self.capabilities_stale = true;
+    }
+
+    /// The extensions enabled through the `ENABLE` command, in upper case.
+    pub fn enabled(&self) -> &HashSet<String> {
+        &self.enabled
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.contains(&name.to_ascii_uppercase())
+    }
+
+    /// The server software, as identified in the server's most recent `ID`
+    /// response (RFC 2971), if any.
+    pub fn server_id(&self) -> Option<&Identity> {
+        self.server_id.as_ref()
+    }
+
+    /// The currently selected mailbox, if any.
+    pub fn selected(&self) -> Option<&SelectedMailbox> {
+        self.selected.as_ref()
+    }
+
+    /// Identifies the current selection, to tell whether a mailbox is still
+    /// selected even if selected again since.
+    pub(crate) fn selection(&self) -> u64 {
+        self.selection
+    }
+
+    pub(crate) fn set_state(&mut self, state: State) {
+        self.state = state;
+        if state != State::Selected {
+            self.selected = None;
+        }
+    }
+
+    pub(crate) fn selecting(&mut self, name: String) {
+        self.selected = Some(SelectedMailbox::new(name));
+        self.selection += 1;
+    }
+
+    pub(crate) fn set_enabled(&mut self, capabilities: &[Capability]) {
+        self.enabled
+            .extend(capabilities.iter().map(Capability::name));
+        // Enabling QRESYNC implies enabling CONDSTORE (RFC 7162, section 3.2.3)
+        if self.enabled.contains("QRESYNC") {
+            self.enabled.insert("CONDSTORE".to_string());
+        }
+    }
+
+    pub(crate) fn update(&mut self, rsp: &Response) {
+        if self.greeting.is_none() {
+            if let Response::Data { status, .. } = rsp {
+                self.greeting = Some(*status);
+                if *status == Status::PreAuth {
+                    self.set_state(State::Authenticated);
+                }
+            }
+        }
+
+        match rsp {
+            Response::Capabilities(caps) => self.set_capabilities(caps),
+            Response::Data { status, code, .. } | Response::Done { status, code, .. } => {
+                if *status == Status::Bye {
+                    self.set_state(State::Logout);
+                }
+                if let Some(code) = code {
+                    self.update_code(code);
+                }
+            }
+            Response::Id(identity) => self.server_id = Some(identity.clone()),
+            Response::Expunge(_) => {
+                if let Some(mailbox) = &mut self.selected {
+                    mailbox.exists = mailbox.exists.saturating_sub(1);
+                }
+            }
+            Response::Vanished {
+                earlier: false,
+                uids,
+            } => {
+                if let Some(mailbox) = &mut self.selected {
+                    mailbox.exists = mailbox.exists.saturating_sub(uids.len() as u32);
+                }
+            }
+            Response::MailboxData(datum) => {
+                let mailbox = match &mut self.selected {
+                    Some(mailbox) => mailbox,
+                    None => return,
+                };
+                match datum {
+                    MailboxDatum::Exists(n) => mailbox.exists = *n,
+                    MailboxDatum::Recent(n) => mailbox.recent = *n,
+                    MailboxDatum::Flags(flags) => {
+                        mailbox.flags = flags.iter().map(|s| s.to_string()).collect()
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn update_code(&mut self, code: &ResponseCode) {
+        if let ResponseCode::Capabilities(caps) = code {
+            return self.set_capabilities(caps);
+        }
+
+        let mailbox = match &mut self.selected {
+            Some(mailbox) => mailbox,
+            None => return,
+        };
+        match code {
+            ResponseCode::HighestModSeq(n) => mailbox.highest_mod_seq = Some(*n),
+            ResponseCode::PermanentFlags(flags) => mailbox.permanent_flags = flags.clone(),
+            ResponseCode::ReadOnly => mailbox.read_only = true,
+            ResponseCode::ReadWrite => mailbox.read_only = false,
+            ResponseCode::UidNext(n) => mailbox.uid_next = Some(*n),
+            ResponseCode::UidValidity(n) => mailbox.uid_validity = Some(*n),
+            ResponseCode::Unseen(n) => mailbox.unseen = Some(*n),
+            _ => {}
+        }
+    }
+
+    fn set_capabilities(&mut self, capabilities: &[Capability]) {
+        self.capabilities = capabilities.iter().map(Capability::name).collect();
+        self.capability_updates += 1;
+        self.capabilities_stale = false;
+    }
+}
+
+/// The state of the currently selected mailbox.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SelectedMailbox {
+    pub name: String,
+    pub read_only: bool,
+    pub exists: u32,
+    pub recent: u32,
+    pub unseen: Option<u32>,
+    pub uid_validity: Option<u32>,
+    pub uid_next: Option<u32>,
+    pub highest_mod_seq: Option<u64>,
+    pub flags: Vec<String>,
+    pub permanent_flags: PermanentFlags,
+}
+
+impl SelectedMailbox {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            read_only: false,
+            exists: 0,
+            recent: 0,
+            unseen: None,
+            uid_validity: None,
+            uid_next: None,
+            highest_mod_seq: None,
+            flags: Vec::new(),
+            permanent_flags: PermanentFlags::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use imap_proto::{Response, State, Status};
+
+    fn update(session: &mut Session, input: &[u8]) {
+        let (_, rsp) = Response::from_bytes(input).unwrap();
+        session.update(&rsp);
+    }
+
+    #[test]
+    fn select_updates_mailbox() {
+        let mut session = Session::new();
+        update(&mut session, b"* OK [CAPABILITY IMAP4rev1 IDLE] ready\r\n");
+        assert!(session.has_capability("idle"));
+
+        session.set_state(State::Authenticated);
+        session.selecting("INBOX".into());
+        update(&mut session, b"* 172 EXISTS\r\n");
+        update(
+            &mut session,
+            b"* OK [UIDVALIDITY 3857529045] UIDs valid\r\n",
+        );
+        update(&mut session, b"* FLAGS (\\Answered \\Seen)\r\n");
+        update(
+            &mut session,
+            b"* OK [PERMANENTFLAGS (\\Seen \\*)] Limited\r\n",
+        );
+        update(&mut session, b"A1 OK [READ-ONLY] EXAMINE completed\r\n");
+        session.set_state(State::Selected);
+
+        let mailbox = session.selected().unwrap();
+        assert_eq!(mailbox.name, "INBOX");
+        assert_eq!(mailbox.exists, 172);
+        assert_eq!(mailbox.uid_validity, Some(3_857_529_045));
+        assert_eq!(mailbox.flags, vec!["\\Answered", "\\Seen"]);
+        assert!(mailbox.read_only);
+        assert!(mailbox.permanent_flags.allows_new_keywords);
+        assert!(mailbox.permanent_flags.allows("\\Seen"));
+
+        update(&mut session, b"* 3 EXPUNGE\r\n");
+        assert_eq!(session.selected().unwrap().exists, 171);
+        update(&mut session, b"* VANISHED 3:5\r\n");
+        assert_eq!(session.selected().unwrap().exists, 168);
+        update(&mut session, b"* VANISHED (EARLIER) 1:2\r\n");
+        assert_eq!(session.selected().unwrap().exists, 168);
+
+        session.set_state(State::Authenticated);
+        assert!(session.selected().is_none());
+    }
+
+    #[test]
+    fn greeting() {
+        let mut session = Session::new();
+        update(&mut session, b"* OK IMAP4rev1 server ready\r\n");
+        assert_eq!(session.greeting(), Some(Status::Ok));
+        assert_eq!(session.state(), State::NotAuthenticated);
+
+        let mut session = Session::new();
+        update(
+            &mut session,
+            b"* PREAUTH [CAPABILITY IMAP4rev1 IDLE] Logged in as djc\r\n",
+        );
+        assert_eq!(session.greeting(), Some(Status::PreAuth));
+        assert_eq!(session.state(), State::Authenticated);
+        assert!(session.has_capability("IDLE"));
+
+        update(&mut session, b"* OK still here\r\n");
+        assert_eq!(session.greeting(), Some(Status::PreAuth));
+    }
+}
@@ -0,0 +1,396 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::{Buf, BytesMut};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Writes large literals (such as message bodies) received by a connection
+/// somewhere other than memory, see `Connection::spill_literals()`.
+///
+/// In responses, a spilled literal is replaced by an empty literal (its size is
+/// written as zeroes, for example `{0000000}`), so that the parsed response
+/// holds an empty `&[u8]` in its place. `ResponseData::spilled()` returns the
+/// handle of the literal for that slice.
+///
+/// Literals are written from within the connection task, using blocking I/O.
+pub struct Spill {
+    threshold: u64,
+    target: Target,
+}
+
+enum Target {
+    TempDir(PathBuf),
+    Sink(Box<dyn LiteralSink>),
+}
+
+impl Spill {
+    /// Write literals larger than `threshold` bytes to files in the temporary
+    /// directory of the system.
+    pub fn temp_files(threshold: u64) -> Self {
+        Self::temp_files_in(threshold, std::env::temp_dir())
+    }
+
+    /// Write literals larger than `threshold` bytes to files in `dir`.
+    pub fn temp_files_in<P: Into<PathBuf>>(threshold: u64, dir: P) -> Self {
+        Self {
+            threshold,
+            target: Target::TempDir(dir.into()),
+        }
+    }
+
+    /// Pass literals larger than `threshold` bytes to `sink`.
+    pub fn sink<S: LiteralSink + 'static>(threshold: u64, sink: S) -> Self {
+        Self {
+            threshold,
+            target: Target::Sink(Box::new(sink)),
+        }
+    }
+
+    fn create(&mut self, len: u64) -> io::Result<(Box<dyn Write + Send>, SpilledLiteral)> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        match &mut self.target {
+            Target::TempDir(dir) => {
+                let path = dir.join(format!("tokio-imap-{}-{}.literal", std::process::id(), id));
+                let file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)?;
+                let literal = SpilledLiteral {
+                    id,
+                    len,
+                    file: Some(Arc::new(TempFile {
+                        path,
+                        keep: AtomicBool::new(false),
+                    })),
+                };
+                Ok((Box::new(BufWriter::new(file)), literal))
+            }
+            Target::Sink(sink) => {
+                let literal = SpilledLiteral {
+                    id,
+                    len,
+                    file: None,
+                };
+                Ok((sink.create(id, len)?, literal))
+            }
+        }
+    }
+}
+
+/// A destination for spilled literals, see `Spill::sink()`.
+pub trait LiteralSink: Send {
+    /// Create the writer for the data of a literal of `len` bytes. `id` is the
+    /// value returned by `SpilledLiteral::id()` for the literal.
+    fn create(&mut self, id: u64, len: u64) -> io::Result<Box<dyn Write + Send>>;
+}
+
+/// A literal that was written to a temporary file or a `LiteralSink`, returned
+/// by `ResponseData::spilled()`.
+///
+/// Temporary files are removed when the last handle referring to them is
+/// dropped, unless moved elsewhere with `persist()`.
+#[derive(Clone, Debug)]
+pub struct SpilledLiteral {
+    id: u64,
+    len: u64,
+    file: Option<Arc<TempFile>>,
+}
+
+impl SpilledLiteral {
+    /// An identifier for the literal, unique within the process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The size of the literal in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The temporary file holding the literal, if it was written to one.
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|file| &*file.path)
+    }
+
+    /// Open the temporary file holding the literal for reading.
+    pub fn open(&self) -> io::Result<File> {
+        match self.path() {
+            Some(path) => File::open(path),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "literal was written to a sink",
+            )),
+        }
+    }
+
+    /// Move the temporary file holding the literal to `path`, which must be on
+    /// the same file system, so that it is kept.
+    pub fn persist<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Err(io::ErrorKind::NotFound.into()),
+        };
+        fs::rename(&file.path, path)?;
+        file.keep.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct TempFile {
+    path: PathBuf,
+    keep: AtomicBool,
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.keep.load(Ordering::Relaxed) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Finds the literals in the data received that are to be spilled, as the
+/// data is received and before it is parsed.
+pub(crate) struct Spiller {
+    spill: Spill,
+    state: State,
+    /// The spilled literals in the read buffer that have not been decoded yet,
+    /// with their offset in the buffer.
+    pending: Vec<(usize, SpilledLiteral)>,
+}
+
+enum State {
+    Line,
+    /// Reading a literal kept in the buffer.
+    Literal(u64),
+    Spilling {
+        writer: Box<dyn Write + Send>,
+        remaining: u64,
+    },
+}
+
+impl Spiller {
+    pub(crate) fn new(spill: Spill) -> Self {
+        Self {
+            spill,
+            state: State::Line,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Process the data in `buf` from `start`, which has not been seen before.
+    pub(crate) fn scan(&mut self, buf: &mut BytesMut, start: usize) -> io::Result<()> {
+        let mut new = buf.split_off(start);
+        while !new.is_empty() {
+            match &mut self.state {
+                State::Line => match new.iter().position(|&b| b == b'\n') {
+                    Some(pos) => {
+                        buf.unsplit(new.split_to(pos + 1));
+                        self.line_end(buf)?;
+                    }
+                    None => buf.unsplit(new.split_off(0)),
+                },
+                State::Literal(remaining) => {
+                    let n = (*remaining).min(new.len() as u64);
+                    buf.unsplit(new.split_to(n as usize));
+                    *remaining -= n;
+                    if *remaining == 0 {
+                        self.state = State::Line;
+                    }
+                }
+                State::Spilling { writer, remaining } => {
+                    let n = (*remaining).min(new.len() as u64);
+                    writer.write_all(&new[..n as usize])?;
+                    new.advance(n as usize);
+                    *remaining -= n;
+                    if *remaining == 0 {
+                        writer.flush()?;
+                        self.state = State::Line;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the line at the end of `buf` announces a literal.
+    fn line_end(&mut self, buf: &mut BytesMut) -> io::Result<()> {
+        let (digits, len) = match literal_header(buf) {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        if len <= self.spill.threshold {
+            if len > 0 {
+                self.state = State::Literal(len);
+            }
+            return Ok(());
+        }
+
+        let (writer, literal) = self.spill.create(len)?;
+        for b in &mut buf[digits] {
+            *b = b'0';
+        }
+        self.pending.push((buf.len(), literal));
+        self.state = State::Spilling {
+            writer,
+            remaining: len,
+        };
+        Ok(())
+    }
+
+    /// Take the spilled literals in a response of `len` bytes decoded from the
+    /// start of the buffer.
+    pub(crate) fn take(&mut self, len: usize) -> Vec<(usize, SpilledLiteral)> {
+        let split = self
+            .pending
+            .iter()
+            .take_while(|(offset, _)| *offset < len)
+            .count();
+        let rest = self.pending.split_off(split);
+        let taken = std::mem::replace(&mut self.pending, rest);
+        for (offset, _) in &mut self.pending {
+            *offset -= len;
+        }
+        taken
+    }
+}
+
+impl fmt::Debug for Spill {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let target = match &self.target {
+            Target::TempDir(dir) => format!("{:?}", dir),
+            Target::Sink(_) => "<sink>".into(),
+        };
+        f.debug_struct("Spill")
+            .field("threshold", &self.threshold)
+            .field("target", &target)
+            .finish()
+    }
+}
+
+/// The position of the digits and the size of the literal announced at the end
+/// of `line` (`{123}\r\n`, or `{123}\n` for servers that need
+/// `Quirks::bare_lf()`), if any.
+pub(crate) fn literal_header(line: &[u8]) -> Option<(std::ops::Range<usize>, u64)> {
+    let line = line.strip_suffix(b"\n")?;
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let end = line.len().checked_sub(1)?;
+    if line[end] != b'}' {
+        return None;
+    }
+    let start = line[..end].iter().rposition(|b| !b.is_ascii_digit())?;
+    if line[start] != b'{' || start + 1 == end {
+        return None;
+    }
+    let len = std::str::from_utf8(&line[start + 1..end])
+        .ok()?
+        .parse()
+        .ok()?;
+    Some((start + 1..end, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    use super::{LiteralSink, Spill};
+    use crate::protocol::{Event, Protocol};
+    use imap_proto::{AttributeValue, Response};
+
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<u8>>>);
+
+    impl LiteralSink for Collect {
+        fn create(&mut self, _: u64, _: u64) -> io::Result<Box<dyn Write + Send>> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    impl Write for Collect {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn body(event: Option<Event>) -> (crate::ResponseData, Option<super::SpilledLiteral>) {
+        let rsp = match event {
+            Some(Event::Unsolicited(rsp)) => rsp,
+            event => panic!("unexpected event: {:?}", event),
+        };
+        let spilled = match rsp.parsed() {
+            Response::Fetch(_, attrs) => match &attrs[0] {
+                AttributeValue::BodySection {
+                    data: Some(data), ..
+                } => rsp.spilled(data).cloned(),
+                attr => panic!("unexpected attribute: {:?}", attr),
+            },
+            rsp => panic!("unexpected response: {:?}", rsp),
+        };
+        (rsp, spilled)
+    }
+
+    #[test]
+    fn spill_to_sink() {
+        let sink = Collect::default();
+        let mut protocol = Protocol::new();
+        protocol.spill_literals(Spill::sink(8, sink.clone()));
+
+        let data = b"* 1 FETCH (BODY[] {5}\r\n{9}\r\n)\r\n* 2 FETCH (BODY[] {12}\r\n\
+                     hello\r\n{1}\r\n)\r\n* 3 FETCH (BODY[] {4}\r\nbye!)\r\n";
+        for chunk in data.chunks(5) {
+            protocol.receive(chunk).unwrap();
+        }
+
+        let (rsp, spilled) = body(protocol.poll_event());
+        assert_eq!(rsp.raw(), b"* 1 FETCH (BODY[] {5}\r\n{9}\r\n)\r\n");
+        assert!(spilled.is_none());
+
+        let (rsp, spilled) = body(protocol.poll_event());
+        assert_eq!(rsp.raw(), b"* 2 FETCH (BODY[] {00}\r\n)\r\n");
+        assert_eq!(spilled.unwrap().len(), 12);
+        assert_eq!(&sink.0.lock().unwrap()[..], b"hello\r\n{1}\r\n");
+
+        let (_, spilled) = body(protocol.poll_event());
+        assert!(spilled.is_none());
+        assert!(protocol.poll_event().is_none());
+    }
+
+    #[test]
+    fn spill_to_temp_file() {
+        let mut protocol = Protocol::new();
+        protocol.spill_literals(Spill::temp_files(0));
+        protocol
+            .receive(b"* 1 FETCH (BODY[] {5}\r\nhello)\r\n")
+            .unwrap();
+
+        let (rsp, spilled) = body(protocol.poll_event());
+        let spilled = spilled.unwrap();
+        let path = spilled.path().unwrap().to_owned();
+        let mut content = String::new();
+        spilled
+            .open()
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "hello");
+
+        drop((rsp, spilled));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use futures::StreamExt;
+
+use crate::client::{Client, ResponseStream};
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::CommandBuilder;
+use imap_proto::{MailboxDatum, Response, StatusAttribute, StatusItem};
+
+/// The status of a mailbox, as returned by `Client::statuses()`. Only the
+/// requested items are set.
+///
+/// Can also be built from the attributes of a `STATUS` response with `From`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MailboxStatus {
+    pub messages: Option<u32>,
+    pub recent: Option<u32>,
+    pub unseen: Option<u32>,
+    pub uid_next: Option<u32>,
+    pub uid_validity: Option<u32>,
+    pub highest_mod_seq: Option<u64>,
+    /// The total size of the messages in octets (RFC 8438).
+    pub size: Option<u64>,
+}
+
+impl From<Vec<StatusAttribute<'_>>> for MailboxStatus {
+    fn from(attrs: Vec<StatusAttribute<'_>>) -> Self {
+        let mut status = Self::default();
+        status.update(&attrs);
+        status
+    }
+}
+
+impl MailboxStatus {
+    fn update(&mut self, attrs: &[StatusAttribute<'_>]) {
+        for attr in attrs {
+            match attr {
+                StatusAttribute::HighestModSeq(n) => self.highest_mod_seq = Some(*n),
+                StatusAttribute::Messages(n) => self.messages = Some(*n),
+                StatusAttribute::Recent(n) => self.recent = Some(*n),
+                StatusAttribute::Size(n) => self.size = Some(*n),
+                StatusAttribute::UidNext(n) => self.uid_next = Some(*n),
+                StatusAttribute::UidValidity(n) => self.uid_validity = Some(*n),
+                StatusAttribute::Unseen(n) => self.unseen = Some(*n),
+                StatusAttribute::Other(..) => {}
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Get the status of several mailboxes, such as their number of unseen messages.
+    ///
+    /// Uses a single `LIST` command if the server supports LIST-STATUS (RFC 5819);
+    /// otherwise, a `STATUS` command is sent for each mailbox, all of them
+    /// pipelined. Mailboxes that the server refuses to report on (for example,
+    /// because they do not exist) are left out of the result. The result is keyed
+    /// by the mailbox names as returned by the server.
+    pub async fn statuses(
+        &self,
+        mailboxes: &[&str],
+        items: &[StatusItem],
+    ) -> Result<HashMap<String, MailboxStatus>, Error> {
+        let mut statuses = HashMap::new();
+        if mailboxes.is_empty() {
+            return Ok(statuses);
+        }
+
+        if self.session().has_capability("LIST-STATUS") {
+            let cmd = CommandBuilder::list_status("", mailboxes, items);
+            collect_statuses(self.call(cmd), &mut statuses).await?;
+            return Ok(statuses);
+        }
+
+        let calls = mailboxes
+            .iter()
+            .map(|mailbox| self.call(CommandBuilder::status(mailbox, items)))
+            .collect::<Vec<_>>();
+        for responses in calls {
+            match collect_statuses(responses, &mut statuses).await {
+                Ok(()) | Err(Error::No(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(statuses)
+    }
+}
+
+async fn collect_statuses(
+    mut responses: ResponseStream,
+    statuses: &mut HashMap<String, MailboxStatus>,
+) -> Result<(), Error> {
+    while let Some(rsp) = responses.next().await {
+        let rsp = rsp?;
+        if let Response::MailboxData(MailboxDatum::Status { mailbox, status }) = rsp.parsed() {
+            statuses
+                .entry(mailbox.to_string())
+                .or_default()
+                .update(status);
+        } else {
+            check_completion(&rsp)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MailboxStatus;
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use imap_proto::{StatusAttribute, StatusItem};
+
+    #[test]
+    fn from_attributes() {
+        let status = MailboxStatus::from(vec![
+            StatusAttribute::Messages(231),
+            StatusAttribute::UidNext(44292),
+            StatusAttribute::Size(1_000_000),
+        ]);
+        assert_eq!(
+            status,
+            MailboxStatus {
+                messages: Some(231),
+                uid_next: Some(44292),
+                size: Some(1_000_000),
+                ..MailboxStatus::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn pipelined_status() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 STATUS \"INBOX\" (MESSAGES UNSEEN)")
+            .expect("A0002 STATUS \"Gone\" (MESSAGES UNSEEN)")
+            .send(
+                "* STATUS INBOX (MESSAGES 17 UNSEEN 3)\r\n\
+                 A0001 OK STATUS completed\r\n\
+                 A0002 NO Mailbox does not exist\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let items = [StatusItem::Messages, StatusItem::Unseen];
+        let statuses = client.statuses(&["INBOX", "Gone"], &items).await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(
+            statuses["INBOX"],
+            MailboxStatus {
+                messages: Some(17),
+                unseen: Some(3),
+                ..MailboxStatus::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn list_status() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 LIST-EXTENDED LIST-STATUS] ready\r\n")
+            .expect("A0001 LIST \"\" (\"INBOX\" \"Sent\") RETURN (STATUS (UNSEEN))")
+            .send(
+                "* LIST () \"/\" INBOX\r\n\
+                 * STATUS INBOX (UNSEEN 2)\r\n\
+                 * LIST () \"/\" Sent\r\n\
+                 * STATUS Sent (UNSEEN 0)\r\n\
+                 A0001 OK LIST completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let statuses = client
+            .statuses(&["INBOX", "Sent"], &[StatusItem::Unseen])
+            .await
+            .unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(statuses["INBOX"].unseen, Some(2));
+        assert_eq!(statuses["Sent"].unseen, Some(0));
+    }
+}
@@ -0,0 +1,300 @@
+use std::str;
+
+use bytes::Bytes;
+
+use crate::spill::literal_header;
+use imap_proto::RequestId;
+
+/// Part of a literal in a `FETCH` response, returned as `Event::Literal` by
+/// `Protocol::poll_event()` as it is received, see `Protocol::stream_literals()`,
+/// or by `ResponseStream::literals()`.
+#[derive(Clone, Debug)]
+pub struct LiteralChunk {
+    pub(crate) request_id: Option<RequestId>,
+    pub(crate) message: u32,
+    pub(crate) attribute: String,
+    pub(crate) offset: u64,
+    pub(crate) len: u64,
+    pub(crate) data: Bytes,
+}
+
+impl LiteralChunk {
+    /// The tag of the command the `FETCH` response is attributed to, if any.
+    pub fn request_id(&self) -> Option<&RequestId> {
+        self.request_id.as_ref()
+    }
+
+    /// The sequence number of the message.
+    pub fn message(&self) -> u32 {
+        self.message
+    }
+
+    /// The attribute holding the literal, as sent by the server (for example
+    /// `BODY[]` or `BODY[1.2]<0>`).
+    pub fn attribute(&self) -> &str {
+        &self.attribute
+    }
+
+    /// The position of this chunk in the literal.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The size of the whole literal.
+    pub fn literal_len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether this chunk ends the literal.
+    pub fn is_last(&self) -> bool {
+        self.offset + self.data.len() as u64 == self.len
+    }
+
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+}
+
+/// Finds the literals in `FETCH` responses as the data is received, and copies
+/// their content into chunks before the response is complete.
+pub(crate) struct Streamer {
+    min_len: u64,
+    state: State,
+    /// The line being received.
+    line: Vec<u8>,
+    /// Whether the line continues a response after a literal.
+    continued: bool,
+    /// The message of the `FETCH` response being received, if any.
+    message: Option<u32>,
+    /// The chunks of responses that have not been decoded yet, with their
+    /// offset in the read buffer.
+    pending: Vec<(usize, LiteralChunk)>,
+}
+
+enum State {
+    Line,
+    /// Reading a literal that is not streamed.
+    Literal(u64),
+    Streaming {
+        message: u32,
+        attribute: String,
+        offset: u64,
+        len: u64,
+    },
+}
+
+impl Streamer {
+    pub(crate) fn new(min_len: u64) -> Self {
+        Self {
+            min_len,
+            state: State::Line,
+            line: Vec::new(),
+            continued: false,
+            message: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Process the data in `buf` from `start`, which has not been seen before.
+    pub(crate) fn scan(&mut self, buf: &[u8], start: usize) {
+        let mut pos = start;
+        while pos < buf.len() {
+            let new = &buf[pos..];
+            match &mut self.state {
+                State::Line => match new.iter().position(|&b| b == b'\n') {
+                    Some(end) => {
+                        self.line.extend_from_slice(&new[..end + 1]);
+                        pos += end + 1;
+                        self.line_end();
+                    }
+                    None => {
+                        self.line.extend_from_slice(new);
+                        pos = buf.len();
+                    }
+                },
+                State::Literal(remaining) => {
+                    let n = (*remaining).min(new.len() as u64);
+                    pos += n as usize;
+                    *remaining -= n;
+                    if *remaining == 0 {
+                        self.state = State::Line;
+                    }
+                }
+                State::Streaming {
+                    message,
+                    attribute,
+                    offset,
+                    len,
+                } => {
+                    let n = (*len - *offset).min(new.len() as u64);
+                    let chunk = LiteralChunk {
+                        request_id: None,
+                        message: *message,
+                        attribute: attribute.clone(),
+                        offset: *offset,
+                        len: *len,
+                        data: Bytes::copy_from_slice(&new[..n as usize]),
+                    };
+                    self.pending.push((pos, chunk));
+                    pos += n as usize;
+                    *offset += n;
+                    if *offset == *len {
+                        self.state = State::Line;
+                    }
+                }
+            }
+        }
+    }
+
+    fn line_end(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        if !self.continued {
+            self.message = fetch_message(&line);
+        }
+
+        let len = match literal_header(&line) {
+            Some((_, len)) => len,
+            None => {
+                self.continued = false;
+                return;
+            }
+        };
+        self.continued = true;
+        let attribute = literal_attribute(&line);
+        self.state = match (self.message, attribute) {
+            (Some(message), Some(attribute)) if len > 0 && len >= self.min_len => {
+                State::Streaming {
+                    message,
+                    attribute: String::from_utf8_lossy(attribute).into_owned(),
+                    offset: 0,
+                    len,
+                }
+            }
+            _ if len > 0 => State::Literal(len),
+            _ => State::Line,
+        };
+    }
+
+    /// Take the chunks of a response of `len` bytes decoded from the start of
+    /// the buffer.
+    pub(crate) fn take(&mut self, len: usize) -> Vec<LiteralChunk> {
+        let split = self
+            .pending
+            .iter()
+            .take_while(|(offset, _)| *offset < len)
+            .count();
+        let rest = self.pending.split_off(split);
+        let taken = std::mem::replace(&mut self.pending, rest);
+        for (offset, _) in &mut self.pending {
+            *offset -= len;
+        }
+        taken.into_iter().map(|(_, chunk)| chunk).collect()
+    }
+
+    /// Take the chunks of the response that has not been received completely.
+    pub(crate) fn take_incomplete(&mut self) -> Vec<LiteralChunk> {
+        self.pending.drain(..).map(|(_, chunk)| chunk).collect()
+    }
+}
+
+/// The message number of a `FETCH` response starting with `line`, if it is one.
+fn fetch_message(line: &[u8]) -> Option<u32> {
+    let rest = line.strip_prefix(b"* ")?;
+    let end = rest.iter().position(|&b| b == b' ')?;
+    let (number, rest) = rest.split_at(end);
+    if !rest[1..].get(..6)?.eq_ignore_ascii_case(b"FETCH ") {
+        return None;
+    }
+    str::from_utf8(number).ok()?.parse().ok()
+}
+
+/// The attribute preceding the literal header at the end of `line`, such as
+/// `BODY[HEADER.FIELDS (FROM)]`.
+fn literal_attribute(line: &[u8]) -> Option<&[u8]> {
+    let open = line.iter().rposition(|&b| b == b'{')?;
+    let end = open.checked_sub(1).filter(|&end| line[end] == b' ')?;
+    let name = &line[..end];
+    // Section specifiers may contain spaces
+    let search = match name.iter().rposition(|&b| b == b']') {
+        Some(close) => name[..close].iter().rposition(|&b| b == b'[')?,
+        None => name.len(),
+    };
+    let start = name[..search]
+        .iter()
+        .rposition(|&b| b == b' ' || b == b'(')
+        .map_or(0, |pos| pos + 1);
+    Some(&name[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fetch_message, literal_attribute};
+    use crate::protocol::{Event, Protocol};
+    use imap_proto::builders::command::CommandBuilder;
+    use imap_proto::{Attribute, Response};
+
+    #[test]
+    fn headers() {
+        assert_eq!(fetch_message(b"* 12 FETCH (BODY[] {5}\r\n"), Some(12));
+        assert_eq!(fetch_message(b"* 12 fetch (BODY[] {5}\r\n"), Some(12));
+        assert_eq!(fetch_message(b"* OK [ALERT] {5}\r\n"), None);
+        assert_eq!(fetch_message(b"* 3 EXISTS\r\n"), None);
+
+        let attr = |line: &[u8]| literal_attribute(line).map(|attr| attr.to_vec());
+        assert_eq!(attr(b"* 1 FETCH (BODY[] {5}\r\n").unwrap(), b"BODY[]");
+        assert_eq!(
+            attr(b"* 1 FETCH (UID 4 BODY[HEADER.FIELDS (FROM TO)] {5}\r\n").unwrap(),
+            b"BODY[HEADER.FIELDS (FROM TO)]"
+        );
+        assert_eq!(attr(b"\"x\" BODY[1]<0> {5}\r\n").unwrap(), b"BODY[1]<0>");
+    }
+
+    #[test]
+    fn stream_fetch_literals() {
+        let mut protocol = Protocol::new();
+        protocol.stream_literals(4);
+        let cmd = CommandBuilder::fetch().range(1..=2).attr(Attribute::Rfc822);
+        let request_id = protocol.enqueue(cmd.into());
+        protocol.transmit();
+
+        protocol
+            .receive(b"* 1 FETCH (FLAGS () BODY[] {10}\r\n")
+            .unwrap();
+        assert!(protocol.poll_event().is_none());
+        protocol.receive(b"hello").unwrap();
+        let chunk = match protocol.poll_event() {
+            Some(Event::Literal(chunk)) => chunk,
+            event => panic!("unexpected event: {:?}", event),
+        };
+        assert_eq!(chunk.request_id(), Some(&request_id));
+        assert_eq!((chunk.message(), chunk.attribute()), (1, "BODY[]"));
+        assert_eq!((chunk.offset(), chunk.literal_len()), (0, 10));
+        assert_eq!(&chunk.data()[..], b"hello");
+        assert!(!chunk.is_last());
+
+        // Short literals are only part of the complete response
+        protocol
+            .receive(b"world BODY[HEADER] {2}\r\nhi)\r\n* 2 FETCH (BODY[] {5}\r\nbye")
+            .unwrap();
+        let chunk = match protocol.poll_event() {
+            Some(Event::Literal(chunk)) => chunk,
+            event => panic!("unexpected event: {:?}", event),
+        };
+        assert_eq!(&chunk.data()[..], b"world");
+        assert!(chunk.is_last());
+        match protocol.poll_event() {
+            Some(Event::Response(_, rsp)) => {
+                assert!(matches!(rsp.parsed(), Response::Fetch(1, _)))
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+        match protocol.poll_event() {
+            Some(Event::Literal(chunk)) => {
+                assert_eq!((chunk.message(), chunk.offset()), (2, 0));
+                assert_eq!(&chunk.data()[..], b"bye");
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert!(protocol.poll_event().is_none());
+    }
+}
@@ -0,0 +1,346 @@
+use futures::StreamExt;
+
+use crate::client::Client;
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::{Command, CommandBuilder};
+use imap_proto::{
+    Attribute, AttributeValue, FlagSet, Response, ResponseCode, SequenceSet, StoreMode,
+};
+
+/// The changes to a mailbox since a previous session, as reported by the
+/// server when selecting it with `Client::select_qresync()`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MailboxChanges {
+    /// Whether the `UIDVALIDITY` of the mailbox changed. If so, the server does
+    /// not report any changes and all state cached for the mailbox must be
+    /// discarded.
+    pub uid_validity_changed: bool,
+    pub uid_validity: Option<u32>,
+    /// The new `HIGHESTMODSEQ` to store for the next resynchronization.
+    pub highest_mod_seq: Option<u64>,
+    /// UIDs of messages that were expunged.
+    pub vanished: SequenceSet,
+    /// Known messages of which the flags changed.
+    pub changed: Vec<MessageChange>,
+    /// Messages that were added to the mailbox.
+    pub new: Vec<MessageChange>,
+}
+
+/// The flag changes in the selected mailbox, as returned by
+/// `Client::fetch_changed_flags()`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FlagChanges {
+    /// The `HIGHESTMODSEQ` to store for the next incremental sync.
+    pub highest_mod_seq: u64,
+    /// Messages of which the flags changed.
+    pub changed: Vec<MessageChange>,
+}
+
+/// The flags of a message, as reported in a `FETCH` response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MessageChange {
+    pub uid: u32,
+    pub flags: Vec<String>,
+    pub mod_seq: Option<u64>,
+}
+
+impl MessageChange {
+    pub fn flag_set(&self) -> FlagSet {
+        self.flags.iter().collect()
+    }
+
+    fn from_attributes(attrs: &[AttributeValue]) -> Option<Self> {
+        let (mut uid, mut flags, mut mod_seq) = (None, Vec::new(), None);
+        for attr in attrs {
+            match attr {
+                AttributeValue::Uid(n) => uid = Some(*n),
+                AttributeValue::Flags(list) => flags = list.iter().map(|s| s.to_string()).collect(),
+                AttributeValue::ModSeq(n) => mod_seq = Some(*n),
+                _ => {}
+            }
+        }
+        Some(Self {
+            uid: uid?,
+            flags,
+            mod_seq,
+        })
+    }
+}
+
+/// The flags to add and remove to change the flags of a message from one set to
+/// another, for example to upload local flag changes after a sync.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FlagDiff {
+    pub added: FlagSet,
+    pub removed: FlagSet,
+}
+
+impl FlagDiff {
+    pub fn between(old: &FlagSet, new: &FlagSet) -> Self {
+        FlagDiff {
+            added: new.difference(old),
+            removed: old.difference(new),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// The `UID STORE` commands applying this diff to the messages in `uids`.
+    ///
+    /// With `unchanged_since`, the server skips messages modified after that
+    /// mod-sequence (RFC 7162), so that concurrent changes are not overwritten.
+    pub fn commands(&self, uids: &SequenceSet, unchanged_since: Option<u64>) -> Vec<Command> {
+        let changes = [
+            (StoreMode::Add, &self.added),
+            (StoreMode::Remove, &self.removed),
+        ];
+        changes
+            .iter()
+            .filter(|(_, flags)| !flags.is_empty())
+            .map(|&(mode, flags)| {
+                let mut cmd = CommandBuilder::uid_store(uids, mode, flags).silent();
+                if let Some(seq) = unchanged_since {
+                    cmd = cmd.unchanged_since(seq);
+                }
+                cmd.into()
+            })
+            .collect()
+    }
+}
+
+impl Client {
+    /// Select `mailbox`, resynchronizing with the state cached from a previous
+    /// session using the QRESYNC extension (RFC 7162).
+    ///
+    /// `uid_validity` and `highest_mod_seq` are the values of the mailbox at the
+    /// time of the last synchronization, and `known_uids` the UIDs of the messages
+    /// cached since. The server reports the messages expunged and changed since
+    /// in one round trip, which are collected in the returned `MailboxChanges`.
+    ///
+    /// QRESYNC is enabled first if necessary. Fails with
+    /// `Error::MissingCapability` if the server does not support it.
+    pub async fn select_qresync(
+        &self,
+        mailbox: &str,
+        uid_validity: u32,
+        highest_mod_seq: u64,
+        known_uids: &SequenceSet,
+    ) -> Result<MailboxChanges, Error> {
+        self.enable_qresync().await?;
+
+        let cmd = CommandBuilder::select(mailbox).qresync(
+            uid_validity,
+            highest_mod_seq,
+            Some(known_uids),
+        );
+        let mut responses = self.call(cmd);
+        let mut changes = MailboxChanges::default();
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            match rsp.parsed() {
+                Response::Vanished { uids, .. } => {
+                    for range in uids.ranges() {
+                        changes.vanished.insert_range(range);
+                    }
+                }
+                Response::Fetch(_, attrs) => {
+                    let change = match MessageChange::from_attributes(attrs) {
+                        Some(change) => change,
+                        None => continue,
+                    };
+                    if known_uids.contains(change.uid) {
+                        changes.changed.push(change);
+                    } else {
+                        changes.new.push(change);
+                    }
+                }
+                Response::Data {
+                    code: Some(code), ..
+                }
+                | Response::Done {
+                    code: Some(code), ..
+                } => match code {
+                    ResponseCode::UidValidity(n) => changes.uid_validity = Some(*n),
+                    ResponseCode::HighestModSeq(n) => changes.highest_mod_seq = Some(*n),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        changes.uid_validity_changed = matches!(changes.uid_validity, Some(v) if v != uid_validity);
+        Ok(changes)
+    }
+
+    /// Fetch the flags of the messages in the selected mailbox that changed since
+    /// `since_mod_seq`, using the CONDSTORE extension (RFC 7162).
+    ///
+    /// Sends `UID FETCH 1:* (FLAGS) (CHANGEDSINCE since_mod_seq)`, so only the
+    /// changed messages are transferred. The returned `highest_mod_seq` is the
+    /// value to pass on the next call. Fails with `Error::MissingCapability` if
+    /// the server does not support CONDSTORE.
+    pub async fn fetch_changed_flags(&self, since_mod_seq: u64) -> Result<FlagChanges, Error> {
+        let session = self.session();
+        let caps = session.capabilities();
+        if !caps.is_empty() && !caps.contains("CONDSTORE") && !caps.contains("QRESYNC") {
+            return Err(Error::MissingCapability("CONDSTORE"));
+        }
+
+        let cmd = CommandBuilder::uid_fetch()
+            .range_from(1..)
+            .attr(Attribute::Flags)
+            .changed_since(since_mod_seq);
+        let mut responses = self.call(cmd);
+        let mut changes = FlagChanges {
+            highest_mod_seq: since_mod_seq,
+            changed: Vec::new(),
+        };
+        while let Some(rsp) = responses.next().await {
+            let rsp = rsp?;
+            check_completion(&rsp)?;
+            if let Response::Fetch(_, attrs) = rsp.parsed() {
+                if let Some(change) = MessageChange::from_attributes(attrs) {
+                    changes.highest_mod_seq =
+                        changes.highest_mod_seq.max(change.mod_seq.unwrap_or(0));
+                    changes.changed.push(change);
+                }
+            }
+        }
+
+        // Mailbox changes without flag changes (such as expunges) also increase
+        // the HIGHESTMODSEQ reported when the mailbox was selected.
+        if let Some(highest) = self.session().selected().and_then(|m| m.highest_mod_seq) {
+            changes.highest_mod_seq = changes.highest_mod_seq.max(highest);
+        }
+        Ok(changes)
+    }
+
+    async fn enable_qresync(&self) -> Result<(), Error> {
+        if self.enable_extensions(&["QRESYNC"]).await?.is_empty() {
+            return Err(Error::MissingCapability("QRESYNC"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlagChanges, FlagDiff, MessageChange};
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use imap_proto::{FlagSet, SequenceSet};
+
+    #[test]
+    fn flag_diff() {
+        let change = MessageChange {
+            uid: 4,
+            flags: vec!["\\Seen".into(), "$Junk".into()],
+            mod_seq: Some(12),
+        };
+        let local: FlagSet = vec!["\\Seen", "\\Flagged"].into_iter().collect();
+        let diff = FlagDiff::between(&change.flag_set(), &local);
+        assert_eq!(diff.added.to_string(), "(\\Flagged)");
+        assert_eq!(diff.removed.to_string(), "($Junk)");
+
+        let cmds = diff.commands(&SequenceSet::from(4..=4), Some(12));
+        let args = cmds.iter().map(|cmd| &cmd.args[..]).collect::<Vec<_>>();
+        assert_eq!(
+            args,
+            vec![
+                &b"UID STORE 4 (UNCHANGEDSINCE 12) +FLAGS.SILENT (\\Flagged)"[..],
+                &b"UID STORE 4 (UNCHANGEDSINCE 12) -FLAGS.SILENT ($Junk)"[..],
+            ]
+        );
+        assert!(FlagDiff::between(&local, &local).is_empty());
+    }
+
+    #[tokio::test]
+    async fn select_qresync() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 ENABLE QRESYNC] ready\r\n")
+            .expect("A0001 ENABLE QRESYNC")
+            .send("* ENABLED QRESYNC\r\nA0001 OK enabled\r\n")
+            .expect("A0002 SELECT \"INBOX\" (QRESYNC (67890007 20050715194045000 41:211,214:541))")
+            .send(
+                "* 314 EXISTS\r\n\
+                 * OK [UIDVALIDITY 67890007] UIDVALIDITY\r\n\
+                 * OK [HIGHESTMODSEQ 20050715194045319] Highest\r\n\
+                 * VANISHED (EARLIER) 41,43:116,118,120:211,214:540\r\n\
+                 * 49 FETCH (UID 117 FLAGS (\\Seen \\Answered) MODSEQ (90060115194045001))\r\n\
+                 * 50 FETCH (UID 542 FLAGS () MODSEQ (90060115194045002))\r\n\
+                 A0002 OK [READ-WRITE] mailbox selected\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let mut known = SequenceSet::from(41..=211);
+        known.insert_range(214..=541);
+        let changes = client
+            .select_qresync("INBOX", 67_890_007, 20_050_715_194_045_000, &known)
+            .await
+            .unwrap();
+        server.finish().await.unwrap();
+
+        assert!(!changes.uid_validity_changed);
+        assert_eq!(changes.highest_mod_seq, Some(20_050_715_194_045_319));
+        assert_eq!(
+            changes.vanished.to_string(),
+            "41,43:116,118,120:211,214:540"
+        );
+        assert_eq!(
+            changes.changed,
+            vec![MessageChange {
+                uid: 117,
+                flags: vec!["\\Seen".into(), "\\Answered".into()],
+                mod_seq: Some(90_060_115_194_045_001),
+            }]
+        );
+        assert_eq!(changes.new.len(), 1);
+        assert_eq!(changes.new[0].uid, 542);
+        assert_eq!(client.session().selected().unwrap().exists, 314);
+    }
+
+    #[tokio::test]
+    async fn fetch_changed_flags() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 CONDSTORE] ready\r\n")
+            .expect("A0001 UID FETCH 1:* (FLAGS) (CHANGEDSINCE 12111230047)")
+            .send(
+                "* 1 FETCH (UID 4 MODSEQ (12121231000) FLAGS (\\Seen))\r\n\
+                 * 2 FETCH (UID 6 MODSEQ (12121230852) FLAGS (\\Deleted))\r\n\
+                 A0001 OK FETCH completed\r\n",
+            );
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let changes = client.fetch_changed_flags(12_111_230_047).await.unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(
+            changes,
+            FlagChanges {
+                highest_mod_seq: 12_121_231_000,
+                changed: vec![
+                    MessageChange {
+                        uid: 4,
+                        flags: vec!["\\Seen".into()],
+                        mod_seq: Some(12_121_231_000),
+                    },
+                    MessageChange {
+                        uid: 6,
+                        flags: vec!["\\Deleted".into()],
+                        mod_seq: Some(12_121_230_852),
+                    },
+                ],
+            }
+        );
+    }
+}
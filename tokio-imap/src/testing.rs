@@ -0,0 +1,142 @@
+//! A scripted IMAP server for testing code that uses the client.
+//!
+//! A `Script` lists the data the server sends and the lines it expects to
+//! receive, in order. `MockServer` plays the script to the first client that
+//! connects to it and reports any deviation from `finish()`:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), tokio_imap::Error> {
+//! use futures::StreamExt;
+//! use tokio_imap::builders::CommandBuilder;
+//! use tokio_imap::testing::{MockServer, Script};
+//! use tokio_imap::Client;
+//!
+//! let script = Script::new()
+//!     .send("* OK IMAP4rev1 ready\r\n")
+//!     .expect("A0001 CHECK")
+//!     .send("A0001 OK CHECK completed\r\n");
+//! let server = MockServer::start(script).await?;
+//!
+//! let (_, client, connection) = Client::from_stream(server.connect().await?).await?;
+//! tokio::spawn(connection);
+//! let mut responses = client.call(CommandBuilder::check());
+//! while let Some(rsp) = responses.next().await {
+//!     rsp?;
+//! }
+//! server.finish().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Client tags are allocated sequentially from `A0001` for each connection. The
+//! server closes the connection once the script is complete.
+
+use std::io;
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// The exchange played by a `MockServer`.
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    Send(Vec<u8>),
+    Expect(Vec<u8>),
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `data` to the client as-is.
+    pub fn send<D: AsRef<[u8]>>(mut self, data: D) -> Self {
+        self.steps.push(Step::Send(data.as_ref().to_vec()));
+        self
+    }
+
+    /// Wait for the next line sent by the client, and check that it is `line`.
+    ///
+    /// The trailing CRLF is added if missing. Literals containing line breaks
+    /// span several lines.
+    pub fn expect<L: AsRef<[u8]>>(mut self, line: L) -> Self {
+        let mut line = line.as_ref().to_vec();
+        if !line.ends_with(b"\r\n") {
+            line.extend_from_slice(b"\r\n");
+        }
+        self.steps.push(Step::Expect(line));
+        self
+    }
+}
+
+/// An in-process server listening on localhost that plays a `Script`.
+pub struct MockServer {
+    addr: SocketAddr,
+    handle: JoinHandle<io::Result<()>>,
+}
+
+impl MockServer {
+    /// Start listening and wait for a client in the background.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub async fn start(script: Script) -> io::Result<Self> {
+        let mut listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let addr = listener.local_addr()?;
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            play(script, stream).await
+        });
+        Ok(Self { addr, handle })
+    }
+
+    /// The address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Open a connection to the server, to be passed to `Client::from_stream()`.
+    pub async fn connect(&self) -> io::Result<TcpStream> {
+        TcpStream::connect(self.addr).await
+    }
+
+    /// Wait for the script to complete.
+    ///
+    /// Fails with `io::ErrorKind::InvalidData` if the client sent anything other
+    /// than the expected lines.
+    pub async fn finish(self) -> io::Result<()> {
+        match self.handle.await {
+            Ok(res) => res,
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+}
+
+async fn play(script: Script, stream: TcpStream) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    for step in script.steps {
+        match step {
+            Step::Send(data) => stream.get_mut().write_all(&data).await?,
+            Step::Expect(expected) => {
+                let mut line = Vec::new();
+                stream.read_until(b'\n', &mut line).await?;
+                if line != expected {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "expected {:?}, received {:?}",
+                            String::from_utf8_lossy(&expected),
+                            String::from_utf8_lossy(&line)
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
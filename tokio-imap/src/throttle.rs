@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{self, Delay, Instant};
+
+use crate::client::{Client, TlsConnection};
+use crate::codec::ResponseData;
+use crate::error::Error;
+use crate::tls::TlsConfig;
+
+/// Limits the rate at which commands are sent, allowing bursts (token bucket).
+pub(crate) struct Throttle {
+    capacity: f64,
+    interval: Duration,
+    tokens: f64,
+    updated: Instant,
+    timer: Option<Delay>,
+}
+
+impl Throttle {
+    pub(crate) fn new(commands: u32, per: Duration) -> Self {
+        let commands = commands.max(1);
+        Self {
+            capacity: f64::from(commands),
+            interval: per / commands,
+            tokens: f64::from(commands),
+            updated: Instant::now(),
+            timer: None,
+        }
+    }
+
+    /// Take the permission to send a command, or arrange for the task to be woken
+    /// up once it is available.
+    pub(crate) fn poll_acquire(&mut self, cx: &mut Context) -> Poll<()> {
+        loop {
+            let now = Instant::now();
+            let elapsed = now.duration_since(self.updated);
+            self.tokens = (self.tokens + elapsed.as_secs_f64() / self.interval.as_secs_f64())
+                .min(self.capacity);
+            self.updated = now;
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+                self.timer = None;
+                return Poll::Ready(());
+            }
+
+            let wait = self.interval.mul_f64(1.0 - self.tokens);
+            let timer = self.timer.get_or_insert_with(|| time::delay_for(wait));
+            timer.reset(now + wait);
+            if Pin::new(timer).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// Limits the number of concurrent connections to each server.
+///
+/// Providers such as Gmail and Office 365 limit the number of simultaneous
+/// connections per account, and may temporarily block clients exceeding them.
+/// Connections opened through the same `ConnectionLimiter` (or a clone of it)
+/// wait until the number of open connections to the server drops below the
+/// limit; a connection counts as open until its `Connection` is dropped.
+#[derive(Clone)]
+pub struct ConnectionLimiter {
+    max_per_host: usize,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_per_host: usize) -> Self {
+        Self {
+            max_per_host: max_per_host.max(1),
+            hosts: Arc::default(),
+        }
+    }
+
+    /// Like `Client::connect_with()`, waiting for fewer than the maximum number of
+    /// connections to `server` to be open first.
+    pub async fn connect(
+        &self,
+        server: &str,
+        tls: &TlsConfig,
+    ) -> Result<(ResponseData, Client, TlsConnection), Error> {
+        let permit = self.acquire(server).await;
+        let (greeting, client, mut connection) = Client::connect_with(server, tls).await?;
+        connection.permit = Some(permit);
+        Ok((greeting, client, connection))
+    }
+
+    pub(crate) async fn acquire(&self, host: &str) -> OwnedSemaphorePermit {
+        let semaphore = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(host.to_ascii_lowercase())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_host)))
+            .clone();
+        semaphore.acquire_owned().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::ConnectionLimiter;
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use futures::{FutureExt, StreamExt};
+    use imap_proto::builders::command::CommandBuilder;
+
+    #[tokio::test]
+    async fn throttle_commands() {
+        let script = Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 NOOP")
+            .expect("A0002 NOOP")
+            .expect("A0003 NOOP")
+            .send("A0001 OK done\r\nA0002 OK done\r\nA0003 OK done\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, mut connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        connection.throttle(1, Duration::from_millis(50));
+        tokio::spawn(connection);
+
+        let start = Instant::now();
+        let calls = (0..3)
+            .map(|_| client.call(CommandBuilder::noop()))
+            .collect::<Vec<_>>();
+        for mut responses in calls {
+            while let Some(rsp) = responses.next().await {
+                rsp.unwrap();
+            }
+        }
+        server.finish().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[tokio::test]
+    async fn limit_connections() {
+        let limiter = ConnectionLimiter::new(1);
+        let permit = limiter.acquire("imap.example.com").await;
+        assert!(limiter.acquire("IMAP.example.com").now_or_never().is_none());
+        assert!(limiter.acquire("imap.example.org").now_or_never().is_some());
+        drop(permit);
+        assert!(limiter.acquire("imap.example.com").now_or_never().is_some());
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::protocol::literal_len;
+
+/// Records the protocol exchange of a `Connection`, for debugging.
+///
+/// Every line sent or received is emitted prefixed with `C:` (client) or `S:`
+/// (server). The arguments of `LOGIN` commands and the initial response of
+/// `AUTHENTICATE` commands are replaced with `<redacted>`, so traces can be
+/// shared without leaking credentials. Literals (such as message bodies) can
+/// optionally be truncated through `truncate_literals()`.
+pub struct Trace {
+    output: Output,
+    max_literal: Option<usize>,
+    // Size of the literal announced at the end of the last line sent
+    literal: Option<usize>,
+    // Whether the rest of the command currently being sent is redacted
+    redacting: bool,
+}
+
+enum Output {
+    Log,
+    Writer(Box<dyn Write + Send>),
+}
+
+impl Trace {
+    /// Emit the trace through the `log` crate, at the `trace` level with the
+    /// `tokio_imap::wire` target.
+    pub fn log() -> Self {
+        Self::new(Output::Log)
+    }
+
+    /// Write the trace to `writer`, one line at a time. Write errors are ignored.
+    pub fn writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self::new(Output::Writer(Box::new(writer)))
+    }
+
+    fn new(output: Output) -> Self {
+        Self {
+            output,
+            max_literal: None,
+            literal: None,
+            redacting: false,
+        }
+    }
+
+    /// Only include the first `max` bytes of literals larger than `max` bytes.
+    pub fn truncate_literals(mut self, max: usize) -> Self {
+        self.max_literal = Some(max);
+        self
+    }
+
+    /// Record the first line of a command (up to the first literal, if any).
+    pub(crate) fn command(&mut self, tag: &[u8], line: &[u8]) {
+        self.literal = literal_len(line);
+        let mut out = String::from_utf8_lossy(tag).into_owned();
+        out.push(' ');
+        match redacted_from(line) {
+            Some(pos) => {
+                self.redacting = true;
+                out.push_str(&String::from_utf8_lossy(&line[..pos]));
+                out.push_str("<redacted>");
+            }
+            None => {
+                self.redacting = false;
+                out.push_str(&self.render(line, None));
+            }
+        }
+        self.emit("C:", &out);
+    }
+
+    /// Record the continuation of a command: the literal announced at the end of
+    /// the previous line, followed by the rest of the command up to the next literal.
+    pub(crate) fn continuation(&mut self, data: &[u8]) {
+        let literal = self.literal.take();
+        self.literal = literal_len(data);
+        if self.redacting {
+            return self.emit("C:", "<redacted>");
+        }
+        let out = self.render(data, literal);
+        self.emit("C:", &out);
+    }
+
+    /// Record a response received from the server.
+    pub(crate) fn response(&mut self, raw: &[u8]) {
+        let out = self.render(raw, None);
+        self.emit("S:", &out);
+    }
+
+    fn render(&self, mut data: &[u8], mut literal: Option<usize>) -> String {
+        let mut out = String::new();
+        loop {
+            if let Some(len) = literal.take() {
+                let (body, rest) = data.split_at(len.min(data.len()));
+                match self.max_literal {
+                    Some(max) if body.len() > max => {
+                        out.push_str(&String::from_utf8_lossy(&body[..max]));
+                        let _ = write!(out, "...[{} bytes]", body.len());
+                    }
+                    _ => out.push_str(&String::from_utf8_lossy(body)),
+                }
+                data = rest;
+            }
+
+            match data.windows(2).position(|w| w == b"\r\n") {
+                Some(pos) => {
+                    literal = literal_len(&data[..pos]);
+                    out.push_str(&String::from_utf8_lossy(&data[..pos + 2]));
+                    data = &data[pos + 2..];
+                }
+                None => {
+                    out.push_str(&String::from_utf8_lossy(data));
+                    return out;
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self, prefix: &str, out: &str) {
+        for line in out.trim_end_matches("\r\n").split("\r\n") {
+            match &mut self.output {
+                Output::Log => log::trace!(target: "tokio_imap::wire", "{} {}", prefix, line),
+                Output::Writer(writer) => {
+                    let _ = writeln!(writer, "{} {}", prefix, line);
+                }
+            }
+        }
+    }
+}
+
+/// The position in a command line from which its arguments must be redacted.
+fn redacted_from(line: &[u8]) -> Option<usize> {
+    let mut words = line.splitn(3, |&b| b == b' ');
+    let name = words.next()?;
+    if name.eq_ignore_ascii_case(b"LOGIN") {
+        Some(name.len() + 1).filter(|&pos| pos <= line.len())
+    } else if name.eq_ignore_ascii_case(b"AUTHENTICATE") {
+        let mechanism = words.next()?;
+        Some(name.len() + mechanism.len() + 2).filter(|&pos| pos <= line.len())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Trace;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn redacts_and_truncates() {
+        let buf = Shared::default();
+        let mut trace = Trace::writer(buf.clone()).truncate_literals(4);
+        trace.command(b"A1", b"LOGIN djc {5}");
+        trace.continuation(b"p\xc3\xa4ss");
+        trace.command(b"A2", b"AUTHENTICATE PLAIN AGRqYwBwYXNz");
+        trace.command(b"A3", b"APPEND Drafts {11}");
+        trace.continuation(b"hello world");
+        trace.response(b"* 1 FETCH (BODY[] {12}\r\nhello\r\nworld)\r\n");
+
+        let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            out,
+            "C: A1 LOGIN <redacted>\n\
+             C: <redacted>\n\
+             C: A2 AUTHENTICATE PLAIN <redacted>\n\
+             C: A3 APPEND Drafts {11}\n\
+             C: hell...[11 bytes]\n\
+             S: * 1 FETCH (BODY[] {12}\n\
+             S: hell...[12 bytes])\n"
+        );
+    }
+}
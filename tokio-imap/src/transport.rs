@@ -0,0 +1,315 @@
+use std::collections::VecDeque;
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, Bytes};
+use futures::{ready, Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::buffer::BufferPolicy;
+use crate::codec::ResponseData;
+use crate::protocol::{Event, Protocol};
+use crate::session::Session;
+use crate::spill::Spill;
+use imap_proto::builders::command::Command;
+use imap_proto::parser::Quirks;
+
+/// A connection to an IMAP server as a `Stream` of responses and a `Sink` of
+/// commands.
+///
+/// This is a lower-level alternative to `Client` and `Connection`: it takes care
+/// of tags, literals and pipelining like the client does, but yields all
+/// responses in the order they are received, leaving it to the caller to match
+/// them to commands by tag. The first response is the server greeting. This
+/// makes it easy to combine with `StreamExt` combinators and `select!`:
+///
+/// ```no_run
+/// # async fn example(stream: tokio::net::TcpStream) -> std::io::Result<()> {
+/// use futures::{SinkExt, StreamExt};
+/// use tokio_imap::builders::CommandBuilder;
+/// use tokio_imap::Transport;
+///
+/// let mut transport = Transport::new(stream);
+/// let _greeting = transport.next().await;
+/// transport.send(CommandBuilder::check()).await?;
+/// while let Some(rsp) = transport.next().await {
+///     if rsp?.request_id().is_some() {
+///         break;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The sink only accepts a new command once all data that can currently be sent
+/// has been written, so a slow server exerts backpressure on the sender. Note that
+/// the remainder of a command containing a synchronizing literal can only be sent
+/// once the server requested it, which requires the stream to be polled.
+pub struct Transport<T> {
+    stream: T,
+    pub(crate) protocol: Protocol,
+    write_buf: WriteQueue,
+    flushed: bool,
+}
+
+impl<T> Transport<T> {
+    pub fn new(stream: T) -> Self {
+        Self {
+            stream,
+            protocol: Protocol::new(),
+            write_buf: WriteQueue::default(),
+            flushed: true,
+        }
+    }
+
+    /// The current session state: capabilities, enabled extensions and the
+    /// selected mailbox.
+    pub fn session(&self) -> &Session {
+        self.protocol.session()
+    }
+
+    /// Write large literals received from now on elsewhere, see
+    /// `Connection::spill_literals()`.
+    pub fn spill_literals(&mut self, spill: Spill) {
+        self.protocol.spill_literals(spill);
+    }
+
+    /// Accept the deviations from the protocol selected by `quirks`, see
+    /// `Connection::quirks()`.
+    pub fn quirks(&mut self, quirks: Quirks) {
+        self.protocol.quirks(quirks);
+    }
+
+    /// Limit the length of the lines received, see
+    /// `Connection::max_line_length()`.
+    pub fn max_line_length(&mut self, max: usize) {
+        self.protocol.max_line_length(max);
+    }
+
+    /// Reject lines terminated by a bare LF, see `Connection::strict_crlf()`.
+    pub fn strict_crlf(&mut self, strict: bool) {
+        self.protocol.strict_crlf(strict);
+    }
+
+    /// Size (and optionally pool) the read buffer according to `policy`.
+    pub fn buffer_policy(&mut self, policy: BufferPolicy) {
+        self.protocol.buffer_policy(policy);
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    /// Whether all commands have been written and completed.
+    pub(crate) fn is_idle(&self) -> bool {
+        self.protocol.is_idle() && !self.write_buf.has_remaining()
+    }
+}
+
+impl<T> Transport<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Write as much of the queued commands as the pipelining rules allow.
+    ///
+    /// Commands and the literals in them are written with vectored I/O if the
+    /// stream supports it, rather than being copied into a single buffer.
+    pub(crate) fn poll_send(&mut self, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        loop {
+            while let Some(data) = self.protocol.transmit() {
+                self.write_buf.push(data);
+            }
+            if !self.write_buf.has_remaining() {
+                break;
+            }
+
+            let n = ready!(Pin::new(&mut self.stream).poll_write_buf(cx, &mut self.write_buf))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.flushed = false;
+        }
+
+        if !self.flushed {
+            ready!(Pin::new(&mut self.stream).poll_flush(cx))?;
+            self.flushed = true;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    pub(crate) fn poll_shutdown(&mut self, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+
+    /// Read and process data from the server. Returns `false` at the end of the stream.
+    pub(crate) fn poll_receive(&mut self, cx: &mut Context) -> Poll<Result<bool, io::Error>> {
+        let buf = self.protocol.read_buf_mut();
+        let n = ready!(Pin::new(&mut self.stream).poll_read_buf(cx, buf))?;
+        if n == 0 {
+            return Poll::Ready(Ok(false));
+        }
+        self.protocol.received(n)?;
+        Poll::Ready(Ok(true))
+    }
+}
+
+impl<T> Stream for Transport<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    type Item = Result<ResponseData, io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let me = self.get_mut();
+        loop {
+            match me.protocol.poll_event() {
+                Some(Event::Response(_, rsp)) | Some(Event::Unsolicited(rsp)) => {
+                    return Poll::Ready(Some(Ok(rsp)))
+                }
+                // Only streamed when enabled by `Connection::stream_literals()`
+                Some(Event::Literal(_)) => continue,
+                None => {}
+            }
+
+            // Continuation requests may have allowed more data to be sent
+            if let Poll::Ready(Err(e)) = me.poll_send(cx) {
+                return Poll::Ready(Some(Err(e)));
+            }
+
+            match ready!(me.poll_receive(cx)) {
+                Ok(true) => {}
+                Ok(false) => return Poll::Ready(None),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+impl<T> Sink<Command> for Transport<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        self.get_mut().poll_send(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, cmd: Command) -> Result<(), io::Error> {
+        self.get_mut().protocol.enqueue(cmd);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        self.get_mut().poll_send(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        let me = self.get_mut();
+        ready!(me.poll_send(cx))?;
+        Pin::new(&mut me.stream).poll_shutdown(cx)
+    }
+}
+
+/// The data returned by `Protocol::transmit()` that has not been written yet.
+#[derive(Default)]
+struct WriteQueue(VecDeque<Bytes>);
+
+impl WriteQueue {
+    fn push(&mut self, data: Bytes) {
+        if !data.is_empty() {
+            self.0.push_back(data);
+        }
+    }
+}
+
+impl Buf for WriteQueue {
+    fn remaining(&self) -> usize {
+        self.0.iter().map(Bytes::len).sum()
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self.0.front() {
+            Some(data) => data,
+            None => &[],
+        }
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        while cnt > 0 {
+            let front = self
+                .0
+                .front_mut()
+                .expect("advanced past the end of the queue");
+            if cnt < front.len() {
+                front.advance(cnt);
+                return;
+            }
+            cnt -= front.len();
+            self.0.pop_front();
+        }
+    }
+
+    fn bytes_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+        let mut n = 0;
+        for (slice, data) in dst.iter_mut().zip(&self.0) {
+            *slice = IoSlice::new(data);
+            n += 1;
+        }
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Transport, WriteQueue};
+    use crate::testing::{MockServer, Script};
+    use bytes::{Buf, Bytes};
+    use futures::{SinkExt, StreamExt};
+    use imap_proto::builders::command::CommandBuilder;
+    use imap_proto::Response;
+    use std::io::IoSlice;
+
+    #[tokio::test]
+    async fn stream_and_sink() {
+        let script = Script::new()
+            .send("* OK ready\r\n")
+            .expect("A0001 CHECK")
+            .send("* 2 EXISTS\r\nA0001 OK CHECK completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+
+        let mut transport = Transport::new(server.connect().await.unwrap());
+        let greeting = transport.next().await.unwrap().unwrap();
+        assert!(matches!(greeting.parsed(), Response::Data { .. }));
+
+        transport.send(CommandBuilder::check()).await.unwrap();
+        let responses = transport.map(|rsp| rsp.unwrap()).collect::<Vec<_>>().await;
+        server.finish().await.unwrap();
+        assert_eq!(responses.len(), 2);
+        assert!(responses[1].request_id().is_some());
+    }
+
+    #[test]
+    fn write_queue() {
+        let mut queue = WriteQueue::default();
+        queue.push(Bytes::from_static(b"A1 APPEND INBOX {5+}\r\n"));
+        queue.push(Bytes::new());
+        queue.push(Bytes::from_static(b"hello"));
+        queue.push(Bytes::from_static(b"\r\n"));
+        assert_eq!(queue.remaining(), 29);
+
+        let mut slices = [IoSlice::new(&[]); 4];
+        assert_eq!(queue.bytes_vectored(&mut slices), 3);
+        assert_eq!(&*slices[1], b"hello");
+
+        queue.advance(24);
+        assert_eq!(queue.bytes(), b"llo");
+        queue.advance(5);
+        assert!(!queue.has_remaining());
+    }
+}
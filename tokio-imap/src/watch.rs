@@ -0,0 +1,448 @@
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::stream::{self, BoxStream};
+use futures::{FutureExt, Stream, StreamExt};
+use tokio::time;
+
+use crate::client::{Client, Idle, UnsolicitedResponses};
+use crate::error::{check_completion, Error};
+use imap_proto::builders::command::CommandBuilder;
+use imap_proto::{AttributeValue, MailboxDatum, Response};
+
+/// A change to a mailbox, yielded by `Watch`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MailboxEvent {
+    /// `count` messages were added to the mailbox, which now holds `exists` messages.
+    NewMessages { count: u32, exists: u32 },
+    /// The message with the given sequence number was expunged.
+    Expunged(u32),
+    /// The flags of a message changed. The UID is only included if the server
+    /// sent it.
+    FlagsChanged {
+        seq: u32,
+        uid: Option<u32>,
+        flags: Vec<String>,
+    },
+    /// The changes received until none were received for the quiet period
+    /// set with `Watch::debounce()`, in place of the other events.
+    Summary {
+        /// The number of messages added.
+        new: u32,
+        /// The number of messages in the mailbox afterwards.
+        exists: u32,
+        expunged: u32,
+        /// The number of flag changes, including those without a UID.
+        changed: u32,
+        /// The UIDs of the messages whose flags changed, if sent by the server.
+        changed_uids: Vec<u32>,
+    },
+}
+
+/// A stream of changes to a mailbox, created by `Client::watch()`.
+///
+/// The mailbox is selected once the stream is first polled. The stream ends
+/// after yielding an error.
+pub struct Watch {
+    client: Client,
+    mailbox: String,
+    poll_interval: Duration,
+    idle_renewal: Option<Duration>,
+    debounce: Option<Duration>,
+    events: Option<BoxStream<'static, Result<MailboxEvent, Error>>>,
+}
+
+impl Watch {
+    /// How often to check for changes if the server does not support `IDLE`.
+    /// Defaults to one minute.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// How often to renew the `IDLE` command, if used (see `Idle::renew_after()`).
+    pub fn renew_idle_after(mut self, interval: Duration) -> Self {
+        self.idle_renewal = Some(interval);
+        self
+    }
+
+    /// Coalesce the changes received in a burst, such as during a bulk
+    /// delivery, into a single `MailboxEvent::Summary`, yielded once no changes
+    /// were received for `quiet`. When polling, the changes found by each poll
+    /// are summarized.
+    pub fn debounce(mut self, quiet: Duration) -> Self {
+        self.debounce = Some(quiet);
+        self
+    }
+}
+
+impl Stream for Watch {
+    type Item = Result<MailboxEvent, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if self.events.is_none() {
+            let start = Watcher::start(
+                self.client.clone(),
+                self.mailbox.clone(),
+                self.poll_interval,
+                self.idle_renewal,
+                self.debounce,
+            );
+            let events = stream::once(start).flat_map(|watcher| match watcher {
+                Ok(watcher) => stream::unfold(watcher, |mut watcher| async move {
+                    let event = watcher.next().await?;
+                    Some((event, watcher))
+                })
+                .left_stream(),
+                Err(e) => stream::once(async { Err(e) }).right_stream(),
+            });
+            self.events = Some(events.boxed());
+        }
+        self.events.as_mut().unwrap().poll_next_unpin(cx) // safe: set above
+    }
+}
+
+impl Client {
+    /// Select `mailbox` and watch it for changes.
+    ///
+    /// Uses `IDLE` (RFC 2177) if the server supports it, so that changes are
+    /// reported as they happen. Otherwise, sends a `NOOP` command every poll
+    /// interval (see `Watch::poll_interval()`) to check for changes.
+    pub fn watch(&self, mailbox: &str) -> Watch {
+        Watch {
+            client: self.clone(),
+            mailbox: mailbox.to_string(),
+            poll_interval: Duration::from_secs(60),
+            idle_renewal: None,
+            debounce: None,
+            events: None,
+        }
+    }
+}
+
+struct Watcher {
+    client: Client,
+    poll_interval: Duration,
+    idle_renewal: Option<Duration>,
+    debounce: Option<Duration>,
+    use_idle: bool,
+    idle: Option<Idle>,
+    unsolicited: UnsolicitedResponses,
+    exists: u32,
+    events: VecDeque<MailboxEvent>,
+    failed: bool,
+    /// The error to yield once the pending events were yielded.
+    error: Option<Error>,
+}
+
+impl Watcher {
+    async fn start(
+        client: Client,
+        mailbox: String,
+        poll_interval: Duration,
+        idle_renewal: Option<Duration>,
+        debounce: Option<Duration>,
+    ) -> Result<Self, Error> {
+        // Subscribe first, so that no changes are missed between commands
+        let unsolicited = client.unsolicited();
+        let mut responses = client.call(CommandBuilder::select(&mailbox));
+        while let Some(rsp) = responses.next().await {
+            check_completion(&rsp?)?;
+        }
+
+        let session = client.session();
+        Ok(Self {
+            use_idle: session.has_capability("IDLE"),
+            exists: session.selected().map_or(0, |mailbox| mailbox.exists),
+            client,
+            poll_interval,
+            idle_renewal,
+            debounce,
+            idle: None,
+            unsolicited,
+            events: VecDeque::new(),
+            failed: false,
+            error: None,
+        })
+    }
+
+    async fn next(&mut self) -> Option<Result<MailboxEvent, Error>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            } else if self.failed {
+                return self.error.take().map(Err);
+            }
+
+            let result = match self.debounce {
+                Some(quiet) => self.wait_quiet(quiet).await,
+                None => self.wait().await,
+            };
+            if let Err(e) = result {
+                self.failed = true;
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// Wait for updates from the server until none were received for `quiet`,
+    /// and summarize them.
+    async fn wait_quiet(&mut self, quiet: Duration) -> Result<(), Error> {
+        while self.events.is_empty() {
+            self.wait().await?;
+        }
+        let mut result = Ok(());
+        while self.use_idle {
+            match time::timeout(quiet, self.wait()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    result = Err(e);
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+        self.summarize();
+        result
+    }
+
+    /// Replace the pending events with a summary of them.
+    fn summarize(&mut self) {
+        let (mut new, mut expunged, mut changed) = (0, 0, 0);
+        let mut changed_uids = Vec::new();
+        for event in self.events.drain(..) {
+            match event {
+                MailboxEvent::NewMessages { count, .. } => new += count,
+                MailboxEvent::Expunged(_) => expunged += 1,
+                MailboxEvent::FlagsChanged { uid, .. } => {
+                    changed += 1;
+                    changed_uids.extend(uid);
+                }
+                MailboxEvent::Summary { .. } => {}
+            }
+        }
+        changed_uids.sort_unstable();
+        changed_uids.dedup();
+        self.events.push_back(MailboxEvent::Summary {
+            new,
+            exists: self.exists,
+            expunged,
+            changed,
+            changed_uids,
+        });
+    }
+
+    /// Wait for updates from the server.
+    async fn wait(&mut self) -> Result<(), Error> {
+        let pending = self.events.len();
+        while let Some(Some(rsp)) = self.unsolicited.next().now_or_never() {
+            self.process(rsp.parsed());
+        }
+        if self.events.len() > pending {
+            return Ok(());
+        }
+
+        if !self.use_idle {
+            time::delay_for(self.poll_interval).await;
+            let mut responses = self.client.call(CommandBuilder::noop());
+            while let Some(rsp) = responses.next().await {
+                let rsp = rsp?;
+                check_completion(&rsp)?;
+                self.process(rsp.parsed());
+            }
+            return Ok(());
+        }
+
+        let (client, renewal) = (&self.client, self.idle_renewal);
+        let idle = self.idle.get_or_insert_with(|| match renewal {
+            Some(interval) => client.idle().renew_after(interval),
+            None => client.idle(),
+        });
+        match idle.next().await {
+            Some(rsp) => {
+                let rsp = rsp?;
+                check_completion(&rsp)?;
+                if rsp.request_id().is_some() {
+                    // The server ended the command; start idling again
+                    self.idle = None;
+                }
+                self.process(rsp.parsed());
+                Ok(())
+            }
+            None => Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "connection closed",
+            ))),
+        }
+    }
+
+    fn process(&mut self, rsp: &Response) {
+        match rsp {
+            Response::MailboxData(MailboxDatum::Exists(exists)) => {
+                if *exists > self.exists {
+                    self.events.push_back(MailboxEvent::NewMessages {
+                        count: exists - self.exists,
+                        exists: *exists,
+                    });
+                }
+                self.exists = *exists;
+            }
+            Response::Expunge(seq) => {
+                self.exists = self.exists.saturating_sub(1);
+                self.events.push_back(MailboxEvent::Expunged(*seq));
+            }
+            Response::Fetch(seq, attrs) => {
+                let mut uid = None;
+                let mut flags = None;
+                for attr in attrs {
+                    match attr {
+                        AttributeValue::Uid(n) => uid = Some(*n),
+                        AttributeValue::Flags(list) => {
+                            flags = Some(list.iter().map(|s| s.to_string()).collect())
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(flags) = flags {
+                    self.events.push_back(MailboxEvent::FlagsChanged {
+                        seq: *seq,
+                        uid,
+                        flags,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::MailboxEvent;
+    use crate::testing::{MockServer, Script};
+    use crate::Client;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn watch_idle() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 IDLE] ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send("* 3 EXISTS\r\nA0001 OK [READ-WRITE] selected\r\n")
+            .expect("A0002 IDLE")
+            .send(
+                "+ idling\r\n\
+                 * 5 EXISTS\r\n\
+                 * 2 EXPUNGE\r\n\
+                 * 1 FETCH (FLAGS (\\Seen))\r\n",
+            )
+            .expect("DONE")
+            .send("A0002 OK IDLE terminated\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let events = client
+            .watch("INBOX")
+            .take(3)
+            .map(|event| event.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        server.finish().await.unwrap();
+        assert_eq!(
+            events,
+            vec![
+                MailboxEvent::NewMessages {
+                    count: 2,
+                    exists: 5
+                },
+                MailboxEvent::Expunged(2),
+                MailboxEvent::FlagsChanged {
+                    seq: 1,
+                    uid: None,
+                    flags: vec!["\\Seen".into()],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_debounced() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1 IDLE] ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send("* 3 EXISTS\r\nA0001 OK [READ-WRITE] selected\r\n")
+            .expect("A0002 IDLE")
+            .send(
+                "+ idling\r\n\
+                 * 4 EXISTS\r\n\
+                 * 5 EXISTS\r\n\
+                 * 1 FETCH (UID 7 FLAGS (\\Seen))\r\n",
+            )
+            .send(
+                "* 6 EXISTS\r\n\
+                 * 2 EXPUNGE\r\n\
+                 * 1 FETCH (FLAGS (\\Seen \\Flagged) UID 7)\r\n\
+                 * 3 FETCH (FLAGS ())\r\n",
+            )
+            .expect("DONE")
+            .send("A0002 OK IDLE terminated\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let mut watch = client.watch("INBOX").debounce(Duration::from_millis(50));
+        let event = watch.next().await.unwrap().unwrap();
+        drop(watch);
+        server.finish().await.unwrap();
+        assert_eq!(
+            event,
+            MailboxEvent::Summary {
+                new: 3,
+                exists: 5,
+                expunged: 1,
+                changed: 3,
+                changed_uids: vec![7],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_polling() {
+        let script = Script::new()
+            .send("* OK [CAPABILITY IMAP4rev1] ready\r\n")
+            .expect("A0001 SELECT \"INBOX\"")
+            .send("* 3 EXISTS\r\nA0001 OK [READ-WRITE] selected\r\n")
+            .expect("A0002 NOOP")
+            .send("A0002 OK nothing new\r\n")
+            .expect("A0003 NOOP")
+            .send("* 4 EXISTS\r\nA0003 OK NOOP completed\r\n");
+        let server = MockServer::start(script).await.unwrap();
+        let (_, client, connection) = Client::from_stream(server.connect().await.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let mut watch = client
+            .watch("INBOX")
+            .poll_interval(Duration::from_millis(10));
+        let event = watch.next().await.unwrap().unwrap();
+        server.finish().await.unwrap();
+        assert_eq!(
+            event,
+            MailboxEvent::NewMessages {
+                count: 1,
+                exists: 4
+            }
+        );
+    }
+}